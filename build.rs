@@ -0,0 +1,59 @@
+//! Lists the files in `static/` for `src/assets.rs` (only with the `embed-static` feature)
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// List every file in `dir` (recursively), skipping anything unreadable
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+fn main() {
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("static_assets.rs");
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("static");
+
+    let mut assets =
+        String::from("/// The embedded static files (by path relative to `static/`)\n");
+    assets.push_str("static ASSETS: &[(&str, &[u8])] = &[\n");
+
+    if env::var("CARGO_FEATURE_EMBED_STATIC").is_ok() {
+        println!("cargo:rerun-if-changed=static");
+
+        for path in files(&root) {
+            let name = path
+                .strip_prefix(&root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            assets.push_str(&format!(
+                "    ({name:?}, include_bytes!({:?})),\n",
+                path.to_string_lossy()
+            ));
+        }
+    }
+
+    assets.push_str("];\n");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    fs::write(out, assets).unwrap();
+}
@@ -0,0 +1,238 @@
+//! Who can view a paste, checked the same way by every per-paste route
+//!
+//! Routes under `/:url` (pages and the API) take a [`ProtectedPaste`], which fetches the paste
+//! from the route's `url` and only lets the request through if [`check`] does: the client's
+//! network first, then the paste's visibility, its time window and its view password (the
+//! `view_password` of the query, or a share link). Wiki pages are checked against their wiki
+//! first. Routes taking the view password some other way (in their body) call [`check`] themselves.
+//!
+//! A [`Denied`] request is answered with the page shown instead of the paste, or, through
+//! `PasteError::from`, with the API's usual errors (the paste is hidden from clients that can't
+//! view it, so it looks like it doesn't exist).
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, Query},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::CookieJar;
+use pastemd::model::{Paste, PasteError};
+use serde::Deserialize;
+use starstraw::model::Profile;
+
+use crate::database::Database;
+use crate::model::Availability;
+
+/// How a request can unlock a paste (from its query)
+#[derive(Deserialize, Default, Clone)]
+pub struct AccessQuery {
+    #[serde(default)]
+    pub view_password: String,
+    /// The signature of a share link (see `crate::signing::sign_share_link`)
+    #[serde(default)]
+    pub sig: String,
+    /// When the share link expires (in milliseconds since the epoch)
+    #[serde(default)]
+    pub exp: u64,
+}
+
+/// Why a paste can't be viewed by the user sending the request
+pub enum Denied {
+    /// The paste couldn't be fetched
+    Missing(PasteError),
+    /// The paste can't be viewed from the client's network (see `ExtraMetadata::allowed_networks`)
+    Network,
+    /// The paste isn't visible to the user (its visibility, or it isn't published yet)
+    Hidden,
+    /// The paste is outside of its time window
+    Unavailable {
+        paste: Paste,
+        /// If the window hasn't opened yet (it's over otherwise)
+        opening: bool,
+        /// When the window opens (or closed)
+        time: u64,
+    },
+    /// The paste needs its view password
+    Password(Paste),
+}
+
+impl From<Denied> for PasteError {
+    fn from(denied: Denied) -> Self {
+        match denied {
+            Denied::Missing(e) => e,
+            Denied::Password(_) => PasteError::PasswordIncorrect,
+            _ => PasteError::NotFound,
+        }
+    }
+}
+
+impl IntoResponse for Denied {
+    fn into_response(self) -> Response {
+        crate::pages::denied_response(self)
+    }
+}
+
+/// Check if the user sending the request can view `paste`
+///
+/// # Arguments
+/// * `view_password` - the view password given with the request
+///
+/// # Returns
+/// * the user sending the request
+pub async fn check(
+    database: &Database,
+    jar: &CookieJar,
+    paste: &Paste,
+    view_password: &str,
+) -> Result<Option<Profile>, Denied> {
+    // get user from token
+    let auth_user = match jar.get("__Secure-Token") {
+        Some(c) => database
+            .base
+            .auth
+            .get_profile_by_unhashed(c.value_trimmed().to_string())
+            .await
+            .ok(),
+        None => None,
+    };
+
+    // a wiki page can only be viewed by those who can view its wiki (the page only copied the
+    // wiki's settings when it was added)
+    let mut unlocked = false;
+    if let Some(page) = database.get_page_by_url(paste.url.clone()).await {
+        if let Ok(namespace) = database.get_paste_by_url(page.namespace).await {
+            check_paste(
+                database,
+                auth_user.as_ref(),
+                &namespace,
+                view_password,
+                false,
+            )
+            .await?;
+            // (the wiki's view password also opens its pages, even if it changed since)
+            unlocked = !namespace.metadata.view_password.is_empty();
+        }
+    }
+
+    check_paste(database, auth_user.as_ref(), paste, view_password, unlocked).await?;
+    Ok(auth_user)
+}
+
+/// Check if `auth_user` can view `paste` on its own
+///
+/// # Arguments
+/// * `unlocked` - if the view password was already checked (for the paste's wiki)
+async fn check_paste(
+    database: &Database,
+    auth_user: Option<&Profile>,
+    paste: &Paste,
+    view_password: &str,
+    unlocked: bool,
+) -> Result<(), Denied> {
+    let extra = database.get_extra_metadata_by_url(paste.url.clone()).await;
+
+    // check the client's network (before anything else, even the password prompt)
+    if !extra.allows_network(crate::middleware::current_client_ip()) {
+        return Err(Denied::Network);
+    }
+
+    // check visibility
+    if !database.can_view_paste_in_window(paste, auth_user).await {
+        return Err(Denied::Hidden);
+    }
+
+    // check the time window
+    match database.get_availability(paste, auth_user).await {
+        Availability::Available => (),
+        availability => {
+            return Err(Denied::Unavailable {
+                paste: paste.clone(),
+                opening: availability == Availability::NotYet,
+                time: match availability {
+                    Availability::NotYet => extra.available_from,
+                    _ => extra.available_until,
+                },
+            })
+        }
+    }
+
+    // check for view password
+    if database.base.options.view_password
+        && !unlocked
+        && !paste.metadata.view_password.is_empty()
+        && (view_password != paste.metadata.view_password)
+    {
+        return Err(Denied::Password(paste.clone()));
+    }
+
+    Ok(())
+}
+
+/// A paste the user sending the request can view (see [`check`])
+pub struct ProtectedPaste {
+    pub paste: Paste,
+    /// The user sending the request
+    pub auth_user: Option<Profile>,
+    /// The query the paste was opened with (passed on to its attachments)
+    pub access: AccessQuery,
+}
+
+impl ProtectedPaste {
+    /// Check if the user sending the request can view `paste`, given the request's query
+    pub async fn new(
+        database: &Database,
+        jar: &CookieJar,
+        paste: Paste,
+        query: &AccessQuery,
+    ) -> Result<Self, Denied> {
+        // a share link stands in for the view password
+        let view_password = match crate::signing::verify_share_link(
+            database,
+            &paste,
+            &query.sig,
+            query.exp as u128,
+        )
+        .await
+        {
+            true => paste.metadata.view_password.as_str(),
+            false => query.view_password.as_str(),
+        };
+
+        let auth_user = check(database, jar, &paste, view_password).await?;
+        Ok(Self {
+            paste,
+            auth_user,
+            access: query.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Database> for ProtectedPaste {
+    type Rejection = Denied;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        database: &Database,
+    ) -> Result<Self, Self::Rejection> {
+        let url = match Path::<HashMap<String, String>>::from_request_parts(parts, database).await {
+            Ok(Path(mut params)) => params.remove("url").unwrap_or_default(),
+            Err(_) => String::new(),
+        };
+
+        // (a query that doesn't parse unlocks nothing)
+        let query = Query::<AccessQuery>::try_from_uri(&parts.uri)
+            .map(|q| q.0)
+            .unwrap_or_default();
+
+        let paste = database
+            .get_paste_by_url(url)
+            .await
+            .map_err(Denied::Missing)?;
+
+        let jar = CookieJar::from_headers(&parts.headers);
+        Self::new(database, &jar, paste, &query).await
+    }
+}
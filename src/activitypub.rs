@@ -0,0 +1,542 @@
+//! ActivityPub publishing (`ACTIVITYPUB`)
+//!
+//! The instance is a single `Service` actor (`@pastes@example.com`) that Fediverse users can
+//! follow. New public pastes are delivered to followers as `Article`s. The only activities
+//! accepted in the inbox are follows (and undoing them), which must carry a valid HTTP signature.
+use std::collections::HashMap;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use pastemd::model::Paste;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rsa::RsaPrivateKey;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::database::Database;
+use crate::markdown::parse_markdown;
+use crate::model::Visibility;
+use crate::signing;
+use crate::utility::{http_date, iso8601, parse_http_date};
+
+/// The `Content-Type` of ActivityPub documents
+const ACTIVITY_JSON: &str = "application/activity+json";
+/// The audience of public activities
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+/// How far the `Date` of a signed request can be from now (in milliseconds)
+const MAX_CLOCK_SKEW: u128 = 1000 * 60 * 60 * 12;
+/// The largest remote actor document fetched (in bytes)
+const MAX_ACTOR_SIZE: usize = 1024 * 1024;
+
+pub fn routes(database: Database) -> Router {
+    Router::new()
+        .route("/.well-known/webfinger", get(webfinger_request))
+        .route("/ap/actor", get(actor_request))
+        .route("/ap/inbox", post(inbox_request))
+        .route("/ap/outbox", get(outbox_request))
+        .route("/ap/followers", get(followers_request))
+        .route("/ap/pastes/:url", get(article_request))
+        .with_state(database)
+}
+
+/// Respond with an ActivityPub document
+fn activity_response(value: Value) -> Response {
+    ([(header::CONTENT_TYPE, ACTIVITY_JSON)], value.to_string()).into_response()
+}
+
+/// Check if a paste can be published (public, without a view password, viewable from any network,
+/// not scheduled for later and in its time window)
+async fn is_publishable(database: &Database, paste: &Paste) -> bool {
+    let extra = database.get_extra_metadata_by_url(paste.url.clone()).await;
+
+    paste.metadata.view_password.is_empty()
+        && (extra.visibility == Visibility::Public)
+        && extra.allowed_networks.trim().is_empty()
+        && (extra.publish_at as u128 <= dorsal::utility::unix_epoch_timestamp())
+        && (extra.availability() == crate::model::Availability::Available)
+}
+
+/// Build the `Article` of a paste
+fn article(paste: &Paste, origin: &str) -> Value {
+    let mut article = json!({
+        "id": format!("{origin}/ap/pastes/{}", paste.url),
+        "type": "Article",
+        "attributedTo": format!("{origin}/ap/actor"),
+        "name": match paste.metadata.title.is_empty() {
+            true => paste.url.clone(),
+            false => paste.metadata.title.clone(),
+        },
+        "content": parse_markdown(paste.content.clone()),
+        "mediaType": "text/html",
+        "source": {
+            "content": paste.content,
+            "mediaType": "text/markdown",
+        },
+        "url": format!("{origin}/{}", paste.url),
+        "published": iso8601(paste.date_published),
+        "to": [PUBLIC],
+        "cc": [format!("{origin}/ap/followers")],
+    });
+
+    if paste.date_edited != paste.date_published {
+        article["updated"] = json!(iso8601(paste.date_edited));
+    }
+
+    if !paste.metadata.description.is_empty() {
+        article["summary"] = json!(paste.metadata.description);
+    }
+
+    article
+}
+
+/// Wrap the `Article` of a paste in a `Create` activity
+fn create_activity(paste: &Paste, origin: &str) -> Value {
+    let object = article(paste, origin);
+
+    json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#create", object["id"].as_str().unwrap_or_default()),
+        "type": "Create",
+        "actor": format!("{origin}/ap/actor"),
+        "published": object["published"],
+        "to": [PUBLIC],
+        "cc": [format!("{origin}/ap/followers")],
+        "object": object,
+    })
+}
+
+// signatures
+
+/// Get the `Host` header the HTTP client sends for `url`
+fn host_of(url: &url::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    }
+}
+
+/// Sign an outgoing request as the instance actor
+///
+/// # Returns
+/// * the `Date`, `Digest` (if there's a body) and `Signature` headers to send
+fn sign_request(
+    key: &RsaPrivateKey,
+    key_id: &str,
+    method: &str,
+    url: &url::Url,
+    body: &[u8],
+) -> Vec<(String, String)> {
+    let date = http_date(dorsal::utility::unix_epoch_timestamp());
+    let target = match url.query() {
+        Some(query) => format!("{} {}?{query}", method.to_lowercase(), url.path()),
+        None => format!("{} {}", method.to_lowercase(), url.path()),
+    };
+
+    let mut headers = vec![("Date".to_string(), date.clone())];
+    let mut signed = "(request-target) host date".to_string();
+    let mut signing_string = format!(
+        "(request-target): {target}\nhost: {}\ndate: {date}",
+        host_of(url)
+    );
+
+    if !body.is_empty() {
+        let digest = format!("SHA-256={}", BASE64.encode(Sha256::digest(body)));
+        signing_string.push_str(&format!("\ndigest: {digest}"));
+        signed.push_str(" digest");
+        headers.push(("Digest".to_string(), digest));
+    }
+
+    let signature = signing::sign(key, signing_string.as_bytes());
+
+    headers.push((
+        "Signature".to_string(),
+        format!(
+            "keyId=\"{key_id}\",algorithm=\"rsa-sha256\",headers=\"{signed}\",signature=\"{}\"",
+            BASE64.encode(signature)
+        ),
+    ));
+
+    headers
+}
+
+/// Send a signed request as the instance actor
+async fn signed_request(
+    database: &Database,
+    origin: &str,
+    method: &'static str,
+    url: &str,
+    body: Vec<u8>,
+) -> Result<crate::http::HttpResponse, String> {
+    let key = signing::private_key(database)
+        .await
+        .ok_or("The instance actor has no key.")?;
+    let parsed = url::Url::parse(url).map_err(|_| "Invalid url.")?;
+
+    let mut headers = sign_request(
+        &key,
+        &format!("{origin}/ap/actor#main-key"),
+        method,
+        &parsed,
+        &body,
+    );
+
+    headers.push(("Accept".to_string(), ACTIVITY_JSON.to_string()));
+
+    if !body.is_empty() {
+        headers.push(("Content-Type".to_string(), ACTIVITY_JSON.to_string()));
+    }
+
+    crate::http::send(method, url.to_string(), headers, body, MAX_ACTOR_SIZE).await
+}
+
+/// Parse a `Signature` header into its parameters
+fn parse_signature(header: &str) -> HashMap<String, String> {
+    header
+        .split(',')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().trim_matches('"').to_string()))
+        .collect()
+}
+
+/// Verify the HTTP signature of a request to the inbox
+///
+/// # Returns
+/// * the actor document of the signer
+async fn verify_inbox_request(
+    database: &Database,
+    origin: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Value, String> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    let signature = parse_signature(header("signature"));
+    let (Some(key_id), Some(signed), Some(value)) = (
+        signature.get("keyId"),
+        signature.get("headers"),
+        signature.get("signature"),
+    ) else {
+        return Err("Missing signature.".to_string());
+    };
+
+    let signed: Vec<&str> = signed.split_whitespace().collect();
+    if !["(request-target)", "host", "date", "digest"]
+        .iter()
+        .all(|h| signed.contains(h))
+    {
+        return Err("Not enough headers are signed.".to_string());
+    }
+
+    // the body has to match the signed digest, and the signature can't be too old
+    let digest = format!("SHA-256={}", BASE64.encode(Sha256::digest(body)));
+    if header("digest") != digest {
+        return Err("Invalid digest.".to_string());
+    }
+
+    let now = dorsal::utility::unix_epoch_timestamp();
+    match parse_http_date(header("date")) {
+        Some(date) if date.abs_diff(now) < MAX_CLOCK_SKEW => (),
+        _ => return Err("Invalid date.".to_string()),
+    }
+
+    let signing_string = signed
+        .iter()
+        .map(|h| match *h {
+            "(request-target)" => "(request-target): post /ap/inbox".to_string(),
+            h => format!("{h}: {}", header(h)),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    // the key is expected to be listed in its actor's document (`https://actor#main-key`)
+    let actor_url = key_id.split('#').next().unwrap_or_default();
+    let response = signed_request(database, origin, "GET", actor_url, Vec::new()).await?;
+
+    if !response.is_success() {
+        return Err("Failed to fetch the actor.".to_string());
+    }
+
+    let actor: Value =
+        serde_json::from_slice(&response.body).map_err(|_| "Invalid actor document.")?;
+
+    if actor["publicKey"]["id"].as_str() != Some(key_id.as_str()) {
+        return Err("The key doesn't belong to the actor.".to_string());
+    }
+
+    let pem = actor["publicKey"]["publicKeyPem"]
+        .as_str()
+        .unwrap_or_default();
+    let key = signing::parse_public_key(pem).ok_or("Invalid public key.")?;
+
+    let value = BASE64.decode(value).map_err(|_| "Invalid signature.")?;
+    if !signing::verify(&key, signing_string.as_bytes(), &value) {
+        return Err("Invalid signature.".to_string());
+    }
+
+    Ok(actor)
+}
+
+/// Deliver an activity to inboxes (in the background)
+fn deliver(database: Database, origin: String, activity: Value, mut inboxes: Vec<String>) {
+    inboxes.sort();
+    inboxes.dedup();
+
+    tokio::spawn(async move {
+        let body = activity.to_string().into_bytes();
+
+        for inbox in inboxes {
+            // followers that can't be reached are kept, they might be back later
+            let _ = signed_request(&database, &origin, "POST", &inbox, body.clone()).await;
+        }
+    });
+}
+
+/// Deliver a new paste to the instance actor's followers
+///
+/// Does nothing if ActivityPub is disabled or the paste isn't public.
+pub async fn publish_paste(database: &Database, paste: &Paste, origin: &str) {
+    if !crate::config::activitypub_enabled() || !is_publishable(database, paste).await {
+        return;
+    }
+
+    let inboxes: Vec<String> = database
+        .get_ap_followers()
+        .await
+        .into_iter()
+        .map(|(_, inbox)| inbox)
+        .collect();
+
+    if inboxes.is_empty() {
+        return;
+    }
+
+    deliver(
+        database.clone(),
+        origin.to_string(),
+        create_activity(paste, origin),
+        inboxes,
+    );
+}
+
+// routes
+
+#[derive(Deserialize)]
+pub struct WebfingerQuery {
+    #[serde(default)]
+    pub resource: String,
+}
+
+/// Find the instance actor (`/.well-known/webfinger?resource=acct:pastes@example.com`)
+pub async fn webfinger_request(
+    headers: HeaderMap,
+    Query(query): Query<WebfingerQuery>,
+) -> Response {
+    let base = crate::base::BaseStore::new();
+    let origin = base.origin(&headers);
+    let host = url::Url::parse(&origin)
+        .map(|u| host_of(&u))
+        .unwrap_or_default();
+
+    let subject = format!("acct:{}@{host}", base.activitypub_username);
+    let actor = format!("{origin}/ap/actor");
+
+    if (query.resource != subject) && (query.resource != actor) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    (
+        [(header::CONTENT_TYPE, "application/jrd+json")],
+        json!({
+            "subject": subject,
+            "aliases": [actor],
+            "links": [{
+                "rel": "self",
+                "type": ACTIVITY_JSON,
+                "href": actor,
+            }],
+        })
+        .to_string(),
+    )
+        .into_response()
+}
+
+/// The instance actor (`/ap/actor`)
+pub async fn actor_request(headers: HeaderMap, State(database): State<Database>) -> Response {
+    let base = crate::base::BaseStore::new();
+    let origin = base.origin(&headers);
+
+    let public_key = match signing::public_key_pem(&database).await {
+        Some(pem) => pem,
+        None => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    activity_response(json!({
+        "@context": [
+            "https://www.w3.org/ns/activitystreams",
+            "https://w3id.org/security/v1",
+        ],
+        "id": format!("{origin}/ap/actor"),
+        "type": "Service",
+        "preferredUsername": base.activitypub_username,
+        "name": base.site_name,
+        "summary": base.t("activitypub.summary"),
+        "url": format!("{origin}/"),
+        "inbox": format!("{origin}/ap/inbox"),
+        "outbox": format!("{origin}/ap/outbox"),
+        "followers": format!("{origin}/ap/followers"),
+        "manuallyApprovesFollowers": false,
+        "publicKey": {
+            "id": format!("{origin}/ap/actor#main-key"),
+            "owner": format!("{origin}/ap/actor"),
+            "publicKeyPem": public_key,
+        },
+    }))
+}
+
+/// Receive an activity (`/ap/inbox`)
+pub async fn inbox_request(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    body: Bytes,
+) -> Response {
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    let actor_id = format!("{origin}/ap/actor");
+
+    let activity: Value = match serde_json::from_slice(&body) {
+        Ok(a) => a,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let is_follow = |v: &Value| {
+        v["type"] == "Follow"
+            && (v["object"] == actor_id.as_str() || v["object"]["id"] == actor_id.as_str())
+    };
+
+    let undo = activity["type"] == "Undo";
+    let handled = is_follow(&activity) || (undo && is_follow(&activity["object"]));
+    if !handled {
+        // nothing else is handled
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    let actor = match verify_inbox_request(&database, &origin, &headers, &body).await {
+        Ok(a) => a,
+        Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
+    };
+
+    // the signer has to be the actor doing the (un)following
+    let id = actor["id"].as_str().unwrap_or_default().to_string();
+    if (activity["actor"] != id.as_str()) || (undo && activity["object"]["actor"] != id.as_str()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    if undo {
+        return match database.remove_ap_follower(id).await {
+            Ok(_) => StatusCode::ACCEPTED.into_response(),
+            Err(e) => e.into_response(),
+        };
+    }
+
+    let Some(inbox) = actor["endpoints"]["sharedInbox"]
+        .as_str()
+        .or(actor["inbox"].as_str())
+        .map(|i| i.to_string())
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    if let Err(e) = database.add_ap_follower(id, inbox.clone()).await {
+        return e.into_response();
+    }
+
+    let accept_id: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+
+    deliver(
+        database.clone(),
+        origin.clone(),
+        json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "id": format!("{origin}/ap/actor#accepts/{accept_id}"),
+            "type": "Accept",
+            "actor": actor_id,
+            "object": activity,
+        }),
+        vec![actor["inbox"].as_str().unwrap_or(&inbox).to_string()],
+    );
+
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Recently published pastes (`/ap/outbox`)
+pub async fn outbox_request(headers: HeaderMap, State(database): State<Database>) -> Response {
+    let origin = crate::base::BaseStore::new().origin(&headers);
+
+    let items: Vec<Value> = database
+        .get_recent_public_pastes()
+        .await
+        .iter()
+        .map(|p| create_activity(p, &origin))
+        .collect();
+
+    activity_response(json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{origin}/ap/outbox"),
+        "type": "OrderedCollection",
+        "totalItems": items.len(),
+        "orderedItems": items,
+    }))
+}
+
+/// Followers of the instance actor (`/ap/followers`), only their count is shown
+pub async fn followers_request(headers: HeaderMap, State(database): State<Database>) -> Response {
+    let origin = crate::base::BaseStore::new().origin(&headers);
+
+    activity_response(json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{origin}/ap/followers"),
+        "type": "OrderedCollection",
+        "totalItems": database.get_ap_followers().await.len(),
+    }))
+}
+
+/// The `Article` of a public paste (`/ap/pastes/:url`)
+pub async fn article_request(
+    headers: HeaderMap,
+    Path(url): Path<String>,
+    State(database): State<Database>,
+) -> Response {
+    let paste = match database.get_paste_by_url(url).await {
+        Ok(p) => p,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    if !is_publishable(&database, &paste).await {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+
+    let mut object = article(&paste, &origin);
+    object["@context"] = json!("https://www.w3.org/ns/activitystreams");
+    activity_response(object)
+}
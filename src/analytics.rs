@@ -0,0 +1,448 @@
+//! Per-paste view analytics (`/:url/stats`)
+//!
+//! Each view of a paste's page is added to a daily bucket in `se_view_days`, by visitor and by
+//! referring site. Visitors are a keyed hash of their address and user agent which changes every
+//! day, so the same visitor is only recognized within a day, and nothing else is kept about them
+//! (no addresses, no location). Referrers are kept as their host only.
+//!
+//! A view is described ([`view`]) while its request is handled, and counted ([`record`]) by a
+//! listener of `crate::events::Event::Viewed`. Like view counts (see [`crate::views`]), buckets
+//! are added up in memory and written when the view counts are, and they're dropped after
+//! [`RETENTION_DAYS`].
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use axum::http::{header, HeaderMap};
+use dorsal::query as sqlquery;
+use dorsal::utility::unix_epoch_timestamp;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::OnceCell;
+
+use crate::database::Database;
+use crate::model::{DayStats, PasteStats, ReferrerCount};
+
+/// How long a day is (in milliseconds)
+const DAY: u128 = 24 * 60 * 60 * 1000;
+
+/// How many days are shown on the stats page (today included)
+pub const STATS_DAYS: u128 = 30;
+
+/// How many days of buckets are kept
+pub const RETENTION_DAYS: u128 = 90;
+
+/// How many referrers are shown on the stats page
+const TOP_REFERRERS: usize = 10;
+
+/// Name of the setting the visitor hash key is stored in
+const KEY_SETTING: &str = "analytics_key";
+
+/// Where some views of a paste came from on a given day
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct Bucket {
+    url: String,
+    /// The start of the day (in milliseconds since the epoch, UTC)
+    day: u128,
+    /// The visitor's hash (see [`visitor`])
+    visitor: String,
+    /// The host of the referring site (empty for none)
+    referrer: String,
+}
+
+/// Who viewed a paste and where they came from (sent with `Event::Viewed`)
+#[derive(Debug, Clone)]
+pub struct View {
+    /// The start of the day of the view
+    day: u128,
+    /// The visitor's hash (see [`visitor`])
+    visitor: String,
+    /// The host of the referring site (empty for none)
+    referrer: String,
+}
+
+/// Views waiting to be written, by bucket
+static PENDING: LazyLock<Mutex<HashMap<Bucket, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The last day old buckets were dropped on (see [`purge`])
+static PURGED: AtomicU64 = AtomicU64::new(0);
+
+/// The visitor hash key (loaded or generated on first use)
+static KEY: OnceCell<Option<String>> = OnceCell::const_new();
+
+/// Get the visitor hash key
+async fn key(database: &Database) -> Option<String> {
+    KEY.get_or_init(|| async {
+        if let Some(key) = database.get_setting(KEY_SETTING).await {
+            return Some(key);
+        }
+
+        let key = dorsal::utility::random_id();
+        database.set_setting(KEY_SETTING, key.clone()).await.ok()?;
+        Some(key)
+    })
+    .await
+    .clone()
+}
+
+/// Get the hash of the client sending the current request on `day`
+async fn visitor(database: &Database, day: u128, headers: &HeaderMap) -> String {
+    let Some(key) = key(database).await else {
+        return String::new();
+    };
+
+    let ip = crate::middleware::current_client_ip()
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("any key length works");
+    mac.update(format!("{day}\n{ip}\n{user_agent}").as_bytes());
+
+    let hash = format!("{:x}", mac.finalize().into_bytes());
+    hash[..16].to_string()
+}
+
+/// Get the host of the site that linked to the current request (empty for none, or the instance
+/// itself)
+fn referrer(headers: &HeaderMap) -> String {
+    let Some(host) = headers
+        .get(header::REFERER)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|r| url::Url::parse(r).ok())
+        .and_then(|r| r.host_str().map(|h| h.to_lowercase()))
+    else {
+        return String::new();
+    };
+
+    let own_host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.split(':').next().unwrap_or(h).to_lowercase());
+
+    match own_host {
+        Some(own_host) if own_host == host => String::new(),
+        _ => host,
+    }
+}
+
+/// Describe the view of a paste by the client sending the current request (with `headers`)
+pub async fn view(database: &Database, headers: &HeaderMap) -> View {
+    let now = unix_epoch_timestamp();
+    let day = now - (now % DAY);
+
+    View {
+        day,
+        visitor: visitor(database, day, headers).await,
+        referrer: referrer(headers),
+    }
+}
+
+/// Count a view of the paste at `url`
+pub async fn record(database: &Database, url: &str, view: View) {
+    let bucket = Bucket {
+        url: url.to_string(),
+        day: view.day,
+        visitor: view.visitor,
+        referrer: view.referrer,
+    };
+
+    if !crate::views::buffering() {
+        write(database, HashMap::from([(bucket, 1)])).await;
+        purge(database).await;
+        return;
+    }
+
+    *PENDING.lock().unwrap().entry(bucket).or_default() += 1;
+}
+
+/// Write buckets, returning the ones that couldn't be
+async fn write(database: &Database, batch: HashMap<Bucket, u32>) -> HashMap<Bucket, u32> {
+    let query: String =
+        if (database.base.base.db._type == "sqlite") | (database.base.base.db._type == "mysql") {
+            "INSERT INTO \"se_view_days\" VALUES (?, ?, ?, ?, ?)"
+        } else {
+            "INSERT INTO \"se_view_days\" VALUES ($1, $2, $3, $4, $5)"
+        }
+        .to_string();
+
+    let c = &database.base.base.db.client;
+    let mut failed = HashMap::new();
+
+    for (bucket, views) in batch {
+        if sqlquery(&query)
+            .bind::<&String>(&bucket.url)
+            .bind::<String>(bucket.day.to_string())
+            .bind::<&String>(&bucket.visitor)
+            .bind::<&String>(&bucket.referrer)
+            .bind::<String>(views.to_string())
+            .execute(c)
+            .await
+            .is_err()
+        {
+            failed.insert(bucket, views);
+        }
+    }
+
+    failed
+}
+
+/// Write every waiting bucket (and drop the ones older than [`RETENTION_DAYS`])
+///
+/// Buckets that fail to be written are kept for the next flush.
+pub async fn flush(database: &Database) {
+    let batch = std::mem::take(&mut *PENDING.lock().unwrap());
+
+    if batch.is_empty() {
+        return;
+    }
+
+    let failed = write(database, batch).await;
+
+    if !failed.is_empty() {
+        tracing::warn!(
+            buckets = failed.len(),
+            "Failed to write view analytics, retrying later."
+        );
+
+        let mut pending = PENDING.lock().unwrap();
+        for (bucket, views) in failed {
+            *pending.entry(bucket).or_default() += views;
+        }
+    }
+
+    purge(database).await;
+}
+
+/// Drop the buckets older than [`RETENTION_DAYS`] (once a day)
+async fn purge(database: &Database) {
+    let now = unix_epoch_timestamp();
+    let today = now - (now % DAY);
+
+    if PURGED.swap(today as u64, Ordering::Relaxed) == today as u64 {
+        return;
+    }
+
+    // (days are stored as text, which compares like the numbers while they have 13 digits)
+    let query: String =
+        if (database.base.base.db._type == "sqlite") | (database.base.base.db._type == "mysql") {
+            "DELETE FROM \"se_view_days\" WHERE \"day\" < ?"
+        } else {
+            "DELETE FROM \"se_view_days\" WHERE \"day\" < $1"
+        }
+        .to_string();
+
+    let _ = sqlquery(&query)
+        .bind::<String>((today - (RETENTION_DAYS * DAY)).to_string())
+        .execute(&database.base.base.db.client)
+        .await;
+}
+
+/// Move the buckets of a paste to its new url (after the paste is renamed)
+///
+/// # Arguments
+/// * `url` - the paste's old `url`
+/// * `new_url` - the paste's new `url`
+pub async fn rename(database: &Database, url: &str, new_url: &str) {
+    {
+        let mut pending = PENDING.lock().unwrap();
+        let moved: Vec<(Bucket, u32)> = pending
+            .iter()
+            .filter(|(b, _)| b.url == url)
+            .map(|(b, v)| (b.clone(), *v))
+            .collect();
+
+        for (bucket, views) in moved {
+            pending.remove(&bucket);
+            *pending
+                .entry(Bucket {
+                    url: new_url.to_string(),
+                    ..bucket
+                })
+                .or_default() += views;
+        }
+    }
+
+    let query: String =
+        if (database.base.base.db._type == "sqlite") | (database.base.base.db._type == "mysql") {
+            "UPDATE \"se_view_days\" SET \"url\" = ? WHERE \"url\" = ?"
+        } else {
+            "UPDATE \"se_view_days\" SET \"url\" = $1 WHERE \"url\" = $2"
+        }
+        .to_string();
+
+    let _ = sqlquery(&query)
+        .bind::<&str>(new_url)
+        .bind::<&str>(url)
+        .execute(&database.base.base.db.client)
+        .await;
+}
+
+/// Delete the buckets of a paste (after the paste is deleted)
+///
+/// # Arguments
+/// * `url` - the paste's (canonical) `url` field
+pub async fn forget(database: &Database, url: &str) {
+    PENDING.lock().unwrap().retain(|b, _| b.url != url);
+
+    let query: String =
+        if (database.base.base.db._type == "sqlite") | (database.base.base.db._type == "mysql") {
+            "DELETE FROM \"se_view_days\" WHERE \"url\" = ?"
+        } else {
+            "DELETE FROM \"se_view_days\" WHERE \"url\" = $1"
+        }
+        .to_string();
+
+    let _ = sqlquery(&query)
+        .bind::<&str>(url)
+        .execute(&database.base.base.db.client)
+        .await;
+}
+
+/// Get how many different visitors viewed a paste each day, added up over the days that are still
+/// kept (see [`RETENTION_DAYS`])
+///
+/// # Arguments
+/// * `url` - the paste's (canonical) `url` field
+pub async fn unique_views(database: &Database, url: &str) -> u64 {
+    let query: String =
+        if (database.base.base.db._type == "sqlite") | (database.base.base.db._type == "mysql") {
+            "SELECT DISTINCT \"day\", \"visitor\" FROM \"se_view_days\" WHERE \"url\" = ?"
+        } else {
+            "SELECT DISTINCT \"day\", \"visitor\" FROM \"se_view_days\" WHERE \"url\" = $1"
+        }
+        .to_string();
+
+    let c = &database.base.base.db.client;
+    let mut visits: Vec<(String, String)> =
+        match sqlquery(&query).bind::<&str>(url).fetch_all(c).await {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| {
+                    let row = database.base.base.textify_row(r).data;
+                    Some((row.get("day")?.to_string(), row.get("visitor")?.to_string()))
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+    visits.extend(
+        PENDING
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|b| b.url == url)
+            .map(|b| (b.day.to_string(), b.visitor.clone())),
+    );
+
+    visits.sort_unstable();
+    visits.dedup();
+    visits.len() as u64
+}
+
+/// Get the [`PasteStats`] of a paste over the last [`STATS_DAYS`] (with the views that weren't
+/// written yet)
+///
+/// # Arguments
+/// * `url` - the paste's (canonical) `url` field
+pub async fn paste_stats(database: &Database, url: String) -> PasteStats {
+    let now = unix_epoch_timestamp();
+    let today = now - (now % DAY);
+    let first_day = today - ((STATS_DAYS - 1) * DAY);
+
+    let query: String =
+        if (database.base.base.db._type == "sqlite") | (database.base.base.db._type == "mysql") {
+            "SELECT * FROM \"se_view_days\" WHERE \"url\" = ? AND \"day\" >= ?"
+        } else {
+            "SELECT * FROM \"se_view_days\" WHERE \"url\" = $1 AND \"day\" >= $2"
+        }
+        .to_string();
+
+    let c = &database.base.base.db.client;
+    let mut buckets: Vec<(Bucket, u32)> = match sqlquery(&query)
+        .bind::<&String>(&url)
+        .bind::<String>(first_day.to_string())
+        .fetch_all(c)
+        .await
+    {
+        Ok(rows) => rows
+            .into_iter()
+            .filter_map(|r| {
+                let row = database.base.base.textify_row(r).data;
+                Some((
+                    Bucket {
+                        url: url.clone(),
+                        day: row.get("day")?.parse().ok()?,
+                        visitor: row.get("visitor")?.to_string(),
+                        referrer: row.get("referrer")?.to_string(),
+                    },
+                    row.get("views")?.parse().ok()?,
+                ))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    buckets.extend(
+        PENDING
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(b, _)| b.url == url)
+            .map(|(b, v)| (b.clone(), *v)),
+    );
+
+    // add them up
+    let days = (0..STATS_DAYS)
+        .map(|i| {
+            let day = first_day + (i * DAY);
+            let of_day = buckets.iter().filter(|(b, _)| b.day == day);
+
+            let mut visitors: Vec<&str> = of_day.clone().map(|(b, _)| b.visitor.as_str()).collect();
+            visitors.sort_unstable();
+            visitors.dedup();
+
+            DayStats {
+                day,
+                views: of_day.map(|(_, v)| *v as u64).sum(),
+                visitors: visitors.len() as u64,
+            }
+        })
+        .collect::<Vec<DayStats>>();
+
+    let mut referrers: HashMap<&str, u64> = HashMap::new();
+    for (bucket, views) in &buckets {
+        if !bucket.referrer.is_empty() {
+            *referrers.entry(bucket.referrer.as_str()).or_default() += *views as u64;
+        }
+    }
+
+    let mut referrers: Vec<ReferrerCount> = referrers
+        .into_iter()
+        .map(|(host, views)| ReferrerCount {
+            host: host.to_string(),
+            views,
+        })
+        .collect();
+
+    referrers.sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.host.cmp(&b.host)));
+    referrers.truncate(TOP_REFERRERS);
+
+    PasteStats {
+        total_views: database.get_views_by_url(url).await.max(0) as u64,
+        views: days.iter().map(|d| d.views).sum(),
+        visitors: days.iter().map(|d| d.visitors).sum(),
+        direct_views: buckets
+            .iter()
+            .filter(|(b, _)| b.referrer.is_empty())
+            .map(|(_, v)| *v as u64)
+            .sum(),
+        days,
+        referrers,
+    }
+}
@@ -0,0 +1,2462 @@
+//! Responds to sealable-specific API requests (mounted next to the pastemd API)
+use crate::access::{Denied, ProtectedPaste};
+use crate::database::Database;
+use crate::markdown::content_stats;
+use crate::model::{
+    Announcement, Collection, CollectionCreate, CollectionEdit, Draft, DraftEdit, FullMetadataEdit,
+    MaintenanceEdit, ExtraMetadata, MirrorExportQuery, MirrorRequest, OEmbed, OEmbedQuery,
+    AliasEdit, Attachment, AttachmentDelete, Comment, CommentCreate, CommentDelete, CommentPolicy,
+    EditLink, EditLinkCreate, PasteEditBody, ShareLink, ShareLinkCreate, NewPaste, ReactionCount,
+    ReactionToggle, NewPasteFile, PasteFile, PasteFileDelete, PasteFileEdit, PasteFork,
+    PasteImport, PasteListing, PastePage, PastePageQuery, PasteRename, PasteReport, PasteStats,
+    PasteStatsRequest, PasteViews, PublicPasteInfo, SignedMirror, Visibility, Webhook,
+    WebhookCreate, WikiPage, WikiPageCreate, WikiPageDelete,
+};
+use crate::events::Event;
+use crate::schema::FieldError;
+use dorsal::DefaultReturn;
+use pastemd::model::{Paste, PasteClone, PasteCreate, PasteDelete, PasteError, PasteMetadata};
+use starstraw::model::{Profile, SkillManager, SkillName};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use axum_extra::extract::cookie::CookieJar;
+
+pub fn routes(config: &crate::config::AppConfig) -> Router {
+    let mut router = Router::new()
+        .route("/new", post(create_paste))
+        .route("/clone", post(clone_paste))
+        .route("/import/url", post(import_paste))
+        .route("/plain", post(plain_upload))
+        // pastes
+        .route("/:url", get(get_paste_by_url))
+        .route("/:url/delete", post(delete_paste_by_url))
+        .route("/:url/edit", post(edit_paste_by_url))
+        .route("/:url/edit_links", post(create_edit_link))
+        .route("/:url/share_links", post(create_share_link))
+        .route("/:url/metadata", post(edit_paste_metadata_by_url))
+        .route("/:url/fork", post(fork_paste_by_url))
+        .route("/:url/rename", post(rename_paste_by_url))
+        .route(
+            "/:url/attachments",
+            get(get_attachments).post(upload_attachments),
+        )
+        .route("/:url/attachments/:name/delete", post(delete_attachment))
+        .route("/:url/files", get(get_files).post(save_file))
+        .route("/:url/files/:name/delete", post(delete_file))
+        .route("/:url/pages", get(get_pages).post(create_page))
+        .route("/:url/pages/:name/delete", post(delete_page))
+        .route("/:url/comments", get(get_comments).post(create_comment))
+        .route("/:url/reactions", get(get_reactions).post(toggle_reaction))
+        .route("/:url/comments/:id/delete", post(delete_comment))
+        .route("/:url/aliases", get(get_aliases).post(create_alias))
+        .route("/:url/aliases/delete", post(delete_alias))
+        .route("/:url/shortlink", post(create_shortlink))
+        .route("/:url/report", post(report_paste_by_url))
+        .route("/:url/stats", post(get_paste_stats))
+        // collections
+        .route("/c/new", post(create_collection))
+        .route("/c/:name", get(get_collection_by_name))
+        .route("/c/:name/edit", post(edit_collection_by_name))
+        .route("/c/:name/delete", post(delete_collection_by_name))
+        // embeds
+        .route("/oembed", get(oembed_request))
+        // schemas
+        .route("/schema/metadata", get(metadata_schema_request))
+        // stats
+        .route("/v1/stats", get(instance_stats_request))
+        .route("/v1/pastes/:url/views", get(get_paste_views))
+        // users
+        .route("/v1/users/:name/pastes", get(get_user_pastes))
+        .route("/v1/me/pastes", get(get_my_pastes))
+        // admin
+        .route("/admin/maintenance", post(set_maintenance))
+        .route("/admin/webhooks", get(get_webhooks).post(create_webhook))
+        .route("/admin/webhooks/:id/delete", post(delete_webhook))
+        .route("/admin/announcement", post(set_announcement));
+
+    if config.mirroring {
+        router = router
+            .route("/mirror/key", get(mirror_key_request))
+            .route("/mirror/export", get(mirror_export_request))
+            .route("/mirror", post(mirror_request))
+            .route("/admin/mirror/push", post(mirror_push_request))
+            .route("/admin/mirror/pull", post(mirror_pull_request));
+    }
+
+    // ...
+    router.with_state(config.database.clone())
+}
+
+/// Get the user we're editing a paste as
+///
+/// Sending an invalid token is an error (instead of falling back to password auth).
+pub(crate) async fn get_editing_as(
+    jar: &CookieJar,
+    database: &Database,
+) -> Result<Option<Profile>, PasteError> {
+    if let Some(cookie) = jar.get("__Secure-Token") {
+        if database.base.options.starstraw {
+            return match database
+                .base
+                .auth
+                .get_profile_by_unhashed(cookie.value_trimmed().to_string())
+                .await
+            {
+                Ok(ua) => Ok(Some(ua)),
+                Err(_) => Err(PasteError::Other),
+            };
+        }
+    }
+
+    Ok(None)
+}
+
+/// Get the username of the user sending the request (if authenticated)
+async fn get_username(jar: &CookieJar, database: &Database) -> Option<String> {
+    if !database.base.options.starstraw {
+        return None;
+    }
+
+    match jar.get("__Secure-Token") {
+        Some(c) => database
+            .base
+            .auth
+            .get_profile_by_unhashed(c.value_trimmed().to_string())
+            .await
+            .ok()
+            .map(|ua| ua.username),
+        None => None,
+    }
+}
+
+/// Get who the user sending the request is, for what's kept for them (drafts, the comments they
+/// can delete): their account, or their browser (by its CSRF cookie) when they aren't logged in
+pub(crate) async fn session_owner(
+    jar: &CookieJar,
+    database: &Database,
+) -> Result<String, PasteError> {
+    if let Some(username) = get_username(jar, database).await {
+        return Ok(format!("user:{username}"));
+    }
+
+    match jar.get("csrf").map(|c| c.value_trimmed()) {
+        Some(token) if !token.is_empty() => Ok(format!(
+            "browser:{}",
+            dorsal::utility::hash(token.to_string())
+        )),
+        _ => Err(PasteError::Other),
+    }
+}
+
+/// Get the draft of a paste (`GET /:url/draft`, `/new/draft` for a new paste)
+pub async fn get_draft(
+    jar: CookieJar,
+    State(database): State<Database>,
+    url: Option<Path<String>>,
+) -> Result<Json<DefaultReturn<Draft>>, PasteError> {
+    let owner = session_owner(&jar, &database).await?;
+    let url = url.map(|Path(url)| url).unwrap_or_default();
+
+    match database.get_draft(&owner, &url).await {
+        Some(draft) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Draft exists"),
+            payload: draft,
+        })),
+        None => Err(PasteError::NotFound),
+    }
+}
+
+/// Save the draft of a paste (`PUT /:url/draft`, `/new/draft` for a new paste)
+///
+/// Drafts are never published, the editor saves them while the paste is being written.
+pub async fn save_draft(
+    jar: CookieJar,
+    State(database): State<Database>,
+    url: Option<Path<String>>,
+    Json(props): Json<DraftEdit>,
+) -> Result<Json<DefaultReturn<u128>>, PasteError> {
+    let owner = session_owner(&jar, &database).await?;
+    let url = url.map(|Path(url)| url).unwrap_or_default();
+
+    let draft = database.save_draft(&owner, &url, props.content).await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Draft saved"),
+        payload: draft.date_updated,
+    }))
+}
+
+/// Throw away the draft of a paste (`DELETE /:url/draft`, `/new/draft` for a new paste)
+pub async fn delete_draft(
+    jar: CookieJar,
+    State(database): State<Database>,
+    url: Option<Path<String>>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let owner = session_owner(&jar, &database).await?;
+    let url = url.map(|Path(url)| url).unwrap_or_default();
+
+    database.delete_draft(&owner, &url).await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Draft deleted"),
+        payload: (),
+    }))
+}
+
+/// Check if the user sending the request is an instance administrator
+async fn is_admin(jar: &CookieJar, database: &Database) -> bool {
+    match get_editing_as(jar, database).await {
+        Ok(Some(ua)) => SkillManager(ua.skills).has_skill(SkillName::Absolute),
+        _ => false,
+    }
+}
+
+/// Enable or disable maintenance mode (`/api/admin/maintenance`)
+async fn set_maintenance(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Json(props): Json<MaintenanceEdit>,
+) -> Result<Json<DefaultReturn<bool>>, PasteError> {
+    if !is_admin(&jar, &database).await {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    match database.set_maintenance(props.enabled).await {
+        Ok(_) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Maintenance mode updated"),
+            payload: props.enabled,
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Set the instance announcement (`/api/admin/announcement`)
+async fn set_announcement(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Json(props): Json<Announcement>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    if !is_admin(&jar, &database).await {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    match database.set_announcement(props).await {
+        Ok(_) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Announcement updated"),
+            payload: (),
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Wrap the content of an uploaded file in a code block, so it's highlighted as its extension
+///
+/// Markdown and text files (and files without an extension) are left as they are.
+fn file_content(filename: &str, content: String) -> String {
+    let extension = match filename.rsplit_once('.') {
+        Some((name, e)) if !name.is_empty() => e.to_lowercase(),
+        _ => return content,
+    };
+
+    if matches!(extension.as_str(), "md" | "markdown" | "txt" | "text")
+        || !extension.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return content;
+    }
+
+    // the fence has to be longer than any run of backticks in the file
+    let mut fence = String::from("```");
+    while content.contains(&fence) {
+        fence.push('`');
+    }
+
+    format!(
+        "{fence}{extension}\n{}\n{fence}",
+        content.trim_end_matches('\n')
+    )
+}
+
+/// Read the paste to create from a `multipart/form-data` body
+///
+/// The content is either a `file` field or a `content` field. Uploaded files are titled after
+/// their name, and any `file` after the first one becomes one of the paste's files.
+///
+/// # Returns
+/// * the paste to create, its title and its other files
+fn multipart_paste(
+    content_type: &str,
+    body: &[u8],
+) -> Result<(PasteCreate, String, Vec<NewPasteFile>), PasteError> {
+    let parts = crate::multipart::parse(content_type, body).ok_or(PasteError::ValueError)?;
+    let text = |name: &str| {
+        crate::multipart::field(&parts, name)
+            .map(|f| String::from_utf8_lossy(&f.data).to_string())
+            .unwrap_or_default()
+    };
+
+    let mut title = text("title");
+    let content = match crate::multipart::field(&parts, "file") {
+        Some(file) => {
+            let content = String::from_utf8(file.data.clone())
+                .map_err(|_| PasteError::ValueError)?
+                .trim_start_matches('\u{feff}')
+                .to_string();
+
+            let filename = file.filename.clone().unwrap_or_default();
+            if title.is_empty() {
+                title = filename.clone();
+            }
+
+            file_content(&filename, content)
+        }
+        None => text("content"),
+    };
+
+    let files = parts
+        .iter()
+        .filter(|p| p.name == "file")
+        .skip(1)
+        .map(|file| {
+            Ok(NewPasteFile {
+                name: file.filename.clone().unwrap_or_default(),
+                content: String::from_utf8(file.data.clone())
+                    .map_err(|_| PasteError::ValueError)?
+                    .trim_start_matches('\u{feff}')
+                    .to_string(),
+            })
+        })
+        .collect::<Result<Vec<NewPasteFile>, PasteError>>()?;
+
+    Ok((
+        PasteCreate {
+            url: text("url"),
+            content,
+            password: text("password"),
+        },
+        title,
+        files,
+    ))
+}
+
+/// Give the metadata of a new paste the presentation of an existing one (title, description,
+/// theme, language and direction), never its passwords, owner or visibility
+///
+/// # Arguments
+/// * `from` - the existing paste
+/// * `metadata` - the rest of the new paste's metadata
+/// * `extra` - the rest of the new paste's extra metadata
+async fn copy_presentation(
+    database: &Database,
+    from: &Paste,
+    metadata: PasteMetadata,
+    extra: ExtraMetadata,
+) -> (PasteMetadata, ExtraMetadata) {
+    let from_extra = database.get_extra_metadata_by_url(from.url.clone()).await;
+
+    (
+        PasteMetadata {
+            title: from.metadata.title.clone(),
+            description: from.metadata.description.clone(),
+            theme_color: from.metadata.theme_color.clone(),
+            favicon: from.metadata.favicon.clone(),
+            ..metadata
+        },
+        ExtraMetadata {
+            direction: from_extra.direction,
+            lang: from_extra.lang,
+            language: match from_extra.language.is_empty() {
+                true => extra.language,
+                false => from_extra.language,
+            },
+            ..extra
+        },
+    )
+}
+
+/// Save the files of a paste that was just created, deleting the paste if one of them can't be
+/// saved (a paste missing some of its files would be worse than none)
+///
+/// # Arguments
+/// * `files` - the name and content of each file
+async fn save_new_files(
+    database: &Database,
+    url: &str,
+    files: impl IntoIterator<Item = (String, String)>,
+) -> Result<(), PasteError> {
+    for (name, content) in files {
+        if let Err(e) = database.save_file(url.to_string(), &name, content).await {
+            let _ = database.force_delete_paste_by_url(url.to_string()).await;
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// The error of routes taking paste content: a [`PasteError`], content over
+/// `MAX_CONTENT_SIZE` (reported with the limit, instead of `pastemd`'s invalid value), or
+/// metadata that doesn't match its schema (reported field by field)
+pub enum ContentError {
+    Paste(PasteError),
+    TooLarge,
+    InvalidMetadata(Vec<FieldError>),
+}
+
+impl From<PasteError> for ContentError {
+    fn from(e: PasteError) -> Self {
+        Self::Paste(e)
+    }
+}
+
+impl ContentError {
+    /// Get the message of the error
+    fn message(&self) -> String {
+        match self {
+            Self::Paste(e) => e.to_string(),
+            Self::TooLarge => format!(
+                "The content is larger than the {} bytes allowed.",
+                crate::config::max_content_size()
+            ),
+            Self::InvalidMetadata(errors) => errors
+                .iter()
+                .map(|e| match e.field.is_empty() {
+                    true => e.error.clone(),
+                    false => format!("{}: {}", e.field, e.error),
+                })
+                .collect::<Vec<String>>()
+                .join(" "),
+        }
+    }
+}
+
+impl IntoResponse for ContentError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Paste(e) => e.into_response(),
+            Self::TooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(DefaultReturn::<u16> {
+                    success: false,
+                    message: self.message(),
+                    payload: 413,
+                }),
+            )
+                .into_response(),
+            Self::InvalidMetadata(ref errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(DefaultReturn {
+                    success: false,
+                    message: self.message(),
+                    payload: errors.clone(),
+                }),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Check that paste content fits in `MAX_CONTENT_SIZE`
+fn check_content_size(content: &str) -> Result<(), ContentError> {
+    match content.len() > crate::config::max_content_size() {
+        true => Err(ContentError::TooLarge),
+        false => Ok(()),
+    }
+}
+
+/// Check the files of a new paste before it's created (their names, how many there are and their
+/// size), so it isn't created without some of them
+fn check_new_files(files: &[NewPasteFile]) -> Result<(), ContentError> {
+    if files.len() > crate::database::MAX_FILES {
+        return Err(PasteError::ValueError.into());
+    }
+
+    let mut names = Vec::new();
+    for file in files {
+        let name = crate::attachments::clean_name(&file.name).ok_or(PasteError::ValueError)?;
+
+        if file.content.is_empty() || names.contains(&name) {
+            return Err(PasteError::ValueError.into());
+        }
+
+        check_content_size(&file.content)?;
+        names.push(name);
+    }
+
+    Ok(())
+}
+
+/// Create a new paste (`/api/new`)
+///
+/// Accepts JSON ([`NewPaste`]), or a `multipart/form-data` form (see [`multipart_paste`]).
+/// Pastes created by logged-in users are owned by them, and pastes written from a template get
+/// its metadata.
+pub async fn create_paste(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    body: Bytes,
+) -> Result<Json<DefaultReturn<(String, Paste)>>, ContentError> {
+    let content_type = headers
+        .get("Content-Type")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+
+    let (props, title) = if crate::multipart::is_multipart(content_type) {
+        let (base, title, files) = multipart_paste(content_type, &body)?;
+        (
+            NewPaste {
+                base,
+                template: String::new(),
+                view_password: String::new(),
+                visibility: Visibility::default(),
+                expires_in: 0,
+                files,
+            },
+            title,
+        )
+    } else {
+        (
+            serde_json::from_slice::<NewPaste>(&body).map_err(|_| PasteError::ValueError)?,
+            String::new(),
+        )
+    };
+
+    check_content_size(&props.base.content)?;
+    check_new_files(&props.files)?;
+
+    let owner = match database.base.options.paste_ownership {
+        true => get_username(&jar, &database).await.unwrap_or_default(),
+        false => String::new(),
+    };
+
+    // private pastes need an owner to be viewable at all
+    if (props.visibility == Visibility::Private) && owner.is_empty() {
+        return Err(PasteError::ValueError.into());
+    }
+
+    let template = match props.template.is_empty() {
+        true => None,
+        false => Some(
+            database
+                .get_template_by_url(
+                    props.template,
+                    get_editing_as(&jar, &database).await?.as_ref(),
+                )
+                .await
+                .ok_or(PasteError::ValueError)?,
+        ),
+    };
+
+    let language = crate::language::detect(&props.base.content);
+
+    let metadata = PasteMetadata {
+        title: title.chars().take(250).collect(),
+        owner,
+        view_password: match database.base.options.view_password {
+            true => props.view_password,
+            false => String::new(),
+        },
+        ..Default::default()
+    };
+
+    let extra = ExtraMetadata {
+        visibility: props.visibility,
+        expires_at: match props.expires_in {
+            0 => 0,
+            secs => (dorsal::utility::unix_epoch_timestamp() as u64)
+                .saturating_add(secs.saturating_mul(1000)),
+        },
+        language: match crate::language::is_code(language) {
+            true => language.to_string(),
+            false => String::new(),
+        },
+        ..Default::default()
+    };
+
+    let (metadata, extra) = match template {
+        Some(template) => {
+            copy_presentation(
+                &database,
+                &template,
+                PasteMetadata {
+                    template: template.url.clone(),
+                    ..metadata
+                },
+                extra,
+            )
+            .await
+        }
+        None => (metadata, extra),
+    };
+
+    match database
+        .create_paste_with(props.base, metadata, extra)
+        .await
+    {
+        Ok(mut paste) => {
+            save_new_files(
+                &database,
+                &paste.1.url,
+                props.files.into_iter().map(|f| (f.name, f.content)),
+            )
+            .await?;
+
+            paste.1 = database.get_paste_by_url(paste.1.url).await?;
+
+            let origin = crate::base::BaseStore::new().origin(&headers);
+            crate::events::publish(Event::Created {
+                paste: paste.1.clone(),
+                origin: origin.clone(),
+            });
+
+            Ok(Json(DefaultReturn {
+                success: true,
+                message: String::from("Paste created"),
+                payload: paste,
+            }))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Create a new paste from a raw request body, sprunge-style (`/api/plain`, `POST /`)
+///
+/// The body is used as the content, unless it's a form with an `f` field (`curl -F 'f=<-'`). The
+/// response is just the link to the paste, and its edit password is in `X-Paste-Password`.
+pub async fn plain_upload(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    body: Bytes,
+) -> Response {
+    let content_type = headers
+        .get("Content-Type")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+
+    let content = if crate::multipart::is_multipart(content_type) {
+        crate::multipart::parse(content_type, &body)
+            .and_then(|parts| crate::multipart::field(&parts, "f").map(|f| f.data.clone()))
+    } else {
+        Some(body.to_vec())
+    };
+
+    let content = match content.map(String::from_utf8) {
+        Some(Ok(c)) => c.trim_start_matches('\u{feff}').to_string(),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "The paste must be text (sent as the body, or the f field).\n",
+            )
+                .into_response()
+        }
+    };
+
+    if let Err(e) = check_content_size(&content) {
+        // (not `413`, which `crate::middleware::body_limit` takes for a body over its limit)
+        return (StatusCode::BAD_REQUEST, format!("{}\n", e.message())).into_response();
+    }
+
+    match database
+        .create_paste(PasteCreate {
+            url: String::new(),
+            content,
+            password: String::new(),
+        })
+        .await
+    {
+        Ok((password, paste)) => {
+            let origin = crate::base::BaseStore::new().origin(&headers);
+            crate::events::publish(Event::Created {
+                paste: paste.clone(),
+                origin: origin.clone(),
+            });
+
+            (
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        "text/plain; charset=utf-8".to_string(),
+                    ),
+                    (HeaderName::from_static("x-paste-password"), password),
+                ],
+                format!("{origin}/{}\n", paste.url),
+            )
+                .into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, format!("{}\n", e.to_string())).into_response(),
+    }
+}
+
+/// Clone an existing paste (`/api/clone`)
+async fn clone_paste(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Json(paste_to_create): Json<PasteClone>,
+) -> Result<Json<DefaultReturn<(String, Paste)>>, PasteError> {
+    // make sure we can see the source (there's no view password to give, so pastes with one
+    // can only be forked)
+    let source = database
+        .get_paste_by_url(paste_to_create.source.clone())
+        .await?;
+
+    crate::access::check(&database, &jar, &source, "").await?;
+
+    match database.clone_paste(paste_to_create).await {
+        Ok(paste) => {
+            let origin = crate::base::BaseStore::new().origin(&headers);
+            crate::events::publish(Event::Created {
+                paste: paste.1.clone(),
+                origin: origin.clone(),
+            });
+
+            Ok(Json(DefaultReturn {
+                success: true,
+                message: String::from("Paste cloned"),
+                payload: paste,
+            }))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Check if a `Content-Type` is text that can be imported
+fn is_importable(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || [
+            "application/json",
+            "application/xml",
+            "application/x-yaml",
+            "application/yaml",
+        ]
+        .contains(&mime.as_str())
+}
+
+/// Create a paste from a remote file (`/api/import/url`)
+async fn import_paste(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Json(paste_to_import): Json<PasteImport>,
+) -> Result<Json<DefaultReturn<(String, Paste)>>, ContentError> {
+    let (source, response) = match crate::http::get_following_redirects(
+        paste_to_import.source,
+        vec![(
+            "Accept".to_string(),
+            "text/markdown, text/plain;q=0.9, text/*;q=0.8".to_string(),
+        )],
+        // (the largest file that can be imported is the most content a paste can have)
+        crate::config::max_content_size(),
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(e) if e == crate::http::TOO_LARGE => return Err(ContentError::TooLarge),
+        Err(_) => return Err(PasteError::ValueError.into()),
+    };
+
+    if !response.is_success()
+        || !is_importable(response.header("content-type").unwrap_or("text/plain"))
+    {
+        return Err(PasteError::ValueError.into());
+    }
+
+    let content = match String::from_utf8(response.body) {
+        Ok(c) => c.trim_start_matches('\u{feff}').to_string(),
+        Err(_) => return Err(PasteError::ValueError.into()),
+    };
+
+    let paste = database
+        .create_paste_with(
+            PasteCreate {
+                url: paste_to_import.url,
+                content,
+                password: paste_to_import.password,
+            },
+            PasteMetadata::default(),
+            ExtraMetadata {
+                source,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    crate::events::publish(Event::Created {
+        paste: paste.1.clone(),
+        origin: origin.clone(),
+    });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Paste imported"),
+        payload: paste,
+    }))
+}
+
+/// Copy a paste into a new one, linking back to it (`/api/:url/fork`)
+///
+/// Only the content (with the paste's files) and how it's presented (title, description, theme,
+/// language, direction) are copied: the fork is public and belongs to whoever forked it.
+async fn fork_paste_by_url(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<PasteFork>,
+) -> Result<Json<DefaultReturn<(String, Paste)>>, PasteError> {
+    let source = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    // make sure we can see the source
+    crate::access::check(&database, &jar, &source, &props.view_password).await?;
+
+    let owner = match (editing_as, database.base.options.paste_ownership) {
+        (Some(ua), true) => ua.username,
+        _ => String::new(),
+    };
+
+    let (metadata, extra) = copy_presentation(
+        &database,
+        &source,
+        PasteMetadata {
+            owner,
+            ..Default::default()
+        },
+        ExtraMetadata {
+            forked_from: source.url.clone(),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    let mut paste = database
+        .create_paste_with(
+            PasteCreate {
+                url: props.url,
+                content: source.content.clone(),
+                password: props.password,
+            },
+            metadata,
+            extra,
+        )
+        .await?;
+
+    save_new_files(
+        &database,
+        &paste.1.url,
+        database
+            .get_files_by_url(source.url.clone())
+            .await
+            .into_iter()
+            .map(|f| (f.name, f.content)),
+    )
+    .await?;
+
+    paste.1 = database.get_paste_by_url(paste.1.url).await?;
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    crate::events::publish(Event::Created {
+        paste: paste.1.clone(),
+        origin: origin.clone(),
+    });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Paste forked"),
+        payload: paste,
+    }))
+}
+
+/// List the other urls of a paste (`GET /api/:url/aliases`)
+async fn get_aliases(
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<Vec<String>>>, PasteError> {
+    let paste = paste?.paste;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Aliases exist"),
+        payload: database.get_aliases_by_url(paste.url).await,
+    }))
+}
+
+/// List the files attached to a paste (`GET /api/:url/attachments`)
+async fn get_attachments(
+    jar: CookieJar,
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<Vec<Attachment>>>, PasteError> {
+    let paste = paste?.paste;
+
+    let mut attachments = database.get_attachments_by_url(paste.url).await;
+
+    if !is_admin(&jar, &database).await {
+        attachments.retain(|a| !a.quarantined);
+    }
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Attachments exist"),
+        payload: attachments,
+    }))
+}
+
+/// List the files of a paste (`GET /api/:url/files`)
+async fn get_files(
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<Vec<PasteFile>>>, PasteError> {
+    let paste = paste?.paste;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Files exist"),
+        payload: database.get_files_by_url(paste.url).await,
+    }))
+}
+
+/// Add a file to a paste, or replace one of its files (`POST /api/:url/files`)
+async fn save_file(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<PasteFileEdit>,
+) -> Result<Json<DefaultReturn<PasteFile>>, ContentError> {
+    check_content_size(&props.content)?;
+
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect.into());
+    }
+
+    let file = database
+        .save_file(paste.url.clone(), &props.name, props.content)
+        .await?;
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    crate::events::publish(Event::Edited { paste, origin });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("File saved"),
+        payload: file,
+    }))
+}
+
+/// Remove a file of a paste (`/api/:url/files/:name/delete`)
+async fn delete_file(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path((url, name)): Path<(String, String)>,
+    Json(props): Json<PasteFileDelete>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    database.delete_file(paste.url.clone(), name).await?;
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    crate::events::publish(Event::Edited { paste, origin });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("File deleted"),
+        payload: (),
+    }))
+}
+
+/// List the pages of a wiki (`GET /api/:url/pages`)
+async fn get_pages(
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<Vec<WikiPage>>>, PasteError> {
+    let paste = paste?.paste;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Pages exist"),
+        payload: database.get_pages(paste.url).await,
+    }))
+}
+
+/// Add a page to a wiki (`POST /api/:url/pages`)
+///
+/// The page is a new paste (at `{url}.{name}`, with its own edit password) shown at
+/// `/:url/:name`. It belongs to the owner of the wiki, and is as visible as the wiki itself (with
+/// the same view password).
+async fn create_page(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<WikiPageCreate>,
+) -> Result<Json<DefaultReturn<(String, Paste)>>, ContentError> {
+    check_content_size(&props.content)?;
+
+    let namespace = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&namespace, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect.into());
+    }
+
+    // (checked before the paste is created, `Database::create_page` checks again)
+    let name = crate::slug::check_page(&props.name)?;
+
+    if database
+        .get_page(namespace.url.clone(), name.clone())
+        .await
+        .is_ok()
+    {
+        return Err(PasteError::AlreadyExists.into());
+    }
+
+    if database
+        .get_page_by_url(namespace.url.clone())
+        .await
+        .is_some()
+    {
+        return Err(PasteError::ValueError.into());
+    }
+
+    let language = crate::language::detect(&props.content);
+    let metadata = PasteMetadata {
+        owner: namespace.metadata.owner.clone(),
+        view_password: namespace.metadata.view_password.clone(),
+        ..Default::default()
+    };
+
+    let extra = ExtraMetadata {
+        visibility: database
+            .get_extra_metadata_by_url(namespace.url.clone())
+            .await
+            .visibility,
+        language: match crate::language::is_code(language) {
+            true => language.to_string(),
+            false => String::new(),
+        },
+        ..Default::default()
+    };
+
+    let paste = database
+        .create_paste_with(
+            PasteCreate {
+                url: format!("{}.{name}", namespace.url),
+                content: props.content,
+                password: String::new(),
+            },
+            metadata,
+            extra,
+        )
+        .await?;
+
+    if let Err(e) = database
+        .create_page(namespace.url.clone(), &name, paste.1.url.clone())
+        .await
+    {
+        let _ = database
+            .force_delete_paste_by_url(paste.1.url.clone())
+            .await;
+        return Err(e.into());
+    }
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    crate::events::publish(Event::Created {
+        paste: paste.1.clone(),
+        origin,
+    });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Page created"),
+        payload: paste,
+    }))
+}
+
+/// Take a page out of a wiki (`/api/:url/pages/:name/delete`)
+///
+/// The page's paste isn't deleted (it's still at its own url).
+async fn delete_page(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path((url, name)): Path<(String, String)>,
+    Json(props): Json<WikiPageDelete>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    database.delete_page(paste.url.clone(), name).await?;
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    crate::events::publish(Event::Edited { paste, origin });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Page deleted"),
+        payload: (),
+    }))
+}
+
+/// Check that comments can be read on a paste (that the user sending the request can view)
+///
+/// # Returns
+/// * the paste's comment policy
+async fn check_comments(database: &Database, paste: &Paste) -> Result<CommentPolicy, PasteError> {
+    if crate::config::comment_mode() == crate::config::CommentMode::Off {
+        return Err(PasteError::Other);
+    }
+
+    match database
+        .get_extra_metadata_by_url(paste.url.clone())
+        .await
+        .comments
+    {
+        CommentPolicy::Off => Err(PasteError::Other),
+        policy => Ok(policy),
+    }
+}
+
+/// Get the comments of a paste, oldest first (`GET /api/:url/comments`)
+async fn get_comments(
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<Vec<Comment>>>, PasteError> {
+    let paste = paste?.paste;
+    check_comments(&database, &paste).await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Comments exist"),
+        payload: database.get_comments_by_url(paste.url).await,
+    }))
+}
+
+/// Leave a comment on a paste (`POST /api/:url/comments`)
+///
+/// Clients can only leave a few comments every few minutes (see `RATE_LIMIT_COMMENT`).
+async fn create_comment(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<CommentCreate>,
+) -> Result<Json<DefaultReturn<Comment>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+
+    crate::access::check(&database, &jar, &paste, &props.view_password).await?;
+
+    if check_comments(&database, &paste).await? != CommentPolicy::Open {
+        return Err(PasteError::Other);
+    }
+
+    // (answers only make sense when they're shown under what they answer)
+    if !props.reply_to.is_empty()
+        && (crate::config::comment_mode() != crate::config::CommentMode::Threaded)
+    {
+        return Err(PasteError::ValueError);
+    }
+
+    let username = get_username(&jar, &database).await.unwrap_or_default();
+    let comment = database
+        .create_comment(Comment {
+            id: String::new(),
+            url: paste.url,
+            reply_to: props.reply_to,
+            author: match username.is_empty() {
+                true => props.author,
+                false => username.clone(),
+            },
+            username,
+            owner: session_owner(&jar, &database).await.unwrap_or_default(),
+            content: props.content,
+            date_created: 0,
+        })
+        .await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Comment created"),
+        payload: comment,
+    }))
+}
+
+/// Delete a comment of a paste, and the comments answering it (`/api/:url/comments/:id/delete`)
+///
+/// Comments can be deleted by whoever left them, and by whoever can edit the paste.
+async fn delete_comment(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path((url, id)): Path<(String, String)>,
+    Json(props): Json<CommentDelete>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    let comment = database
+        .get_comments_by_url(paste.url.clone())
+        .await
+        .into_iter()
+        .find(|c| c.id == id)
+        .ok_or(PasteError::NotFound)?;
+
+    let owner = session_owner(&jar, &database).await.unwrap_or_default();
+    let by_author = !owner.is_empty() && (comment.owner == owner);
+
+    if !by_author && !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    database.delete_comment(paste.url, comment.id).await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Comment deleted"),
+        payload: (),
+    }))
+}
+
+/// Get how many times a paste got each reaction (`GET /api/:url/reactions`)
+async fn get_reactions(
+    jar: CookieJar,
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<Vec<ReactionCount>>>, PasteError> {
+    let paste = paste?.paste;
+
+    let owner = session_owner(&jar, &database).await.unwrap_or_default();
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Reactions exist"),
+        payload: database.get_reactions_by_url(paste.url, &owner).await,
+    }))
+}
+
+/// React to a paste, or take the reaction back (`POST /api/:url/reactions`)
+///
+/// Everyone can react once with each reaction: reactions are kept for their account, or their
+/// browser when they aren't logged in (see [`session_owner`]).
+async fn toggle_reaction(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<ReactionToggle>,
+) -> Result<Json<DefaultReturn<Vec<ReactionCount>>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    crate::access::check(&database, &jar, &paste, &props.view_password).await?;
+
+    let owner = session_owner(&jar, &database).await?;
+    let reacted = database
+        .toggle_reaction(paste.url.clone(), &props.reaction, &owner)
+        .await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from(match reacted {
+            true => "Reaction added",
+            false => "Reaction removed",
+        }),
+        payload: database.get_reactions_by_url(paste.url, &owner).await,
+    }))
+}
+
+/// Attach files to a paste (`POST /api/:url/attachments`)
+///
+/// Takes a `multipart/form-data` form with the edit `password` and any number of `file` fields.
+/// Files replace the attachments with the same name.
+async fn upload_attachments(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    body: Bytes,
+) -> Result<Json<DefaultReturn<Vec<Attachment>>>, PasteError> {
+    let content_type = headers
+        .get("Content-Type")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+
+    let parts = crate::multipart::parse(content_type, &body).ok_or(PasteError::ValueError)?;
+    let password = crate::multipart::field(&parts, "password")
+        .map(|f| String::from_utf8_lossy(&f.data).to_string())
+        .unwrap_or_default();
+
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    let files: Vec<_> = parts.into_iter().filter(|p| p.name == "file").collect();
+    let mut names = Vec::new();
+
+    for file in &files {
+        let name = crate::attachments::clean_name(file.filename.as_deref().unwrap_or_default())
+            .ok_or(PasteError::ValueError)?;
+        names.push(name);
+    }
+
+    // (the whole form has to fit, so nothing is attached from a form that doesn't)
+    let used: usize = database
+        .get_attachments_by_url(paste.url.clone())
+        .await
+        .iter()
+        .filter(|a| !names.contains(&a.name))
+        .map(|a| a.size)
+        .sum();
+
+    if files.is_empty()
+        || (used + files.iter().map(|f| f.data.len()).sum::<usize>()
+            > crate::config::max_attachments_size())
+    {
+        return Err(PasteError::ValueError);
+    }
+
+    let mut attachments = Vec::new();
+    for (file, name) in files.into_iter().zip(names) {
+        attachments.push(
+            database
+                .create_attachment(paste.url.clone(), &name, file.data)
+                .await?,
+        );
+    }
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Files attached"),
+        payload: attachments,
+    }))
+}
+
+/// Remove a file attached to a paste (`/api/:url/attachments/:name/delete`)
+async fn delete_attachment(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path((url, name)): Path<(String, String)>,
+    Json(props): Json<AttachmentDelete>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    database.delete_attachment(paste.url, name).await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Attachment deleted"),
+        payload: (),
+    }))
+}
+
+/// Give a paste another url (`POST /api/:url/aliases`)
+async fn create_alias(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<AliasEdit>,
+) -> Result<Json<DefaultReturn<String>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    let alias = database.create_alias(paste.url, props.alias).await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Alias created"),
+        payload: alias,
+    }))
+}
+
+/// Remove one of the other urls of a paste (`/api/:url/aliases/delete`)
+async fn delete_alias(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<AliasEdit>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    database.delete_alias(paste.url, props.alias).await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Alias deleted"),
+        payload: (),
+    }))
+}
+
+/// Delete an existing paste (`/api/:url/delete`)
+async fn delete_paste_by_url(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(paste_to_delete): Json<PasteDelete>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let existing = database.get_paste_by_url(url.clone()).await?;
+
+    match database
+        .base
+        .delete_paste_by_url(url, paste_to_delete.password)
+        .await
+    {
+        Ok(_) => {
+            database
+                .delete_extra_metadata_by_url(existing.url.clone())
+                .await?;
+            database
+                .delete_shortlink_by_url(existing.url.clone())
+                .await?;
+            database.delete_aliases_by_url(existing.url.clone()).await?;
+            database.delete_redirects_to(existing.url.clone()).await?;
+            database
+                .delete_attachments_by_url(existing.url.clone())
+                .await?;
+            database.delete_files_by_url(existing.url.clone()).await?;
+            database.delete_pages_by_url(existing.url.clone()).await?;
+            database
+                .delete_comments_by_url(existing.url.clone())
+                .await?;
+            database
+                .delete_reactions_by_url(existing.url.clone())
+                .await?;
+            database
+                .delete_edit_tokens_by_url(existing.url.clone())
+                .await?;
+            crate::analytics::forget(&database, &existing.url).await;
+
+            let origin = crate::base::BaseStore::new().origin(&headers);
+            crate::events::publish(Event::Deleted {
+                paste: existing,
+                origin,
+            });
+
+            Ok(Json(DefaultReturn {
+                success: true,
+                message: String::from("Paste deleted"),
+                payload: (),
+            }))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Report a paste to the instance team (`/api/:url/report`)
+///
+/// Reports aren't stored, they're sent to the configured chat channels (see [`crate::notify`]).
+/// Only pastes the client can view can be reported.
+async fn report_paste_by_url(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<PasteReport>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let base = crate::base::BaseStore::new();
+
+    if !crate::notify::is_enabled(&base) {
+        return Err(PasteError::Other);
+    }
+
+    if props.reason.trim().is_empty() || (props.reason.len() > 1000) {
+        return Err(PasteError::ValueError);
+    }
+
+    let paste = database.get_paste_by_url(url).await?;
+
+    // (a paste the client can't view looks like it doesn't exist)
+    if crate::access::check(&database, &jar, &paste, &props.view_password)
+        .await
+        .is_err()
+    {
+        return Err(PasteError::NotFound);
+    }
+
+    crate::events::publish(Event::Reported {
+        paste,
+        origin: base.origin(&headers),
+        reason: props.reason.trim().to_string(),
+    });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Paste reported"),
+        payload: (),
+    }))
+}
+
+/// Finish saving new content to a paste (after `edit_paste_by_url`)
+///
+/// Pastes without a language get one once their content looks like code, and everything
+/// listening for edits hears about it.
+pub(crate) async fn finish_edit(
+    database: &Database,
+    url: String,
+    origin: String,
+) -> Result<(), PasteError> {
+    let Ok(p) = database.get_paste_by_url(url).await else {
+        return Ok(());
+    };
+
+    let extra = database.get_extra_metadata_by_url(p.url.clone()).await;
+    let detected = crate::language::detect(&p.content);
+
+    if extra.language.is_empty() && crate::language::is_code(detected) {
+        database
+            .edit_extra_metadata_by_url(
+                p.url.clone(),
+                ExtraMetadata {
+                    language: detected.to_string(),
+                    ..extra
+                },
+            )
+            .await?;
+    }
+
+    crate::events::publish(Event::Edited { paste: p, origin });
+    Ok(())
+}
+
+/// Edit an existing paste (`/api/:url/edit`)
+///
+/// With the token of an edit link, only the content can change (and the link is used up).
+async fn edit_paste_by_url(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(body): Json<PasteEditBody>,
+) -> Result<Json<DefaultReturn<()>>, ContentError> {
+    let paste_to_edit = body.base;
+    check_content_size(&paste_to_edit.new_content)?;
+    let existing = database.get_paste_by_url(url.clone()).await?;
+
+    if !body.token.is_empty() {
+        if !paste_to_edit.new_url.is_empty() || !paste_to_edit.new_password.is_empty() {
+            return Err(PasteError::ValueError.into());
+        }
+
+        database
+            .use_edit_token(existing.url.clone(), body.token)
+            .await?;
+        database
+            .set_paste_content(existing.url.clone(), paste_to_edit.new_content)
+            .await?;
+
+        let origin = crate::base::BaseStore::new().origin(&headers);
+        finish_edit(&database, existing.url, origin).await?;
+
+        return Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Paste updated"),
+            payload: (),
+        }));
+    }
+
+    // (pastes already at a reserved url can still be edited, they just can't be moved to one)
+    let new_url = match paste_to_edit.new_url.trim().to_lowercase() {
+        same if same == existing.url => same,
+        new_url => database.check_new_url(&new_url).await?,
+    };
+
+    match database
+        .base
+        .edit_paste_by_url(
+            url,
+            paste_to_edit.password,
+            paste_to_edit.new_content,
+            new_url.clone(),
+            paste_to_edit.new_password,
+            get_editing_as(&jar, &database).await?,
+        )
+        .await
+    {
+        Ok(_) => {
+            // keep our metadata attached to the paste if it was moved
+            if !new_url.is_empty() {
+                if let Ok(p) = database.get_paste_by_url(new_url.clone()).await {
+                    if p.url != existing.url {
+                        database.move_paste(existing.url.clone(), p.url).await?;
+                    }
+                }
+            }
+
+            let url = match new_url.is_empty() {
+                true => existing.url,
+                false => new_url,
+            };
+
+            let origin = crate::base::BaseStore::new().origin(&headers);
+            finish_edit(&database, url, origin).await?;
+
+            Ok(Json(DefaultReturn {
+                success: true,
+                message: String::from("Paste updated"),
+                payload: (),
+            }))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Create a single-use link to edit a paste without its password (`/api/:url/edit_links`)
+async fn create_edit_link(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<EditLinkCreate>,
+) -> Result<Json<DefaultReturn<EditLink>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    let expires_in = props
+        .expires_in
+        .unwrap_or(crate::database::EDIT_LINK_LIFETIME);
+
+    if (expires_in == 0) || (expires_in > crate::database::MAX_EDIT_LINK_LIFETIME) {
+        return Err(PasteError::ValueError);
+    }
+
+    let expires_at = dorsal::utility::unix_epoch_timestamp() + (expires_in as u128 * 1000);
+    let token = database
+        .create_edit_token(paste.url.clone(), expires_at)
+        .await?;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Edit link created"),
+        payload: EditLink {
+            url: format!(
+                "{}/{}/edit?token={token}",
+                crate::config::base_path(),
+                paste.url
+            ),
+            token,
+            expires_at,
+        },
+    }))
+}
+
+/// Get the views of a paste over the last days (`/api/:url/stats`)
+async fn get_paste_stats(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<PasteStatsRequest>,
+) -> Result<Json<DefaultReturn<PasteStats>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Stats exist"),
+        payload: crate::analytics::paste_stats(&database, paste.url).await,
+    }))
+}
+
+/// Sign a temporary link that shows a paste without its view password (`/api/:url/share_links`)
+async fn create_share_link(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<ShareLinkCreate>,
+) -> Result<Json<DefaultReturn<ShareLink>>, PasteError> {
+    let paste = database.get_paste_by_url(url).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    if !database.can_edit_paste(&paste, &props.password, editing_as.as_ref()) {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    let expires_in = props
+        .expires_in
+        .unwrap_or(crate::signing::SHARE_LINK_LIFETIME);
+
+    if (expires_in == 0) || (expires_in > crate::signing::MAX_SHARE_LINK_LIFETIME) {
+        return Err(PasteError::ValueError);
+    }
+
+    let expires_at = dorsal::utility::unix_epoch_timestamp() + (expires_in as u128 * 1000);
+    let Some(sig) = crate::signing::sign_share_link(&database, &paste, expires_at).await else {
+        return Err(PasteError::Other);
+    };
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Share link created"),
+        payload: ShareLink {
+            url: format!(
+                "{}/{}?sig={sig}&exp={expires_at}",
+                crate::config::base_path(),
+                paste.url
+            ),
+            expires_at,
+        },
+    }))
+}
+
+/// Move a paste to a new url, leaving a redirect at the old one (`/api/:url/rename`)
+async fn rename_paste_by_url(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(props): Json<PasteRename>,
+) -> Result<Json<DefaultReturn<String>>, PasteError> {
+    let existing = database.get_paste_by_url(url).await?;
+    let new_url = database.check_new_url(&props.new_url).await?;
+
+    if new_url.is_empty() {
+        return Err(PasteError::ValueError);
+    }
+
+    database
+        .base
+        .edit_paste_by_url(
+            existing.url.clone(),
+            props.password,
+            existing.content.clone(),
+            new_url.clone(),
+            String::new(),
+            get_editing_as(&jar, &database).await?,
+        )
+        .await?;
+
+    let paste = database.get_paste_by_url(new_url).await?;
+    database
+        .move_paste(existing.url.clone(), paste.url.clone())
+        .await?;
+
+    let new_url = paste.url.clone();
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    crate::events::publish(Event::Edited { paste, origin });
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Paste renamed"),
+        payload: new_url,
+    }))
+}
+
+/// Edit an existing paste's metadata (`/api/:url/metadata`)
+async fn edit_paste_metadata_by_url(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Path(url): Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<DefaultReturn<()>>, ContentError> {
+    let errors = crate::schema::validate_metadata(&body["metadata"]);
+    if !errors.is_empty() {
+        return Err(ContentError::InvalidMetadata(errors));
+    }
+
+    let Ok(mut paste_to_edit) = serde_json::from_value::<FullMetadataEdit>(body) else {
+        return Err(PasteError::ValueError.into());
+    };
+
+    // (metadata is stored next to the content, so it's held to the same limit)
+    check_content_size(&serde_json::to_string(&paste_to_edit.metadata).unwrap_or_default())?;
+    let existing = database.get_paste_by_url(url.clone()).await?;
+    let editing_as = get_editing_as(&jar, &database).await?;
+
+    // if we've been given an authentication cookie (and it's allowed),
+    // we'll check the user and then set metadata.owner
+    paste_to_edit.metadata.base.owner = match editing_as {
+        Some(ref ua) if database.base.options.paste_ownership => ua.username.clone(),
+        // clear owner field if paste is edited by an anonymous user
+        _ => String::new(),
+    };
+
+    // the import source and fork origin are kept as-is
+    let current = database
+        .get_extra_metadata_by_url(existing.url.clone())
+        .await;
+    paste_to_edit.metadata.extra.source = current.source;
+    paste_to_edit.metadata.extra.forked_from = current.forked_from;
+
+    // private pastes need an owner to be viewable at all
+    if (paste_to_edit.metadata.extra.visibility == Visibility::Private)
+        && paste_to_edit.metadata.base.owner.is_empty()
+    {
+        return Err(PasteError::ValueError.into());
+    }
+
+    // (a paste restricted to a network that doesn't parse couldn't be viewed by anyone)
+    if paste_to_edit.metadata.extra.networks().is_none() {
+        return Err(PasteError::ValueError.into());
+    }
+
+    // (an empty language is detected again)
+    let language = &mut paste_to_edit.metadata.extra.language;
+    if language.is_empty() {
+        let detected = crate::language::detect(&existing.content);
+        if crate::language::is_code(detected) {
+            *language = detected.to_string();
+        }
+    }
+
+    // ...
+    match database
+        .base
+        .edit_paste_metadata_by_url(
+            url,
+            paste_to_edit.password,
+            paste_to_edit.metadata.base,
+            editing_as,
+        )
+        .await
+    {
+        Ok(_) => {
+            database
+                .edit_extra_metadata_by_url(existing.url.clone(), paste_to_edit.metadata.extra)
+                .await?;
+
+            if let Ok(p) = database.get_paste_by_url(existing.url).await {
+                let origin = crate::base::BaseStore::new().origin(&headers);
+                crate::events::publish(Event::Edited { paste: p, origin });
+            }
+
+            Ok(Json(DefaultReturn {
+                success: true,
+                message: String::from("Paste updated"),
+                payload: (),
+            }))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The JSON Schema of the metadata `/api/:url/metadata` takes (`/api/schema/metadata`)
+async fn metadata_schema_request() -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/schema+json")],
+        Json(crate::schema::metadata_schema()),
+    )
+        .into_response()
+}
+
+/// Get the `InstanceStats` of the instance (`/api/v1/stats`), for dashboards and status pages
+///
+/// Stats are only recomputed every `STATS_CACHE_TIME`, and clients can cache them as long.
+async fn instance_stats_request(State(database): State<Database>) -> Response {
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!(
+                "public, max-age={}",
+                crate::database::STATS_CACHE_TIME / 1000
+            ),
+        )],
+        Json(DefaultReturn {
+            success: true,
+            message: String::from("Stats exist"),
+            payload: database.get_instance_stats().await,
+        }),
+    )
+        .into_response()
+}
+
+/// Get the view counts of a paste (`/api/v1/pastes/:url/views`), for badges and dashboards
+async fn get_paste_views(
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<PasteViews>>, PasteError> {
+    let paste = paste?.paste;
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Views exist"),
+        payload: PasteViews {
+            views: database.get_views_by_url(paste.url.clone()).await.max(0) as u64,
+            unique_views: crate::analytics::unique_views(&database, &paste.url).await,
+            url: paste.url,
+        },
+    }))
+}
+
+/// List a page of the pastes owned by `owner`, as `as_user` can see them
+///
+/// Others only get the public pastes they can view (and without a view password), the owner
+/// gets all of them.
+async fn list_pastes(
+    database: &Database,
+    owner: String,
+    as_user: Option<Profile>,
+    query: PastePageQuery,
+) -> Json<DefaultReturn<PastePage>> {
+    let is_owner = as_user.as_ref().is_some_and(|ua| ua.username == owner);
+    let page = query.page.max(0);
+
+    // (pastes without an owner aren't anyone's)
+    let (pastes, full) = match owner.is_empty() {
+        true => (Vec::new(), false),
+        false => database.get_pastes_by_owner(owner, page).await,
+    };
+
+    let next_page = full.then_some(page + 1);
+    let mut listed = Vec::new();
+
+    for paste in pastes {
+        let extra = database.get_extra_metadata_by_url(paste.url.clone()).await;
+
+        if extra.is_expired()
+            || query
+                .visibility
+                .as_ref()
+                .is_some_and(|v| *v != extra.visibility)
+        {
+            continue;
+        }
+
+        if !is_owner
+            && ((extra.visibility != Visibility::Public)
+                || !paste.metadata.view_password.is_empty()
+                || !database.can_view_paste(&paste, as_user.as_ref()).await)
+        {
+            continue;
+        }
+
+        listed.push(PasteListing {
+            url: paste.url,
+            title: paste.metadata.title,
+            description: paste.metadata.description,
+            visibility: extra.visibility,
+            view_password: !paste.metadata.view_password.is_empty(),
+            date_published: paste.date_published,
+            date_edited: paste.date_edited,
+        });
+    }
+
+    Json(DefaultReturn {
+        success: true,
+        message: String::from("Pastes exist"),
+        payload: PastePage {
+            pastes: listed,
+            page,
+            next_page,
+        },
+    })
+}
+
+/// List the pastes of a user (`/api/v1/users/:name/pastes`)
+async fn get_user_pastes(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(name): Path<String>,
+    Query(query): Query<PastePageQuery>,
+) -> Result<Json<DefaultReturn<PastePage>>, PasteError> {
+    let as_user = get_editing_as(&jar, &database).await?;
+    Ok(list_pastes(&database, name, as_user, query).await)
+}
+
+/// List the pastes of the user sending the request (`/api/v1/me/pastes`)
+///
+/// Clients send the account's token as `Authorization: Bearer`, or as the login cookie.
+async fn get_my_pastes(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Query(query): Query<PastePageQuery>,
+) -> Result<Json<DefaultReturn<PastePage>>, PasteError> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    let as_user = match bearer {
+        Some(token) if database.base.options.starstraw => database
+            .base
+            .auth
+            .get_profile_by_unhashed(token.trim().to_string())
+            .await
+            .ok(),
+        Some(_) => None,
+        None => get_editing_as(&jar, &database).await?,
+    };
+
+    let Some(as_user) = as_user else {
+        return Err(PasteError::PasswordIncorrect);
+    };
+
+    Ok(list_pastes(&database, as_user.username.clone(), Some(as_user), query).await)
+}
+
+/// Get (or create) the shortlink code of a paste (`/api/:url/shortlink`)
+async fn create_shortlink(
+    State(database): State<Database>,
+    existing: Result<ProtectedPaste, Denied>,
+) -> Result<Json<DefaultReturn<String>>, PasteError> {
+    let existing = existing?.paste;
+
+    match database.create_shortlink(existing.url).await {
+        Ok(code) => Ok(Json(DefaultReturn {
+            success: true,
+            message: format!("{}/s/{code}", crate::config::base_path()),
+            payload: code,
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get an existing paste by url (`/api/:url`)
+pub async fn get_paste_by_url(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    paste: Result<ProtectedPaste, Denied>,
+) -> Result<Response, PasteError> {
+    let mut p = paste?.paste;
+
+    // the view password isn't given back (a share link doesn't tell it)
+    p.metadata.view_password = String::new();
+
+    let extra = database.get_extra_metadata_by_url(p.url.clone()).await;
+    let etag = crate::pages::paste_etag(&p, &extra, &["json"]);
+
+    if crate::utility::etag_matches(&headers, &etag) {
+        return Ok(crate::pages::not_modified(etag));
+    }
+
+    Ok((
+        [
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, crate::pages::REVALIDATE.to_string()),
+        ],
+        Json(DefaultReturn {
+            success: true,
+            message: String::from("Paste exists"),
+            payload: PublicPasteInfo {
+                stats: content_stats(&p.content),
+                paste: p.into(),
+                extra: extra.custom,
+            },
+        }),
+    )
+        .into_response())
+}
+
+/// Describe a paste for oEmbed consumers (`/api/oembed?url=`)
+///
+/// Only the `json` format is supported.
+async fn oembed_request(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Query(props): Query<OEmbedQuery>,
+) -> Response {
+    if !props.format.is_empty() && (props.format != "json") {
+        return StatusCode::NOT_IMPLEMENTED.into_response();
+    }
+
+    // the paste url is the first path segment of the given url
+    let path = match props.url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').map(|(_, p)| p).unwrap_or_default(),
+        None => props.url.trim_start_matches('/'),
+    };
+
+    let url = path
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let paste = match database.get_paste_by_url(url).await {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+
+    if !paste.metadata.view_password.is_empty() {
+        return PasteError::Other.into_response();
+    }
+
+    if !database.can_view_paste(&paste, None).await {
+        return PasteError::NotFound.into_response();
+    }
+
+    // ...
+    let base = crate::base::BaseStore::new();
+    let origin = base.origin(&headers);
+
+    let width = props.maxwidth.unwrap_or(600).min(600);
+    let height = props.maxheight.unwrap_or(400).min(400);
+
+    let (author_name, author_url) = if base.starstraw && !paste.metadata.owner.is_empty() {
+        (
+            paste.metadata.owner.clone(),
+            format!("{}/~{}", origin, paste.metadata.owner),
+        )
+    } else {
+        (String::new(), String::new())
+    };
+
+    Json(OEmbed {
+        version: String::from("1.0"),
+        r#type: String::from("rich"),
+        title: match paste.metadata.title.is_empty() {
+            true => paste.url.clone(),
+            false => paste.metadata.title.clone(),
+        },
+        author_name,
+        author_url,
+        provider_name: base.site_name,
+        provider_url: origin.clone(),
+        html: format!(
+            "<iframe src=\"{}/{}/embed\" width=\"{}\" height=\"{}\" frameborder=\"0\"></iframe>",
+            origin, paste.url, width, height
+        ),
+        width,
+        height,
+    })
+    .into_response()
+}
+
+/// Create a new collection (`/api/c/new`)
+async fn create_collection(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Json(props): Json<CollectionCreate>,
+) -> Result<Json<DefaultReturn<Collection>>, PasteError> {
+    let owner = match get_username(&jar, &database).await {
+        Some(u) => u,
+        None => return Err(PasteError::PasswordIncorrect),
+    };
+
+    match database.create_collection(props, owner).await {
+        Ok(collection) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Collection created"),
+            payload: collection,
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get an existing collection by name (`/api/c/:name`)
+async fn get_collection_by_name(
+    State(database): State<Database>,
+    Path(name): Path<String>,
+) -> Result<Json<DefaultReturn<Collection>>, PasteError> {
+    match database.get_collection_by_name(name).await {
+        Ok(collection) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Collection exists"),
+            payload: collection,
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Edit an existing collection (`/api/c/:name/edit`)
+async fn edit_collection_by_name(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(name): Path<String>,
+    Json(props): Json<CollectionEdit>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let editing_as = match get_username(&jar, &database).await {
+        Some(u) => u,
+        None => return Err(PasteError::PasswordIncorrect),
+    };
+
+    match database
+        .edit_collection_by_name(name, props, editing_as)
+        .await
+    {
+        Ok(_) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Collection updated"),
+            payload: (),
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Delete an existing collection (`/api/c/:name/delete`)
+async fn delete_collection_by_name(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(name): Path<String>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    let deleting_as = match get_username(&jar, &database).await {
+        Some(u) => u,
+        None => return Err(PasteError::PasswordIncorrect),
+    };
+
+    match database.delete_collection_by_name(name, deleting_as).await {
+        Ok(_) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Collection deleted"),
+            payload: (),
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+// webhooks
+
+/// List the instance webhooks (`/api/admin/webhooks`)
+async fn get_webhooks(
+    jar: CookieJar,
+    State(database): State<Database>,
+) -> Result<Json<DefaultReturn<Vec<Webhook>>>, PasteError> {
+    if !is_admin(&jar, &database).await {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Webhooks"),
+        payload: database.get_webhooks().await,
+    }))
+}
+
+/// Add a webhook (`/api/admin/webhooks`)
+async fn create_webhook(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Json(props): Json<WebhookCreate>,
+) -> Result<Json<DefaultReturn<Webhook>>, PasteError> {
+    if !is_admin(&jar, &database).await {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    match database.create_webhook(props).await {
+        Ok(webhook) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Webhook created"),
+            payload: webhook,
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+/// Remove a webhook (`/api/admin/webhooks/:id/delete`)
+async fn delete_webhook(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Path(id): Path<String>,
+) -> Result<Json<DefaultReturn<()>>, PasteError> {
+    if !is_admin(&jar, &database).await {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    match database.delete_webhook(id).await {
+        Ok(_) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Webhook deleted"),
+            payload: (),
+        })),
+        Err(e) => Err(e),
+    }
+}
+
+// mirroring
+
+/// Get the instance signing key mirrored pastes are checked against (`/api/mirror/key`)
+async fn mirror_key_request(
+    State(database): State<Database>,
+) -> Result<Json<DefaultReturn<String>>, PasteError> {
+    if crate::base::BaseStore::new().mirror_peers.is_empty() {
+        return Err(PasteError::NotFound);
+    }
+
+    match crate::signing::public_key_pem(&database).await {
+        Some(pem) => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Mirror key"),
+            payload: pem,
+        })),
+        None => Err(PasteError::Other),
+    }
+}
+
+/// Export pastes for a peer to pull (`/api/mirror/export?urls=`)
+async fn mirror_export_request(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Query(query): Query<MirrorExportQuery>,
+) -> Result<Json<SignedMirror>, PasteError> {
+    let base = crate::base::BaseStore::new();
+
+    if base.mirror_peers.is_empty() {
+        return Err(PasteError::NotFound);
+    }
+
+    let urls: Vec<String> = query
+        .urls
+        .split(',')
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty())
+        .collect();
+
+    match crate::mirror::export(&database, &base.origin(&headers), &urls).await {
+        Some(signed) => Ok(Json(signed)),
+        None => Err(PasteError::Other),
+    }
+}
+
+/// Receive pastes pushed by a peer (`/api/mirror`)
+async fn mirror_request(
+    State(database): State<Database>,
+    Json(signed): Json<SignedMirror>,
+) -> Result<Json<DefaultReturn<Vec<String>>>, PasteError> {
+    let bundle = match crate::mirror::verify(&signed).await {
+        Ok(b) => b,
+        Err(_) => return Err(PasteError::PasswordIncorrect),
+    };
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Pastes mirrored"),
+        payload: crate::mirror::apply(&database, bundle).await,
+    }))
+}
+
+/// Push pastes to a peer (`/api/admin/mirror/push`)
+async fn mirror_push_request(
+    jar: CookieJar,
+    headers: HeaderMap,
+    State(database): State<Database>,
+    Json(props): Json<MirrorRequest>,
+) -> Result<Json<DefaultReturn<Vec<String>>>, PasteError> {
+    if !is_admin(&jar, &database).await {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    if !crate::mirror::is_peer(&props.peer) {
+        return Err(PasteError::ValueError);
+    }
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    let signed = match crate::mirror::export(&database, &origin, &props.urls).await {
+        Some(s) => s,
+        None => return Err(PasteError::Other),
+    };
+
+    let response = match crate::http::send(
+        "POST",
+        format!("{}/api/mirror", props.peer.trim_end_matches('/')),
+        vec![("Content-Type".to_string(), "application/json".to_string())],
+        serde_json::to_vec(&signed).unwrap_or_default(),
+        64 * 1024,
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(_) => return Err(PasteError::Other),
+    };
+
+    // the peer answers with the urls it stored
+    match serde_json::from_slice::<DefaultReturn<Vec<String>>>(&response.body) {
+        Ok(r) if r.success => Ok(Json(DefaultReturn {
+            success: true,
+            message: String::from("Pastes pushed"),
+            payload: r.payload,
+        })),
+        _ => Err(PasteError::Other),
+    }
+}
+
+/// Pull pastes from a peer (`/api/admin/mirror/pull`)
+async fn mirror_pull_request(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Json(props): Json<MirrorRequest>,
+) -> Result<Json<DefaultReturn<Vec<String>>>, PasteError> {
+    if !is_admin(&jar, &database).await {
+        return Err(PasteError::PasswordIncorrect);
+    }
+
+    let peer = props.peer.trim_end_matches('/');
+    if !crate::mirror::is_peer(peer) {
+        return Err(PasteError::ValueError);
+    }
+
+    let url = match url::Url::parse_with_params(
+        &format!("{peer}/api/mirror/export"),
+        [("urls", props.urls.join(","))],
+    ) {
+        Ok(u) => u,
+        Err(_) => return Err(PasteError::ValueError),
+    };
+
+    let signed = match crate::http::send(
+        "GET",
+        url.to_string(),
+        Vec::new(),
+        Vec::new(),
+        crate::mirror::MAX_MIRROR_SIZE,
+    )
+    .await
+    {
+        Ok(r) => match serde_json::from_slice::<SignedMirror>(&r.body) {
+            Ok(s) => s,
+            Err(_) => return Err(PasteError::Other),
+        },
+        Err(_) => return Err(PasteError::Other),
+    };
+
+    // the bundle has to be from the peer we asked
+    let bundle = match crate::mirror::verify(&signed).await {
+        Ok(b) if b.origin == peer => b,
+        _ => return Err(PasteError::PasswordIncorrect),
+    };
+
+    Ok(Json(DefaultReturn {
+        success: true,
+        message: String::from("Pastes pulled"),
+        payload: crate::mirror::apply(&database, bundle).await,
+    }))
+}
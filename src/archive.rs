@@ -0,0 +1,135 @@
+//! Zip archives (`/account/archive.zip`)
+//!
+//! Archives are encoded one file at a time, so they can be streamed while they're built. Only
+//! the central directory (names and offsets) is kept until the archive is finished.
+use std::io::Write;
+
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+/// A file already written to the archive
+struct Entry {
+    name: String,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+    time: u16,
+    date: u16,
+}
+
+/// Encoder for a streamed zip archive
+///
+/// Archives are limited to 4 GiB (no zip64).
+#[derive(Default)]
+pub struct ZipWriter {
+    entries: Vec<Entry>,
+    /// Amount of bytes written so far
+    offset: u32,
+}
+
+/// Convert a unix timestamp (in milliseconds) to an MS-DOS time and date
+fn dos_datetime(timestamp: u128) -> (u16, u16) {
+    let (year, month, day, time) = crate::utility::civil_datetime(timestamp);
+
+    // MS-DOS dates start in 1980
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+
+    (
+        (((time / 3600) << 11) | (((time % 3600) / 60) << 5) | ((time % 60) / 2)) as u16,
+        (((year - 1980).min(127) << 9) | (month << 5) | day) as u16,
+    )
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compress a file into the archive
+    ///
+    /// # Arguments
+    /// * `name` - the path of the file in the archive
+    /// * `modified` - when the file was last modified (unix timestamp, in milliseconds)
+    /// * `data` - the file content
+    ///
+    /// # Returns
+    /// * the bytes to append to the archive
+    pub fn file(&mut self, name: &str, modified: u128, data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let (time, date) = dos_datetime(modified);
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32fast::hash(data),
+            compressed_size: compressed.len() as u32,
+            size: data.len() as u32,
+            offset: self.offset,
+            time,
+            date,
+        };
+
+        // local file header
+        let mut out = Vec::with_capacity(30 + name.len() + compressed.len());
+        out.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        out.extend_from_slice(&20u16.to_le_bytes()); // version needed (2.0)
+        out.extend_from_slice(&(1u16 << 11).to_le_bytes()); // flags (utf-8 names)
+        out.extend_from_slice(&8u16.to_le_bytes()); // deflate
+        out.extend_from_slice(&entry.time.to_le_bytes());
+        out.extend_from_slice(&entry.date.to_le_bytes());
+        out.extend_from_slice(&entry.crc.to_le_bytes());
+        out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        out.extend_from_slice(&entry.size.to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        out.extend_from_slice(name.as_bytes());
+        out.extend(compressed);
+
+        self.offset += out.len() as u32;
+        self.entries.push(entry);
+        out
+    }
+
+    /// Finish the archive
+    ///
+    /// # Returns
+    /// * the central directory, to append to the archive
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        for entry in &self.entries {
+            out.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            out.extend_from_slice(&20u16.to_le_bytes()); // version made by
+            out.extend_from_slice(&20u16.to_le_bytes()); // version needed
+            out.extend_from_slice(&(1u16 << 11).to_le_bytes());
+            out.extend_from_slice(&8u16.to_le_bytes());
+            out.extend_from_slice(&entry.time.to_le_bytes());
+            out.extend_from_slice(&entry.date.to_le_bytes());
+            out.extend_from_slice(&entry.crc.to_le_bytes());
+            out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            out.extend_from_slice(&entry.size.to_le_bytes());
+            out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&[0; 8]); // extra field, comment, disk number, internal attributes
+            out.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+            out.extend_from_slice(&entry.offset.to_le_bytes());
+            out.extend_from_slice(entry.name.as_bytes());
+        }
+
+        // end of central directory
+        let count = self.entries.len() as u16;
+        let directory_size = out.len() as u32;
+
+        out.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        out.extend_from_slice(&[0; 4]); // disk numbers
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&directory_size.to_le_bytes());
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        out
+    }
+}
@@ -0,0 +1,194 @@
+//! Static files compiled into the binary (with the `embed-static` feature), and their
+//! fingerprinted urls
+//!
+//! Files in the static directory always win, so embedded files are only served when the
+//! directory (or the file) is missing, like when the binary runs from another directory.
+//!
+//! Pages link to static files through [`url`], which puts a hash of the file in its name
+//! (`style.3f2a1b0c.css`). Those urls never change content, so browsers can keep them for a year
+//! instead of asking again on every page.
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+/// How many hex digits of the hash are put in fingerprinted names
+const HASH_LENGTH: usize = 8;
+
+/// `Cache-Control` of fingerprinted urls
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// `Cache-Control` of everything else (checked again with `If-Modified-Since` before it's reused)
+const REVALIDATE: &str = "public, no-cache";
+
+/// The hash of a file (`None` if it doesn't exist), with when it was modified (to notice changes)
+type Fingerprint = (Option<SystemTime>, Option<String>);
+
+/// The fingerprint of each file linked through [`url`]
+static HASHES: LazyLock<Mutex<HashMap<String, Fingerprint>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// generated by `build.rs` (empty without the `embed-static` feature)
+include!(concat!(env!("OUT_DIR"), "/static_assets.rs"));
+
+/// Get an embedded file
+///
+/// # Arguments
+/// * `path` - the path of the file, relative to the static directory (`js/footer.js`)
+pub fn get(path: &str) -> Option<&'static [u8]> {
+    ASSETS
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, data)| *data)
+}
+
+/// Check if static files were embedded (built with the `embed-static` feature)
+pub fn embedded() -> bool {
+    !ASSETS.is_empty()
+}
+
+/// Read a static file from the static directory, or the embedded files
+pub fn read(path: &str) -> Option<Vec<u8>> {
+    std::fs::read(crate::config::static_dir().join(path))
+        .ok()
+        .or_else(|| get(path).map(|d| d.to_vec()))
+}
+
+/// Get the hash put in the fingerprinted name of a static file (`None` if it doesn't exist)
+fn hash(path: &str) -> Option<String> {
+    let modified = std::fs::metadata(crate::config::static_dir().join(path))
+        .and_then(|m| m.modified())
+        .ok();
+
+    let mut hashes = HASHES.lock().unwrap();
+
+    if let Some((when, hash)) = hashes.get(path) {
+        if *when == modified {
+            return hash.clone();
+        }
+    }
+
+    let hash = read(path).map(|data| {
+        Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>()[..HASH_LENGTH]
+            .to_string()
+    });
+
+    hashes.insert(path.to_string(), (modified, hash.clone()));
+    hash
+}
+
+/// Get the url of a static file, with a hash of its content in the name
+/// (`/static/style.3f2a1b0c.css`)
+///
+/// Files that can't be read keep their plain url.
+///
+/// # Arguments
+/// * `path` - the path of the file, relative to the static directory (`js/footer.js`)
+pub fn url(path: &str) -> String {
+    let prefix = format!(
+        "{}{}",
+        crate::config::base_path(),
+        crate::config::static_prefix()
+    );
+
+    let Some(hash) = hash(path) else {
+        return format!("{prefix}/{path}");
+    };
+
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let dir = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{dir}/")
+    };
+
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            format!("{prefix}/{dir}{stem}.{hash}.{extension}")
+        }
+        _ => format!("{prefix}/{dir}{name}.{hash}"),
+    }
+}
+
+/// Split the hash out of a fingerprinted path (`js/footer.3f2a1b0c.js` to `js/footer.js`)
+fn unfingerprint(path: &str) -> Option<(String, &str)> {
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let dir = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{dir}/")
+    };
+    let (rest, last) = name.rsplit_once('.')?;
+
+    match rest.rsplit_once('.') {
+        Some((stem, hash)) if is_hash(hash) => Some((format!("{dir}{stem}.{last}"), hash)),
+        // files without an extension
+        _ if is_hash(last) => Some((format!("{dir}{rest}"), last)),
+        _ => None,
+    }
+}
+
+fn is_hash(value: &str) -> bool {
+    value.len() == HASH_LENGTH && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Serve fingerprinted urls (see [`url`]) as the file they point to, and set `Cache-Control`
+///
+/// A fingerprint that doesn't match the file anymore (an old page asking after an update) still
+/// gets the file, but it isn't cached for long.
+pub async fn layer(mut req: Request, next: Next) -> Response {
+    let path = req.uri().path().trim_start_matches('/').to_string();
+    let mut immutable = false;
+
+    if let Some((file, fingerprint)) = unfingerprint(&path) {
+        immutable = hash(&file).is_some_and(|h| h == fingerprint);
+
+        let query = req
+            .uri()
+            .query()
+            .map(|q| format!("?{q}"))
+            .unwrap_or_default();
+        if let Ok(uri) = format!("/{file}{query}").parse() {
+            *req.uri_mut() = uri;
+        }
+    }
+
+    let mut res = next.run(req).await;
+
+    if res.status().is_success() || res.status() == StatusCode::NOT_MODIFIED {
+        res.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(if immutable { IMMUTABLE } else { REVALIDATE }),
+        );
+    }
+
+    res
+}
+
+/// Serve an embedded file (the fallback of the static directory)
+pub async fn request(uri: Uri) -> Response {
+    let path = uri.path().trim_start_matches('/');
+
+    match get(path) {
+        Some(data) => (
+            [(
+                header::CONTENT_TYPE,
+                mime_guess::from_path(path)
+                    .first_or_octet_stream()
+                    .to_string(),
+            )],
+            data,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -0,0 +1,227 @@
+//! Files attached to pastes
+//!
+//! Attachments are uploaded through `/api/:url/attachments` and served at
+//! `/:url/attachments/:name`, with the same access rules as the paste. Their content is kept in
+//! the configured [`AttachmentStorage`] under a random key (so renaming a paste doesn't move
+//! anything), and the rest in `se_attachments`.
+//!
+//! Markdown refers to them as `attachment:name` (`![diagram](attachment:diagram.png)`), those
+//! links are pointed at the paste's attachments once the content is rendered (see [`resolve`]).
+use crate::access::AccessQuery;
+use crate::config::{AttachmentStorage, S3Bucket};
+use hmac::{Hmac, Mac};
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+use std::sync::LazyLock;
+
+/// The longest name an attachment can have (in characters)
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The largest response read from the object storage (what a single upload can be, plus some
+/// room for headers)
+fn max_object_size() -> usize {
+    crate::config::body_limits().upload + 64 * 1024
+}
+
+/// Types of files browsers can show without running anything, the others are always downloaded
+const INLINE_TYPES: [&str; 8] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/avif",
+    "video/mp4",
+    "video/webm",
+    "audio/mpeg",
+];
+
+/// Get the name an uploaded file is attached as: its file name, with everything but letters,
+/// digits, `.`, `-` and `_` replaced (`None` if nothing is left)
+pub fn clean_name(filename: &str) -> Option<String> {
+    let name = filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() | matches!(c, '.' | '-' | '_') => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+
+    let name = name.trim_matches(['.', '-']);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Get the `Content-Type` of an attachment (from its name, so it can't be made up by the uploader)
+pub fn content_type(name: &str) -> String {
+    mime_guess::from_path(name)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string()
+}
+
+/// Check if an attachment can be shown in the browser (instead of downloaded)
+pub fn is_inline(content_type: &str) -> bool {
+    INLINE_TYPES.contains(&content_type)
+}
+
+/// Point the `attachment:` links of rendered content at the attachments of a paste
+///
+/// # Arguments
+/// * `html` - the rendered content
+/// * `url` - the paste's `url`
+/// * `access` - the query the paste was opened with (its share link, or view password, is passed
+///   on to the links)
+pub fn resolve(html: &str, url: &str, access: &AccessQuery) -> String {
+    static LINK: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"(src|href)="attachment:([^"?#]+)""#).unwrap());
+
+    let encode = |s: &str| {
+        percent_encoding::utf8_percent_encode(s, percent_encoding::NON_ALPHANUMERIC).to_string()
+    };
+
+    // (a share link is passed on instead of the view password it stands in for)
+    let query = if !access.sig.is_empty() {
+        format!("?sig={}&amp;exp={}", encode(&access.sig), access.exp)
+    } else if !access.view_password.is_empty() {
+        format!("?view_password={}", encode(&access.view_password))
+    } else {
+        String::new()
+    };
+
+    let base_path = crate::config::base_path();
+    LINK.replace_all(html, |c: &Captures| {
+        format!(
+            "{}=\"{base_path}/{url}/attachments/{}{query}\"",
+            &c[1], &c[2]
+        )
+    })
+    .to_string()
+}
+
+/// Store the content of an attachment
+pub async fn write(key: String, data: Vec<u8>) -> Result<(), String> {
+    let storage = crate::config::attachment_storage();
+
+    tokio::task::spawn_blocking(move || match storage {
+        AttachmentStorage::Filesystem(dir) => {
+            std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            std::fs::write(dir.join(key), data).map_err(|e| e.to_string())
+        }
+        AttachmentStorage::S3(bucket) => match s3_request(&bucket, "PUT", &key, &data)? {
+            res if res.is_success() => Ok(()),
+            res => Err(format!("The object storage answered with {}.", res.status)),
+        },
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Read the content of an attachment
+pub async fn read(key: String) -> Option<Vec<u8>> {
+    let storage = crate::config::attachment_storage();
+
+    tokio::task::spawn_blocking(move || match storage {
+        AttachmentStorage::Filesystem(dir) => std::fs::read(dir.join(key)).ok(),
+        AttachmentStorage::S3(bucket) => s3_request(&bucket, "GET", &key, &[])
+            .ok()
+            .filter(|res| res.is_success())
+            .map(|res| res.body),
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Delete the content of an attachment (attachments already gone are fine)
+pub async fn remove(key: String) {
+    let storage = crate::config::attachment_storage();
+
+    let removed = tokio::task::spawn_blocking(move || match storage {
+        AttachmentStorage::Filesystem(dir) => match std::fs::remove_file(dir.join(key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        },
+        AttachmentStorage::S3(bucket) => match s3_request(&bucket, "DELETE", &key, &[])? {
+            res if res.is_success() || (res.status == 404) => Ok(()),
+            res => Err(format!("The object storage answered with {}.", res.status)),
+        },
+    })
+    .await;
+
+    if let Ok(Err(e)) = removed {
+        tracing::warn!(error = %e, "Failed to delete an attachment.");
+    }
+}
+
+/// Send a signed request for an object of a bucket (blocking)
+///
+/// See <https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html>.
+fn s3_request(
+    bucket: &S3Bucket,
+    method: &str,
+    key: &str,
+    body: &[u8],
+) -> Result<crate::http::HttpResponse, String> {
+    let endpoint = url::Url::parse(&bucket.endpoint).map_err(|_| "Invalid S3_ENDPOINT.")?;
+    let host = match endpoint.port() {
+        Some(port) => format!("{}:{port}", endpoint.host_str().unwrap_or_default()),
+        None => endpoint.host_str().unwrap_or_default().to_string(),
+    };
+
+    // (keys are random ids, so nothing in the path needs encoding)
+    let path = format!(
+        "{}/{}/{key}",
+        endpoint.path().trim_end_matches('/'),
+        bucket.bucket
+    );
+
+    let date =
+        crate::utility::iso8601(dorsal::utility::unix_epoch_timestamp()).replace(['-', ':'], "");
+    let day = &date[..8];
+    let payload_hash = format!("{:x}", Sha256::digest(body));
+
+    let canonical_request = format!(
+        "{method}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{date}\n\nhost;x-amz-content-sha256;x-amz-date\n{payload_hash}"
+    );
+
+    let scope = format!("{day}/{}/s3/aws4_request", bucket.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{date}\n{scope}\n{:x}",
+        Sha256::digest(canonical_request.as_bytes())
+    );
+
+    let sign = |key: &[u8], data: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key length works");
+        mac.update(data.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    };
+
+    let mut signing_key = sign(format!("AWS4{}", bucket.secret_key).as_bytes(), day);
+    for part in [bucket.region.as_str(), "s3", "aws4_request"] {
+        signing_key = sign(&signing_key, part);
+    }
+
+    let signature = sign(&signing_key, &string_to_sign)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+
+    let headers = [
+        ("x-amz-date".to_string(), date.clone()),
+        ("x-amz-content-sha256".to_string(), payload_hash),
+        (
+            "Authorization".to_string(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={signature}",
+                bucket.access_key
+            ),
+        ),
+    ];
+
+    let url = format!("{}://{host}{path}", endpoint.scheme());
+
+    crate::http::send_trusted_blocking(method, &url, &headers, body, max_object_size())
+}
@@ -0,0 +1,100 @@
+//! Social card images (`/:url/card.png`)
+//!
+//! Cards are drawn with the bitmap font of [`crate::raster`].
+use crate::raster::{parse_hex_color, Canvas, DEFAULT_ACCENT, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// Card width (in pixels)
+pub const CARD_WIDTH: usize = 1200;
+/// Card height (in pixels)
+pub const CARD_HEIGHT: usize = 630;
+
+/// Space between the card edge and its content (in pixels)
+const PADDING: usize = 64;
+
+const BACKGROUND: [u8; 3] = [23, 23, 23];
+const FOREGROUND: [u8; 3] = [229, 231, 235];
+const MUTED: [u8; 3] = [163, 163, 163];
+
+/// Wrap `text` into at most `max_lines` lines of at most `width` characters
+///
+/// The last line ends with `...` if the text doesn't fit.
+fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        // split words that can never fit on a line
+        let chars: Vec<char> = word.chars().collect();
+        for part in chars.chunks(width) {
+            let part: String = part.iter().collect();
+
+            if line.is_empty() {
+                line = part;
+            } else if line.chars().count() + 1 + part.chars().count() <= width {
+                line.push(' ');
+                line.push_str(&part);
+            } else {
+                lines.push(std::mem::replace(&mut line, part));
+            }
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+
+        let last = &mut lines[max_lines - 1];
+        let kept: String = last.chars().take(width.saturating_sub(3)).collect();
+        *last = format!("{}...", kept);
+    }
+
+    lines
+}
+
+/// Render the social card of a paste as PNG
+///
+/// # Arguments
+/// * `title` - the paste title
+/// * `snippet` - preview text of the paste content
+/// * `site_name` - the name of the instance
+/// * `accent` - the instance accent color (`THEME_ACCENT`)
+pub fn render_card(title: &str, snippet: &str, site_name: &str, accent: &str) -> Vec<u8> {
+    let accent = parse_hex_color(accent).unwrap_or(DEFAULT_ACCENT);
+    let mut canvas = Canvas::new(CARD_WIDTH, CARD_HEIGHT, BACKGROUND);
+
+    // accent bar
+    canvas.fill(0, 0, CARD_WIDTH, 16, accent);
+
+    // title
+    let (title_scale, snippet_scale) = (8, 4);
+    let columns = |scale: usize| (CARD_WIDTH - PADDING * 2) / ((GLYPH_WIDTH + 1) * scale);
+
+    let mut y = PADDING + 16;
+    for line in wrap(title, columns(title_scale), 2) {
+        canvas.text(PADDING, y, &line, title_scale, FOREGROUND);
+        y += (GLYPH_HEIGHT + 3) * title_scale;
+    }
+
+    // snippet
+    y += 16;
+    for line in wrap(snippet, columns(snippet_scale), 5) {
+        canvas.text(PADDING, y, &line, snippet_scale, MUTED);
+        y += (GLYPH_HEIGHT + 4) * snippet_scale;
+    }
+
+    // site name
+    let footer_y = CARD_HEIGHT - PADDING - GLYPH_HEIGHT * snippet_scale;
+    canvas.fill(PADDING, footer_y - 24, 96, 6, accent);
+    canvas.text(
+        PADDING,
+        footer_y,
+        &wrap(site_name, columns(snippet_scale), 1).concat(),
+        snippet_scale,
+        FOREGROUND,
+    );
+
+    canvas.png()
+}
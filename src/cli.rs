@@ -0,0 +1,449 @@
+//! Command line modes of the binary (`sealable paste ...`, `sealable admin ...`)
+//!
+//! Without a subcommand, the binary runs the server.
+use std::io::{IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The settings of the paste client (stored in `~/.config/sealable/cli.json`)
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ClientConfig {
+    /// The origin of the instance pastes are sent to
+    #[serde(default)]
+    instance: String,
+    /// The `__Secure-Token` pastes are created as (pastes are anonymous without one)
+    #[serde(default)]
+    token: String,
+}
+
+const PASTE_USAGE: &str = "usage:
+    sealable paste [FILE] [--url URL] [--password PASSWORD] [--copy]
+    sealable paste login INSTANCE [TOKEN]
+
+Reads the paste from FILE (or stdin) and prints its link.
+SEALABLE_INSTANCE and SEALABLE_TOKEN override the stored login.";
+
+const ADMIN_USAGE: &str = "usage:
+    sealable admin list [--owner USERNAME] [--page PAGE]
+    sealable admin delete URL
+    sealable admin lock URL
+    sealable admin set-password URL PASSWORD
+    sealable admin purge-expired
+    sealable admin quarantined
+    sealable admin release URL [NAME]
+
+Works on the database configured in the environment (or .env).";
+
+/// Run the subcommand in `args` (without the binary name)
+///
+/// # Returns
+/// * `None` if `args` isn't a subcommand (and the server should run)
+/// * the exit code otherwise
+pub async fn run(args: &[String]) -> Option<i32> {
+    let result = match args.first().map(|a| a.as_str()) {
+        Some("paste") => paste(&args[1..]).await,
+        Some("admin") => admin(&args[1..]).await,
+        _ => return None,
+    };
+
+    Some(match result {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    })
+}
+
+/// Get the path of the client config file
+fn config_path() -> Option<PathBuf> {
+    let dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(d) if !d.is_empty() => PathBuf::from(d),
+        _ => PathBuf::from(
+            std::env::var("HOME")
+                .or_else(|_| std::env::var("APPDATA"))
+                .ok()?,
+        )
+        .join(".config"),
+    };
+
+    Some(dir.join("sealable").join("cli.json"))
+}
+
+/// Load the client config (with environment overrides)
+fn load_config() -> ClientConfig {
+    let mut config: ClientConfig = config_path()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default();
+
+    if let Ok(instance) = std::env::var("SEALABLE_INSTANCE") {
+        config.instance = instance;
+    }
+
+    if let Ok(token) = std::env::var("SEALABLE_TOKEN") {
+        config.token = token;
+    }
+
+    config.instance = config.instance.trim_end_matches('/').to_string();
+    config
+}
+
+/// Copy `text` to the clipboard with whichever clipboard tool is installed
+fn copy_to_clipboard(text: &str) -> bool {
+    let tools: [(&str, &[&str]); 4] = [
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("pbcopy", &[]),
+        ("clip.exe", &[]),
+    ];
+
+    tools.iter().any(|(tool, args)| {
+        let Ok(mut child) = Command::new(tool)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return false;
+        };
+
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut s| s.write_all(text.as_bytes()).is_ok());
+
+        written && child.wait().is_ok_and(|s| s.success())
+    })
+}
+
+/// Add a field to a `multipart/form-data` body
+fn push_field(body: &mut Vec<u8>, boundary: &str, name: &str, filename: Option<&str>, data: &[u8]) {
+    let disposition = match filename {
+        Some(f) => format!(
+            "form-data; name=\"{name}\"; filename=\"{}\"",
+            f.replace('"', "")
+        ),
+        None => format!("form-data; name=\"{name}\""),
+    };
+
+    body.extend_from_slice(
+        format!("--{boundary}\r\nContent-Disposition: {disposition}\r\n\r\n").as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(b"\r\n");
+}
+
+/// `sealable paste`
+async fn paste(args: &[String]) -> Result<(), String> {
+    if args.first().is_some_and(|a| a == "login") {
+        return login(&args[1..]);
+    }
+
+    let (mut file, mut url, mut password, mut copy) = (None, String::new(), String::new(), false);
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--url" => url = args.next().ok_or(PASTE_USAGE)?.clone(),
+            "--password" => password = args.next().ok_or(PASTE_USAGE)?.clone(),
+            "--copy" => copy = true,
+            "-h" | "--help" => return Err(PASTE_USAGE.to_string()),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg.clone()),
+            _ => return Err(PASTE_USAGE.to_string()),
+        }
+    }
+
+    let config = load_config();
+    if config.instance.is_empty() {
+        return Err(
+            "No instance configured, run `sealable paste login INSTANCE` first.".to_string(),
+        );
+    }
+
+    // read the paste
+    let (content, filename) = match file.as_deref() {
+        Some("-") | None => {
+            if std::io::stdin().is_terminal() {
+                eprintln!("Reading the paste from stdin (end it with Ctrl+D)...");
+            }
+
+            let mut content = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut content)
+                .map_err(|e| format!("Failed to read stdin: {e}"))?;
+
+            (content, None)
+        }
+        Some(path) => (
+            std::fs::read(path).map_err(|e| format!("Failed to read {path}: {e}"))?,
+            std::path::Path::new(path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string()),
+        ),
+    };
+
+    // send it
+    let boundary = format!("sealable{}", dorsal::utility::random_id());
+    let mut body = Vec::new();
+
+    push_field(
+        &mut body,
+        &boundary,
+        if filename.is_some() {
+            "file"
+        } else {
+            "content"
+        },
+        filename.as_deref(),
+        &content,
+    );
+    push_field(&mut body, &boundary, "url", None, url.as_bytes());
+    push_field(&mut body, &boundary, "password", None, password.as_bytes());
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+    let mut headers = vec![(
+        "Content-Type".to_string(),
+        format!("multipart/form-data; boundary={boundary}"),
+    )];
+
+    if !config.token.is_empty() {
+        headers.push((
+            "Cookie".to_string(),
+            format!("__Secure-Token={}", config.token),
+        ));
+    }
+
+    // the instance was picked by the user, so it can be on the local network
+    std::env::set_var("ALLOW_PRIVATE_NETWORK", "true");
+
+    let response = crate::http::send(
+        "POST",
+        format!("{}/api/new", config.instance),
+        headers,
+        body,
+        10 * 1024 * 1024,
+    )
+    .await?;
+
+    let res: Value = serde_json::from_slice(&response.body)
+        .map_err(|_| format!("The instance responded with {}.", response.status))?;
+
+    if res["success"] != Value::Bool(true) {
+        return Err(res["message"]
+            .as_str()
+            .unwrap_or("Failed to create the paste.")
+            .to_string());
+    }
+
+    let link = format!(
+        "{}/{}",
+        config.instance,
+        res["payload"][1]["url"].as_str().unwrap_or_default()
+    );
+
+    println!("{link}");
+    eprintln!(
+        "Edit password: {}",
+        res["payload"][0].as_str().unwrap_or_default()
+    );
+
+    if copy && !copy_to_clipboard(&link) {
+        eprintln!("No clipboard tool found (wl-copy, xclip, pbcopy).");
+    }
+
+    Ok(())
+}
+
+/// `sealable paste login`
+fn login(args: &[String]) -> Result<(), String> {
+    let (instance, token) = match args {
+        [instance] => (instance, String::new()),
+        [instance, token] => (instance, token.clone()),
+        _ => return Err(PASTE_USAGE.to_string()),
+    };
+
+    if !(instance.starts_with("http://") || instance.starts_with("https://")) {
+        return Err("The instance should be a url (https://paste.example.com).".to_string());
+    }
+
+    let config = ClientConfig {
+        instance: instance.trim_end_matches('/').to_string(),
+        token,
+    };
+
+    let path = config_path().ok_or("Couldn't find the config directory.")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {dir:?}: {e}"))?;
+    }
+
+    std::fs::write(
+        &path,
+        serde_json::to_string_pretty(&config).unwrap_or_default(),
+    )
+    .map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+
+    // the token is a password, so only the user should be able to read it
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+
+    println!("Saved to {}", path.display());
+    Ok(())
+}
+
+/// `sealable admin`
+async fn admin(args: &[String]) -> Result<(), String> {
+    let database = crate::database::Database::from_env().await;
+    database.init().await;
+
+    match args
+        .iter()
+        .map(|a| a.as_str())
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        ["list", options @ ..] => {
+            let (mut owner, mut page) = (None, 0);
+            let mut options = options.iter();
+
+            while let Some(option) = options.next() {
+                match *option {
+                    "--owner" => owner = Some(options.next().ok_or(ADMIN_USAGE)?.to_string()),
+                    "--page" => {
+                        page = options
+                            .next()
+                            .and_then(|p| p.parse().ok())
+                            .ok_or(ADMIN_USAGE)?
+                    }
+                    _ => return Err(ADMIN_USAGE.to_string()),
+                }
+            }
+
+            let pastes = match owner {
+                Some(owner) => database.get_pastes_by_owner(owner, page).await.0,
+                None => database.get_pastes(page).await,
+            };
+
+            for paste in pastes {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    paste.url,
+                    crate::utility::iso8601(paste.date_published),
+                    if paste.metadata.owner.is_empty() {
+                        "-"
+                    } else {
+                        &paste.metadata.owner
+                    },
+                    paste.metadata.title
+                );
+            }
+        }
+        ["delete", url] => {
+            let paste = database
+                .force_delete_paste_by_url(url.to_string())
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("Deleted {}", paste.url);
+        }
+        ["lock", url] => {
+            // nobody knows the new password, so only the owner (and administrators) can edit it
+            database
+                .set_paste_password(url.to_string(), dorsal::utility::random_id())
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("Locked {url}");
+        }
+        ["set-password", url, password] => {
+            if password.is_empty() {
+                return Err("The password can't be empty.".to_string());
+            }
+
+            database
+                .set_paste_password(url.to_string(), password.to_string())
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("Changed the password of {url}");
+        }
+        ["purge-expired"] => {
+            let urls = database.get_expired_urls().await;
+
+            for url in &urls {
+                if let Err(e) = database.force_delete_paste_by_url(url.clone()).await {
+                    eprintln!("Failed to delete {url}: {}", e.to_string());
+                }
+            }
+
+            println!("Purged {} expired pastes", urls.len());
+        }
+        ["quarantined"] => {
+            use crate::database::QUARANTINE_ATTACHMENT;
+
+            for entry in database.get_quarantined().await {
+                // (attachments are listed as `url/name`)
+                let what = match entry.kind.as_str() {
+                    QUARANTINE_ATTACHMENT => {
+                        match database.get_attachment_by_key(&entry.id).await {
+                            Some(a) => format!("{}/{}", a.url, a.name),
+                            None => continue,
+                        }
+                    }
+                    _ => entry.id,
+                };
+
+                println!(
+                    "{}\t{what}\t{}\t{}",
+                    entry.kind,
+                    crate::utility::iso8601(entry.date_created),
+                    entry.reason
+                );
+            }
+        }
+        ["release", url, name @ ..] if name.len() <= 1 => {
+            use crate::database::{QUARANTINE_ATTACHMENT, QUARANTINE_PASTE};
+
+            let paste = database
+                .get_paste_by_url(url.to_string())
+                .await
+                .map_err(|e| e.to_string())?;
+
+            // (a paste is released along with its attachments, unless one is named)
+            let attachments = database
+                .get_attachments_by_url(paste.url.clone())
+                .await
+                .into_iter()
+                .filter(|a| a.quarantined && name.first().is_none_or(|n| a.name == *n));
+
+            let mut released = 0;
+            for attachment in attachments {
+                database
+                    .release(QUARANTINE_ATTACHMENT, &attachment.key)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                released += 1;
+            }
+
+            if name.is_empty() && database.is_quarantined(QUARANTINE_PASTE, &paste.url).await {
+                database
+                    .release(QUARANTINE_PASTE, &paste.url)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                released += 1;
+            }
+
+            println!("Released {released} quarantined items of {}", paste.url);
+        }
+        _ => return Err(ADMIN_USAGE.to_string()),
+    }
+
+    Ok(())
+}
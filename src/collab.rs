@@ -0,0 +1,709 @@
+//! Editing a paste together (`/ws/:url/edit`)
+//!
+//! Everyone editing a paste at the same time joins one session, which holds the content and the
+//! changes made to it since the session opened. Changes are operations in the format of
+//! [ot.js](https://github.com/Operational-Transformation/ot.js) (`[5, "text", -3]` keeps 5
+//! characters, inserts `text` and deletes 3, counted in UTF-16 code units like JavaScript
+//! strings). Each one is made against the last revision its editor has seen: the session
+//! transforms it past the changes made since then, applies it, and sends it on to every other
+//! editor, so everyone ends up with the same content.
+//!
+//! Content is saved like any other edit (see [`crate::api::finish_edit`]) a few seconds after it
+//! changes, and once the last editor leaves.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::CookieJar;
+use pastemd::model::PasteError;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starstraw::model::Profile;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::database::Database;
+use crate::ws::{close, WebSocketUpgrade};
+
+/// How long content has to stay the same before it's saved
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// How many people can edit a paste at once
+const MAX_EDITORS: usize = 16;
+
+/// How many changes a session remembers (changes made against older revisions are refused)
+const MAX_HISTORY: usize = 1000;
+
+/// The longest name an editor can have (in characters)
+const MAX_NAME_LENGTH: usize = 32;
+
+/// A part of an [`Operation`]
+#[derive(Debug, Clone, PartialEq)]
+enum Component {
+    Retain(usize),
+    Insert(Vec<u16>),
+    Delete(usize),
+}
+
+/// A change to some content, going over all of it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Operation {
+    components: Vec<Component>,
+    /// The length of the content the operation applies to
+    base_len: usize,
+    /// The length of the content once it's applied
+    target_len: usize,
+}
+
+impl Operation {
+    fn retain(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        self.base_len += n;
+        self.target_len += n;
+
+        match self.components.last_mut() {
+            Some(Component::Retain(last)) => *last += n,
+            _ => self.components.push(Component::Retain(n)),
+        }
+    }
+
+    fn insert(&mut self, text: &[u16]) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.target_len += text.len();
+
+        // (inserts always go before deletes, so equal operations look the same)
+        let len = self.components.len();
+        match self.components.as_mut_slice() {
+            [.., Component::Insert(last)] => last.extend_from_slice(text),
+            [.., Component::Insert(before), Component::Delete(_)] => before.extend_from_slice(text),
+            [.., Component::Delete(_)] => self
+                .components
+                .insert(len - 1, Component::Insert(text.to_vec())),
+            _ => self.components.push(Component::Insert(text.to_vec())),
+        }
+    }
+
+    fn delete(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        self.base_len += n;
+
+        match self.components.last_mut() {
+            Some(Component::Delete(last)) => *last += n,
+            _ => self.components.push(Component::Delete(n)),
+        }
+    }
+
+    /// Read an operation sent by an editor (`None` if it isn't one)
+    pub fn from_json(value: &[Value]) -> Option<Self> {
+        let mut operation = Self::default();
+
+        for component in value {
+            match component {
+                Value::String(text) => operation.insert(&text.encode_utf16().collect::<Vec<_>>()),
+                Value::Number(n) => match n.as_i64()? {
+                    0 => return None,
+                    n if n > 0 => operation.retain(n as usize),
+                    n => operation.delete(n.unsigned_abs() as usize),
+                },
+                _ => return None,
+            }
+        }
+
+        Some(operation)
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.components
+                .iter()
+                .map(|c| match c {
+                    Component::Retain(n) => json!(n),
+                    Component::Insert(text) => json!(String::from_utf16_lossy(text)),
+                    Component::Delete(n) => json!(-(*n as i64)),
+                })
+                .collect(),
+        )
+    }
+
+    /// Apply the operation to some content (`None` if it doesn't fit)
+    pub fn apply(&self, content: &[u16]) -> Option<Vec<u16>> {
+        if content.len() != self.base_len {
+            return None;
+        }
+
+        let mut out = Vec::with_capacity(self.target_len);
+        let mut at = 0;
+
+        for component in &self.components {
+            match component {
+                Component::Retain(n) => {
+                    out.extend_from_slice(&content[at..at + n]);
+                    at += n;
+                }
+                Component::Insert(text) => out.extend_from_slice(text),
+                Component::Delete(n) => at += n,
+            }
+        }
+
+        Some(out)
+    }
+
+    /// Transform two operations made against the same content, so each one can be applied after
+    /// the other (`apply(b', apply(a, s)) == apply(a', apply(b, s))`)
+    ///
+    /// When both insert at the same place, `a`'s text goes first.
+    ///
+    /// # Returns
+    /// `(a', b')`, or `None` if they weren't made against the same content
+    pub fn transform(a: &Self, b: &Self) -> Option<(Self, Self)> {
+        if a.base_len != b.base_len {
+            return None;
+        }
+
+        let (mut a2, mut b2) = (Self::default(), Self::default());
+        let (mut ops_a, mut ops_b) = (a.components.iter().cloned(), b.components.iter().cloned());
+        let (mut op_a, mut op_b) = (ops_a.next(), ops_b.next());
+
+        loop {
+            match (&op_a, &op_b) {
+                (None, None) => break,
+                (Some(Component::Insert(text)), _) => {
+                    a2.insert(text);
+                    b2.retain(text.len());
+                    op_a = ops_a.next();
+                    continue;
+                }
+                (_, Some(Component::Insert(text))) => {
+                    a2.retain(text.len());
+                    b2.insert(text);
+                    op_b = ops_b.next();
+                    continue;
+                }
+                (None, _) | (_, None) => return None,
+                _ => (),
+            }
+
+            let (Some(first), Some(second)) = (op_a.take(), op_b.take()) else {
+                unreachable!()
+            };
+
+            let (n_a, n_b) = match (&first, &second) {
+                (Component::Retain(n_a), Component::Retain(n_b)) => {
+                    let n = *n_a.min(n_b);
+                    a2.retain(n);
+                    b2.retain(n);
+                    (*n_a, *n_b)
+                }
+                (Component::Delete(n_a), Component::Delete(n_b)) => (*n_a, *n_b),
+                (Component::Delete(n_a), Component::Retain(n_b)) => {
+                    a2.delete(*n_a.min(n_b));
+                    (*n_a, *n_b)
+                }
+                (Component::Retain(n_a), Component::Delete(n_b)) => {
+                    b2.delete(*n_a.min(n_b));
+                    (*n_a, *n_b)
+                }
+                _ => unreachable!(),
+            };
+
+            // (the longer one carries on with what's left of it)
+            let rest = |c: &Component, n: usize| match c {
+                Component::Retain(_) => Component::Retain(n),
+                _ => Component::Delete(n),
+            };
+
+            (op_a, op_b) = match n_a.cmp(&n_b) {
+                std::cmp::Ordering::Greater => (Some(rest(&first, n_a - n_b)), ops_b.next()),
+                std::cmp::Ordering::Equal => (ops_a.next(), ops_b.next()),
+                std::cmp::Ordering::Less => (ops_a.next(), Some(rest(&second, n_b - n_a))),
+            };
+        }
+
+        Some((a2, b2))
+    }
+
+    /// Move a position in the content past the operation (like a cursor)
+    pub fn transform_index(&self, index: usize) -> usize {
+        let (mut index, mut new_index) = (index as i64, index as i64);
+
+        for component in &self.components {
+            match component {
+                Component::Retain(n) => index -= *n as i64,
+                Component::Insert(text) => new_index += text.len() as i64,
+                Component::Delete(n) => {
+                    new_index -= index.min(*n as i64);
+                    index -= *n as i64;
+                }
+            }
+
+            if index < 0 {
+                break;
+            }
+        }
+
+        new_index.max(0) as usize
+    }
+}
+
+/// Someone in a session
+struct Editor {
+    name: String,
+    /// Where their cursor is
+    cursor: usize,
+    /// Messages waiting to be sent to them
+    outbox: mpsc::UnboundedSender<String>,
+}
+
+/// Who changes are saved as (the password, or the user, an editor joined with)
+#[derive(Clone)]
+struct Credentials {
+    password: String,
+    user: Option<Profile>,
+}
+
+/// Everyone editing a paste
+struct Session {
+    /// The paste's (canonical) `url`
+    url: String,
+    content: Vec<u16>,
+    /// How many changes were made to the content since it was saved before the session
+    revision: usize,
+    /// The last changes (up to [`MAX_HISTORY`]), the last one made `revision`
+    history: Vec<Operation>,
+    editors: HashMap<u64, Editor>,
+    /// Who made the last change, which is saved as them
+    saving_as: Option<Credentials>,
+    /// If the content changed since it was saved
+    dirty: bool,
+    /// If a save is waiting for [`SAVE_DELAY`]
+    save_scheduled: bool,
+    /// The public origin of the instance (for the `Edited` event)
+    origin: String,
+    /// Set once the last editor left (joining then opens a new session)
+    closed: bool,
+}
+
+impl Session {
+    /// Send a message to everyone but `except`
+    fn broadcast(&self, message: &Value, except: Option<u64>) {
+        let message = message.to_string();
+
+        for (id, editor) in &self.editors {
+            if Some(*id) != except {
+                let _ = editor.outbox.send(message.clone());
+            }
+        }
+    }
+
+    /// Everyone in the session, with their cursor (the `editors` message)
+    fn editors_message(&self) -> Value {
+        json!({
+            "type": "editors",
+            "editors": self
+                .editors
+                .iter()
+                .map(|(id, e)| json!({ "id": id, "name": e.name, "cursor": e.cursor }))
+                .collect::<Vec<Value>>(),
+        })
+    }
+}
+
+/// The open sessions (by the `url` of their paste)
+static SESSIONS: LazyLock<std::sync::Mutex<HashMap<String, Arc<Mutex<Session>>>>> =
+    LazyLock::new(Default::default);
+
+/// The id of the next editor
+static NEXT_EDITOR: AtomicU64 = AtomicU64::new(1);
+
+/// A message sent by an editor
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// The first message: who's joining (the edit password isn't needed by the owner)
+    Join {
+        #[serde(default)]
+        password: String,
+        #[serde(default)]
+        name: String,
+    },
+    /// A change made against `revision`, and where the editor's cursor is after it
+    Op {
+        revision: usize,
+        op: Vec<Value>,
+        #[serde(default)]
+        cursor: Option<usize>,
+    },
+    Cursor {
+        cursor: usize,
+    },
+}
+
+/// Save the content of a session (with the credentials of the last editor who changed it)
+async fn save(database: &Database, session: &Arc<Mutex<Session>>) {
+    let (url, content, credentials, origin) = {
+        let mut session = session.lock().await;
+        session.save_scheduled = false;
+
+        let Some(credentials) = session.saving_as.clone().filter(|_| session.dirty) else {
+            return;
+        };
+
+        session.dirty = false;
+        (
+            session.url.clone(),
+            String::from_utf16_lossy(&session.content),
+            credentials,
+            session.origin.clone(),
+        )
+    };
+
+    let saved = match database
+        .base
+        .edit_paste_by_url(
+            url.clone(),
+            credentials.password,
+            content,
+            String::new(),
+            String::new(),
+            credentials.user,
+        )
+        .await
+    {
+        Ok(_) => crate::api::finish_edit(database, url, origin).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = saved {
+        tracing::warn!(
+            error = e.to_string(),
+            "Failed to save a paste edited together."
+        );
+        session.lock().await.broadcast(
+            &json!({ "type": "error", "message": crate::i18n::error_text(&e) }),
+            None,
+        );
+    }
+}
+
+/// Save a session once its content stays the same for [`SAVE_DELAY`]
+fn schedule_save(database: &Database, session: &Arc<Mutex<Session>>) {
+    let (database, session) = (database.clone(), session.clone());
+
+    tokio::spawn(async move {
+        tokio::time::sleep(SAVE_DELAY).await;
+        save(&database, &session).await;
+    });
+}
+
+/// Join the session of a paste, opening one if nobody is editing it
+///
+/// # Returns
+/// The session, the editor's id and the `joined` message
+async fn join(
+    database: &Database,
+    url: &str,
+    name: String,
+    outbox: mpsc::UnboundedSender<String>,
+) -> Result<(Arc<Mutex<Session>>, u64, Value), PasteError> {
+    loop {
+        let existing = SESSIONS.lock().unwrap().get(url).cloned();
+
+        let session = match existing {
+            Some(session) => session,
+            None => {
+                let paste = database.get_paste_by_url(url.to_string()).await?;
+                let session = Arc::new(Mutex::new(Session {
+                    url: paste.url.clone(),
+                    // (like the editor, which only has `\n`)
+                    content: paste.content.replace("\r\n", "\n").encode_utf16().collect(),
+                    revision: 0,
+                    history: Vec::new(),
+                    editors: HashMap::new(),
+                    saving_as: None,
+                    dirty: false,
+                    save_scheduled: false,
+                    origin: String::new(),
+                    closed: false,
+                }));
+
+                // (someone else may have opened one in the meantime)
+                SESSIONS
+                    .lock()
+                    .unwrap()
+                    .entry(url.to_string())
+                    .or_insert(session)
+                    .clone()
+            }
+        };
+
+        let mut locked = session.lock().await;
+
+        if locked.closed {
+            continue;
+        }
+
+        if locked.editors.len() >= MAX_EDITORS {
+            return Err(PasteError::ValueError);
+        }
+
+        let id = NEXT_EDITOR.fetch_add(1, Ordering::Relaxed);
+        locked.editors.insert(
+            id,
+            Editor {
+                name,
+                cursor: 0,
+                outbox,
+            },
+        );
+
+        let joined = json!({
+            "type": "joined",
+            "id": id,
+            "revision": locked.revision,
+            "content": String::from_utf16_lossy(&locked.content),
+        });
+
+        locked.broadcast(&locked.editors_message(), None);
+        drop(locked);
+
+        return Ok((session, id, joined));
+    }
+}
+
+/// Leave a session, saving and closing it if nobody is left
+async fn leave(database: &Database, session: &Arc<Mutex<Session>>, id: u64) {
+    let mut locked = session.lock().await;
+    locked.editors.remove(&id);
+
+    if !locked.editors.is_empty() {
+        locked.broadcast(&locked.editors_message(), None);
+        return;
+    }
+
+    locked.closed = true;
+    let url = locked.url.clone();
+    drop(locked);
+
+    save(database, session).await;
+
+    let mut sessions = SESSIONS.lock().unwrap();
+    if sessions.get(&url).is_some_and(|s| Arc::ptr_eq(s, session)) {
+        sessions.remove(&url);
+    }
+}
+
+/// Apply a change made by an editor against `revision`, and tell them (`ack`)
+///
+/// The `ack` is sent before anyone else can change the content, so editors always get it before
+/// changes made after theirs.
+async fn apply(
+    database: &Database,
+    session: &Arc<Mutex<Session>>,
+    id: u64,
+    credentials: &Credentials,
+    revision: usize,
+    op: &[Value],
+    cursor: Option<usize>,
+) -> Result<(), &'static str> {
+    let mut locked = session.lock().await;
+
+    let first = locked.revision - locked.history.len();
+    if (revision < first) || (revision > locked.revision) {
+        return Err("The change was made against a revision that's too old.");
+    }
+
+    let mut op = Operation::from_json(op).ok_or("The change isn't valid.")?;
+
+    for past in &locked.history[revision - first..] {
+        op = Operation::transform(&op, past)
+            .ok_or("The change doesn't fit the content.")?
+            .0;
+    }
+
+    let content = op
+        .apply(&locked.content)
+        .ok_or("The change doesn't fit the content.")?;
+
+    if String::from_utf16_lossy(&content).len() > crate::config::max_content_size() {
+        return Err("The content is too large.");
+    }
+
+    locked.content = content;
+    locked.revision += 1;
+    locked.history.push(op.clone());
+
+    if locked.history.len() > MAX_HISTORY {
+        locked.history.remove(0);
+    }
+
+    for (editor_id, editor) in locked.editors.iter_mut() {
+        editor.cursor = match (*editor_id == id, cursor) {
+            (true, Some(cursor)) => cursor,
+            _ => op.transform_index(editor.cursor),
+        };
+    }
+
+    let revision = locked.revision;
+    locked.broadcast(
+        &json!({ "type": "op", "revision": revision, "op": op.to_json(), "editor": id }),
+        Some(id),
+    );
+
+    if let Some(editor) = locked.editors.get(&id) {
+        let ack = json!({ "type": "ack", "revision": revision });
+        let _ = editor.outbox.send(ack.to_string());
+    }
+
+    locked.saving_as = Some(credentials.clone());
+    locked.dirty = true;
+
+    if !locked.save_scheduled {
+        locked.save_scheduled = true;
+        schedule_save(database, session);
+    }
+
+    Ok(())
+}
+
+/// Edit a paste together (`/ws/:url/edit`)
+///
+/// The first message joins the session (`{"type":"join","password":"...","name":"..."}`), which
+/// is answered with the content and its revision (`joined`). Then editors send their changes
+/// (`{"type":"op","revision":3,"op":[...],"cursor":12}`) and cursors
+/// (`{"type":"cursor","cursor":12}`), and get everyone else's changes (`op`), who's editing
+/// (`editors`) and `{"type":"ack","revision":4}` once their own change is applied.
+pub async fn socket(
+    jar: CookieJar,
+    headers: HeaderMap,
+    Path(url): Path<String>,
+    State(database): State<Database>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let paste = match database.get_paste_by_url(url).await {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+
+    let user = match crate::api::get_editing_as(&jar, &database).await {
+        Ok(user) => user,
+        Err(e) => return e.into_response(),
+    };
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+
+    ws.on_upgrade(
+        crate::config::max_content_size() * 2,
+        move |socket| async move {
+            let (mut messages, sender) = socket.split();
+
+            // join
+            let Some(Ok(ClientMessage::Join { password, name })) = messages
+                .recv()
+                .await
+                .map(|m| serde_json::from_str::<ClientMessage>(&m))
+            else {
+                sender.close(close::PROTOCOL_ERROR).await;
+                return;
+            };
+
+            if !database.can_edit_paste(&paste, &password, user.as_ref()) {
+                let error = crate::i18n::error_text(&PasteError::PasswordIncorrect);
+                sender
+                    .send(&json!({ "type": "error", "message": error }).to_string())
+                    .await;
+                sender.close(close::POLICY_VIOLATION).await;
+                return;
+            }
+
+            let name = match &user {
+                Some(user) => user.username.clone(),
+                None => name.trim().chars().take(MAX_NAME_LENGTH).collect(),
+            };
+
+            let (outbox, mut outgoing) = mpsc::unbounded_channel::<String>();
+            let (session, id, joined) = match join(&database, &paste.url, name, outbox).await {
+                Ok(joined) => joined,
+                Err(e) => {
+                    let error = crate::i18n::error_text(&e);
+                    sender
+                        .send(&json!({ "type": "error", "message": error }).to_string())
+                        .await;
+                    sender.close(close::POLICY_VIOLATION).await;
+                    return;
+                }
+            };
+
+            session.lock().await.origin = origin;
+            sender.send(&joined.to_string()).await;
+
+            // (so a slow editor doesn't hold back the session)
+            let forward = {
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    while let Some(message) = outgoing.recv().await {
+                        if !sender.send(&message).await {
+                            break;
+                        }
+                    }
+                })
+            };
+
+            let credentials = Credentials { password, user };
+
+            while let Some(message) = messages.recv().await {
+                match serde_json::from_str::<ClientMessage>(&message) {
+                    Ok(ClientMessage::Op {
+                        revision,
+                        op,
+                        cursor,
+                    }) => {
+                        let applied =
+                            apply(&database, &session, id, &credentials, revision, &op, cursor)
+                                .await;
+
+                        // (the editor can't make sense of anything after this, so it rejoins)
+                        if let Err(error) = applied {
+                            let reply =
+                                json!({ "type": "error", "message": error, "rejoin": true });
+                            let locked = session.lock().await;
+
+                            if let Some(editor) = locked.editors.get(&id) {
+                                let _ = editor.outbox.send(reply.to_string());
+                            }
+                        }
+                    }
+                    Ok(ClientMessage::Cursor { cursor }) => {
+                        let mut locked = session.lock().await;
+                        if let Some(editor) = locked.editors.get_mut(&id) {
+                            editor.cursor = cursor;
+                        }
+
+                        locked.broadcast(
+                            &json!({ "type": "cursor", "editor": id, "cursor": cursor }),
+                            Some(id),
+                        );
+                    }
+                    _ => break,
+                }
+            }
+
+            leave(&database, &session, id).await;
+            forward.abort();
+            sender.close(close::NORMAL).await;
+        },
+    )
+}
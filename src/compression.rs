@@ -0,0 +1,142 @@
+//! Response compression (gzip), see [`crate::AppConfig::compression`]
+//!
+//! Rendered pastes, raw text and JSON compress very well, so responses of those types are gzipped
+//! for clients sending `Accept-Encoding: gzip`. Only responses of a known size are compressed
+//! (streamed ones, like event streams, are left alone), and anything under [`MIN_SIZE`] isn't
+//! worth the trouble. Responses are compressed on the blocking pool, off the async workers.
+//!
+//! Only gzip is supported: there's no brotli or zstd encoder to build on.
+use std::io::Write;
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// The smallest response compressed (in bytes), gzip doesn't shrink anything much smaller
+const MIN_SIZE: u64 = 1024;
+
+/// The largest response compressed (in bytes), since the whole response is kept in memory (and
+/// compressing takes a while)
+const MAX_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Content types worth compressing (images, fonts and archives already are compressed)
+const COMPRESSIBLE_TYPES: [&str; 11] = [
+    "text/html",
+    "text/plain",
+    "text/markdown",
+    "text/css",
+    "text/javascript",
+    "application/javascript",
+    "application/json",
+    "application/activity+json",
+    "application/atom+xml",
+    "application/xml",
+    "image/svg+xml",
+];
+
+/// Check if the client accepts gzip (`Accept-Encoding`, without `q=0`)
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .any(|encoding| {
+            let mut parts = encoding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+
+            let q = parts
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && q > 0.0
+        })
+}
+
+/// Check if a response is worth compressing (before looking at its size)
+fn compressible(res: &Response) -> bool {
+    let headers = res.headers();
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .map(|t| {
+            t.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        })
+        .unwrap_or_default();
+
+    // partial responses are ranges of the uncompressed body
+    res.status() != StatusCode::PARTIAL_CONTENT
+        && !headers.contains_key(header::CONTENT_ENCODING)
+        && !headers.contains_key(header::CONTENT_RANGE)
+        && COMPRESSIBLE_TYPES.contains(&content_type.as_str())
+}
+
+/// Gzip responses worth compressing, for clients that accept it
+pub async fn layer(req: Request, next: Next) -> Response {
+    let gzip = accepts_gzip(req.headers());
+    let mut res = next.run(req).await;
+
+    if !compressible(&res) {
+        return res;
+    }
+
+    // the response depends on `Accept-Encoding` whether or not it's compressed this time
+    res.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+    let size = res.body().size_hint().exact();
+
+    if !gzip || !size.is_some_and(|size| (MIN_SIZE..=MAX_SIZE).contains(&size)) {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, MAX_SIZE as usize).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let compressed = tokio::task::spawn_blocking(move || {
+        let mut encoder =
+            GzEncoder::new(Vec::with_capacity(body.len() / 4), Compression::default());
+
+        match encoder.write_all(&body).and_then(|_| encoder.finish()) {
+            Ok(compressed) => Ok(compressed),
+            Err(_) => Err(body),
+        }
+    })
+    .await;
+
+    let compressed = match compressed {
+        Ok(Ok(compressed)) => compressed,
+        // (sent as it is)
+        Ok(Err(body)) => return Response::from_parts(parts, Body::from(body)),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let headers = &mut parts.headers;
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(compressed.len()));
+
+    // the compressed body isn't byte-for-byte the one a strong `ETag` was made for
+    if let Some(etag) = headers.get(header::ETAG).and_then(|h| h.to_str().ok()) {
+        if !etag.starts_with("W/") {
+            if let Ok(weak) = HeaderValue::from_str(&format!("W/{etag}")) {
+                headers.insert(header::ETAG, weak);
+            }
+        }
+    }
+
+    Response::from_parts(parts, Body::from(compressed))
+}
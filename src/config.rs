@@ -0,0 +1,787 @@
+//! App configuration for [`crate::routes`]
+//!
+//! Everything not in [`AppConfig`] is still read from the environment (see `crate::base`). The
+//! config of the running app is kept around for code that isn't handed it (rendering, exports).
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::database::Database;
+use crate::hooks::Hooks;
+use crate::scan::{ClamAv, HttpScanner, Scanner};
+
+/// The config of the running app (set by [`crate::routes`])
+static CURRENT: RwLock<Option<AppConfig>> = RwLock::new(None);
+
+/// Options for rendering paste markdown
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// If the custom blocks (`[s]...[/s]` spans, and `<(...)>` attribute blocks) are rendered
+    pub custom_blocks: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            custom_blocks: true,
+        }
+    }
+}
+
+/// The largest request bodies accepted (in bytes), see [`crate::middleware::body_limit`]
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimits {
+    /// Creating and editing pastes, and everything else without its own limit (`MAX_PASTE_SIZE`)
+    pub paste: usize,
+    /// Previews rendered through `/api/render` (`MAX_RENDER_SIZE`)
+    pub render: usize,
+    /// File uploads (`MAX_UPLOAD_SIZE`)
+    pub upload: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            paste: 2 * 1024 * 1024,
+            render: 512 * 1024,
+            upload: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl BodyLimits {
+    /// Read the limits from the environment (falling back to the defaults)
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            paste: var("MAX_PASTE_SIZE", defaults.paste),
+            render: var("MAX_RENDER_SIZE", defaults.render),
+            upload: var("MAX_UPLOAD_SIZE", defaults.upload),
+        }
+    }
+
+    /// Get the limit of the request body sent to `path`
+    pub fn for_path(&self, path: &str) -> usize {
+        match path {
+            "/api/render" => self.render,
+            "/api/mirror" => crate::mirror::MAX_MIRROR_SIZE,
+            p if p.starts_with("/api/") && p.ends_with("/attachments") => self.upload,
+            _ => self.paste,
+        }
+    }
+}
+
+/// Where the files attached to pastes are kept (see [`crate::attachments`])
+#[derive(Debug, Clone)]
+pub enum AttachmentStorage {
+    /// A directory on the server
+    Filesystem(PathBuf),
+    /// A bucket of an S3 compatible object storage
+    S3(S3Bucket),
+}
+
+/// An S3 bucket (requests are signed with AWS Signature Version 4, and use path-style urls)
+#[derive(Debug, Clone)]
+pub struct S3Bucket {
+    /// The url of the storage service (`https://s3.eu-west-1.amazonaws.com`)
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+impl AttachmentStorage {
+    /// Read the storage from the environment: an S3 bucket when `S3_BUCKET` is set (along with
+    /// `S3_ENDPOINT`, `S3_REGION`, `S3_ACCESS_KEY` and `S3_SECRET_KEY`), `ATTACHMENTS_DIR` (or
+    /// `./attachments`) otherwise
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+
+        match var("S3_BUCKET") {
+            bucket if !bucket.is_empty() => Self::S3(S3Bucket {
+                endpoint: var("S3_ENDPOINT").trim_end_matches('/').to_string(),
+                bucket,
+                region: match var("S3_REGION") {
+                    region if region.is_empty() => "us-east-1".to_string(),
+                    region => region,
+                },
+                access_key: var("S3_ACCESS_KEY"),
+                secret_key: var("S3_SECRET_KEY"),
+            }),
+            _ => Self::Filesystem(match var("ATTACHMENTS_DIR") {
+                dir if dir.is_empty() => PathBuf::from("./attachments"),
+                dir => PathBuf::from(dir),
+            }),
+        }
+    }
+}
+
+/// How long requests can take, and how many can be handled at once (see
+/// [`crate::middleware::load_shed`])
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// How long a request can take to respond (`REQUEST_TIMEOUT`, in seconds)
+    pub timeout: Duration,
+    /// How long exports, cards, imports and mirroring can take (`SLOW_REQUEST_TIMEOUT`)
+    pub slow_timeout: Duration,
+    /// How many requests can be handled at once, the rest are turned away
+    /// (`MAX_CONCURRENT_REQUESTS`, 0 for no limit)
+    pub max_concurrent: usize,
+    /// How long a markdown preview (`/api/render`) can take to render (`RENDER_TIMEOUT`)
+    pub render_timeout: Duration,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            slow_timeout: Duration::from_secs(120),
+            max_concurrent: 256,
+            render_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RequestLimits {
+    /// Read the limits from the environment (falling back to the defaults)
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        Self {
+            timeout: var("REQUEST_TIMEOUT")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+            slow_timeout: var("SLOW_REQUEST_TIMEOUT")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.slow_timeout),
+            max_concurrent: var("MAX_CONCURRENT_REQUESTS")
+                .map(|m| m as usize)
+                .unwrap_or(defaults.max_concurrent),
+            render_timeout: var("RENDER_TIMEOUT")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.render_timeout),
+        }
+    }
+
+    /// Get how long a request to `path` can take
+    pub fn timeout_for(&self, path: &str) -> Duration {
+        let slow = path.contains("/export/")
+            || path.ends_with("/card.png")
+            || path.starts_with("/api/import/")
+            || path.starts_with("/api/admin/mirror/");
+
+        match slow {
+            true => self.slow_timeout,
+            false => self.timeout,
+        }
+    }
+}
+
+/// How many requests a client can send to a group of routes (see [`crate::rate_limit`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// How many requests are allowed every `window`
+    pub requests: u32,
+    pub window: Duration,
+    /// How many requests can be sent at once (after a quiet spell), `requests` by default
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub const fn new(requests: u32, window_secs: u64) -> Self {
+        Self {
+            requests,
+            window: Duration::from_secs(window_secs),
+            burst: requests,
+        }
+    }
+
+    /// Parse a limit (`requests/seconds`, like `60/60`), `None` if it's `off` (or invalid)
+    fn parse(value: &str) -> Option<Self> {
+        let (requests, window) = value.trim().split_once('/')?;
+        let requests: u32 = requests.trim().parse().ok()?;
+        let window: u64 = window.trim().trim_end_matches('s').parse().ok()?;
+
+        if requests == 0 || window == 0 {
+            return None;
+        }
+
+        Some(Self::new(requests, window))
+    }
+}
+
+/// The rate limits of each group of routes, per client address (`None` for no limit)
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimits {
+    /// Pages, raw content, drafts and the API's read routes (`RATE_LIMIT_VIEW`)
+    pub view: Option<RateLimit>,
+    /// Creating, editing and deleting pastes and collections (`RATE_LIMIT_WRITE`)
+    pub write: Option<RateLimit>,
+    /// Views with a view password, so passwords can't be guessed quickly (`RATE_LIMIT_UNLOCK`)
+    pub unlock: Option<RateLimit>,
+    /// Markdown previews (`RATE_LIMIT_RENDER`)
+    pub render: Option<RateLimit>,
+    /// Leaving comments, so they can't be spammed (`RATE_LIMIT_COMMENT`)
+    pub comment: Option<RateLimit>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            view: Some(RateLimit::new(600, 60)),
+            write: Some(RateLimit::new(30, 60)),
+            unlock: Some(RateLimit::new(10, 60)),
+            render: Some(RateLimit::new(120, 60)),
+            comment: Some(RateLimit::new(5, 300)),
+        }
+    }
+}
+
+impl RateLimits {
+    /// Read the limits from the environment (falling back to the defaults)
+    ///
+    /// Every limit is read from `RATE_LIMIT_{NAME}` (`requests/seconds`, or `off`) and
+    /// `RATE_LIMIT_{NAME}_BURST`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str, default: Option<RateLimit>| {
+            let mut limit = match std::env::var(format!("RATE_LIMIT_{name}")) {
+                Ok(v) if v.trim() == "off" => return None,
+                Ok(v) => match RateLimit::parse(&v) {
+                    Some(limit) => Some(limit),
+                    None => {
+                        tracing::warn!("Ignoring the invalid rate limit RATE_LIMIT_{name}={v:?}.");
+                        default
+                    }
+                },
+                Err(_) => default,
+            }?;
+
+            if let Some(burst) = std::env::var(format!("RATE_LIMIT_{name}_BURST"))
+                .ok()
+                .and_then(|b| b.parse::<u32>().ok())
+            {
+                limit.burst = burst.max(1);
+            }
+
+            Some(limit)
+        };
+
+        Self {
+            view: var("VIEW", defaults.view),
+            write: var("WRITE", defaults.write),
+            unlock: var("UNLOCK", defaults.unlock),
+            render: var("RENDER", defaults.render),
+            comment: var("COMMENT", defaults.comment),
+        }
+    }
+
+    /// Get the group a request belongs to, and its limit
+    ///
+    /// # Returns
+    /// `None` for requests that aren't limited (static files, metrics, authentication and
+    /// administration)
+    pub fn for_request(
+        &self,
+        method: &axum::http::Method,
+        path: &str,
+        query: Option<&str>,
+    ) -> Option<(&'static str, RateLimit)> {
+        use axum::http::Method;
+
+        if path.starts_with(&format!("{}/", static_prefix()))
+            || path == "/metrics"
+            || path.starts_with("/_dev/")
+            || path.starts_with("/api/admin/")
+            || path.starts_with("/api/auth/")
+            || path.starts_with("/star/")
+            || path.starts_with("/a/pongo")
+            || method == Method::OPTIONS
+        {
+            return None;
+        }
+
+        if path == "/api/render" {
+            return self.render.map(|l| ("render", l));
+        }
+
+        // (the editor saves drafts every few seconds, they'd use up the write limit)
+        if path.ends_with("/draft") {
+            return self.view.map(|l| ("view", l));
+        }
+
+        if (method == Method::POST) && path.starts_with("/api/") && path.ends_with("/comments") {
+            return self.comment.map(|l| ("comment", l));
+        }
+
+        if !matches!(*method, Method::GET | Method::HEAD) {
+            return (path != "/preferences")
+                .then_some(self.write)
+                .flatten()
+                .map(|l| ("write", l));
+        }
+
+        let unlocking = query.is_some_and(|q| {
+            q.split('&')
+                .any(|p| p.starts_with("view_password=") && p.len() > "view_password=".len())
+        });
+
+        match unlocking {
+            true => self.unlock.map(|l| ("unlock", l)),
+            false => self.view.map(|l| ("view", l)),
+        }
+    }
+}
+
+/// A network of addresses (`10.0.0.0/8`, or a single address like `127.0.0.1`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    pub addr: IpAddr,
+    /// How many leading bits of `addr` are fixed
+    pub prefix: u8,
+}
+
+impl IpNetwork {
+    /// Parse a network (`addr/prefix`), or a single address
+    pub fn parse(value: &str) -> Option<Self> {
+        let (addr, prefix) = match value.trim().split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+            None => (value.trim().parse::<IpAddr>().ok()?, None),
+        };
+
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+
+        if prefix > max {
+            return None;
+        }
+
+        Some(Self { addr, prefix })
+    }
+
+    /// Check if an address is in the network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let bits = |ip: IpAddr| match ip.to_canonical() {
+            IpAddr::V4(ip) => (u128::from(u32::from(ip)), 32),
+            IpAddr::V6(ip) => (u128::from(ip), 128),
+        };
+
+        let ((network, len), (ip, ip_len)) = (bits(self.addr), bits(ip));
+        if len != ip_len {
+            return false;
+        }
+
+        let shift = u32::from(len - self.prefix);
+        (self.prefix == 0) || (network.checked_shr(shift) == ip.checked_shr(shift))
+    }
+}
+
+/// The proxies trusted by default (loopback, so a reverse proxy on the same machine works)
+fn loopback() -> Vec<IpNetwork> {
+    ["127.0.0.0/8", "::1"]
+        .iter()
+        .filter_map(|n| IpNetwork::parse(n))
+        .collect()
+}
+
+/// Everything needed to build the app's [`axum::Router`]
+#[derive(Clone)]
+pub struct AppConfig {
+    /// The database (already initialized)
+    pub database: Database,
+    /// The directory served at `static_prefix` (`STATIC_DIR` by default, needs a built `style.css`,
+    /// falls back to embedded files)
+    pub static_dir: PathBuf,
+    /// The path static files are served at (`STATIC_PREFIX` by default, `/static` without it)
+    pub static_prefix: String,
+    /// The path the embedding app mounts the app at (with `Router::nest`, like `/docs`), which
+    /// links, redirects and `origin` start with (empty at the root)
+    pub base_path: String,
+    /// The directory layout overrides are loaded from (`TEMPLATES_DIR` by default, see
+    /// `crate::layout`)
+    pub templates_dir: Option<PathBuf>,
+    /// How markdown is rendered
+    pub render: RenderOptions,
+    /// How many rendered pastes are kept in memory (`RENDER_CACHE_SIZE` by default, 0 for none)
+    pub render_cache_size: usize,
+    /// The largest request bodies accepted (`MAX_PASTE_SIZE`, `MAX_RENDER_SIZE` and
+    /// `MAX_UPLOAD_SIZE` by default)
+    pub body_limits: BodyLimits,
+    /// Request timeouts and the concurrency limit (`REQUEST_TIMEOUT`, `SLOW_REQUEST_TIMEOUT` and
+    /// `MAX_CONCURRENT_REQUESTS` by default)
+    pub request_limits: RequestLimits,
+    /// Rate limits of each group of routes (`RATE_LIMIT_VIEW`, `RATE_LIMIT_WRITE`,
+    /// `RATE_LIMIT_UNLOCK`, `RATE_LIMIT_RENDER` and `RATE_LIMIT_COMMENT` by default)
+    pub rate_limits: RateLimits,
+    /// If public pastes are published over ActivityPub (`ACTIVITYPUB` by default)
+    pub activitypub: bool,
+    /// If pastes can be mirrored with `MIRROR_PEERS` (mounts `/api/mirror` and its admin routes)
+    pub mirroring: bool,
+    /// If `/metrics` is mounted and requests are measured (`METRICS` by default)
+    pub metrics: bool,
+    /// If responses are compressed (`COMPRESSION` by default, off when it's `false`)
+    pub compression: bool,
+    /// If layout overrides and static files are watched, and pages reload when they change
+    pub dev: bool,
+    /// Peers whose `X-Forwarded-For` header is believed (`TRUSTED_PROXIES` by default, loopback
+    /// without it)
+    pub trusted_proxies: Vec<IpNetwork>,
+    /// Paste urls nobody can take, on top of the app's own routes (`RESERVED_URLS` by default,
+    /// see `crate::slug`)
+    pub reserved_urls: Vec<String>,
+    /// Where the files attached to pastes are kept (see [`AttachmentStorage::from_env`])
+    pub attachments: AttachmentStorage,
+    /// How many bytes of files can be attached to each paste (`MAX_ATTACHMENTS_SIZE` by default)
+    pub max_attachments_size: usize,
+    /// How many bytes of content a paste can have (`MAX_CONTENT_SIZE` by default, at most
+    /// [`MAX_CONTENT_SIZE`])
+    pub max_content_size: usize,
+    /// Hooks run by the app (see [`AppConfig::hook`])
+    pub hooks: Vec<Arc<dyn Hooks>>,
+    /// What attachments and large pastes are scanned with (`CLAMAV_ADDRESS` and `SCANNER_URL` by
+    /// default, see [`AppConfig::scanner`])
+    pub scanners: Vec<Arc<dyn Scanner>>,
+    /// What happens to flagged content (`SCAN_ACTION` by default)
+    pub scan_action: ScanAction,
+    /// How many bytes a paste's content has to be for it to be scanned (`SCAN_PASTE_SIZE` by
+    /// default)
+    pub scan_paste_size: usize,
+    /// If pastes take comments, and how they're shown (`COMMENTS` by default)
+    pub comments: CommentMode,
+}
+
+/// If pastes take comments (each paste can still turn them off, see
+/// [`crate::model::CommentPolicy`])
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CommentMode {
+    /// Nobody can comment (existing comments aren't shown)
+    #[default]
+    Off,
+    /// Comments are shown one after the other
+    Flat,
+    /// Comments can answer each other, and are shown under what they answer
+    Threaded,
+}
+
+impl CommentMode {
+    /// Read the mode from `COMMENTS` (`flat` or `threaded`, off otherwise)
+    pub fn from_env() -> Self {
+        match std::env::var("COMMENTS").as_deref() {
+            Ok("flat") => Self::Flat,
+            Ok("threaded") => Self::Threaded,
+            _ => Self::Off,
+        }
+    }
+}
+
+/// What happens to content flagged by a scanner (see [`crate::scan`])
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScanAction {
+    /// It isn't saved
+    #[default]
+    Reject,
+    /// It's saved, but only administrators can see it until it's released
+    Quarantine,
+}
+
+impl ScanAction {
+    /// Read the action from `SCAN_ACTION` (`reject` or `quarantine`)
+    pub fn from_env() -> Self {
+        match std::env::var("SCAN_ACTION").as_deref() {
+            Ok("quarantine") => Self::Quarantine,
+            _ => Self::Reject,
+        }
+    }
+}
+
+/// Set up the scanners configured with `CLAMAV_ADDRESS` and `SCANNER_URL`
+fn scanners_from_env() -> Vec<Arc<dyn Scanner>> {
+    let mut scanners: Vec<Arc<dyn Scanner>> = Vec::new();
+
+    if let Ok(address) = std::env::var("CLAMAV_ADDRESS") {
+        if !address.is_empty() {
+            scanners.push(Arc::new(ClamAv { address }));
+        }
+    }
+
+    if let Ok(url) = std::env::var("SCANNER_URL") {
+        if !url.is_empty() {
+            scanners.push(Arc::new(HttpScanner { url }));
+        }
+    }
+
+    scanners
+}
+
+/// Read how many bytes a paste's content has to be for it to be scanned from `SCAN_PASTE_SIZE`
+fn scan_paste_size_from_env() -> usize {
+    std::env::var("SCAN_PASTE_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(64 * 1024)
+}
+
+/// The most content `pastemd` creates pastes with (in bytes), so the highest
+/// [`AppConfig::max_content_size`] can be
+pub const MAX_CONTENT_SIZE: usize = 200_000;
+
+/// Read how many bytes of content a paste can have from `MAX_CONTENT_SIZE`
+fn max_content_size_from_env() -> usize {
+    std::env::var("MAX_CONTENT_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(MAX_CONTENT_SIZE)
+}
+
+/// Read how many bytes of files can be attached to each paste from `MAX_ATTACHMENTS_SIZE`
+fn max_attachments_size_from_env() -> usize {
+    std::env::var("MAX_ATTACHMENTS_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(25 * 1024 * 1024)
+}
+
+/// Read the reserved paste urls from `RESERVED_URLS` (comma-separated)
+fn reserved_urls_from_env() -> Vec<String> {
+    std::env::var("RESERVED_URLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|u| u.trim().to_lowercase())
+        .filter(|u| !u.is_empty())
+        .collect()
+}
+
+impl AppConfig {
+    /// Create a config with the defaults (toggles are read from the environment)
+    pub fn new(database: Database) -> Self {
+        Self {
+            database,
+            static_dir: std::env::var("STATIC_DIR")
+                .ok()
+                .filter(|d| !d.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("./static")),
+            static_prefix: match std::env::var("STATIC_PREFIX") {
+                Ok(p) if !p.is_empty() => p.trim_end_matches('/').to_string(),
+                _ => "/static".to_string(),
+            },
+            base_path: String::new(),
+            templates_dir: std::env::var("TEMPLATES_DIR")
+                .ok()
+                .filter(|d| !d.is_empty())
+                .map(PathBuf::from),
+            render: RenderOptions::default(),
+            render_cache_size: std::env::var("RENDER_CACHE_SIZE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(128),
+            body_limits: BodyLimits::from_env(),
+            request_limits: RequestLimits::from_env(),
+            rate_limits: RateLimits::from_env(),
+            activitypub: crate::base::BaseStore::new().activitypub,
+            mirroring: true,
+            metrics: std::env::var("METRICS").is_ok_and(|m| m == "true"),
+            compression: std::env::var("COMPRESSION").map_or(true, |c| c != "false"),
+            dev: false,
+            trusted_proxies: match std::env::var("TRUSTED_PROXIES") {
+                Ok(proxies) if !proxies.trim().is_empty() => proxies
+                    .split(',')
+                    .filter(|p| !p.trim().is_empty())
+                    .filter_map(|p| {
+                        let network = IpNetwork::parse(p);
+                        if network.is_none() {
+                            tracing::warn!("Ignoring the invalid trusted proxy {p:?}.");
+                        }
+
+                        network
+                    })
+                    .collect(),
+                _ => loopback(),
+            },
+            reserved_urls: reserved_urls_from_env(),
+            attachments: AttachmentStorage::from_env(),
+            max_attachments_size: max_attachments_size_from_env(),
+            max_content_size: max_content_size_from_env(),
+            hooks: Vec::new(),
+            scanners: scanners_from_env(),
+            scan_action: ScanAction::from_env(),
+            scan_paste_size: scan_paste_size_from_env(),
+            comments: CommentMode::from_env(),
+        }
+    }
+
+    /// Register hooks (run after the ones registered before them)
+    pub fn hook(mut self, hooks: impl Hooks + 'static) -> Self {
+        self.hooks.push(Arc::new(hooks));
+        self
+    }
+
+    /// Register a scanner (content is only published once every scanner finds it clean)
+    pub fn scanner(mut self, scanner: impl Scanner + 'static) -> Self {
+        self.scanners.push(Arc::new(scanner));
+        self
+    }
+
+    /// Check that the configured directories exist and the static prefix can be mounted
+    ///
+    /// # Returns
+    /// * an error explaining what's wrong (meant to be shown to whoever runs the app)
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.static_dir.is_dir() && !crate::assets::embedded() {
+            return Err(format!(
+                "The static directory {:?} doesn't exist (set STATIC_DIR, or run from the directory containing `static`).",
+                self.static_dir
+            ));
+        }
+
+        if !self.static_prefix.starts_with('/') || (self.static_prefix.len() < 2) {
+            return Err(format!(
+                "The static prefix {:?} should be a path like /static (set STATIC_PREFIX).",
+                self.static_prefix
+            ));
+        }
+
+        if !self.base_path.is_empty()
+            && (!self.base_path.starts_with('/') || self.base_path.ends_with('/'))
+        {
+            return Err(format!(
+                "The base path {:?} should be a path like /docs (or empty, at the root).",
+                self.base_path
+            ));
+        }
+
+        if let Some(ref dir) = self.templates_dir {
+            if !dir.is_dir() {
+                return Err(format!(
+                    "The templates directory {dir:?} doesn't exist (set TEMPLATES_DIR)."
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Make this the config of the running app
+    pub(crate) fn install(&self) {
+        *CURRENT.write().unwrap() = Some(self.clone());
+    }
+}
+
+/// Get the config of the running app (`None` before [`crate::routes`] is called)
+pub(crate) fn current() -> Option<AppConfig> {
+    CURRENT.read().unwrap().clone()
+}
+
+/// Get the directory static files are served from
+pub(crate) fn static_dir() -> PathBuf {
+    current()
+        .map(|c| c.static_dir)
+        .unwrap_or_else(|| PathBuf::from("./static"))
+}
+
+/// Get the path static files are served at (without a trailing slash)
+pub fn static_prefix() -> String {
+    current()
+        .map(|c| c.static_prefix)
+        .unwrap_or_else(|| "/static".to_string())
+}
+
+/// Get the path the app is mounted at (without a trailing slash, empty at the root)
+pub fn base_path() -> String {
+    current().map(|c| c.base_path).unwrap_or_default()
+}
+
+/// Get the options markdown is rendered with
+pub(crate) fn render_options() -> RenderOptions {
+    current().map(|c| c.render).unwrap_or_default()
+}
+
+/// Get how many rendered pastes are kept in memory
+pub(crate) fn render_cache_size() -> usize {
+    current().map_or(0, |c| c.render_cache_size)
+}
+
+/// Get the paste urls reserved by the operator (read from the environment outside of the app, for
+/// the command line and the mail gateway)
+pub(crate) fn reserved_urls() -> Vec<String> {
+    current().map_or_else(reserved_urls_from_env, |c| c.reserved_urls)
+}
+
+/// Get where the files attached to pastes are kept (read from the environment outside of the
+/// app, for the command line)
+pub(crate) fn attachment_storage() -> AttachmentStorage {
+    current().map_or_else(AttachmentStorage::from_env, |c| c.attachments)
+}
+
+/// Get how many bytes of files can be attached to each paste
+pub(crate) fn max_attachments_size() -> usize {
+    current().map_or_else(max_attachments_size_from_env, |c| c.max_attachments_size)
+}
+
+/// Get how many bytes of content a paste can have (read from the environment outside of the app,
+/// for the mail gateway)
+pub(crate) fn max_content_size() -> usize {
+    current()
+        .map_or_else(max_content_size_from_env, |c| c.max_content_size)
+        .min(MAX_CONTENT_SIZE)
+}
+
+/// Get the networks of the proxies trusted to forward the client's address
+pub(crate) fn trusted_proxies() -> Vec<IpNetwork> {
+    current()
+        .map(|c| c.trusted_proxies)
+        .unwrap_or_else(loopback)
+}
+
+/// Get the largest request bodies accepted
+pub(crate) fn body_limits() -> BodyLimits {
+    current().map(|c| c.body_limits).unwrap_or_default()
+}
+
+/// Get the request timeouts and concurrency limit
+pub(crate) fn request_limits() -> RequestLimits {
+    current().map(|c| c.request_limits).unwrap_or_default()
+}
+
+/// Get the hooks registered by the running app
+pub(crate) fn hooks() -> Vec<Arc<dyn Hooks>> {
+    current().map(|c| c.hooks).unwrap_or_default()
+}
+
+/// Get what attachments and large pastes are scanned with (read from the environment outside of
+/// the app, for the command line and the mail gateway)
+pub(crate) fn scanners() -> Vec<Arc<dyn Scanner>> {
+    current().map_or_else(scanners_from_env, |c| c.scanners)
+}
+
+/// Get what happens to flagged content
+pub(crate) fn scan_action() -> ScanAction {
+    current().map_or_else(ScanAction::from_env, |c| c.scan_action)
+}
+
+/// Get how many bytes a paste's content has to be for it to be scanned
+pub(crate) fn scan_paste_size() -> usize {
+    current().map_or_else(scan_paste_size_from_env, |c| c.scan_paste_size)
+}
+
+/// Get if pastes take comments
+pub(crate) fn comment_mode() -> CommentMode {
+    current().map_or_else(CommentMode::from_env, |c| c.comments)
+}
+
+/// Check if pastes are published over ActivityPub
+pub(crate) fn activitypub_enabled() -> bool {
+    current()
+        .map(|c| c.activitypub)
+        .unwrap_or_else(|| crate::base::BaseStore::new().activitypub)
+}
@@ -0,0 +1,4171 @@
+//! Sealable-specific database layer on top of [`pastemd::database::Database`]
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::model::{
+    Announcement, Attachment, Availability, Collection, CollectionCreate, CollectionEdit, Comment,
+    DailyPastes, Draft, ExtraMetadata, InstanceStats, PasteFile, Preferences, Quarantined,
+    ReactionCount, Visibility, Webhook, WebhookCreate, WikiPage,
+};
+
+use dorsal::query as sqlquery;
+use dorsal::utility;
+use rand::Rng;
+use pastemd::database::ViewMode;
+use pastemd::model::{Paste, PasteClone, PasteCreate, PasteError, PasteMetadata};
+use redis::Commands;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use starstraw::model::{Profile, SkillManager, SkillName};
+
+pub type Result<T> = std::result::Result<T, PasteError>;
+
+/// Amount of search results shown per page
+pub const SEARCH_PAGE_SIZE: i32 = 25;
+
+/// How long computed [`InstanceStats`] are reused for (in milliseconds)
+pub const STATS_CACHE_TIME: u128 = 60_000;
+
+/// Amount of pastes listed in the instance feed (`/feed.atom`)
+pub const FEED_SIZE: i32 = 20;
+
+/// SQL condition matching pastes without a view password (`p` is the pastes table), see
+/// [`Database::public_condition`]
+const PUBLIC_CONDITION: &str = "p.\":metadata\" LIKE '%\"view_password\":\"\"%'";
+
+/// Amount of pastes fetched at once while building an account archive
+pub const ARCHIVE_PAGE_SIZE: i32 = 50;
+
+/// How long drafts are kept after they were last saved (in milliseconds, a week)
+pub const DRAFT_LIFETIME: u128 = 7 * 24 * 60 * 60 * 1000;
+
+/// How many files a paste can have (on top of its content)
+pub const MAX_FILES: usize = 20;
+
+/// How long edit links last by default (in seconds, a day)
+pub const EDIT_LINK_LIFETIME: u64 = 24 * 60 * 60;
+
+/// How long edit links can last at most (in seconds, a week)
+pub const MAX_EDIT_LINK_LIFETIME: u64 = 7 * 24 * 60 * 60;
+
+/// How many pages a wiki can have
+pub const MAX_PAGES: usize = 100;
+
+/// How many comments a paste can have
+pub const MAX_COMMENTS: usize = 500;
+
+/// How long a comment can be (in characters)
+pub const MAX_COMMENT_LENGTH: usize = 2000;
+
+/// How long the name a comment is left under can be (in characters)
+pub const MAX_COMMENT_AUTHOR_LENGTH: usize = 32;
+
+/// The reactions pastes can get (name, emoji), in the order they're shown
+pub const REACTIONS: [(&str, &str); 6] = [
+    ("upvote", "\u{1F44D}"),
+    ("heart", "\u{2764}\u{FE0F}"),
+    ("laugh", "\u{1F604}"),
+    ("hooray", "\u{1F389}"),
+    ("eyes", "\u{1F440}"),
+    ("rocket", "\u{1F680}"),
+];
+
+/// The `kind` of quarantined pastes (identified by their `url`)
+pub const QUARANTINE_PASTE: &str = "paste";
+
+/// The `kind` of quarantined attachments (identified by their storage key)
+pub const QUARANTINE_ATTACHMENT: &str = "attachment";
+
+/// How many random shortlink codes are tried before the code length is increased
+pub const SHORTLINK_ATTEMPTS: usize = 8;
+
+/// In-memory copy of the stored [`Announcement`], so page templates can read it without a query
+static ANNOUNCEMENT: RwLock<Option<Announcement>> = RwLock::new(None);
+
+/// Get the current announcement (if one is set and hasn't expired)
+pub fn current_announcement() -> Option<Announcement> {
+    match ANNOUNCEMENT.read() {
+        Ok(a) => a.clone().filter(|a| a.is_active()),
+        Err(_) => None,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchResult {
+    /// The paste url
+    pub url: String,
+    /// The paste title (falls back to the url)
+    pub title: String,
+    /// HTML snippet of the content around the first match (already escaped)
+    pub snippet: String,
+    pub date_published: u128,
+}
+
+/// Database connector
+#[derive(Clone)]
+pub struct Database {
+    pub base: pastemd::database::Database,
+}
+
+impl Database {
+    pub fn new(base: pastemd::database::Database) -> Self {
+        Self { base }
+    }
+
+    /// Connect to the database configured through the environment (`DB_TYPE`, `USE_STARSTRAW`, ...)
+    pub async fn from_env() -> Self {
+        Self::new(
+            pastemd::database::Database::new(
+                pongo::Database::env_options(),
+                pastemd::database::ServerOptions {
+                    view_password: true,
+                    starstraw: std::env::var("USE_STARSTRAW").is_ok(),
+                    paste_ownership: true,
+                    view_mode: if std::env::var("USE_STARSTRAW").is_ok() {
+                        pastemd::database::ViewMode::AuthenticatedOnce
+                    } else {
+                        pastemd::database::ViewMode::OpenMultiple
+                    },
+                    table_pastes: pastemd::database::PastesTableConfig {
+                        table_name: "se_pastes".to_string(),
+                        prefix: "se_paste".to_string(),
+                        ..Default::default()
+                    },
+                    table_views: pastemd::database::ViewsTableConfig {
+                        table_name: "se_views".to_string(),
+                        prefix: "se_views".to_string(),
+                    },
+                },
+            )
+            .await,
+        )
+    }
+
+    /// Init database
+    pub async fn init(&self) {
+        self.base.init().await;
+
+        // create tables
+        let c = &self.base.base.db.client;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_metadata\" (
+                url      TEXT,
+                metadata TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_preferences\" (
+                username    TEXT,
+                preferences TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_shortlinks\" (
+                code TEXT,
+                url  TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_aliases\" (
+                alias TEXT,
+                url   TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_redirects\" (
+                url    TEXT,
+                target TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_attachments\" (
+                url          TEXT,
+                name         TEXT,
+                storage_key  TEXT,
+                content_type TEXT,
+                size         TEXT,
+                date_created TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_files\" (
+                url         TEXT,
+                name        TEXT,
+                content     TEXT,
+                position    TEXT,
+                date_edited TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_pages\" (
+                namespace TEXT,
+                name      TEXT,
+                url       TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_comments\" (
+                id           TEXT,
+                url          TEXT,
+                reply_to     TEXT,
+                author       TEXT,
+                username     TEXT,
+                owner        TEXT,
+                content      TEXT,
+                date_created TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_reactions\" (
+                url          TEXT,
+                name         TEXT,
+                owner        TEXT,
+                date_created TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_view_days\" (
+                url      TEXT,
+                day      TEXT,
+                visitor  TEXT,
+                referrer TEXT,
+                views    TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_edit_tokens\" (
+                token      TEXT,
+                url        TEXT,
+                expires_at TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_quarantine\" (
+                kind         TEXT,
+                id           TEXT,
+                reason       TEXT,
+                date_created TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_settings\" (
+                name  TEXT,
+                value TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_ap_followers\" (
+                actor TEXT,
+                inbox TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_collections\" (
+                name         TEXT,
+                title        TEXT,
+                owner        TEXT,
+                pastes       TEXT,
+                date_created TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"se_drafts\" (
+                owner        TEXT,
+                url          TEXT,
+                content      TEXT,
+                date_updated TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        // create search index
+        if self.base.base.db._type == "sqlite" {
+            let t = &self.base.options.table_pastes;
+
+            let _ = sqlquery(
+                "CREATE VIRTUAL TABLE IF NOT EXISTS \"se_search\" USING fts5(url UNINDEXED, title, content)",
+            )
+            .execute(c)
+            .await;
+
+            // keep the index in sync with the pastes table
+            for (name, body) in [
+                (
+                    "se_search_insert",
+                    "AFTER INSERT ON \":t\" BEGIN
+                        INSERT INTO \"se_search\" VALUES (new.\":url\", json_extract(new.\":metadata\", '$.title'), new.\":content\");
+                    END",
+                ),
+                (
+                    "se_search_update",
+                    "AFTER UPDATE ON \":t\" BEGIN
+                        DELETE FROM \"se_search\" WHERE \"url\" = old.\":url\";
+                        INSERT INTO \"se_search\" VALUES (new.\":url\", json_extract(new.\":metadata\", '$.title'), new.\":content\");
+                    END",
+                ),
+                (
+                    "se_search_delete",
+                    "AFTER DELETE ON \":t\" BEGIN
+                        DELETE FROM \"se_search\" WHERE \"url\" = old.\":url\";
+                    END",
+                ),
+            ] {
+                let _ = sqlquery(
+                    &format!("CREATE TRIGGER IF NOT EXISTS \"{name}\" {body}")
+                        .replace(":t", &t.table_name)
+                        .replace(":url", &t.url)
+                        .replace(":metadata", &t.metadata)
+                        .replace(":content", &t.content),
+                )
+                .execute(c)
+                .await;
+            }
+
+            // rebuild the index when it doesn't cover every paste (it was just created, or pastes
+            // were created before the triggers existed), the triggers keep it up to date after that
+            let stale = sqlquery(
+                &"SELECT 1 WHERE (SELECT COUNT(*) FROM \"se_search\") <> (SELECT COUNT(*) FROM \":t\")"
+                    .replace(":t", &t.table_name),
+            )
+            .fetch_optional(c)
+            .await;
+
+            if matches!(stale, Ok(Some(_))) {
+                let _ = sqlquery("DELETE FROM \"se_search\"").execute(c).await;
+                let _ = sqlquery(
+                    &"INSERT INTO \"se_search\" SELECT \":url\", json_extract(\":metadata\", '$.title'), \":content\" FROM \":t\""
+                        .replace(":t", &t.table_name)
+                        .replace(":url", &t.url)
+                        .replace(":metadata", &t.metadata)
+                        .replace(":content", &t.content),
+                )
+                .execute(c)
+                .await;
+            }
+        }
+
+        self.load_announcement().await;
+    }
+
+    // ...
+
+    /// Build a [`Paste`] from a textified row of the pastes table
+    pub fn paste_from_row(&self, res: HashMap<String, String>) -> Option<Paste> {
+        let t = &self.base.options.table_pastes;
+        Some(Paste {
+            id: res.get(&t.id)?.to_string(),
+            url: res.get(&t.url)?.to_string(),
+            password: res.get(&t.password)?.to_string(),
+            content: res.get(&t.content)?.to_string(),
+            date_published: res.get(&t.date_published)?.parse::<u128>().ok()?,
+            date_edited: res.get(&t.date_edited)?.parse::<u128>().ok()?,
+            metadata: serde_json::from_str(res.get(&t.metadata)?).ok()?,
+        })
+    }
+
+    // extra metadata
+
+    /// Get a paste by its url (or shortlink code, or one of its aliases) from pastemd (traced, see
+    /// `crate::telemetry`)
+    #[tracing::instrument(skip(self))]
+    pub async fn get_paste_by_url(&self, url: String) -> Result<Paste> {
+        match self.base.get_paste_by_url(url.clone()).await {
+            Err(PasteError::NotFound) => match self.get_url_by_alias(url).await {
+                Some(url) => self.base.get_paste_by_url(url).await,
+                None => Err(PasteError::NotFound),
+            },
+            res => res,
+        }
+    }
+
+    /// Check the url a paste is requested at (see [`crate::slug::check`]), and that it isn't
+    /// taken by another paste (or its alias)
+    ///
+    /// `pastemd` checks this itself when creating pastes, but not when moving them.
+    ///
+    /// # Returns
+    /// The normalized url
+    pub async fn check_new_url(&self, url: &str) -> Result<String> {
+        let url = crate::slug::check(url)?;
+
+        if !url.is_empty()
+            && (self.get_url_by_alias(url.clone()).await.is_some()
+                || self.base.get_paste_by_url(url.clone()).await.is_ok())
+        {
+            return Err(PasteError::AlreadyExists);
+        }
+
+        Ok(url)
+    }
+
+    /// Scan the content of a new paste if it's large enough (see [`crate::scan`])
+    ///
+    /// Flagged pastes are quarantined before they're created (so they're never reachable), which
+    /// needs their url: a random one is picked here when none was requested.
+    ///
+    /// # Returns
+    /// * the url the paste is quarantined at
+    async fn scan_new_paste(&self, url: &mut String, content: &str) -> Result<Option<String>> {
+        if content.len() < crate::config::scan_paste_size() {
+            return Ok(None);
+        }
+
+        let Some(reason) = crate::scan::check("paste", content.as_bytes()).await? else {
+            return Ok(None);
+        };
+
+        if url.is_empty() {
+            *url = utility::random_id().chars().take(10).collect();
+        }
+
+        let quarantined = crate::slug::canonical(url);
+        self.quarantine(QUARANTINE_PASTE, &quarantined, &reason)
+            .await?;
+
+        Ok(Some(quarantined))
+    }
+
+    /// Create a paste (with pastemd's default metadata), at a url allowed by
+    /// [`Database::check_new_url`] (and once its content is checked against `MAX_CONTENT_SIZE` and
+    /// scanned)
+    pub async fn create_paste(&self, props: PasteCreate) -> Result<(String, Paste)> {
+        self.create_paste_with(props, PasteMetadata::default(), ExtraMetadata::default())
+            .await
+    }
+
+    /// Create a paste with its metadata, like [`Database::create_paste`]
+    ///
+    /// The paste is inserted into pastemd's table with `metadata`, after `extra` is stored, so
+    /// it's never reachable without either (created through pastemd, it would be public and
+    /// without its view password until its metadata is edited).
+    #[tracing::instrument(skip_all)]
+    pub async fn create_paste_with(
+        &self,
+        mut props: PasteCreate,
+        metadata: PasteMetadata,
+        extra: ExtraMetadata,
+    ) -> Result<(String, Paste)> {
+        if props.content.is_empty() || (props.content.len() > crate::config::max_content_size()) {
+            return Err(PasteError::ValueError);
+        }
+
+        props.url = self.check_new_url(&props.url).await?;
+        let quarantined = self.scan_new_paste(&mut props.url, &props.content).await?;
+
+        // (the rest of pastemd's checks are `crate::slug::check`'s)
+        if props.url.is_empty() {
+            props.url = utility::random_id().chars().take(10).collect();
+        }
+
+        if props.password.is_empty() {
+            props.password = utility::random_id().chars().take(10).collect();
+        }
+
+        // (punycode can make the url longer)
+        let url = crate::slug::canonical(&props.url);
+        let created = if url.chars().count() > crate::slug::MAX_LENGTH {
+            Err(PasteError::ValueError)
+        } else if self.base.get_paste_by_url(url.clone()).await.is_ok() {
+            Err(PasteError::AlreadyExists)
+        } else {
+            self.insert_paste(url, props, metadata, extra).await
+        };
+
+        if let (Err(_), Some(url)) = (&created, quarantined) {
+            let _ = self.release(QUARANTINE_PASTE, &url).await;
+        }
+
+        created
+    }
+
+    /// Insert a new paste into pastemd's table, once its `extra` metadata is stored
+    async fn insert_paste(
+        &self,
+        url: String,
+        props: PasteCreate,
+        metadata: PasteMetadata,
+        extra: ExtraMetadata,
+    ) -> Result<(String, Paste)> {
+        self.edit_extra_metadata_by_url(url.clone(), extra).await?;
+
+        let paste = Paste {
+            id: utility::random_id(),
+            url,
+            content: props.content,
+            password: utility::hash(props.password.clone()),
+            date_published: utility::unix_epoch_timestamp(),
+            date_edited: utility::unix_epoch_timestamp(),
+            metadata,
+        };
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \":t\" VALUES (?, ?, ?, ?, ?, ?, ?)"
+            } else {
+                "INSERT INTO \":t\" VALUES ($1, $2, $3, $4, $5, $6, $7)"
+            }
+            .replace(":t", &self.base.options.table_pastes.table_name);
+
+        let c = &self.base.base.db.client;
+        let inserted = match serde_json::to_string(&paste.metadata) {
+            Ok(ref m) => sqlquery(&query)
+                .bind::<&String>(&paste.id)
+                .bind::<&String>(&paste.url)
+                .bind::<&String>(&paste.password)
+                .bind::<&String>(&paste.content)
+                .bind::<&String>(&paste.date_published.to_string())
+                .bind::<&String>(&paste.date_edited.to_string())
+                .bind::<&String>(m)
+                .execute(c)
+                .await
+                .is_ok(),
+            Err(_) => false,
+        };
+
+        if !inserted {
+            let _ = self.delete_extra_metadata_by_url(paste.url.clone()).await;
+            return Err(PasteError::Other);
+        }
+
+        Ok((props.password, paste))
+    }
+
+    /// Clone a paste with pastemd, at a url allowed by [`Database::check_new_url`] (and once its
+    /// content is checked against `MAX_CONTENT_SIZE` and scanned)
+    #[tracing::instrument(skip_all)]
+    pub async fn clone_paste(&self, mut props: PasteClone) -> Result<(String, Paste)> {
+        props.url = self.check_new_url(&props.url).await?;
+
+        let source = self.base.get_paste_by_url(props.source.clone()).await?;
+        if source.content.len() > crate::config::max_content_size() {
+            return Err(PasteError::ValueError);
+        }
+
+        let quarantined = self.scan_new_paste(&mut props.url, &source.content).await?;
+
+        let created = self.base.clone_paste(props).await;
+        if let (Err(_), Some(url)) = (&created, quarantined) {
+            let _ = self.release(QUARANTINE_PASTE, &url).await;
+        }
+
+        created
+    }
+
+    /// Get the size of a paste's content without loading it
+    ///
+    /// # Returns
+    /// * `(bytes, characters)`, `None` if there's no paste with this exact `url`
+    #[tracing::instrument(skip(self))]
+    pub async fn get_paste_size_by_url(&self, url: String) -> Option<(usize, usize)> {
+        let t = &self.base.options.table_pastes;
+
+        // (all values come back as text, see `textify_row`)
+        let query = match self.base.base.db._type.as_str() {
+            "sqlite" => "SELECT CAST(LENGTH(CAST(\":content\" AS BLOB)) AS TEXT) AS \"bytes\", CAST(LENGTH(\":content\") AS TEXT) AS \"chars\" FROM \":t\" WHERE \":url\" = ?",
+            "mysql" => "SELECT CAST(LENGTH(\":content\") AS CHAR) AS \"bytes\", CAST(CHAR_LENGTH(\":content\") AS CHAR) AS \"chars\" FROM \":t\" WHERE \":url\" = ?",
+            _ => "SELECT CAST(OCTET_LENGTH(\":content\") AS TEXT) AS \"bytes\", CAST(CHAR_LENGTH(\":content\") AS TEXT) AS \"chars\" FROM \":t\" WHERE \":url\" = $1",
+        }
+        .replace(":t", &t.table_name)
+        .replace(":content", &t.content)
+        .replace(":url", &t.url);
+
+        let c = &self.base.base.db.client;
+        let row = sqlquery(&query)
+            .bind::<&String>(&url)
+            .fetch_one(c)
+            .await
+            .ok()?;
+        let data = self.base.base.textify_row(row).data;
+
+        Some((
+            data.get("bytes")?.parse().ok()?,
+            data.get("chars")?.parse().ok()?,
+        ))
+    }
+
+    /// Get a paste without its content (left empty), for pastes too large to load at once
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    #[tracing::instrument(skip(self))]
+    pub async fn get_paste_info_by_url(&self, url: String) -> Option<Paste> {
+        let t = &self.base.options.table_pastes;
+
+        let query = if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql")
+        {
+            "SELECT \":id\", \":url\", \":password\", '' AS \":content\", \":date_published\", \":date_edited\", \":metadata\" FROM \":t\" WHERE \":url\" = ?"
+        } else {
+            "SELECT \":id\", \":url\", \":password\", '' AS \":content\", \":date_published\", \":date_edited\", \":metadata\" FROM \":t\" WHERE \":url\" = $1"
+        }
+        .replace(":t", &t.table_name)
+        .replace(":id", &t.id)
+        .replace(":url", &t.url)
+        .replace(":password", &t.password)
+        .replace(":content", &t.content)
+        .replace(":date_published", &t.date_published)
+        .replace(":date_edited", &t.date_edited)
+        .replace(":metadata", &t.metadata);
+
+        let c = &self.base.base.db.client;
+        let row = sqlquery(&query)
+            .bind::<&String>(&url)
+            .fetch_one(c)
+            .await
+            .ok()?;
+        self.paste_from_row(self.base.base.textify_row(row).data)
+    }
+
+    /// Get part of a paste's content
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `start` - the first character (counting from 0)
+    /// * `length` - how many characters to get (less are returned at the end of the content)
+    #[tracing::instrument(skip(self))]
+    pub async fn get_paste_content_part(
+        &self,
+        url: String,
+        start: usize,
+        length: usize,
+    ) -> Option<String> {
+        let t = &self.base.options.table_pastes;
+
+        // (`SUBSTR` counts characters from 1 in every database)
+        let query = if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql")
+        {
+            "SELECT SUBSTR(\":content\", :start, :length) AS \"part\" FROM \":t\" WHERE \":url\" = ?"
+        } else {
+            "SELECT SUBSTR(\":content\", :start, :length) AS \"part\" FROM \":t\" WHERE \":url\" = $1"
+        }
+        .replace(":t", &t.table_name)
+        .replace(":content", &t.content)
+        .replace(":url", &t.url)
+        .replace(":start", &(start + 1).to_string())
+        .replace(":length", &length.to_string());
+
+        let c = &self.base.base.db.client;
+        let row = sqlquery(&query)
+            .bind::<&String>(&url)
+            .fetch_one(c)
+            .await
+            .ok()?;
+        self.base.base.textify_row(row).data.remove("part")
+    }
+
+    /// Get the [`ExtraMetadata`] of a paste (defaults if none has been stored)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    #[tracing::instrument(skip(self))]
+    pub async fn get_extra_metadata_by_url(&self, url: String) -> ExtraMetadata {
+        // check in cache
+        if let Some(c) = self
+            .base
+            .base
+            .cachedb
+            .get(format!("se_metadata:{}", url))
+            .await
+        {
+            if let Ok(m) = serde_json::from_str::<ExtraMetadata>(c.as_str()) {
+                crate::metrics::cache("metadata", true);
+                return m;
+            }
+        }
+
+        crate::metrics::cache("metadata", false);
+
+        // pull from database
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_metadata\" WHERE \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_metadata\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let metadata = match sqlquery(&query).bind::<&String>(&url).fetch_one(c).await {
+            Ok(r) => match self.base.base.textify_row(r).data.get("metadata") {
+                Some(m) => serde_json::from_str::<ExtraMetadata>(m).unwrap_or_default(),
+                None => ExtraMetadata::default(),
+            },
+            Err(_) => ExtraMetadata::default(),
+        };
+
+        // store in cache
+        self.base
+            .base
+            .cachedb
+            .set(
+                format!("se_metadata:{}", url),
+                serde_json::to_string::<ExtraMetadata>(&metadata).unwrap(),
+            )
+            .await;
+
+        metadata
+    }
+
+    /// Store the [`ExtraMetadata`] of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `metadata` - the new metadata
+    pub async fn edit_extra_metadata_by_url(
+        &self,
+        url: String,
+        metadata: ExtraMetadata,
+    ) -> Result<()> {
+        self.delete_extra_metadata_by_url(url.clone()).await?;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_metadata\" VALUES (?, ?)"
+            } else {
+                "INSERT INTO \"se_metadata\" VALUES ($1, $2)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&url)
+            .bind::<&String>(match serde_json::to_string(&metadata) {
+                Ok(ref m) => m,
+                Err(_) => return Err(PasteError::ValueError),
+            })
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete the [`ExtraMetadata`] of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_extra_metadata_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_metadata\" WHERE \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_metadata\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => {
+                // remove from cache
+                self.base
+                    .base
+                    .cachedb
+                    .remove(format!("se_metadata:{}", url))
+                    .await;
+
+                Ok(())
+            }
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Move the [`ExtraMetadata`] of a paste to a new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_extra_metadata(&self, url: String, new_url: String) -> Result<()> {
+        let metadata = self.get_extra_metadata_by_url(url.clone()).await;
+        self.delete_extra_metadata_by_url(url).await?;
+        self.edit_extra_metadata_by_url(new_url, metadata).await
+    }
+
+    /// Move everything attached to a paste to its new url (after the paste is renamed), and
+    /// redirect the old url to it
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_paste(&self, url: String, new_url: String) -> Result<()> {
+        self.move_extra_metadata(url.clone(), new_url.clone())
+            .await?;
+        self.move_shortlink(url.clone(), new_url.clone()).await?;
+        self.move_aliases(url.clone(), new_url.clone()).await?;
+        self.move_attachments(url.clone(), new_url.clone()).await?;
+        self.move_files(url.clone(), new_url.clone()).await?;
+        self.move_pages(url.clone(), new_url.clone()).await?;
+        self.move_comments(url.clone(), new_url.clone()).await?;
+        self.move_reactions(url.clone(), new_url.clone()).await?;
+        self.move_edit_tokens(url.clone(), new_url.clone()).await?;
+        self.move_quarantine(url.clone(), new_url.clone()).await?;
+        crate::views::rename(self, &url, &new_url).await;
+        crate::analytics::rename(self, &url, &new_url).await;
+        self.create_redirect(url, new_url).await
+    }
+
+    /// Check if the given paste can be viewed by `as_user`, given its [`Visibility`] and its time
+    /// window
+    ///
+    /// # Arguments
+    /// * `paste` - the paste being viewed
+    /// * `as_user` - the userstate of the user viewing the paste
+    pub async fn can_view_paste(&self, paste: &Paste, as_user: Option<&Profile>) -> bool {
+        self.can_view_paste_in_window(paste, as_user).await
+            && (self.get_availability(paste, as_user).await == Availability::Available)
+    }
+
+    /// Check if the given paste can be viewed by `as_user` while it's in its time window (see
+    /// [`Database::can_view_paste`])
+    ///
+    /// # Arguments
+    /// * `paste` - the paste being viewed
+    /// * `as_user` - the userstate of the user viewing the paste
+    #[tracing::instrument(skip_all)]
+    pub async fn can_view_paste_in_window(&self, paste: &Paste, as_user: Option<&Profile>) -> bool {
+        let extra = self.get_extra_metadata_by_url(paste.url.clone()).await;
+        let scheduled = extra.publish_at as u128 > utility::unix_epoch_timestamp();
+
+        // expired pastes are as good as deleted
+        if extra.is_expired() {
+            return false;
+        }
+
+        // (the networks a paste is restricted to apply to everyone, its owner included)
+        if !extra.allows_network(crate::middleware::current_client_ip()) {
+            return false;
+        }
+
+        // (flagged by a scanner, administrators decide what happens to it)
+        if self.is_quarantined(QUARANTINE_PASTE, &paste.url).await {
+            return as_user
+                .is_some_and(|ua| SkillManager(ua.skills.clone()).has_skill(SkillName::Absolute));
+        }
+
+        if (extra.visibility != Visibility::Private) && !scheduled {
+            return true;
+        }
+
+        match as_user {
+            Some(ua) => {
+                // private (and scheduled) pastes can only be viewed by their owner (or an
+                // administrator)
+                (!paste.metadata.owner.is_empty() && ua.username == paste.metadata.owner)
+                    || SkillManager(ua.skills.clone()).has_skill(SkillName::Absolute)
+            }
+            None => false,
+        }
+    }
+
+    /// Check if a paste is in its time window for `as_user` (it always is for its owner and
+    /// administrators, so they can check it before it opens)
+    ///
+    /// # Arguments
+    /// * `paste` - the paste being viewed
+    /// * `as_user` - the userstate of the user viewing the paste
+    pub async fn get_availability(&self, paste: &Paste, as_user: Option<&Profile>) -> Availability {
+        let exempt = as_user.is_some_and(|ua| {
+            (!paste.metadata.owner.is_empty() && (ua.username == paste.metadata.owner))
+                || SkillManager(ua.skills.clone()).has_skill(SkillName::Absolute)
+        });
+
+        match exempt {
+            true => Availability::Available,
+            false => self
+                .get_extra_metadata_by_url(paste.url.clone())
+                .await
+                .availability(),
+        }
+    }
+
+    /// Check if a paste can be edited: with its edit password, by its owner or by an administrator
+    /// (the same rules as `pastemd`)
+    ///
+    /// # Arguments
+    /// * `paste` - the paste being edited
+    /// * `password` - the edit password given (unhashed)
+    /// * `as_user` - the user editing the paste
+    pub fn can_edit_paste(&self, paste: &Paste, password: &str, as_user: Option<&Profile>) -> bool {
+        let by_user = as_user.is_some_and(|ua| {
+            (!paste.metadata.owner.is_empty() && (ua.username == paste.metadata.owner))
+                || SkillManager(ua.skills.clone()).has_skill(SkillName::Absolute)
+        });
+
+        by_user || (utility::hash(password.to_string()) == paste.password)
+    }
+
+    /// Get a paste new pastes can be written from: one marked as a template (or listed in
+    /// `PASTE_TEMPLATES`) that `as_user` can view without a password
+    ///
+    /// # Arguments
+    /// * `url` - the url of the template
+    /// * `as_user` - the user writing the new paste
+    pub async fn get_template_by_url(
+        &self,
+        url: String,
+        as_user: Option<&Profile>,
+    ) -> Option<Paste> {
+        let paste = self.get_paste_by_url(url).await.ok()?;
+
+        let is_template = (paste.metadata.template == "@")
+            || crate::base::BaseStore::new().templates.contains(&paste.url);
+
+        (is_template
+            && paste.metadata.view_password.is_empty()
+            && self.can_view_paste(&paste, as_user).await)
+            .then_some(paste)
+    }
+
+    /// Get the SQL expression reading a number from the extra metadata in `m` (0 when it's
+    /// missing)
+    fn metadata_number(&self, field: &str) -> String {
+        match self.base.base.db._type.as_str() {
+            "sqlite" => format!("COALESCE(json_extract(m.\"metadata\", '$.{field}'), 0)"),
+            "mysql" => format!("COALESCE(JSON_EXTRACT(m.\"metadata\", '$.{field}'), 0)"),
+            _ => format!("COALESCE((m.\"metadata\"::json->>'{field}')::numeric, 0)"),
+        }
+    }
+
+    /// Get the SQL expression reading a string from the extra metadata in `m` (empty when it's
+    /// missing)
+    fn metadata_text(&self, field: &str) -> String {
+        match self.base.base.db._type.as_str() {
+            "sqlite" => format!("COALESCE(json_extract(m.\"metadata\", '$.{field}'), '')"),
+            "mysql" => {
+                format!("COALESCE(JSON_UNQUOTE(JSON_EXTRACT(m.\"metadata\", '$.{field}')), '')")
+            }
+            _ => format!("COALESCE(m.\"metadata\"::json->>'{field}', '')"),
+        }
+    }
+
+    /// Get the SQL expression reading a string from the pastemd metadata in `p` (`:metadata` is
+    /// left for the caller to replace, empty when it's missing)
+    fn paste_metadata_text(&self, field: &str) -> String {
+        match self.base.base.db._type.as_str() {
+            "sqlite" => format!("COALESCE(json_extract(p.\":metadata\", '$.{field}'), '')"),
+            "mysql" => {
+                format!("COALESCE(JSON_UNQUOTE(JSON_EXTRACT(p.\":metadata\", '$.{field}')), '')")
+            }
+            _ => format!("COALESCE(p.\":metadata\"::json->>'{field}', '')"),
+        }
+    }
+
+    /// Get the SQL condition matching public pastes: [`PUBLIC_CONDITION`], for listed pastes
+    /// (by their own `visibility`) viewable from any network, whose `publish_at` passed, that
+    /// haven't expired, that are in their time window and that aren't quarantined
+    ///
+    /// Wiki pages also need their wiki to match (the inner query's `p` and `m` are the wiki's).
+    fn public_condition(&self) -> String {
+        let now = utility::unix_epoch_timestamp();
+        let expires_at = self.metadata_number("expires_at");
+        let available_until = self.metadata_number("available_until");
+
+        let own = format!(
+            "{PUBLIC_CONDITION} AND {} IN ('', 'public') AND TRIM({}) = '' AND {} <= {now} AND {} <= {now} AND ({expires_at} = 0 OR {expires_at} > {now}) AND ({available_until} = 0 OR {available_until} > {now}) AND p.\":url\" NOT IN (SELECT \"id\" FROM \"se_quarantine\" WHERE \"kind\" = '{QUARANTINE_PASTE}')",
+            self.metadata_text("visibility"),
+            self.metadata_text("allowed_networks"),
+            self.metadata_number("publish_at"),
+            self.metadata_number("available_from")
+        );
+
+        format!(
+            "{own} AND NOT EXISTS (SELECT 1 FROM \"se_pages\" w WHERE w.\"url\" = p.\":url\" AND w.\"namespace\" NOT IN (SELECT p.\":url\" FROM \":t\" p LEFT JOIN \"se_metadata\" m ON m.\"url\" = p.\":url\" WHERE {own}))"
+        )
+    }
+
+    /// Get the urls of every expired paste
+    pub async fn get_expired_urls(&self) -> Vec<String> {
+        let expires_at = self.metadata_number("expires_at");
+        let query = format!(
+            "SELECT m.\"url\" FROM \"se_metadata\" m WHERE {expires_at} > 0 AND {expires_at} <= {}",
+            utility::unix_epoch_timestamp()
+        );
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).fetch_all(c).await {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| self.base.base.textify_row(r).data.remove("url"))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Delete a paste without its password (for administrators)
+    ///
+    /// # Returns
+    /// The deleted paste
+    pub async fn force_delete_paste_by_url(&self, url: String) -> Result<Paste> {
+        // pastes can only be deleted with their password, so it's replaced first
+        let password = utility::random_id();
+        self.set_paste_password(url.clone(), password.clone())
+            .await?;
+
+        let paste = self.get_paste_by_url(url).await?;
+        self.base
+            .delete_paste_by_url(paste.url.clone(), password)
+            .await?;
+
+        let _ = self.delete_extra_metadata_by_url(paste.url.clone()).await;
+        let _ = self.delete_shortlink_by_url(paste.url.clone()).await;
+        let _ = self.delete_aliases_by_url(paste.url.clone()).await;
+        let _ = self.delete_redirects_to(paste.url.clone()).await;
+        let _ = self.delete_attachments_by_url(paste.url.clone()).await;
+        let _ = self.delete_files_by_url(paste.url.clone()).await;
+        let _ = self.delete_pages_by_url(paste.url.clone()).await;
+        let _ = self.delete_comments_by_url(paste.url.clone()).await;
+        let _ = self.delete_reactions_by_url(paste.url.clone()).await;
+        crate::analytics::forget(self, &paste.url).await;
+        let _ = self.delete_edit_tokens_by_url(paste.url.clone()).await;
+        let _ = self.release(QUARANTINE_PASTE, &paste.url).await;
+
+        Ok(paste)
+    }
+
+    // search
+
+    /// Search the titles and content of all public pastes
+    ///
+    /// # Arguments
+    /// * `query` - the user-provided search query
+    /// * `page` - the page of results to fetch (starting at 0)
+    ///
+    /// # Returns
+    /// * the results on this page, and if there is another page after it
+    #[tracing::instrument(skip(self))]
+    pub async fn search_pastes(&self, query: String, page: i32) -> (Vec<SearchResult>, bool) {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|s| s.to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        if terms.is_empty() {
+            return (Vec::new(), false);
+        }
+
+        let t = &self.base.options.table_pastes;
+        let c = &self.base.base.db.client;
+        let public = self.public_condition();
+
+        // we fetch one extra row to know if there's a next page
+        let limit = format!(
+            "LIMIT {} OFFSET {}",
+            SEARCH_PAGE_SIZE + 1,
+            page.max(0) * SEARCH_PAGE_SIZE
+        );
+
+        let (sql, binds): (String, Vec<String>) = match self.base.base.db._type.as_str() {
+            "sqlite" => (
+                // quote every term so user input can't use (or break) fts5 syntax
+                format!(
+                    "SELECT p.* FROM \"se_search\" s JOIN \":t\" p ON p.\":url\" = s.\"url\" LEFT JOIN \"se_metadata\" m ON m.\"url\" = p.\":url\" WHERE \"se_search\" MATCH ? AND {public} ORDER BY s.rank {limit}"
+                ),
+                vec![terms
+                    .iter()
+                    .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+                    .collect::<Vec<String>>()
+                    .join(" ")],
+            ),
+            "postgres" => (
+                format!(
+                    "SELECT p.* FROM \":t\" p LEFT JOIN \"se_metadata\" m ON m.\"url\" = p.\":url\" WHERE to_tsvector(coalesce(p.\":metadata\"::json->>'title', '') || ' ' || p.\":content\") @@ plainto_tsquery($1) AND {public} ORDER BY p.\":date_published\" DESC {limit}"
+                ),
+                vec![terms.join(" ")],
+            ),
+            _ => {
+                // fallback: every term has to appear in the content or metadata
+                let mut conditions = Vec::new();
+
+                for _ in &terms {
+                    conditions.push(
+                        "(LOWER(p.\":content\") LIKE ? OR LOWER(p.\":metadata\") LIKE ?)".to_string(),
+                    );
+                }
+
+                (
+                    format!(
+                        "SELECT p.* FROM \":t\" p LEFT JOIN \"se_metadata\" m ON m.\"url\" = p.\":url\" WHERE {} AND {public} ORDER BY p.\":date_published\" DESC {limit}",
+                        conditions.join(" AND ")
+                    ),
+                    terms
+                        .iter()
+                        .flat_map(|t| {
+                            let pattern = format!("%{t}%");
+                            [pattern.clone(), pattern]
+                        })
+                        .collect(),
+                )
+            }
+        };
+
+        let sql = sql
+            .replace(":t", &t.table_name)
+            .replace(":url", &t.url)
+            .replace(":metadata", &t.metadata)
+            .replace(":content", &t.content)
+            .replace(":date_published", &t.date_published);
+
+        let mut q = sqlquery(&sql);
+
+        for bind in &binds {
+            q = q.bind::<&String>(bind);
+        }
+
+        let rows = match q.fetch_all(c).await {
+            Ok(r) => r,
+            Err(_) => return (Vec::new(), false),
+        };
+
+        // build results
+        let has_next = rows.len() > SEARCH_PAGE_SIZE as usize;
+        let mut out = Vec::new();
+
+        for row in rows.into_iter().take(SEARCH_PAGE_SIZE as usize) {
+            let paste = match self.paste_from_row(self.base.base.textify_row(row).data) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            out.push(SearchResult {
+                snippet: search_snippet(&paste.content, &terms),
+                title: if paste.metadata.title.is_empty() {
+                    paste.url.clone()
+                } else {
+                    paste.metadata.title
+                },
+                url: paste.url,
+                date_published: paste.date_published,
+            });
+        }
+
+        (out, has_next)
+    }
+
+    /// Get the most recently published public pastes (newest first, at most [`FEED_SIZE`])
+    #[tracing::instrument(skip(self))]
+    pub async fn get_recent_public_pastes(&self) -> Vec<Paste> {
+        let t = &self.base.options.table_pastes;
+
+        let query = format!(
+            "SELECT p.* FROM \":t\" p LEFT JOIN \"se_metadata\" m ON m.\"url\" = p.\":url\" WHERE {} ORDER BY p.\":date_published\" DESC LIMIT {FEED_SIZE}",
+            self.public_condition()
+        )
+        .replace(":t", &t.table_name)
+        .replace(":url", &t.url)
+        .replace(":metadata", &t.metadata)
+        .replace(":date_published", &t.date_published);
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).fetch_all(c).await {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| self.paste_from_row(self.base.base.textify_row(r).data))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Get the public pastes that got the most reactions since `since` (most first, a
+    /// [`FEED_SIZE`] at most)
+    ///
+    /// # Arguments
+    /// * `since` - when the oldest reaction counted was left (in milliseconds)
+    pub async fn get_most_reacted_public_pastes(&self, since: u128) -> Vec<Paste> {
+        let t = &self.base.options.table_pastes;
+
+        // (dates are stored as text, which compares like the numbers while they have 13 digits)
+        let query = format!(
+            "SELECT p.* FROM \":t\" p LEFT JOIN \"se_metadata\" m ON m.\"url\" = p.\":url\" JOIN (SELECT \"url\", COUNT(*) AS \"score\" FROM \"se_reactions\" WHERE \"date_created\" >= '{since}' GROUP BY \"url\") r ON r.\"url\" = p.\":url\" WHERE {} ORDER BY r.\"score\" DESC LIMIT {FEED_SIZE}",
+            self.public_condition()
+        )
+        .replace(":t", &t.table_name)
+        .replace(":url", &t.url)
+        .replace(":metadata", &t.metadata);
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).fetch_all(c).await {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| self.paste_from_row(self.base.base.textify_row(r).data))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Get a page of the pastes owned by `owner` (oldest first)
+    ///
+    /// # Arguments
+    /// * `owner` - the username of the paste owner
+    /// * `page` - the page of pastes to fetch (starting at 0, [`ARCHIVE_PAGE_SIZE`] per page)
+    ///
+    /// # Returns
+    /// The pastes, and whether the page was full (so the next one may have more)
+    #[tracing::instrument(skip(self))]
+    pub async fn get_pastes_by_owner(&self, owner: String, page: i32) -> (Vec<Paste>, bool) {
+        let t = &self.base.options.table_pastes;
+
+        let query = format!(
+            "SELECT * FROM \":t\" p WHERE {} = {} ORDER BY \":date_published\" ASC LIMIT :limit OFFSET :offset",
+            self.paste_metadata_text("owner"),
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "?"
+            } else {
+                "$1"
+            }
+        )
+        .replace(":t", &t.table_name)
+        .replace(":metadata", &t.metadata)
+        .replace(":date_published", &t.date_published)
+        .replace(":limit", &ARCHIVE_PAGE_SIZE.to_string())
+        .replace(":offset", &(page.max(0) * ARCHIVE_PAGE_SIZE).to_string());
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&owner).fetch_all(c).await {
+            Ok(rows) => {
+                let full = rows.len() as i32 == ARCHIVE_PAGE_SIZE;
+                let pastes = rows
+                    .into_iter()
+                    .filter_map(|r| self.paste_from_row(self.base.base.textify_row(r).data))
+                    .collect();
+
+                (pastes, full)
+            }
+            Err(_) => (Vec::new(), false),
+        }
+    }
+
+    /// Get a page of all pastes (newest first)
+    ///
+    /// # Arguments
+    /// * `page` - the page of pastes to fetch (starting at 0, [`ARCHIVE_PAGE_SIZE`] per page)
+    #[tracing::instrument(skip(self))]
+    pub async fn get_pastes(&self, page: i32) -> Vec<Paste> {
+        let t = &self.base.options.table_pastes;
+
+        let query =
+            "SELECT * FROM \":t\" ORDER BY \":date_published\" DESC LIMIT :limit OFFSET :offset"
+                .replace(":t", &t.table_name)
+                .replace(":date_published", &t.date_published)
+                .replace(":limit", &ARCHIVE_PAGE_SIZE.to_string())
+                .replace(":offset", &(page.max(0) * ARCHIVE_PAGE_SIZE).to_string());
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).fetch_all(c).await {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| self.paste_from_row(self.base.base.textify_row(r).data))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Replace the edit password of a paste (without knowing the current one)
+    ///
+    /// # Arguments
+    /// * `url` - the url of the paste
+    /// * `password` - the new password (unhashed)
+    pub async fn set_paste_password(&self, url: String, password: String) -> Result<()> {
+        let t = &self.base.options.table_pastes;
+
+        // make sure the paste exists
+        let url = self.get_paste_by_url(url).await?.url;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \":t\" SET \":password\" = ? WHERE \":url\" = ?"
+            } else {
+                "UPDATE \":t\" SET \":password\" = $1 WHERE \":url\" = $2"
+            }
+            .replace(":t", &t.table_name)
+            .replace(":password", &t.password)
+            .replace(":url", &t.url);
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&utility::hash(password))
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => {
+                // remove from cache
+                self.base
+                    .base
+                    .cachedb
+                    .remove(format!("{}:{}", t.prefix, url))
+                    .await;
+
+                Ok(())
+            }
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Replace the content of a paste (without its password, see [`Database::use_edit_token`])
+    ///
+    /// # Arguments
+    /// * `url` - the url of the paste
+    /// * `content` - the new content
+    pub async fn set_paste_content(&self, url: String, content: String) -> Result<()> {
+        let t = &self.base.options.table_pastes;
+        let url = self.get_paste_by_url(url).await?.url;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \":t\" SET \":content\" = ?, \":date_edited\" = ? WHERE \":url\" = ?"
+            } else {
+                "UPDATE \":t\" SET \":content\" = $1, \":date_edited\" = $2 WHERE \":url\" = $3"
+            }
+            .replace(":t", &t.table_name)
+            .replace(":content", &t.content)
+            .replace(":date_edited", &t.date_edited)
+            .replace(":url", &t.url);
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&content)
+            .bind::<&String>(&utility::unix_epoch_timestamp().to_string())
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => {
+                // remove from cache
+                self.base
+                    .base
+                    .cachedb
+                    .remove(format!("{}:{}", t.prefix, url))
+                    .await;
+
+                Ok(())
+            }
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // preferences
+
+    /// Get the stored [`Preferences`] of a user (`None` if they never saved any)
+    ///
+    /// # Arguments
+    /// * `username` - the username of the user
+    pub async fn get_preferences_by_username(&self, username: String) -> Option<Preferences> {
+        // check in cache
+        if let Some(c) = self
+            .base
+            .base
+            .cachedb
+            .get(format!("se_preferences:{}", username))
+            .await
+        {
+            return serde_json::from_str::<Preferences>(c.as_str()).ok();
+        }
+
+        // pull from database
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_preferences\" WHERE \"username\" = ?"
+            } else {
+                "SELECT * FROM \"se_preferences\" WHERE \"username\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let preferences = match sqlquery(&query)
+            .bind::<&String>(&username)
+            .fetch_one(c)
+            .await
+        {
+            Ok(r) => match self.base.base.textify_row(r).data.get("preferences") {
+                Some(p) => serde_json::from_str::<Preferences>(p).ok(),
+                None => None,
+            },
+            Err(_) => return None,
+        };
+
+        // store in cache
+        if let Some(ref p) = preferences {
+            self.base
+                .base
+                .cachedb
+                .set(
+                    format!("se_preferences:{}", username),
+                    serde_json::to_string::<Preferences>(p).unwrap(),
+                )
+                .await;
+        }
+
+        preferences
+    }
+
+    /// Store the [`Preferences`] of a user
+    ///
+    /// # Arguments
+    /// * `username` - the username of the user
+    /// * `preferences` - the new [`Preferences`]
+    pub async fn edit_preferences_by_username(
+        &self,
+        username: String,
+        preferences: Preferences,
+    ) -> Result<()> {
+        let c = &self.base.base.db.client;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_preferences\" WHERE \"username\" = ?"
+            } else {
+                "DELETE FROM \"se_preferences\" WHERE \"username\" = $1"
+            }
+            .to_string();
+
+        if sqlquery(&query)
+            .bind::<&String>(&username)
+            .execute(c)
+            .await
+            .is_err()
+        {
+            return Err(PasteError::Other);
+        }
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_preferences\" VALUES (?, ?)"
+            } else {
+                "INSERT INTO \"se_preferences\" VALUES ($1, $2)"
+            }
+            .to_string();
+
+        match sqlquery(&query)
+            .bind::<&String>(&username)
+            .bind::<&String>(match serde_json::to_string(&preferences) {
+                Ok(ref p) => p,
+                Err(_) => return Err(PasteError::ValueError),
+            })
+            .execute(c)
+            .await
+        {
+            Ok(_) => {
+                self.base
+                    .base
+                    .cachedb
+                    .remove(format!("se_preferences:{}", username))
+                    .await;
+
+                Ok(())
+            }
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // shortlinks
+
+    /// Get the paste url a shortlink points to
+    ///
+    /// # Arguments
+    /// * `code` - the shortlink code
+    pub async fn get_url_by_shortlink(&self, code: String) -> Result<String> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_shortlinks\" WHERE \"code\" = ?"
+            } else {
+                "SELECT * FROM \"se_shortlinks\" WHERE \"code\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&code).fetch_one(c).await {
+            Ok(r) => match self.base.base.textify_row(r).data.get("url") {
+                Some(url) => Ok(url.to_owned()),
+                None => Err(PasteError::ValueError),
+            },
+            Err(_) => Err(PasteError::NotFound),
+        }
+    }
+
+    /// Get the shortlink code of a paste (if it has one)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn get_shortlink_by_url(&self, url: String) -> Option<String> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_shortlinks\" WHERE \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_shortlinks\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).fetch_one(c).await {
+            Ok(r) => self.base.base.textify_row(r).data.get("code").cloned(),
+            Err(_) => None,
+        }
+    }
+
+    /// Get the shortlink code of a paste, creating one if it doesn't have one yet
+    ///
+    /// Codes use `SHORTLINK_ALPHABET` and start at `SHORTLINK_LENGTH` characters; the length
+    /// grows whenever [`SHORTLINK_ATTEMPTS`] random codes in a row are already taken.
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn create_shortlink(&self, url: String) -> Result<String> {
+        if let Some(code) = self.get_shortlink_by_url(url.clone()).await {
+            return Ok(code);
+        }
+
+        let base = crate::base::BaseStore::new();
+        let alphabet: Vec<char> = base.shortlink_alphabet.chars().collect();
+
+        if alphabet.len() < 2 {
+            return Err(PasteError::Other);
+        }
+
+        // pick an unused code
+        let mut length = base.shortlink_length.max(1);
+        let code = 'search: loop {
+            for _ in 0..SHORTLINK_ATTEMPTS {
+                let code: String = {
+                    let mut rng = rand::thread_rng();
+                    (0..length)
+                        .map(|_| alphabet[rng.gen_range(0..alphabet.len())])
+                        .collect()
+                };
+
+                if self.get_url_by_shortlink(code.clone()).await.is_err() {
+                    break 'search code;
+                }
+            }
+
+            length += 1;
+        };
+
+        // ...
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_shortlinks\" VALUES (?, ?)"
+            } else {
+                "INSERT INTO \"se_shortlinks\" VALUES ($1, $2)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&code)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(code),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Point the shortlink of a paste at its new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_shortlink(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_shortlinks\" SET \"url\" = ? WHERE \"url\" = ?"
+            } else {
+                "UPDATE \"se_shortlinks\" SET \"url\" = $1 WHERE \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete the shortlink of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_shortlink_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_shortlinks\" WHERE \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_shortlinks\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // aliases
+
+    /// Get the url of the paste an alias points to
+    ///
+    /// # Arguments
+    /// * `alias` - the alias
+    pub async fn get_url_by_alias(&self, alias: String) -> Option<String> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_aliases\" WHERE \"alias\" = ?"
+            } else {
+                "SELECT * FROM \"se_aliases\" WHERE \"alias\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&crate::slug::canonical(&alias))
+            .fetch_one(c)
+            .await
+        {
+            Ok(r) => self.base.base.textify_row(r).data.get("url").cloned(),
+            Err(_) => None,
+        }
+    }
+
+    /// Get every alias of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn get_aliases_by_url(&self, url: String) -> Vec<String> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_aliases\" WHERE \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_aliases\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).fetch_all(c).await {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| self.base.base.textify_row(r).data.remove("alias"))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Give a paste another url
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `alias` - the new url (checked like the url of a new paste)
+    ///
+    /// # Returns
+    /// The stored alias
+    pub async fn create_alias(&self, url: String, alias: String) -> Result<String> {
+        let alias = self.check_new_url(&alias).await?;
+
+        if alias.is_empty() {
+            return Err(PasteError::ValueError);
+        }
+
+        let alias = crate::slug::canonical(&alias);
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_aliases\" VALUES (?, ?)"
+            } else {
+                "INSERT INTO \"se_aliases\" VALUES ($1, $2)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&alias)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(alias),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Remove an alias of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `alias` - the alias to remove
+    pub async fn delete_alias(&self, url: String, alias: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_aliases\" WHERE \"alias\" = ? AND \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_aliases\" WHERE \"alias\" = $1 AND \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&crate::slug::canonical(&alias))
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Point the aliases of a paste at its new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_aliases(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_aliases\" SET \"url\" = ? WHERE \"url\" = ?"
+            } else {
+                "UPDATE \"se_aliases\" SET \"url\" = $1 WHERE \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete every alias of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_aliases_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_aliases\" WHERE \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_aliases\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // attachments
+
+    /// Read an attachment from a row of `se_attachments`
+    fn attachment_from_row(&self, row: HashMap<String, String>) -> Option<Attachment> {
+        Some(Attachment {
+            url: row.get("url")?.clone(),
+            name: row.get("name")?.clone(),
+            key: row.get("storage_key")?.clone(),
+            content_type: row.get("content_type")?.clone(),
+            size: row.get("size")?.parse().unwrap_or(0),
+            date_created: row.get("date_created")?.parse().unwrap_or(0),
+            quarantined: false,
+        })
+    }
+
+    /// Read an attachment from a row of `se_attachments`, and check if it's quarantined
+    async fn attachment_from_row_checked(
+        &self,
+        row: HashMap<String, String>,
+    ) -> Option<Attachment> {
+        let mut attachment = self.attachment_from_row(row)?;
+        attachment.quarantined = self
+            .is_quarantined(QUARANTINE_ATTACHMENT, &attachment.key)
+            .await;
+
+        Some(attachment)
+    }
+
+    /// Get every attachment of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn get_attachments_by_url(&self, url: String) -> Vec<Attachment> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_attachments\" WHERE \"url\" = ? ORDER BY \"name\" ASC"
+            } else {
+                "SELECT * FROM \"se_attachments\" WHERE \"url\" = $1 ORDER BY \"name\" ASC"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let rows = match sqlquery(&query).bind::<&String>(&url).fetch_all(c).await {
+            Ok(rows) => rows,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut attachments = Vec::new();
+        for row in rows {
+            let row = self.base.base.textify_row(row).data;
+            if let Some(attachment) = self.attachment_from_row_checked(row).await {
+                attachments.push(attachment);
+            }
+        }
+
+        attachments
+    }
+
+    /// Get an attachment of a paste by its name
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `name` - the attachment's name
+    pub async fn get_attachment(&self, url: String, name: String) -> Result<Attachment> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_attachments\" WHERE \"url\" = ? AND \"name\" = ?"
+            } else {
+                "SELECT * FROM \"se_attachments\" WHERE \"url\" = $1 AND \"name\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&url)
+            .bind::<&String>(&name)
+            .fetch_one(c)
+            .await
+        {
+            Ok(r) => self
+                .attachment_from_row_checked(self.base.base.textify_row(r).data)
+                .await
+                .ok_or(PasteError::Other),
+            Err(_) => Err(PasteError::NotFound),
+        }
+    }
+
+    /// Get an attachment by the key its content is stored under
+    pub async fn get_attachment_by_key(&self, key: &str) -> Option<Attachment> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_attachments\" WHERE \"storage_key\" = ?"
+            } else {
+                "SELECT * FROM \"se_attachments\" WHERE \"storage_key\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let row = sqlquery(&query).bind::<&str>(key).fetch_one(c).await.ok()?;
+        self.attachment_from_row_checked(self.base.base.textify_row(row).data)
+            .await
+    }
+
+    /// Attach a file to a paste, replacing the attachment with the same name
+    ///
+    /// Files can only be attached while the paste's attachments stay under
+    /// `MAX_ATTACHMENTS_SIZE` altogether, and once they're scanned (see [`crate::scan`]).
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `filename` - the name of the uploaded file (see [`crate::attachments::clean_name`])
+    /// * `data` - the content of the file
+    pub async fn create_attachment(
+        &self,
+        url: String,
+        filename: &str,
+        data: Vec<u8>,
+    ) -> Result<Attachment> {
+        let name = crate::attachments::clean_name(filename).ok_or(PasteError::ValueError)?;
+        let used: usize = self
+            .get_attachments_by_url(url.clone())
+            .await
+            .iter()
+            .filter(|a| a.name != name)
+            .map(|a| a.size)
+            .sum();
+
+        if used + data.len() > crate::config::max_attachments_size() {
+            return Err(PasteError::ValueError);
+        }
+
+        let flagged = crate::scan::check(&name, &data).await?;
+
+        let attachment = Attachment {
+            content_type: crate::attachments::content_type(&name),
+            url,
+            name,
+            key: utility::random_id(),
+            size: data.len(),
+            date_created: utility::unix_epoch_timestamp(),
+            quarantined: flagged.is_some(),
+        };
+
+        if let Some(reason) = flagged {
+            self.quarantine(QUARANTINE_ATTACHMENT, &attachment.key, &reason)
+                .await?;
+        }
+
+        if let Err(e) = crate::attachments::write(attachment.key.clone(), data).await {
+            tracing::error!(error = %e, "Failed to store an attachment.");
+            let _ = self.release(QUARANTINE_ATTACHMENT, &attachment.key).await;
+            return Err(PasteError::Other);
+        }
+
+        let _ = self
+            .delete_attachment(attachment.url.clone(), attachment.name.clone())
+            .await;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_attachments\" VALUES (?, ?, ?, ?, ?, ?)"
+            } else {
+                "INSERT INTO \"se_attachments\" VALUES ($1, $2, $3, $4, $5, $6)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&attachment.url)
+            .bind::<&String>(&attachment.name)
+            .bind::<&String>(&attachment.key)
+            .bind::<&String>(&attachment.content_type)
+            .bind::<&String>(&attachment.size.to_string())
+            .bind::<&String>(&attachment.date_created.to_string())
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(attachment),
+            Err(_) => {
+                let _ = self.release(QUARANTINE_ATTACHMENT, &attachment.key).await;
+                crate::attachments::remove(attachment.key).await;
+                Err(PasteError::Other)
+            }
+        }
+    }
+
+    /// Delete an attachment of a paste (and its content)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `name` - the attachment's name
+    pub async fn delete_attachment(&self, url: String, name: String) -> Result<()> {
+        let attachment = self.get_attachment(url, name).await?;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_attachments\" WHERE \"storage_key\" = ?"
+            } else {
+                "DELETE FROM \"se_attachments\" WHERE \"storage_key\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&attachment.key)
+            .execute(c)
+            .await
+        {
+            Ok(_) => {
+                let _ = self.release(QUARANTINE_ATTACHMENT, &attachment.key).await;
+                crate::attachments::remove(attachment.key).await;
+                Ok(())
+            }
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Move the attachments of a paste to its new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_attachments(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_attachments\" SET \"url\" = ? WHERE \"url\" = ?"
+            } else {
+                "UPDATE \"se_attachments\" SET \"url\" = $1 WHERE \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete every attachment of a paste (after the paste is deleted)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_attachments_by_url(&self, url: String) -> Result<()> {
+        for attachment in self.get_attachments_by_url(url).await {
+            self.delete_attachment(attachment.url, attachment.name)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // files
+
+    /// Read a file from a row of `se_files`
+    fn file_from_row(&self, row: HashMap<String, String>) -> Option<PasteFile> {
+        Some(PasteFile {
+            url: row.get("url")?.clone(),
+            name: row.get("name")?.clone(),
+            content: row.get("content")?.clone(),
+            position: row.get("position")?.parse().unwrap_or(0),
+            date_edited: row.get("date_edited")?.parse().unwrap_or(0),
+        })
+    }
+
+    /// Get every file of a paste (in the order they're shown)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn get_files_by_url(&self, url: String) -> Vec<PasteFile> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_files\" WHERE \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_files\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let mut files: Vec<PasteFile> =
+            match sqlquery(&query).bind::<&String>(&url).fetch_all(c).await {
+                Ok(rows) => rows
+                    .into_iter()
+                    .filter_map(|r| self.file_from_row(self.base.base.textify_row(r).data))
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+
+        // (positions are stored as text, so they're sorted here)
+        files.sort_by_key(|f| f.position);
+        files
+    }
+
+    /// Get a file of a paste by its name
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `name` - the file's name
+    pub async fn get_file(&self, url: String, name: String) -> Result<PasteFile> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_files\" WHERE \"url\" = ? AND \"name\" = ?"
+            } else {
+                "SELECT * FROM \"se_files\" WHERE \"url\" = $1 AND \"name\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&url)
+            .bind::<&String>(&name)
+            .fetch_one(c)
+            .await
+        {
+            Ok(r) => self
+                .file_from_row(self.base.base.textify_row(r).data)
+                .ok_or(PasteError::Other),
+            Err(_) => Err(PasteError::NotFound),
+        }
+    }
+
+    /// Add a file to a paste, or replace the content of the file with the same name (which keeps
+    /// its position)
+    ///
+    /// Files are named like attachments (see [`crate::attachments::clean_name`]), and have the
+    /// same size limit as the content. Large files are scanned like the content of new pastes,
+    /// a flagged file quarantines the whole paste.
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `name` - the name of the file
+    /// * `content` - the content of the file
+    pub async fn save_file(&self, url: String, name: &str, content: String) -> Result<PasteFile> {
+        let name = crate::attachments::clean_name(name).ok_or(PasteError::ValueError)?;
+
+        if content.is_empty() || (content.len() > crate::config::max_content_size()) {
+            return Err(PasteError::ValueError);
+        }
+
+        let files = self.get_files_by_url(url.clone()).await;
+        let existing = files.iter().find(|f| f.name == name);
+
+        if existing.is_none() && (files.len() >= MAX_FILES) {
+            return Err(PasteError::ValueError);
+        }
+
+        if content.len() >= crate::config::scan_paste_size() {
+            if let Some(reason) = crate::scan::check(&name, content.as_bytes()).await? {
+                if !self.is_quarantined(QUARANTINE_PASTE, &url).await {
+                    self.quarantine(QUARANTINE_PASTE, &url, &reason).await?;
+                }
+            }
+        }
+
+        let file = PasteFile {
+            position: existing.map_or_else(
+                || files.iter().map(|f| f.position + 1).max().unwrap_or(0),
+                |f| f.position,
+            ),
+            url,
+            name,
+            content,
+            date_edited: utility::unix_epoch_timestamp(),
+        };
+
+        let query: String = match (existing.is_some(), self.base.base.db._type.as_str()) {
+            (true, "sqlite" | "mysql") => "UPDATE \"se_files\" SET \"content\" = ?, \"position\" = ?, \"date_edited\" = ? WHERE \"url\" = ? AND \"name\" = ?",
+            (true, _) => "UPDATE \"se_files\" SET \"content\" = $1, \"position\" = $2, \"date_edited\" = $3 WHERE \"url\" = $4 AND \"name\" = $5",
+            (false, "sqlite" | "mysql") => "INSERT INTO \"se_files\" (\"content\", \"position\", \"date_edited\", \"url\", \"name\") VALUES (?, ?, ?, ?, ?)",
+            (false, _) => "INSERT INTO \"se_files\" (\"content\", \"position\", \"date_edited\", \"url\", \"name\") VALUES ($1, $2, $3, $4, $5)",
+        }
+        .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&file.content)
+            .bind::<&String>(&file.position.to_string())
+            .bind::<&String>(&file.date_edited.to_string())
+            .bind::<&String>(&file.url)
+            .bind::<&String>(&file.name)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(file),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete a file of a paste
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `name` - the file's name
+    pub async fn delete_file(&self, url: String, name: String) -> Result<()> {
+        // (so deleting a file that doesn't exist is an error)
+        self.get_file(url.clone(), name.clone()).await?;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_files\" WHERE \"url\" = ? AND \"name\" = ?"
+            } else {
+                "DELETE FROM \"se_files\" WHERE \"url\" = $1 AND \"name\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&url)
+            .bind::<&String>(&name)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Move the files of a paste to its new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_files(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_files\" SET \"url\" = ? WHERE \"url\" = ?"
+            } else {
+                "UPDATE \"se_files\" SET \"url\" = $1 WHERE \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete every file of a paste (after the paste is deleted)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_files_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_files\" WHERE \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_files\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // wikis
+
+    /// Read a page from a row of `se_pages`
+    fn page_from_row(&self, row: HashMap<String, String>) -> Option<WikiPage> {
+        Some(WikiPage {
+            namespace: row.get("namespace")?.clone(),
+            name: row.get("name")?.clone(),
+            url: row.get("url")?.clone(),
+        })
+    }
+
+    /// Get the pages under a paste (by name)
+    ///
+    /// # Arguments
+    /// * `namespace` - the paste's (canonical) `url` field
+    pub async fn get_pages(&self, namespace: String) -> Vec<WikiPage> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_pages\" WHERE \"namespace\" = ? ORDER BY \"name\" ASC"
+            } else {
+                "SELECT * FROM \"se_pages\" WHERE \"namespace\" = $1 ORDER BY \"name\" ASC"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&namespace)
+            .fetch_all(c)
+            .await
+        {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| self.page_from_row(self.base.base.textify_row(r).data))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Get a page of a wiki by its name
+    ///
+    /// # Arguments
+    /// * `namespace` - the (canonical) `url` of the paste the page is under
+    /// * `name` - the page's name
+    pub async fn get_page(&self, namespace: String, name: String) -> Result<WikiPage> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_pages\" WHERE \"namespace\" = ? AND \"name\" = ?"
+            } else {
+                "SELECT * FROM \"se_pages\" WHERE \"namespace\" = $1 AND \"name\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&namespace)
+            .bind::<&String>(&name)
+            .fetch_one(c)
+            .await
+        {
+            Ok(r) => self
+                .page_from_row(self.base.base.textify_row(r).data)
+                .ok_or(PasteError::Other),
+            Err(_) => Err(PasteError::NotFound),
+        }
+    }
+
+    /// Get the wiki page a paste is (`None` if it isn't one)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn get_page_by_url(&self, url: String) -> Option<WikiPage> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_pages\" WHERE \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_pages\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let row = sqlquery(&query)
+            .bind::<&String>(&url)
+            .fetch_one(c)
+            .await
+            .ok()?;
+        self.page_from_row(self.base.base.textify_row(row).data)
+    }
+
+    /// Make a paste a page of a wiki
+    ///
+    /// Wikis are one level deep: pages can't have pages, and a paste can only be a page of one
+    /// wiki.
+    ///
+    /// # Arguments
+    /// * `namespace` - the (canonical) `url` of the paste the page is under
+    /// * `name` - the page's name (see [`crate::slug::check_page`])
+    /// * `url` - the page's own (canonical) `url`
+    pub async fn create_page(
+        &self,
+        namespace: String,
+        name: &str,
+        url: String,
+    ) -> Result<WikiPage> {
+        let name = crate::slug::check_page(name)?;
+
+        if self.get_page(namespace.clone(), name.clone()).await.is_ok() {
+            return Err(PasteError::AlreadyExists);
+        }
+
+        if self.get_page_by_url(namespace.clone()).await.is_some()
+            || self.get_page_by_url(url.clone()).await.is_some()
+            || !self.get_pages(url.clone()).await.is_empty()
+            || (self.get_pages(namespace.clone()).await.len() >= MAX_PAGES)
+        {
+            return Err(PasteError::ValueError);
+        }
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_pages\" VALUES (?, ?, ?)"
+            } else {
+                "INSERT INTO \"se_pages\" VALUES ($1, $2, $3)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&namespace)
+            .bind::<&String>(&name)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(WikiPage {
+                namespace,
+                name,
+                url,
+            }),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Take a page out of its wiki (the paste itself stays)
+    ///
+    /// # Arguments
+    /// * `namespace` - the (canonical) `url` of the paste the page is under
+    /// * `name` - the page's name
+    pub async fn delete_page(&self, namespace: String, name: String) -> Result<()> {
+        // (so removing a page that doesn't exist is an error)
+        self.get_page(namespace.clone(), name.clone()).await?;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_pages\" WHERE \"namespace\" = ? AND \"name\" = ?"
+            } else {
+                "DELETE FROM \"se_pages\" WHERE \"namespace\" = $1 AND \"name\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&namespace)
+            .bind::<&String>(&name)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Keep a paste's wiki together after the paste is renamed (as its namespace, or as one of
+    /// its pages)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_pages(&self, url: String, new_url: String) -> Result<()> {
+        let c = &self.base.base.db.client;
+
+        for column in ["namespace", "url"] {
+            let query: String =
+                if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                    format!("UPDATE \"se_pages\" SET \"{column}\" = ? WHERE \"{column}\" = ?")
+                } else {
+                    format!("UPDATE \"se_pages\" SET \"{column}\" = $1 WHERE \"{column}\" = $2")
+                };
+
+            if sqlquery(&query)
+                .bind::<&String>(&new_url)
+                .bind::<&String>(&url)
+                .execute(c)
+                .await
+                .is_err()
+            {
+                return Err(PasteError::Other);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take a paste out of its wiki, and its pages out of its own (after the paste is deleted)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_pages_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_pages\" WHERE \"namespace\" = ? OR \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_pages\" WHERE \"namespace\" = $1 OR \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // comments
+
+    /// Read a comment from a row of `se_comments`
+    fn comment_from_row(&self, row: HashMap<String, String>) -> Option<Comment> {
+        Some(Comment {
+            id: row.get("id")?.clone(),
+            url: row.get("url")?.clone(),
+            reply_to: row.get("reply_to")?.clone(),
+            author: row.get("author")?.clone(),
+            username: row.get("username")?.clone(),
+            owner: row.get("owner")?.clone(),
+            content: row.get("content")?.clone(),
+            date_created: row.get("date_created")?.parse().unwrap_or(0),
+        })
+    }
+
+    /// Get every comment of a paste (oldest first)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn get_comments_by_url(&self, url: String) -> Vec<Comment> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_comments\" WHERE \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_comments\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let mut comments: Vec<Comment> =
+            match sqlquery(&query).bind::<&String>(&url).fetch_all(c).await {
+                Ok(rows) => rows
+                    .into_iter()
+                    .filter_map(|r| self.comment_from_row(self.base.base.textify_row(r).data))
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+
+        // (dates are stored as text, so they're sorted here)
+        comments.sort_by_key(|c| c.date_created);
+        comments
+    }
+
+    /// Leave a comment on a paste
+    ///
+    /// Comments can't be empty, and the same person can't leave the same comment twice in a row.
+    ///
+    /// # Arguments
+    /// * `comment` - the comment (its `id` and `date_created` are set here)
+    pub async fn create_comment(&self, mut comment: Comment) -> Result<Comment> {
+        comment.content = comment.content.trim().replace("\r\n", "\n");
+        comment.author = comment.author.trim().to_string();
+
+        if comment.content.is_empty()
+            || (comment.content.chars().count() > MAX_COMMENT_LENGTH)
+            || (comment.author.chars().count() > MAX_COMMENT_AUTHOR_LENGTH)
+            || comment.author.chars().any(char::is_control)
+        {
+            return Err(PasteError::ValueError);
+        }
+
+        let comments = self.get_comments_by_url(comment.url.clone()).await;
+
+        if (comments.len() >= MAX_COMMENTS)
+            || (!comment.reply_to.is_empty() && !comments.iter().any(|c| c.id == comment.reply_to))
+        {
+            return Err(PasteError::ValueError);
+        }
+
+        if !comment.owner.is_empty()
+            && comments
+                .iter()
+                .rev()
+                .find(|c| c.owner == comment.owner)
+                .is_some_and(|c| c.content == comment.content)
+        {
+            return Err(PasteError::ValueError);
+        }
+
+        comment.id = utility::random_id();
+        comment.date_created = utility::unix_epoch_timestamp();
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_comments\" VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            } else {
+                "INSERT INTO \"se_comments\" VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&comment.id)
+            .bind::<&String>(&comment.url)
+            .bind::<&String>(&comment.reply_to)
+            .bind::<&String>(&comment.author)
+            .bind::<&String>(&comment.username)
+            .bind::<&String>(&comment.owner)
+            .bind::<&String>(&comment.content)
+            .bind::<&String>(&comment.date_created.to_string())
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(comment),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete a comment of a paste, along with everything answering it
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `id` - the comment's `id`
+    pub async fn delete_comment(&self, url: String, id: String) -> Result<()> {
+        let comments = self.get_comments_by_url(url.clone()).await;
+
+        if !comments.iter().any(|c| c.id == id) {
+            return Err(PasteError::NotFound);
+        }
+
+        // (answers always come after what they answer)
+        let mut deleted = vec![id];
+        for comment in &comments {
+            if deleted.contains(&comment.reply_to) {
+                deleted.push(comment.id.clone());
+            }
+        }
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_comments\" WHERE \"url\" = ? AND \"id\" = ?"
+            } else {
+                "DELETE FROM \"se_comments\" WHERE \"url\" = $1 AND \"id\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        for id in deleted {
+            if sqlquery(&query)
+                .bind::<&String>(&url)
+                .bind::<&String>(&id)
+                .execute(c)
+                .await
+                .is_err()
+            {
+                return Err(PasteError::Other);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move the comments of a paste to its new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_comments(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_comments\" SET \"url\" = ? WHERE \"url\" = ?"
+            } else {
+                "UPDATE \"se_comments\" SET \"url\" = $1 WHERE \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete every comment of a paste (after the paste is deleted)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_comments_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_comments\" WHERE \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_comments\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // reactions
+
+    /// Get how many times a paste got each of the [`REACTIONS`]
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `owner` - who is asking (see `crate::api::session_owner`, empty for nobody)
+    pub async fn get_reactions_by_url(&self, url: String, owner: &str) -> Vec<ReactionCount> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT \"name\", \"owner\" FROM \"se_reactions\" WHERE \"url\" = ?"
+            } else {
+                "SELECT \"name\", \"owner\" FROM \"se_reactions\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        let rows: Vec<HashMap<String, String>> =
+            match sqlquery(&query).bind::<&String>(&url).fetch_all(c).await {
+                Ok(rows) => rows
+                    .into_iter()
+                    .map(|r| self.base.base.textify_row(r).data)
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+
+        REACTIONS
+            .iter()
+            .map(|(name, emoji)| {
+                let given = rows
+                    .iter()
+                    .filter(|r| r.get("name").map(String::as_str) == Some(name));
+
+                ReactionCount {
+                    name: name.to_string(),
+                    emoji: emoji.to_string(),
+                    count: given.clone().count(),
+                    reacted: !owner.is_empty()
+                        && given
+                            .clone()
+                            .any(|r| r.get("owner").map(String::as_str) == Some(owner)),
+                }
+            })
+            .collect()
+    }
+
+    /// React to a paste, or take the reaction back if `owner` already reacted with it
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `name` - the name of the reaction (one of the [`REACTIONS`])
+    /// * `owner` - who is reacting (see `crate::api::session_owner`)
+    ///
+    /// # Returns
+    /// * if the paste has the reaction now
+    pub async fn toggle_reaction(&self, url: String, name: &str, owner: &str) -> Result<bool> {
+        if owner.is_empty() || !REACTIONS.iter().any(|(n, _)| *n == name) {
+            return Err(PasteError::ValueError);
+        }
+
+        let reacted = self
+            .get_reactions_by_url(url.clone(), owner)
+            .await
+            .into_iter()
+            .any(|r| r.reacted && (r.name == name));
+
+        let query: String = match (reacted, self.base.base.db._type.as_str()) {
+            (true, "sqlite" | "mysql") => "DELETE FROM \"se_reactions\" WHERE \"url\" = ? AND \"name\" = ? AND \"owner\" = ?",
+            (true, _) => "DELETE FROM \"se_reactions\" WHERE \"url\" = $1 AND \"name\" = $2 AND \"owner\" = $3",
+            (false, "sqlite" | "mysql") => "INSERT INTO \"se_reactions\" VALUES (?, ?, ?, ?)",
+            (false, _) => "INSERT INTO \"se_reactions\" VALUES ($1, $2, $3, $4)",
+        }
+        .to_string();
+
+        let c = &self.base.base.db.client;
+        let mut query = sqlquery(&query)
+            .bind::<&str>(&url)
+            .bind::<&str>(name)
+            .bind::<&str>(owner);
+
+        let now = utility::unix_epoch_timestamp().to_string();
+        if !reacted {
+            query = query.bind::<&String>(&now);
+        }
+
+        match query.execute(c).await {
+            Ok(_) => Ok(!reacted),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Move the reactions to a paste to its new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_reactions(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_reactions\" SET \"url\" = ? WHERE \"url\" = ?"
+            } else {
+                "UPDATE \"se_reactions\" SET \"url\" = $1 WHERE \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete every reaction to a paste (after the paste is deleted)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_reactions_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_reactions\" WHERE \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_reactions\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // redirects
+
+    /// Get the url a renamed paste was moved to
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    pub async fn get_redirect(&self, url: String) -> Option<String> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_redirects\" WHERE \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_redirects\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&crate::slug::canonical(&url))
+            .fetch_one(c)
+            .await
+        {
+            Ok(r) => self.base.base.textify_row(r).data.remove("target"),
+            Err(_) => None,
+        }
+    }
+
+    /// Redirect the old url of a renamed paste to its new one
+    ///
+    /// Redirects always point straight at a paste: the ones pointing at `url` are moved to
+    /// `new_url`, and the ones leaving from `new_url` (which is a paste again) are dropped. So a
+    /// redirect can never lead to another redirect (or loop back to itself).
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    async fn create_redirect(&self, url: String, new_url: String) -> Result<()> {
+        let sqlite_or_mysql =
+            (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql");
+        let c = &self.base.base.db.client;
+
+        let queries: [(&str, &str, [&String; 2]); 3] = [
+            (
+                "DELETE FROM \"se_redirects\" WHERE \"url\" = ? OR \"url\" = ?",
+                "DELETE FROM \"se_redirects\" WHERE \"url\" = $1 OR \"url\" = $2",
+                [&url, &new_url],
+            ),
+            (
+                "UPDATE \"se_redirects\" SET \"target\" = ? WHERE \"target\" = ?",
+                "UPDATE \"se_redirects\" SET \"target\" = $1 WHERE \"target\" = $2",
+                [&new_url, &url],
+            ),
+            (
+                "INSERT INTO \"se_redirects\" VALUES (?, ?)",
+                "INSERT INTO \"se_redirects\" VALUES ($1, $2)",
+                [&url, &new_url],
+            ),
+        ];
+
+        for (query, postgres, [a, b]) in queries {
+            let query = if sqlite_or_mysql { query } else { postgres };
+
+            if sqlquery(query)
+                .bind::<&String>(a)
+                .bind::<&String>(b)
+                .execute(c)
+                .await
+                .is_err()
+            {
+                return Err(PasteError::Other);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete every redirect to a paste (after the paste is deleted)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_redirects_to(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_redirects\" WHERE \"target\" = ?"
+            } else {
+                "DELETE FROM \"se_redirects\" WHERE \"target\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // quarantine
+
+    /// Quarantine flagged content (see [`crate::scan`])
+    ///
+    /// # Arguments
+    /// * `kind` - [`QUARANTINE_PASTE`] or [`QUARANTINE_ATTACHMENT`]
+    /// * `id` - the paste's (canonical) `url`, or the attachment's storage key
+    /// * `reason` - why it was flagged
+    pub async fn quarantine(&self, kind: &str, id: &str, reason: &str) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_quarantine\" VALUES (?, ?, ?, ?)"
+            } else {
+                "INSERT INTO \"se_quarantine\" VALUES ($1, $2, $3, $4)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&str>(kind)
+            .bind::<&str>(id)
+            .bind::<&str>(reason)
+            .bind::<&String>(&utility::unix_epoch_timestamp().to_string())
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Check if content is quarantined
+    ///
+    /// # Arguments
+    /// * `kind` - [`QUARANTINE_PASTE`] or [`QUARANTINE_ATTACHMENT`]
+    /// * `id` - the paste's (canonical) `url`, or the attachment's storage key
+    pub async fn is_quarantined(&self, kind: &str, id: &str) -> bool {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_quarantine\" WHERE \"kind\" = ? AND \"id\" = ?"
+            } else {
+                "SELECT * FROM \"se_quarantine\" WHERE \"kind\" = $1 AND \"id\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        sqlquery(&query)
+            .bind::<&str>(kind)
+            .bind::<&str>(id)
+            .fetch_one(c)
+            .await
+            .is_ok()
+    }
+
+    /// Get everything in quarantine (oldest first)
+    pub async fn get_quarantined(&self) -> Vec<Quarantined> {
+        let query = "SELECT * FROM \"se_quarantine\" ORDER BY \"date_created\" ASC";
+
+        let c = &self.base.base.db.client;
+        let rows = match sqlquery(query).fetch_all(c).await {
+            Ok(rows) => rows,
+            Err(_) => return Vec::new(),
+        };
+
+        rows.into_iter()
+            .filter_map(|r| {
+                let row = self.base.base.textify_row(r).data;
+
+                Some(Quarantined {
+                    kind: row.get("kind")?.clone(),
+                    id: row.get("id")?.clone(),
+                    reason: row.get("reason")?.clone(),
+                    date_created: row.get("date_created")?.parse().unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    /// Release quarantined content (making it as reachable as it would have been)
+    ///
+    /// # Arguments
+    /// * `kind` - [`QUARANTINE_PASTE`] or [`QUARANTINE_ATTACHMENT`]
+    /// * `id` - the paste's (canonical) `url`, or the attachment's storage key
+    pub async fn release(&self, kind: &str, id: &str) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_quarantine\" WHERE \"kind\" = ? AND \"id\" = ?"
+            } else {
+                "DELETE FROM \"se_quarantine\" WHERE \"kind\" = $1 AND \"id\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&str>(kind)
+            .bind::<&str>(id)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Keep a paste quarantined after it's renamed
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_quarantine(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_quarantine\" SET \"id\" = ? WHERE \"kind\" = ? AND \"id\" = ?"
+            } else {
+                "UPDATE \"se_quarantine\" SET \"id\" = $1 WHERE \"kind\" = $2 AND \"id\" = $3"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&str>(QUARANTINE_PASTE)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // drafts
+
+    /// Get the draft `owner` saved for a paste
+    ///
+    /// # Arguments
+    /// * `owner` - who the draft belongs to (see `crate::api::session_owner`)
+    /// * `url` - the paste being edited (empty for a new paste)
+    pub async fn get_draft(&self, owner: &str, url: &str) -> Option<Draft> {
+        let c = &self.base.base.db.client;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_drafts\" WHERE \"owner\" = ? AND \"url\" = ?"
+            } else {
+                "SELECT * FROM \"se_drafts\" WHERE \"owner\" = $1 AND \"url\" = $2"
+            }
+            .to_string();
+
+        let row = sqlquery(&query)
+            .bind::<&str>(owner)
+            .bind::<&str>(url)
+            .fetch_one(c)
+            .await
+            .ok()?;
+
+        let row = self.base.base.textify_row(row).data;
+
+        Some(Draft {
+            url: url.to_string(),
+            content: row.get("content")?.clone(),
+            date_updated: row.get("date_updated")?.parse().unwrap_or(0),
+        })
+    }
+
+    /// Save (or replace) the draft `owner` is writing for a paste
+    ///
+    /// Drafts that weren't saved for [`DRAFT_LIFETIME`] are deleted along the way.
+    pub async fn save_draft(&self, owner: &str, url: &str, content: String) -> Result<Draft> {
+        self.delete_draft(owner, url).await?;
+
+        let c = &self.base.base.db.client;
+        let now = utility::unix_epoch_timestamp();
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_drafts\" VALUES (?, ?, ?, ?)"
+            } else {
+                "INSERT INTO \"se_drafts\" VALUES ($1, $2, $3, $4)"
+            }
+            .to_string();
+
+        if sqlquery(&query)
+            .bind::<&str>(owner)
+            .bind::<&str>(url)
+            .bind::<&String>(&content)
+            .bind::<&String>(&now.to_string())
+            .execute(c)
+            .await
+            .is_err()
+        {
+            return Err(PasteError::Other);
+        }
+
+        // (timestamps in milliseconds all have 13 digits until 2286, so they compare as text)
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_drafts\" WHERE \"date_updated\" < ?"
+            } else {
+                "DELETE FROM \"se_drafts\" WHERE \"date_updated\" < $1"
+            }
+            .to_string();
+
+        let _ = sqlquery(&query)
+            .bind::<&String>(&now.saturating_sub(DRAFT_LIFETIME).to_string())
+            .execute(c)
+            .await;
+
+        Ok(Draft {
+            url: url.to_string(),
+            content,
+            date_updated: now,
+        })
+    }
+
+    /// Delete the draft `owner` saved for a paste (once it's published, or thrown away)
+    pub async fn delete_draft(&self, owner: &str, url: &str) -> Result<()> {
+        let c = &self.base.base.db.client;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_drafts\" WHERE \"owner\" = ? AND \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_drafts\" WHERE \"owner\" = $1 AND \"url\" = $2"
+            }
+            .to_string();
+
+        match sqlquery(&query)
+            .bind::<&str>(owner)
+            .bind::<&str>(url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // edit links
+
+    /// Create a single-use token that can edit the content of a paste once, without its
+    /// password (only its hash is stored)
+    ///
+    /// Expired tokens are deleted along the way.
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `expires_at` - when the token stops working (in milliseconds since the epoch)
+    ///
+    /// # Returns
+    /// * the token
+    pub async fn create_edit_token(&self, url: String, expires_at: u128) -> Result<String> {
+        let c = &self.base.base.db.client;
+        let token = utility::random_id();
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_edit_tokens\" VALUES (?, ?, ?)"
+            } else {
+                "INSERT INTO \"se_edit_tokens\" VALUES ($1, $2, $3)"
+            }
+            .to_string();
+
+        if sqlquery(&query)
+            .bind::<&String>(&utility::hash(token.clone()))
+            .bind::<&String>(&url)
+            .bind::<&String>(&expires_at.to_string())
+            .execute(c)
+            .await
+            .is_err()
+        {
+            return Err(PasteError::Other);
+        }
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_edit_tokens\" WHERE \"expires_at\" <= ?"
+            } else {
+                "DELETE FROM \"se_edit_tokens\" WHERE \"expires_at\" <= $1"
+            }
+            .to_string();
+
+        let _ = sqlquery(&query)
+            .bind::<&String>(&utility::unix_epoch_timestamp().to_string())
+            .execute(c)
+            .await;
+
+        Ok(token)
+    }
+
+    /// Check if `token` can (still) edit a paste, without using it up
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `token` - the token given by [`Database::create_edit_token`]
+    pub async fn check_edit_token(&self, url: String, token: String) -> bool {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_edit_tokens\" WHERE \"token\" = ? AND \"url\" = ? AND \"expires_at\" > ?"
+            } else {
+                "SELECT * FROM \"se_edit_tokens\" WHERE \"token\" = $1 AND \"url\" = $2 AND \"expires_at\" > $3"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        sqlquery(&query)
+            .bind::<&String>(&utility::hash(token))
+            .bind::<&String>(&url)
+            .bind::<&String>(&utility::unix_epoch_timestamp().to_string())
+            .fetch_optional(c)
+            .await
+            .is_ok_and(|r| r.is_some())
+    }
+
+    /// Use up `token` (so it can't edit the paste again)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    /// * `token` - the token given by [`Database::create_edit_token`]
+    ///
+    /// # Returns
+    /// * `Err(PasteError::PasswordIncorrect)` if the token is unknown, expired or already used
+    pub async fn use_edit_token(&self, url: String, token: String) -> Result<()> {
+        // (deleting is what checks the token, so two requests can't both use it)
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_edit_tokens\" WHERE \"token\" = ? AND \"url\" = ? AND \"expires_at\" > ?"
+            } else {
+                "DELETE FROM \"se_edit_tokens\" WHERE \"token\" = $1 AND \"url\" = $2 AND \"expires_at\" > $3"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&utility::hash(token))
+            .bind::<&String>(&url)
+            .bind::<&String>(&utility::unix_epoch_timestamp().to_string())
+            .execute(c)
+            .await
+        {
+            Ok(r) if r.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(PasteError::PasswordIncorrect),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Move the edit tokens of a paste to its new url (after the paste is renamed)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's old `url`
+    /// * `new_url` - the paste's new `url`
+    pub async fn move_edit_tokens(&self, url: String, new_url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_edit_tokens\" SET \"url\" = ? WHERE \"url\" = ?"
+            } else {
+                "UPDATE \"se_edit_tokens\" SET \"url\" = $1 WHERE \"url\" = $2"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&new_url)
+            .bind::<&String>(&url)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete every edit token of a paste (after the paste is deleted)
+    ///
+    /// # Arguments
+    /// * `url` - the paste's (canonical) `url` field
+    pub async fn delete_edit_tokens_by_url(&self, url: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_edit_tokens\" WHERE \"url\" = ?"
+            } else {
+                "DELETE FROM \"se_edit_tokens\" WHERE \"url\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&url).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // mirroring
+
+    /// Store a mirrored paste as-is (replacing the paste with the same url)
+    ///
+    /// # Arguments
+    /// * `paste` - the paste (its `password` should already be hashed)
+    pub async fn store_mirrored_paste(&self, paste: &Paste) -> Result<()> {
+        let t = &self.base.options.table_pastes;
+        let c = &self.base.base.db.client;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \":t\" WHERE \":url\" = ?"
+            } else {
+                "DELETE FROM \":t\" WHERE \":url\" = $1"
+            }
+            .replace(":t", &t.table_name)
+            .replace(":url", &t.url);
+
+        if sqlquery(&query)
+            .bind::<&String>(&paste.url)
+            .execute(c)
+            .await
+            .is_err()
+        {
+            return Err(PasteError::Other);
+        }
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \":t\" VALUES (?, ?, ?, ?, ?, ?, ?)"
+            } else {
+                "INSERT INTO \":t\" VALUES ($1, $2, $3, $4, $5, $6, $7)"
+            }
+            .replace(":t", &t.table_name);
+
+        let result = sqlquery(&query)
+            .bind::<&String>(&paste.id)
+            .bind::<&String>(&paste.url)
+            .bind::<&String>(&paste.password)
+            .bind::<&String>(&paste.content)
+            .bind::<&String>(&paste.date_published.to_string())
+            .bind::<&String>(&paste.date_edited.to_string())
+            .bind::<&String>(match serde_json::to_string(&paste.metadata) {
+                Ok(ref m) => m,
+                Err(_) => return Err(PasteError::ValueError),
+            })
+            .execute(c)
+            .await;
+
+        // remove from cache
+        self.base
+            .base
+            .cachedb
+            .remove(format!("{}:{}", t.prefix, paste.url))
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // settings
+
+    /// Get a stored instance setting by `name`
+    ///
+    /// # Arguments
+    /// * `name` - the name of the setting
+    pub async fn get_setting(&self, name: &str) -> Option<String> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_settings\" WHERE \"name\" = ?"
+            } else {
+                "SELECT * FROM \"se_settings\" WHERE \"name\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&str>(name).fetch_one(c).await {
+            Ok(r) => self.base.base.textify_row(r).data.get("value").cloned(),
+            Err(_) => None,
+        }
+    }
+
+    /// Store an instance setting
+    ///
+    /// # Arguments
+    /// * `name` - the name of the setting
+    /// * `value` - the new value of the setting
+    pub async fn set_setting(&self, name: &str, value: String) -> Result<()> {
+        let c = &self.base.base.db.client;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_settings\" WHERE \"name\" = ?"
+            } else {
+                "DELETE FROM \"se_settings\" WHERE \"name\" = $1"
+            }
+            .to_string();
+
+        if sqlquery(&query)
+            .bind::<&str>(name)
+            .execute(c)
+            .await
+            .is_err()
+        {
+            return Err(PasteError::Other);
+        }
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_settings\" VALUES (?, ?)"
+            } else {
+                "INSERT INTO \"se_settings\" VALUES ($1, $2)"
+            }
+            .to_string();
+
+        match sqlquery(&query)
+            .bind::<&str>(name)
+            .bind::<&String>(&value)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // activitypub
+
+    /// Get every ActivityPub follower of the instance actor
+    ///
+    /// # Returns
+    /// * `(actor id, inbox url)` of each follower
+    pub async fn get_ap_followers(&self) -> Vec<(String, String)> {
+        let c = &self.base.base.db.client;
+        match sqlquery("SELECT * FROM \"se_ap_followers\"")
+            .fetch_all(c)
+            .await
+        {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| {
+                    let row = self.base.base.textify_row(r).data;
+                    Some((row.get("actor")?.clone(), row.get("inbox")?.clone()))
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Add (or update) an ActivityPub follower of the instance actor
+    ///
+    /// # Arguments
+    /// * `actor` - the id of the following actor
+    /// * `inbox` - the inbox activities are delivered to (the actor's shared inbox if it has one)
+    pub async fn add_ap_follower(&self, actor: String, inbox: String) -> Result<()> {
+        self.remove_ap_follower(actor.clone()).await?;
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_ap_followers\" VALUES (?, ?)"
+            } else {
+                "INSERT INTO \"se_ap_followers\" VALUES ($1, $2)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&actor)
+            .bind::<&String>(&inbox)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Remove an ActivityPub follower of the instance actor
+    ///
+    /// # Arguments
+    /// * `actor` - the id of the actor that stopped following
+    pub async fn remove_ap_follower(&self, actor: String) -> Result<()> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_ap_followers\" WHERE \"actor\" = ?"
+            } else {
+                "DELETE FROM \"se_ap_followers\" WHERE \"actor\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&actor).execute(c).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    // webhooks
+
+    /// Get every configured [`Webhook`]
+    pub async fn get_webhooks(&self) -> Vec<Webhook> {
+        match self.get_setting("webhooks").await {
+            Some(v) => serde_json::from_str(&v).unwrap_or_default(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Add a [`Webhook`]
+    ///
+    /// # Arguments
+    /// * `props` - [`WebhookCreate`]
+    pub async fn create_webhook(&self, props: WebhookCreate) -> Result<Webhook> {
+        let valid_url =
+            url::Url::parse(&props.url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+
+        if !valid_url || props.secret.is_empty() || (props.secret.len() > 256) {
+            return Err(PasteError::ValueError);
+        }
+
+        let webhook = Webhook {
+            id: utility::random_id(),
+            url: props.url,
+            secret: props.secret,
+            events: props.events,
+        };
+
+        let mut webhooks = self.get_webhooks().await;
+        webhooks.push(webhook.clone());
+
+        match serde_json::to_string(&webhooks) {
+            Ok(v) => self.set_setting("webhooks", v).await?,
+            Err(_) => return Err(PasteError::ValueError),
+        };
+
+        Ok(webhook)
+    }
+
+    /// Remove a [`Webhook`]
+    ///
+    /// # Arguments
+    /// * `id` - the id of the webhook
+    pub async fn delete_webhook(&self, id: String) -> Result<()> {
+        let mut webhooks = self.get_webhooks().await;
+        let count = webhooks.len();
+        webhooks.retain(|w| w.id != id);
+
+        if webhooks.len() == count {
+            return Err(PasteError::NotFound);
+        }
+
+        match serde_json::to_string(&webhooks) {
+            Ok(v) => self.set_setting("webhooks", v).await,
+            Err(_) => Err(PasteError::ValueError),
+        }
+    }
+
+    // announcement
+
+    /// Load the stored announcement into memory (see [`current_announcement`])
+    pub async fn load_announcement(&self) {
+        let announcement = match self.get_setting("announcement").await {
+            Some(v) => serde_json::from_str::<Announcement>(&v).ok(),
+            None => None,
+        };
+
+        if let Ok(mut a) = ANNOUNCEMENT.write() {
+            *a = announcement;
+        }
+    }
+
+    /// Set the instance announcement (an empty `text` removes it)
+    ///
+    /// # Arguments
+    /// * `announcement` - the new [`Announcement`]
+    pub async fn set_announcement(&self, announcement: Announcement) -> Result<()> {
+        if announcement.text.len() > 1000 {
+            return Err(PasteError::ValueError);
+        }
+
+        let value = match serde_json::to_string(&announcement) {
+            Ok(v) => v,
+            Err(_) => return Err(PasteError::ValueError),
+        };
+
+        self.set_setting("announcement", value).await?;
+        self.load_announcement().await;
+        Ok(())
+    }
+
+    // maintenance
+
+    /// Check if maintenance mode is enabled
+    pub async fn get_maintenance(&self) -> bool {
+        match self
+            .base
+            .base
+            .cachedb
+            .get("se_maintenance".to_string())
+            .await
+        {
+            Some(v) => v == "true",
+            None => false,
+        }
+    }
+
+    /// Enable or disable maintenance mode
+    ///
+    /// # Arguments
+    /// * `enabled` - if write routes should be rejected
+    pub async fn set_maintenance(&self, enabled: bool) -> Result<()> {
+        match self
+            .base
+            .base
+            .cachedb
+            .set("se_maintenance".to_string(), enabled.to_string())
+            .await
+        {
+            true => Ok(()),
+            false => Err(PasteError::Other),
+        }
+    }
+
+    // views
+
+    /// Count a view of a paste (see [`crate::views`])
+    ///
+    /// # Arguments
+    /// * `as_user` - the user viewing the paste (for [`ViewMode::AuthenticatedOnce`])
+    pub async fn incr_views_by_url(&self, url: String, as_user: Option<Profile>) -> Result<()> {
+        if self.base.options.view_mode == ViewMode::AuthenticatedOnce {
+            return self.base.incr_views_by_url(url, as_user).await;
+        }
+
+        crate::views::record(self, url).await;
+        Ok(())
+    }
+
+    /// Get the view count of a paste, with the views that weren't written yet
+    pub async fn get_views_by_url(&self, url: String) -> i32 {
+        self.base.get_views_by_url(url.clone()).await + crate::views::pending(self, &url)
+    }
+
+    // stats
+
+    /// Run a `COUNT(*)` query (or any query with a single number named `count`), returning 0 on
+    /// failure
+    ///
+    /// `:count` in the query is replaced with a text-casted `COUNT(*)` (named `count`)
+    async fn count(&self, query: &str, binds: Vec<String>) -> i64 {
+        let query = query.replace(
+            ":count",
+            if self.base.base.db._type == "mysql" {
+                "CAST(COUNT(*) AS CHAR) AS \"count\""
+            } else {
+                "CAST(COUNT(*) AS TEXT) AS \"count\""
+            },
+        );
+
+        let mut q = sqlquery(&query);
+
+        for bind in &binds {
+            q = q.bind::<&String>(bind);
+        }
+
+        let c = &self.base.base.db.client;
+        match q.fetch_one(c).await {
+            Ok(r) => match self.base.base.textify_row(r).data.get("count") {
+                Some(count) => count.parse::<i64>().unwrap_or(0),
+                None => 0,
+            },
+            Err(_) => 0,
+        }
+    }
+
+    /// Get the total amount of paste views
+    async fn count_views(&self) -> i64 {
+        if self.base.options.view_mode == ViewMode::AuthenticatedOnce {
+            // every view is logged in the views table
+            return self
+                .count(
+                    &format!(
+                        "SELECT :count FROM \"{}\"",
+                        self.base.options.table_views.table_name
+                    ),
+                    Vec::new(),
+                )
+                .await;
+        }
+
+        // views only exist in the cache in this mode
+        let mut con = self.base.base.cachedb.get_con().await;
+        let keys: Vec<String> = con
+            .keys(format!("{}:*", self.base.options.table_views.prefix))
+            .unwrap_or_default();
+
+        if keys.is_empty() {
+            return 0;
+        }
+
+        let counts: Vec<Option<i64>> = con.mget(&keys).unwrap_or_default();
+        counts.into_iter().flatten().sum()
+    }
+
+    /// Get how many bytes a text column takes up in total
+    async fn sum_bytes(&self, table: &str, column: &str) -> i64 {
+        let query = match self.base.base.db._type.as_str() {
+            "sqlite" => "SELECT CAST(COALESCE(SUM(LENGTH(CAST(\":c\" AS BLOB))), 0) AS TEXT) AS \"count\" FROM \":t\"",
+            "mysql" => "SELECT CAST(COALESCE(SUM(LENGTH(\":c\")), 0) AS CHAR) AS \"count\" FROM \":t\"",
+            _ => "SELECT CAST(COALESCE(SUM(OCTET_LENGTH(\":c\")), 0) AS TEXT) AS \"count\" FROM \":t\"",
+        }
+        .replace(":t", table)
+        .replace(":c", column);
+
+        self.count(&query, Vec::new()).await
+    }
+
+    /// Get how much the content of pastes takes up (in bytes, with their files and attachments)
+    async fn count_storage_bytes(&self) -> i64 {
+        let t = &self.base.options.table_pastes;
+
+        // (attachment sizes are stored as text)
+        let attachments = match self.base.base.db._type.as_str() {
+            "mysql" => "SELECT CAST(COALESCE(SUM(CAST(\"size\" AS SIGNED)), 0) AS CHAR) AS \"count\" FROM \"se_attachments\"",
+            _ => "SELECT CAST(COALESCE(SUM(CAST(\"size\" AS BIGINT)), 0) AS TEXT) AS \"count\" FROM \"se_attachments\"",
+        };
+
+        self.sum_bytes(&t.table_name, &t.content).await
+            + self.sum_bytes("se_files", "content").await
+            + self.count(attachments, Vec::new()).await
+    }
+
+    /// Get how many pastes were created on each of the last `days` days (oldest first, today
+    /// included)
+    async fn count_daily_pastes(&self, days: u128) -> Vec<DailyPastes> {
+        const DAY: u128 = 86_400_000;
+
+        let now = utility::unix_epoch_timestamp();
+        let first_day = now - (now % DAY) - ((days - 1) * DAY);
+
+        // timestamps are always 13 digits, so we can compare them as text
+        let t = &self.base.options.table_pastes;
+        let query = if self.base.base.db._type == "postgres" {
+            "SELECT \":date_published\" FROM \":t\" WHERE \":date_published\" >= $1"
+        } else {
+            "SELECT \":date_published\" FROM \":t\" WHERE \":date_published\" >= ?"
+        }
+        .replace(":t", &t.table_name)
+        .replace(":date_published", &t.date_published);
+
+        let c = &self.base.base.db.client;
+        let published: Vec<u128> = match sqlquery(&query)
+            .bind::<String>(first_day.to_string())
+            .fetch_all(c)
+            .await
+        {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| {
+                    self.base
+                        .base
+                        .textify_row(r)
+                        .data
+                        .get(&t.date_published)?
+                        .parse()
+                        .ok()
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        (0..days)
+            .map(|i| {
+                let day = first_day + (i * DAY);
+                DailyPastes {
+                    day,
+                    pastes: published
+                        .iter()
+                        .filter(|p| (day..day + DAY).contains(*p))
+                        .count() as i64,
+                }
+            })
+            .collect()
+    }
+
+    /// Get [`InstanceStats`] (recomputed at most every [`STATS_CACHE_TIME`])
+    #[tracing::instrument(skip(self))]
+    pub async fn get_instance_stats(&self) -> InstanceStats {
+        let now = utility::unix_epoch_timestamp();
+
+        // check in cache
+        if let Some(c) = self.base.base.cachedb.get("se_stats".to_string()).await {
+            if let Ok(stats) = serde_json::from_str::<InstanceStats>(c.as_str()) {
+                if now - stats.computed_at < STATS_CACHE_TIME {
+                    crate::metrics::cache("stats", true);
+                    return stats;
+                }
+            }
+        }
+
+        crate::metrics::cache("stats", false);
+
+        // count
+        let t = &self.base.options.table_pastes;
+        let daily_pastes = self.count_daily_pastes(30).await;
+        let stats = InstanceStats {
+            pastes: self
+                .count(
+                    &format!("SELECT :count FROM \"{}\"", t.table_name),
+                    Vec::new(),
+                )
+                .await,
+            views: self.count_views().await,
+            pastes_today: daily_pastes.last().map_or(0, |d| d.pastes),
+            storage_bytes: self.count_storage_bytes().await,
+            daily_pastes,
+            computed_at: now,
+        };
+
+        // store in cache
+        self.base
+            .base
+            .cachedb
+            .set(
+                "se_stats".to_string(),
+                serde_json::to_string::<InstanceStats>(&stats).unwrap(),
+            )
+            .await;
+
+        stats
+    }
+
+    /// Forget the cached [`InstanceStats`] (so they're recomputed on the next request)
+    pub async fn clear_instance_stats(&self) {
+        self.base.base.cachedb.remove("se_stats".to_string()).await;
+    }
+
+    // collections
+
+    /// Build a [`Collection`] from a textified row of the collections table
+    fn collection_from_row(&self, res: HashMap<String, String>) -> Option<Collection> {
+        Some(Collection {
+            name: res.get("name")?.to_string(),
+            title: res.get("title")?.to_string(),
+            owner: res.get("owner")?.to_string(),
+            pastes: serde_json::from_str(res.get("pastes")?).ok()?,
+            date_created: res.get("date_created")?.parse::<u128>().ok()?,
+        })
+    }
+
+    /// Get an existing collection by `name`
+    ///
+    /// # Arguments
+    /// * `name` - [`String`] of the collection's `name` field
+    #[tracing::instrument(skip(self))]
+    pub async fn get_collection_by_name(&self, name: String) -> Result<Collection> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_collections\" WHERE \"name\" = ?"
+            } else {
+                "SELECT * FROM \"se_collections\" WHERE \"name\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&name.to_lowercase())
+            .fetch_one(c)
+            .await
+        {
+            Ok(r) => match self.collection_from_row(self.base.base.textify_row(r).data) {
+                Some(collection) => Ok(collection),
+                None => Err(PasteError::ValueError),
+            },
+            Err(_) => Err(PasteError::NotFound),
+        }
+    }
+
+    /// Get all collections owned by `owner`
+    ///
+    /// # Arguments
+    /// * `owner` - the username of the collection owner
+    pub async fn get_collections_by_owner(&self, owner: String) -> Vec<Collection> {
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "SELECT * FROM \"se_collections\" WHERE \"owner\" = ? ORDER BY \"name\" ASC"
+            } else {
+                "SELECT * FROM \"se_collections\" WHERE \"owner\" = $1 ORDER BY \"name\" ASC"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query).bind::<&String>(&owner).fetch_all(c).await {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|r| self.collection_from_row(self.base.base.textify_row(r).data))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Create a new collection
+    ///
+    /// # Arguments
+    /// * `props` - [`CollectionCreate`]
+    /// * `owner` - the username of the user creating the collection
+    pub async fn create_collection(
+        &self,
+        mut props: CollectionCreate,
+        owner: String,
+    ) -> Result<Collection> {
+        props.name = props.name.trim().to_lowercase();
+
+        // check name
+        if (props.name.len() > 64) | (props.name.len() < 2) {
+            return Err(PasteError::ValueError);
+        }
+
+        let regex = RegexBuilder::new("^[\\w\\_\\-\\.\\!]+$").build().unwrap();
+
+        if !regex.is_match(&props.name) {
+            return Err(PasteError::ValueError);
+        }
+
+        if props.title.len() > 250 {
+            return Err(PasteError::ValueError);
+        }
+
+        // make sure collection doesn't already exist
+        if self
+            .get_collection_by_name(props.name.clone())
+            .await
+            .is_ok()
+        {
+            return Err(PasteError::AlreadyExists);
+        }
+
+        // ...
+        let collection = Collection {
+            title: if props.title.is_empty() {
+                props.name.clone()
+            } else {
+                props.title
+            },
+            name: props.name,
+            owner,
+            pastes: Vec::new(),
+            date_created: utility::unix_epoch_timestamp(),
+        };
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "INSERT INTO \"se_collections\" VALUES (?, ?, ?, ?, ?)"
+            } else {
+                "INSERT INTO \"se_collections\" VALUES ($1, $2, $3, $4, $5)"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&collection.name)
+            .bind::<&String>(&collection.title)
+            .bind::<&String>(&collection.owner)
+            .bind::<&String>(&serde_json::to_string(&collection.pastes).unwrap())
+            .bind::<&String>(&collection.date_created.to_string())
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(collection),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Edit an existing collection by `name`
+    ///
+    /// # Arguments
+    /// * `name` - the collection to edit
+    /// * `props` - [`CollectionEdit`]
+    /// * `editing_as` - the username of the user editing the collection
+    pub async fn edit_collection_by_name(
+        &self,
+        name: String,
+        props: CollectionEdit,
+        editing_as: String,
+    ) -> Result<()> {
+        let existing = self.get_collection_by_name(name).await?;
+
+        if existing.owner != editing_as {
+            return Err(PasteError::PasswordIncorrect);
+        }
+
+        if props.title.len() > 250 {
+            return Err(PasteError::ValueError);
+        }
+
+        // only pastes owned by the collection owner can be added
+        let mut pastes: Vec<String> = Vec::new();
+
+        for url in props.pastes {
+            let paste = self.get_paste_by_url(url).await?;
+
+            if paste.metadata.owner != existing.owner {
+                return Err(PasteError::ValueError);
+            }
+
+            if !pastes.contains(&paste.url) {
+                pastes.push(paste.url);
+            }
+        }
+
+        // edit collection
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "UPDATE \"se_collections\" SET \"title\" = ?, \"pastes\" = ? WHERE \"name\" = ?"
+            } else {
+                "UPDATE \"se_collections\" SET \"title\" = $1, \"pastes\" = $2 WHERE \"name\" = $3"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(if props.title.is_empty() {
+                &existing.title
+            } else {
+                &props.title
+            })
+            .bind::<&String>(&serde_json::to_string(&pastes).unwrap())
+            .bind::<&String>(&existing.name)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+
+    /// Delete an existing collection by `name` (the pastes in it are kept)
+    ///
+    /// # Arguments
+    /// * `name` - the collection to delete
+    /// * `deleting_as` - the username of the user deleting the collection
+    pub async fn delete_collection_by_name(&self, name: String, deleting_as: String) -> Result<()> {
+        let existing = self.get_collection_by_name(name).await?;
+
+        if existing.owner != deleting_as {
+            return Err(PasteError::PasswordIncorrect);
+        }
+
+        let query: String =
+            if (self.base.base.db._type == "sqlite") | (self.base.base.db._type == "mysql") {
+                "DELETE FROM \"se_collections\" WHERE \"name\" = ?"
+            } else {
+                "DELETE FROM \"se_collections\" WHERE \"name\" = $1"
+            }
+            .to_string();
+
+        let c = &self.base.base.db.client;
+        match sqlquery(&query)
+            .bind::<&String>(&existing.name)
+            .execute(c)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PasteError::Other),
+        }
+    }
+}
+
+/// Escape the given string for use in HTML
+pub fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Create an excerpt of `content` around the first match of any of `terms`,
+/// with all matches wrapped in `<mark>`
+fn search_snippet(content: &str, terms: &[String]) -> String {
+    let regex = match RegexBuilder::new(
+        &terms
+            .iter()
+            .map(|t| regex::escape(t))
+            .collect::<Vec<String>>()
+            .join("|"),
+    )
+    .case_insensitive(true)
+    .build()
+    {
+        Ok(r) => r,
+        Err(_) => return String::new(),
+    };
+
+    // find window (on char boundaries)
+    let first = regex.find(content).map(|m| m.start()).unwrap_or(0);
+
+    let start = content[..first]
+        .char_indices()
+        .rev()
+        .nth(60)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+
+    let end = content[first..]
+        .char_indices()
+        .nth(200)
+        .map(|(i, _)| first + i)
+        .unwrap_or(content.len());
+
+    let window = &content[start..end];
+
+    // highlight
+    let mut out = String::new();
+    let mut last = 0;
+
+    if start > 0 {
+        out.push('…');
+    }
+
+    for m in regex.find_iter(window) {
+        out.push_str(&escape_html(&window[last..m.start()]));
+        out.push_str(&format!("<mark>{}</mark>", escape_html(m.as_str())));
+        last = m.end();
+    }
+
+    out.push_str(&escape_html(&window[last..]));
+
+    if end < content.len() {
+        out.push('…');
+    }
+
+    out
+}
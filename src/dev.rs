@@ -0,0 +1,97 @@
+//! Development mode (`sealable --dev`, see [`crate::AppConfig::dev`])
+//!
+//! Layout overrides and static files are watched (by polling, there's no file event API to rely
+//! on everywhere), overrides are reloaded when they change, and open pages reload themselves after
+//! any change. Pages are compiled in, so changing `templates/` still needs a rebuild.
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+
+/// How often watched directories are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Changes every time a watched file changes (and on restart), open pages reload when it does
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Check if the running app is in development mode
+pub fn enabled() -> bool {
+    crate::config::current().is_some_and(|c| c.dev)
+}
+
+/// List every file in `dir` (recursively) with when it was last modified
+fn snapshot(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                files.push((path, modified));
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Start watching the layout overrides in `templates_dir` and the files in `static_dir`
+pub fn watch(templates_dir: Option<PathBuf>, static_dir: PathBuf) {
+    // a restarted server (`cargo watch`) also counts as a change
+    VERSION.store(
+        dorsal::utility::unix_epoch_timestamp() as u64,
+        Ordering::Relaxed,
+    );
+
+    std::thread::spawn(move || {
+        let mut templates = templates_dir.as_deref().map(snapshot);
+        let mut statics = snapshot(&static_dir);
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let new_templates = templates_dir.as_deref().map(snapshot);
+            if new_templates != templates {
+                crate::layout::load(templates_dir.as_deref());
+                tracing::info!("Reloaded layout overrides.");
+
+                templates = new_templates;
+                VERSION.fetch_add(1, Ordering::Relaxed);
+            }
+
+            let new_statics = snapshot(&static_dir);
+            if new_statics != statics {
+                tracing::info!("Static files changed.");
+
+                statics = new_statics;
+                VERSION.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+}
+
+/// Get the current version of the watched files (`/_dev/version`)
+pub async fn version_request() -> String {
+    VERSION.load(Ordering::Relaxed).to_string()
+}
+
+/// Keep browsers from caching anything, so changes show up on reload
+pub async fn no_store(req: Request, next: Next) -> Response {
+    let mut res = next.run(req).await;
+
+    res.headers_mut()
+        .insert("Cache-Control", HeaderValue::from_static("no-store"));
+
+    res
+}
@@ -0,0 +1,170 @@
+//! Internal event bus
+//!
+//! Handlers [`publish`] what happened to pastes instead of calling every subsystem that cares.
+//! Each subsystem listens on its own receiver (see [`start`]), so a slow one (federation,
+//! webhooks) doesn't hold back the others, and none of them slow down requests.
+use pastemd::model::Paste;
+use std::future::Future;
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+use crate::database::Database;
+use crate::model::WebhookEvent;
+use crate::notify::NotifyEvent;
+
+/// How many events can wait for a listener before it starts missing them
+const CAPACITY: usize = 1024;
+
+/// The sending half of the bus (`None` until [`start`] is called)
+static BUS: OnceLock<broadcast::Sender<Event>> = OnceLock::new();
+
+/// Something that happened to a paste
+#[derive(Debug, Clone)]
+pub enum Event {
+    Created {
+        paste: Paste,
+        /// The public origin of the instance (for absolute links)
+        origin: String,
+    },
+    Edited {
+        paste: Paste,
+        origin: String,
+    },
+    Viewed {
+        paste: Paste,
+        /// Who viewed it (for `crate::analytics`)
+        view: crate::analytics::View,
+    },
+    Deleted {
+        /// The paste as it was before being deleted
+        paste: Paste,
+        origin: String,
+    },
+    Reported {
+        paste: Paste,
+        origin: String,
+        reason: String,
+    },
+}
+
+/// Send an event to every listener
+///
+/// Events published before [`start`] (or outside the server, like in `sealable admin`) are dropped.
+pub fn publish(event: Event) {
+    if let Some(bus) = BUS.get() {
+        // sending only fails when nobody is listening
+        let _ = bus.send(event);
+    }
+}
+
+/// Get a receiver of every event published from now on (`None` before [`start`])
+pub fn subscribe() -> Option<broadcast::Receiver<Event>> {
+    BUS.get().map(|bus| bus.subscribe())
+}
+
+/// Run `handler` for every event, in order, until the bus closes
+fn listen<F, Fut>(bus: &broadcast::Sender<Event>, handler: F)
+where
+    F: Fn(Event) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let mut receiver = bus.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => handler(event).await,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("An event listener fell behind and missed {missed} events.")
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
+/// Start the bus and its listeners (only the first call does anything)
+pub fn start(database: Database) {
+    if BUS.get().is_some() {
+        return;
+    }
+
+    let bus = BUS.get_or_init(|| broadcast::channel(CAPACITY).0);
+
+    // federation
+    let db = database.clone();
+    listen(bus, move |event| {
+        let db = db.clone();
+        async move {
+            if let Event::Created { paste, origin } = event {
+                crate::activitypub::publish_paste(&db, &paste, &origin).await;
+            }
+        }
+    });
+
+    // webhooks
+    let db = database.clone();
+    listen(bus, move |event| {
+        let db = db.clone();
+        async move {
+            let (webhook_event, paste, origin) = match event {
+                Event::Created { paste, origin } => (WebhookEvent::Create, paste, origin),
+                Event::Edited { paste, origin } => (WebhookEvent::Edit, paste, origin),
+                Event::Deleted { paste, origin } => (WebhookEvent::Delete, paste, origin),
+                _ => return,
+            };
+
+            crate::webhooks::fire(&db, webhook_event, &paste, &origin).await;
+        }
+    });
+
+    // chat notifications
+    listen(bus, |event| async move {
+        match event {
+            Event::Created { paste, origin } => {
+                crate::notify::notify(NotifyEvent::Create, &paste, &origin, "")
+            }
+            Event::Reported {
+                paste,
+                origin,
+                reason,
+            } => crate::notify::notify(NotifyEvent::Report, &paste, &origin, &reason),
+            _ => (),
+        }
+    });
+
+    // embedder hooks
+    listen(bus, |event| async move {
+        match event {
+            Event::Created { paste, .. } => crate::hooks::paste_created(&paste),
+            Event::Viewed { paste, .. } => crate::hooks::paste_viewed(&paste),
+            _ => (),
+        }
+    });
+
+    // view analytics
+    let db = database.clone();
+    listen(bus, move |event| {
+        let db = db.clone();
+        async move {
+            if let Event::Viewed { paste, view } = event {
+                crate::analytics::record(&db, &paste.url, view).await;
+            }
+        }
+    });
+
+    // caches
+    listen(bus, move |event| {
+        let db = database.clone();
+        async move {
+            if matches!(event, Event::Created { .. } | Event::Deleted { .. }) {
+                db.clear_instance_stats().await;
+            }
+
+            // (renders are checked against the content anyway, this just frees them sooner)
+            if let Event::Edited { paste, .. } | Event::Deleted { paste, .. } = &event {
+                crate::markdown::forget_paste(&paste.url);
+            }
+        }
+    });
+}
@@ -0,0 +1,46 @@
+//! Hooks for apps embedding sealable (registered in [`crate::AppConfig::hooks`])
+//!
+//! [`Hooks::before_render`] runs while rendering, so it should be quick. The other hooks are run
+//! in order by a background task after the request (see `crate::events`), so a slow hook delays the
+//! ones after it (but not requests).
+use pastemd::model::Paste;
+
+/// Callbacks run at points of a paste's life
+///
+/// Every method does nothing by default, so only the interesting ones need implementing.
+pub trait Hooks: Send + Sync {
+    /// Called after a paste is created (through the API, uploads, imports or email)
+    fn on_paste_created(&self, _paste: &Paste) {}
+
+    /// Called when a paste page is viewed (after the viewer passes its password check)
+    fn on_paste_viewed(&self, _paste: &Paste) {}
+
+    /// Change markdown before it's rendered (pastes, the homepage and pages)
+    ///
+    /// # Returns
+    /// * the markdown to render
+    fn before_render(&self, content: String) -> String {
+        content
+    }
+}
+
+/// Run the [`Hooks::on_paste_created`] hooks
+pub(crate) fn paste_created(paste: &Paste) {
+    for hooks in crate::config::hooks() {
+        hooks.on_paste_created(paste);
+    }
+}
+
+/// Run the [`Hooks::on_paste_viewed`] hooks
+pub(crate) fn paste_viewed(paste: &Paste) {
+    for hooks in crate::config::hooks() {
+        hooks.on_paste_viewed(paste);
+    }
+}
+
+/// Run the [`Hooks::before_render`] hooks (in the order they were registered)
+pub(crate) fn before_render(content: String) -> String {
+    crate::config::hooks()
+        .iter()
+        .fold(content, |content, hooks| hooks.before_render(content))
+}
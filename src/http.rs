@@ -0,0 +1,309 @@
+//! Outgoing HTTP requests (federation, imports, ...)
+//!
+//! A small HTTP/1.1 client over `native-tls`. Requests run on the blocking thread pool and only
+//! ever reach public addresses unless `ALLOW_PRIVATE_NETWORK` is set, since the urls requested are
+//! often provided by users (and other servers).
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long connecting, sending or receiving can take
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// The most bytes of response headers read
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+/// How many redirects are followed by [`get_following_redirects`]
+const MAX_REDIRECTS: usize = 5;
+/// The error of responses with a body over the `max_size` they were requested with
+pub const TOO_LARGE: &str = "The response is too large.";
+
+/// The response to an outgoing request
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    /// Response headers (names are lowercase)
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Get the value of a response header
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Check if the request succeeded (`2xx`)
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Check if an address is on the public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // shared address space (RFC 6598)
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local (fc00::/7) and link local (fe80::/10)
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolve the address to connect to for `url`
+///
+/// # Arguments
+/// * `trusted` - if the url comes from the instance config (so it can be private)
+fn resolve(url: &url::Url, trusted: bool) -> Result<(String, SocketAddr), String> {
+    let host = url.host_str().ok_or("The url has no host.")?.to_string();
+    let port = url.port_or_known_default().ok_or("The url has no port.")?;
+
+    let allow_private =
+        trusted || std::env::var("ALLOW_PRIVATE_NETWORK").is_ok_and(|v| v == "true");
+    let addr = (host.trim_start_matches('[').trim_end_matches(']'), port)
+        .to_socket_addrs()
+        .map_err(|_| "The host could not be resolved.")?
+        .find(|a| allow_private || is_public(a.ip()))
+        .ok_or("The host isn't on the public internet.")?;
+
+    Ok((host, addr))
+}
+
+/// Decode a `Transfer-Encoding: chunked` body
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or("Invalid chunked body.")?;
+
+        let size = std::str::from_utf8(&body[..line_end])
+            .ok()
+            .and_then(|s| usize::from_str_radix(s.split(';').next()?.trim(), 16).ok())
+            .ok_or("Invalid chunk size.")?;
+
+        body = &body[line_end + 2..];
+
+        if size == 0 {
+            return Ok(out);
+        }
+
+        if body.len() < size {
+            return Err("Truncated chunked body.".to_string());
+        }
+
+        out.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Send a request and read the response, over an established connection
+fn exchange<S: Read + Write>(
+    mut stream: S,
+    request: &[u8],
+    max_size: usize,
+) -> Result<HttpResponse, String> {
+    stream
+        .write_all(request)
+        .map_err(|_| "Failed to send the request.")?;
+
+    let mut raw = Vec::new();
+    stream
+        .take((MAX_HEAD_SIZE + max_size + 1) as u64)
+        .read_to_end(&mut raw)
+        .map_err(|_| "Failed to read the response.")?;
+
+    let head_end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or("Invalid response.")?;
+
+    let head = String::from_utf8_lossy(&raw[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+
+    let status = lines
+        .next()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse().ok())
+        .ok_or("Invalid response status.")?;
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(n, v)| (n.trim().to_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let mut response = HttpResponse {
+        status,
+        headers,
+        body: raw[head_end + 4..].to_vec(),
+    };
+
+    if response
+        .header("transfer-encoding")
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+    {
+        response.body = dechunk(&response.body)?;
+    } else if let Some(length) = response
+        .header("content-length")
+        .and_then(|v| v.parse().ok())
+    {
+        response.body.truncate(length);
+    }
+
+    if response.body.len() > max_size {
+        return Err(TOO_LARGE.to_string());
+    }
+
+    Ok(response)
+}
+
+/// Send a request (blocking)
+fn send_blocking(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+    max_size: usize,
+    trusted: bool,
+) -> Result<HttpResponse, String> {
+    let url = url::Url::parse(url).map_err(|_| "Invalid url.")?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Only http and https urls are supported.".to_string());
+    }
+
+    let (host, addr) = resolve(&url, trusted)?;
+    let host_header = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.clone(),
+    };
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+
+    // build request
+    let mut request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {host_header}\r\nUser-Agent: sealable/{}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        env!("CARGO_PKG_VERSION"),
+        body.len()
+    );
+
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+
+    request.push_str("\r\n");
+
+    let mut request = request.into_bytes();
+    request.extend_from_slice(body);
+
+    // connect
+    let stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|_| "Failed to connect.")?;
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    let _ = stream.set_write_timeout(Some(TIMEOUT));
+
+    if url.scheme() == "https" {
+        let connector = native_tls::TlsConnector::new().map_err(|_| "Failed to set up TLS.")?;
+        let stream = connector
+            .connect(&host, stream)
+            .map_err(|_| "TLS handshake failed.")?;
+
+        exchange(stream, &request, max_size)
+    } else {
+        exchange(stream, &request, max_size)
+    }
+}
+
+/// Send a request
+///
+/// # Arguments
+/// * `method` - the request method
+/// * `url` - the url to request (`http` or `https`)
+/// * `headers` - extra request headers
+/// * `body` - the request body
+/// * `max_size` - the largest response body accepted (in bytes)
+pub async fn send(
+    method: &'static str,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    max_size: usize,
+) -> Result<HttpResponse, String> {
+    tokio::task::spawn_blocking(move || {
+        send_blocking(method, &url, &headers, &body, max_size, false)
+    })
+    .await
+    .map_err(|_| "The request stopped unexpectedly.".to_string())?
+}
+
+/// Send a request to a url from the instance config (blocking, private addresses are allowed)
+///
+/// See [`send`] for the arguments.
+pub fn send_trusted_blocking(
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+    max_size: usize,
+) -> Result<HttpResponse, String> {
+    send_blocking(method, url, headers, body, max_size, true)
+}
+
+/// Send a `GET` request, following redirects
+///
+/// Every redirect is checked like the first request, so a public url can't redirect to a private
+/// address.
+///
+/// # Returns
+/// * the final url, and its response
+pub async fn get_following_redirects(
+    url: String,
+    headers: Vec<(String, String)>,
+    max_size: usize,
+) -> Result<(String, HttpResponse), String> {
+    let mut url = url;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let response = send("GET", url.clone(), headers.clone(), Vec::new(), max_size).await?;
+
+        if !matches!(response.status, 301 | 302 | 303 | 307 | 308) {
+            return Ok((url, response));
+        }
+
+        // locations can be relative to the current url
+        let location = response.header("location").ok_or("Invalid redirect.")?;
+        url = url::Url::parse(&url)
+            .and_then(|u| u.join(location))
+            .map_err(|_| "Invalid redirect.")?
+            .to_string();
+    }
+
+    Err("Too many redirects.".to_string())
+}
@@ -0,0 +1,145 @@
+//! Translations for page templates (`locales/*.json`)
+//!
+//! Locales are flat JSON objects mapping a key to its translated text. The built-in `en` locale
+//! is always available; operators can add (or override) locales by placing `{locale}.json` files
+//! in `LOCALES_DIR`.
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::{env, fs};
+
+use pastemd::model::PasteError;
+
+/// The locale used when no other locale matches
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// All loaded locales (by locale name)
+static LOCALES: LazyLock<HashMap<String, HashMap<String, String>>> = LazyLock::new(|| {
+    let mut locales = HashMap::new();
+
+    locales.insert(
+        DEFAULT_LOCALE.to_string(),
+        serde_json::from_str(include_str!("../locales/en.json")).unwrap(),
+    );
+
+    if let Ok(dir) = env::var("LOCALES_DIR") {
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+
+                if path.extension().is_none_or(|e| e != "json") {
+                    continue;
+                }
+
+                let name = match path.file_stem() {
+                    Some(n) => n.to_string_lossy().to_lowercase(),
+                    None => continue,
+                };
+
+                if let Ok(content) = fs::read_to_string(&path) {
+                    if let Ok(strings) = serde_json::from_str::<HashMap<String, String>>(&content) {
+                        locales.insert(name, strings);
+                    }
+                }
+            }
+        }
+    }
+
+    locales
+});
+
+/// Get the text of `key` in `locale` (falling back to [`DEFAULT_LOCALE`], then the key itself)
+///
+/// # Arguments
+/// * `locale` - the locale name
+/// * `key` - the translation key
+pub fn text(locale: &str, key: &str) -> String {
+    LOCALES
+        .get(locale)
+        .and_then(|l| l.get(key))
+        .or_else(|| LOCALES.get(DEFAULT_LOCALE).and_then(|l| l.get(key)))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// Get the text of `key` in the current request's locale
+///
+/// # Arguments
+/// * `key` - the translation key
+pub fn current_text(key: &str) -> String {
+    text(&crate::middleware::current_locale(), key)
+}
+
+/// Get the translated description of a [`PasteError`] in the current request's locale
+///
+/// Error pages are rendered with this, so the error is logged here too.
+pub fn error_text(e: &PasteError) -> String {
+    let key = match e {
+        PasteError::PasswordIncorrect => "error.password_incorrect",
+        PasteError::AlreadyExists => "error.already_exists",
+        PasteError::ValueError => "error.value_error",
+        PasteError::NotFound => "error.not_found",
+        _ => "error.other",
+    };
+
+    tracing::warn!(error = key, "Showing an error page.");
+
+    if matches!(e, PasteError::Other) {
+        crate::sentry::capture_message("error", "Showing an error page for an unspecified error.");
+    }
+    current_text(key)
+}
+
+/// Pick the best available locale for a request
+///
+/// The `lang` cookie (`cookie`) wins if it names an available locale, otherwise the
+/// `Accept-Language` header is negotiated (by quality, then by primary language subtag).
+///
+/// # Arguments
+/// * `cookie` - the value of the `lang` cookie
+/// * `accept_language` - the value of the `Accept-Language` header
+pub fn negotiate(cookie: Option<&str>, accept_language: Option<&str>) -> String {
+    if let Some(c) = cookie {
+        let c = c.to_lowercase();
+
+        if LOCALES.contains_key(&c) {
+            return c;
+        }
+    }
+
+    if let Some(header) = accept_language {
+        let mut wanted: Vec<(String, f32)> = header
+            .split(',')
+            .filter_map(|part| {
+                let mut parts = part.trim().split(';');
+                let tag = parts.next()?.trim().to_lowercase();
+
+                if tag.is_empty() || (tag == "*") {
+                    return None;
+                }
+
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+
+                Some((tag, quality))
+            })
+            .collect();
+
+        wanted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        for (tag, _) in wanted {
+            if LOCALES.contains_key(&tag) {
+                return tag;
+            }
+
+            if let Some(primary) = tag.split('-').next() {
+                if LOCALES.contains_key(primary) {
+                    return primary.to_string();
+                }
+            }
+        }
+    }
+
+    DEFAULT_LOCALE.to_string()
+}
@@ -0,0 +1,136 @@
+//! Background jobs for work too slow to finish inside a request (exports, deliveries)
+//!
+//! Jobs are kept in memory and forgotten after [`JOB_LIFETIME`], so their results are only
+//! downloadable for a while and don't survive restarts.
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+/// How long a job (and its result) is kept around after being started (in milliseconds)
+const JOB_LIFETIME: u128 = 1000 * 60 * 30;
+/// How many jobs can be running at the same time
+const MAX_PENDING_JOBS: usize = 16;
+/// Length of job ids (ids are the only thing protecting a job's result)
+const JOB_ID_LENGTH: usize = 32;
+/// How many times a retried task is attempted before giving up
+const MAX_ATTEMPTS: u32 = 5;
+/// How long to wait before the first retry (doubled after every failed attempt)
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// A file produced by a job
+#[derive(Debug, Clone)]
+pub struct JobOutput {
+    /// The `Content-Type` of the file
+    pub content_type: &'static str,
+    /// The name the file is downloaded as
+    pub file_name: String,
+    pub data: Vec<u8>,
+}
+
+/// The state of a job
+#[derive(Debug, Clone)]
+pub enum JobStatus {
+    Pending,
+    Done(JobOutput),
+    Failed(String),
+}
+
+struct Job {
+    status: JobStatus,
+    started: u128,
+}
+
+static JOBS: LazyLock<Mutex<HashMap<String, Job>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Start a job on the blocking thread pool
+///
+/// # Returns
+/// * the id of the job, `None` if too many jobs are already running
+pub fn spawn<F>(work: F) -> Option<String>
+where
+    F: FnOnce() -> Result<JobOutput, String> + Send + 'static,
+{
+    let now = dorsal::utility::unix_epoch_timestamp();
+    let mut jobs = JOBS.lock().unwrap();
+
+    // forget expired jobs
+    jobs.retain(|_, j| now - j.started < JOB_LIFETIME);
+
+    if jobs
+        .values()
+        .filter(|j| matches!(j.status, JobStatus::Pending))
+        .count()
+        >= MAX_PENDING_JOBS
+    {
+        return None;
+    }
+
+    let id: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(JOB_ID_LENGTH)
+        .map(char::from)
+        .collect();
+
+    jobs.insert(
+        id.clone(),
+        Job {
+            status: JobStatus::Pending,
+            started: now,
+        },
+    );
+
+    let job_id = id.clone();
+    tokio::spawn(async move {
+        let status = match tokio::task::spawn_blocking(work).await {
+            Ok(Ok(output)) => JobStatus::Done(output),
+            Ok(Err(e)) => JobStatus::Failed(e),
+            Err(_) => JobStatus::Failed("The job stopped unexpectedly.".to_string()),
+        };
+
+        if let Some(job) = JOBS.lock().unwrap().get_mut(&job_id) {
+            job.status = status;
+        }
+    });
+
+    Some(id)
+}
+
+/// Run a task in the background, retrying it with exponential backoff while it fails
+///
+/// Tasks don't have a result to download, so they aren't tracked like other jobs (and are also
+/// lost on restart).
+pub fn spawn_with_retries<F, Fut>(task: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), String>> + Send,
+{
+    tokio::spawn(async move {
+        let mut delay = RETRY_DELAY;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            if task().await.is_ok() {
+                return;
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    });
+}
+
+/// Get the status of a job
+pub fn status(id: &str) -> Option<JobStatus> {
+    let now = dorsal::utility::unix_epoch_timestamp();
+
+    JOBS.lock()
+        .unwrap()
+        .get(id)
+        .filter(|j| now - j.started < JOB_LIFETIME)
+        .map(|j| j.status.clone())
+}
@@ -0,0 +1,282 @@
+//! The (programming) language of a paste's content
+//!
+//! Pastes are markdown unless their `language` metadata says otherwise. Pastes in another language
+//! are shown as one highlighted code block, and their raw content is sent with a matching
+//! `Content-Type`. The language is guessed from the content when a paste is saved without one.
+use regex::{Regex, RegexBuilder};
+use std::sync::LazyLock;
+
+/// A language pastes can be written in
+pub struct Language {
+    /// The name stored in the metadata (also the highlight.js name)
+    pub name: &'static str,
+    /// The name shown to people
+    pub label: &'static str,
+    /// The `Content-Type` of the raw content
+    pub content_type: &'static str,
+}
+
+/// The language of pastes without one
+pub const DEFAULT: &str = "markdown";
+
+/// Everything [`Language::name`] can be
+///
+/// Content browsers would run (HTML, SVG, XML, JavaScript) is still sent as `text/plain`, it's
+/// served from the instance's own origin.
+pub const LANGUAGES: [Language; 20] = [
+    // (browsers download `text/markdown` instead of showing it)
+    lang("markdown", "Markdown", "text/plain"),
+    lang("plaintext", "Plain text", "text/plain"),
+    lang("bash", "Shell", "text/x-shellscript"),
+    lang("c", "C", "text/x-c"),
+    lang("cpp", "C++", "text/x-c++"),
+    lang("css", "CSS", "text/css"),
+    lang("diff", "Diff", "text/x-diff"),
+    lang("go", "Go", "text/x-go"),
+    lang("html", "HTML", "text/plain"),
+    lang("java", "Java", "text/x-java"),
+    lang("javascript", "JavaScript", "text/plain"),
+    lang("json", "JSON", "application/json"),
+    lang("python", "Python", "text/x-python"),
+    lang("ruby", "Ruby", "text/x-ruby"),
+    lang("rust", "Rust", "text/x-rust"),
+    lang("sql", "SQL", "application/sql"),
+    lang("toml", "TOML", "application/toml"),
+    lang("typescript", "TypeScript", "text/plain"),
+    lang("xml", "XML", "text/plain"),
+    lang("yaml", "YAML", "application/yaml"),
+];
+
+const fn lang(name: &'static str, label: &'static str, content_type: &'static str) -> Language {
+    Language {
+        name,
+        label,
+        content_type,
+    }
+}
+
+/// Get a language by its name (`None` if it isn't one of [`LANGUAGES`])
+pub fn get(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|l| l.name == name)
+}
+
+/// File extensions of the languages (the language of a paste's files is picked from their name)
+const EXTENSIONS: [(&str, &str); 31] = [
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("txt", "plaintext"),
+    ("sh", "bash"),
+    ("bash", "bash"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cpp", "cpp"),
+    ("cc", "cpp"),
+    ("hpp", "cpp"),
+    ("css", "css"),
+    ("diff", "diff"),
+    ("patch", "diff"),
+    ("go", "go"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("java", "java"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("json", "json"),
+    ("py", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("sql", "sql"),
+    ("toml", "toml"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("xml", "xml"),
+    ("svg", "xml"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+];
+
+/// Get the language of a file: from its extension, or guessed from its content (see [`detect`])
+pub fn for_file(name: &str, content: &str) -> &'static str {
+    let extension = name.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+
+    EXTENSIONS
+        .iter()
+        .find(|(e, _)| Some(*e) == extension.as_deref())
+        .map_or_else(|| detect(content), |(_, name)| *name)
+}
+
+/// Check if content in a language is shown as code (instead of rendered as markdown)
+pub fn is_code(name: &str) -> bool {
+    !name.is_empty() && (name != DEFAULT) && get(name).is_some()
+}
+
+/// Get the `Content-Type` raw content in a language is sent with
+pub fn content_type(name: &str) -> String {
+    let content_type = get(name).map_or("text/plain", |l| l.content_type);
+    format!("{content_type}; charset=utf-8")
+}
+
+/// Get the markdown showing `content` in a language: the content itself for markdown, a fenced
+/// code block for everything else
+pub fn as_markdown(content: &str, name: &str) -> String {
+    if !is_code(name) {
+        return content.to_string();
+    }
+
+    // (the fence has to be longer than any run of backticks in the content)
+    let mut longest = 0;
+    let mut run = 0;
+
+    for c in content.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}{name}\n{content}\n{fence}")
+}
+
+/// How much of the content is looked at by [`detect`] (in bytes)
+const DETECT_LENGTH: usize = 16 * 1024;
+
+/// Patterns that suggest content is in a language, each match counting once
+static PATTERNS: LazyLock<Vec<(&'static str, Regex)>> = LazyLock::new(|| {
+    let patterns: [(&str, &str); 14] = [
+        (
+            "markdown",
+            r"^#{1,6} \S|^\s*[-*+] \S|^\s*\d+\. \S|\[[^\]]+\]\([^)]+\)|^> |\*\*\S|^```",
+        ),
+        (
+            "rust",
+            r"^\s*(pub(\(crate\))? )?(fn|struct|enum|impl|trait|mod) \w|^\s*use \w+::|\blet mut \b|^\s*#\[\w+|->\s*(Self|Result<|Option<)|\w!\(",
+        ),
+        (
+            "python",
+            r"^\s*def \w+\(.*\):\s*$|^\s*(from \w+(\.\w+)* )?import \w|^\s*(elif|except|class \w+(\(.*\))?)\b.*:\s*$|\bself\.\w|__name__|\bNone\b|\bprint\(",
+        ),
+        (
+            "javascript",
+            r"\bfunction\s*\w*\(|^\s*(const|let|var) \w+ = |=>|\bconsole\.\w+\(|\brequire\(|^\s*export (default )?\w|\bdocument\.\w|===",
+        ),
+        (
+            "typescript",
+            r"^\s*(export )?(interface|type) \w+|:\s*(string|number|boolean|void|any)\b|^\s*import .* from '",
+        ),
+        (
+            "go",
+            r"^package \w+|^\s*func (\(\w+ \*?\w+\) )?\w+\(|:= |^import \(|\bfmt\.\w",
+        ),
+        (
+            "c",
+            r"^#include <\w+\.h>|\bint main\(|\bprintf\(|\bmalloc\(|^#define \w",
+        ),
+        (
+            "cpp",
+            r"^#include <\w+>|\bstd::\w|\bcout\b|^\s*(class|namespace|template) \w|\bnullptr\b",
+        ),
+        (
+            "java",
+            r"^\s*(public|private|protected) (static )?(final )?(class|void|\w+ \w+\()|\bSystem\.out\.|^import java\.|@Override",
+        ),
+        (
+            "sql",
+            r"\bselect\b.+\bfrom\b|\binsert into\b|\bcreate table\b|\bupdate \w+ set\b|\bwhere \w+ =|\bdelete from\b",
+        ),
+        (
+            "bash",
+            r"^\s*(if|while|for) .*; (then|do)\b|^\s*(fi|done|esac)\s*$|^\s*echo |\$\{\w+|\$\(\w|^\s*(sudo|apt|cd|export) ",
+        ),
+        (
+            "css",
+            r"^\s*[.#]?[\w-]+( [.#]?[\w-]+)*\s*\{\s*$|^\s*[\w-]+: [^;]+;\s*$|@media\b",
+        ),
+        (
+            "ruby",
+            r"^\s*def \w+[?!]?\s*$|^\s*end\s*$|^\s*require '|\bputs\b|\.each do\b|^\s*(module|class) [A-Z]\w*\s*$",
+        ),
+        ("yaml", r"^[\w-]+:(\s+\S.*)?$|^\s+- \w[\w ]*:\s|^---\s*$"),
+    ];
+
+    patterns
+        .into_iter()
+        .map(|(name, pattern)| {
+            let regex = RegexBuilder::new(pattern)
+                .multi_line(true)
+                .case_insensitive(name == "sql")
+                .build()
+                .unwrap();
+
+            (name, regex)
+        })
+        .collect()
+});
+
+/// Guess the language of `content` (see [`LANGUAGES`])
+///
+/// Formats with a recognizable start (JSON, HTML, XML, diffs, TOML and scripts starting with
+/// `#!`) are checked first. Anything else is the language whose patterns match the most lines,
+/// as long as it beats markdown by a margin, since prose trips up some of them.
+pub fn detect(content: &str) -> &'static str {
+    let mut end = content.len().min(DETECT_LENGTH);
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let content = &content[..end];
+    let trimmed = content.trim_start();
+    let lowercase = trimmed.chars().take(64).collect::<String>().to_lowercase();
+
+    if let Some(shebang) = trimmed.lines().next().filter(|l| l.starts_with("#!")) {
+        for (interpreter, name) in [
+            ("python", "python"),
+            ("node", "javascript"),
+            ("ruby", "ruby"),
+            ("sh", "bash"),
+        ] {
+            if shebang.contains(interpreter) {
+                return name;
+            }
+        }
+    }
+
+    // (only complete documents parse, so this is only for content under `DETECT_LENGTH`)
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(content).is_ok()
+    {
+        return "json";
+    }
+
+    if lowercase.starts_with("<!doctype html") || lowercase.starts_with("<html") {
+        return "html";
+    }
+
+    if lowercase.starts_with("<?xml") {
+        return "xml";
+    }
+
+    if trimmed.starts_with("diff --git")
+        || (trimmed.starts_with("--- ") && content.contains("\n+++ ") && content.contains("\n@@ "))
+    {
+        return "diff";
+    }
+
+    if trimmed.starts_with('[')
+        && trimmed.lines().filter(|l| l.contains(" = ")).count() >= 2
+        && !content.contains("](")
+    {
+        return "toml";
+    }
+
+    let score = |regex: &Regex| regex.find_iter(content).count();
+    let markdown = score(&PATTERNS[0].1);
+
+    let best = PATTERNS[1..]
+        .iter()
+        .map(|(name, regex)| (*name, score(regex)))
+        .max_by_key(|(_, score)| *score);
+
+    match best {
+        Some((name, score)) if (score >= 3) && (score > markdown * 2) => name,
+        _ => DEFAULT,
+    }
+}
@@ -0,0 +1,58 @@
+//! Layout overrides loaded from `TEMPLATES_DIR` (see [`crate::AppConfig::templates_dir`])
+//!
+//! Pages are compiled in, but the parts of the layout instances usually change (extra `<head>`
+//! tags, a header, the footer links) can be replaced with HTML files without recompiling.
+//! Missing files keep the compiled-in layout.
+use std::path::Path;
+use std::sync::RwLock;
+
+/// The loaded overrides
+static OVERRIDES: RwLock<Overrides> = RwLock::new(Overrides {
+    head: None,
+    header: None,
+    footer: None,
+});
+
+/// HTML replacing parts of `base.html`
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// `head.html`, added at the end of `<head>` (after the stylesheet, so it can override it)
+    pub head: Option<String>,
+    /// `header.html`, shown above the content of every page
+    pub header: Option<String>,
+    /// `footer.html`, replacing the footer links
+    pub footer: Option<String>,
+}
+
+/// Read an override file (`None` if it doesn't exist)
+fn read(dir: &Path, name: &str) -> Option<String> {
+    let path = dir.join(name);
+
+    match std::fs::read_to_string(&path) {
+        Ok(s) => Some(s),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to read template override {path:?}.");
+            None
+        }
+    }
+}
+
+/// Load the overrides in `dir` (or clear them if `dir` is `None`)
+pub fn load(dir: Option<&Path>) {
+    let overrides = match dir {
+        Some(dir) => Overrides {
+            head: read(dir, "head.html"),
+            header: read(dir, "header.html"),
+            footer: read(dir, "footer.html"),
+        },
+        None => Overrides::default(),
+    };
+
+    *OVERRIDES.write().unwrap() = overrides;
+}
+
+/// Get the loaded overrides
+pub fn get() -> Overrides {
+    OVERRIDES.read().unwrap().clone()
+}
@@ -0,0 +1,157 @@
+//! 🦭 Sealable, a markdown pastebin
+//!
+//! The whole app is built from an [`AppConfig`] by [`routes`], so it can be mounted inside other
+//! axum apps (the `sealable` binary is one of them). Authentication routes (`/star`) and the
+//! pongo dashboard aren't included, and are mounted next to it by the binary.
+use axum::Router;
+
+mod access;
+mod activitypub;
+mod analytics;
+mod api;
+mod archive;
+mod assets;
+mod attachments;
+mod base;
+mod card;
+mod cli;
+mod collab;
+mod compression;
+mod config;
+pub mod database;
+mod dev;
+mod events;
+mod hooks;
+mod http;
+mod i18n;
+mod jobs;
+mod language;
+mod layout;
+mod listeners;
+mod mail;
+mod markdown;
+mod metrics;
+mod middleware;
+mod mirror;
+pub mod model;
+mod multipart;
+mod notify;
+mod pages;
+mod pdf;
+mod raster;
+mod rate_limit;
+mod scan;
+mod schema;
+mod screenshot;
+mod sentry;
+mod server;
+mod shutdown;
+mod signing;
+mod slug;
+mod telemetry;
+mod tls;
+mod utility;
+mod views;
+mod webhooks;
+mod ws;
+
+pub use cli::run as run_command;
+pub use config::{
+    AppConfig, AttachmentStorage, BodyLimits, IpNetwork, RateLimit, RateLimits, RenderOptions,
+    RequestLimits, S3Bucket, ScanAction,
+};
+pub use database::Database;
+pub use hooks::Hooks;
+pub use listeners::{from_env as listeners_from_env, serve, Listener};
+pub use mail::serve as serve_mail;
+pub use middleware::{canonical_host, frame_options, security_headers};
+pub use pages::extra_starstraw_routes;
+pub use scan::{ClamAv, HttpScanner, Scanner, Verdict};
+pub use sentry::init as init_sentry;
+#[cfg(unix)]
+pub use server::serve_unix;
+pub use shutdown::{finish as shutdown, signal as shutdown_signal};
+pub use telemetry::init as init_telemetry;
+pub use tls::serve as serve_tls;
+
+/// Build the app's router (pages, the API and federation, with their middleware)
+pub fn routes(config: AppConfig) -> Router {
+    config.install();
+    layout::load(config.templates_dir.as_deref());
+    let database = config.database.clone();
+    events::start(database.clone());
+    views::start(database.clone());
+
+    let mut app = Router::new()
+        .merge(pages::routes(&config))
+        .nest("/api", api::routes(&config));
+
+    if config.activitypub {
+        app = app.merge(activitypub::routes(database.clone()));
+    }
+
+    app = app.layer(tower_http::catch_panic::CatchPanicLayer::custom(
+        sentry::panic_response,
+    ));
+
+    let permits = match config.request_limits.max_concurrent {
+        0 => None,
+        max => Some(std::sync::Arc::new(tokio::sync::Semaphore::new(max))),
+    };
+
+    app = app.layer(axum::middleware::from_fn_with_state(
+        permits,
+        middleware::load_shed,
+    ));
+
+    if config.metrics {
+        app = app
+            .route(
+                "/metrics",
+                axum::routing::get(metrics::metrics_request).with_state(database.clone()),
+            )
+            .layer(axum::middleware::from_fn(metrics::layer));
+    }
+
+    if sentry::enabled() {
+        app = app.layer(axum::middleware::from_fn(sentry::layer));
+    }
+
+    app = app.layer(axum::middleware::from_fn(telemetry::layer));
+
+    if config.compression {
+        app = app.layer(axum::middleware::from_fn(compression::layer));
+    }
+
+    if config.dev {
+        dev::watch(config.templates_dir.clone(), config.static_dir.clone());
+        app = app
+            .route("/_dev/version", axum::routing::get(dev::version_request))
+            .layer(axum::middleware::from_fn(dev::no_store));
+    }
+
+    app.layer(axum::middleware::from_fn_with_state(
+        database.clone(),
+        middleware::renamed,
+    ))
+    .layer(axum::middleware::from_fn_with_state(
+        database.clone(),
+        middleware::maintenance,
+    ))
+    .layer(axum::middleware::from_fn(middleware::read_only))
+    .layer(axum::middleware::from_fn(middleware::body_limit))
+    // (`body_limit` replaces axum's limit of 2 MB)
+    .layer(axum::extract::DefaultBodyLimit::disable())
+    // (before anything reads the body)
+    .layer(axum::middleware::from_fn_with_state(
+        std::sync::Arc::new(rate_limit::RateLimiter::new(config.rate_limits)),
+        rate_limit::layer,
+    ))
+    .layer(axum::middleware::from_fn(middleware::security_headers))
+    .layer(axum::middleware::from_fn(middleware::csrf))
+    .layer(axum::middleware::from_fn_with_state(
+        database,
+        middleware::preferences,
+    ))
+    .layer(axum::middleware::from_fn(middleware::request_id))
+}
@@ -0,0 +1,260 @@
+//! Listening on several addresses at once (`LISTEN`)
+//!
+//! Every listener serves the same app, so an instance can take HTTPS from the internet, HTTP from
+//! a local proxy and requests on a unix socket at the same time. `redirect://` listeners don't
+//! serve the app at all, they send every request to the HTTPS version of the instance (for port
+//! 80 next to port 443).
+use std::env;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Where the server listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listener {
+    /// HTTP (`127.0.0.1:8080`, `http://127.0.0.1:8080`)
+    Http(SocketAddr),
+    /// HTTPS with `TLS_CERT` and `TLS_KEY` (`https://0.0.0.0:443`)
+    Https(SocketAddr),
+    /// HTTP redirecting every request to HTTPS (`redirect://0.0.0.0:80`)
+    Redirect(SocketAddr),
+    /// A unix socket, with the permissions of `UNIX_SOCKET_MODE` (`unix:/run/sealable.sock`)
+    Unix(PathBuf),
+}
+
+impl FromStr for Listener {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let addr = |a: &str| {
+            a.parse::<SocketAddr>()
+                .map_err(|_| format!("Invalid listener address: {s:?} (expected host:port)"))
+        };
+
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(format!("Invalid listener: {s:?} (expected unix:/path)"));
+            }
+
+            return Ok(Self::Unix(path.into()));
+        }
+
+        match s.split_once("://") {
+            Some(("http", a)) => addr(a).map(Self::Http),
+            Some(("https", a)) => addr(a).map(Self::Https),
+            Some(("redirect", a)) => addr(a).map(Self::Redirect),
+            Some((scheme, _)) => Err(format!("Unknown listener scheme: {scheme:?}")),
+            None => addr(s).map(Self::Http),
+        }
+    }
+}
+
+impl std::fmt::Display for Listener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http(addr) => write!(f, "http://{addr}"),
+            Self::Https(addr) => write!(f, "https://{addr}"),
+            Self::Redirect(addr) => write!(f, "redirect://{addr}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Get the listeners from `LISTEN` (comma-separated)
+///
+/// Without `LISTEN`, the server listens on `UNIX_SOCKET` if it's set, otherwise on `HOST` and
+/// `PORT` (over HTTPS when `TLS_CERT` and `TLS_KEY` are set).
+pub fn from_env() -> Result<Vec<Listener>, String> {
+    let tls = env::var("TLS_CERT").is_ok() && env::var("TLS_KEY").is_ok();
+
+    let listeners = match env::var("LISTEN") {
+        Ok(listen) if !listen.trim().is_empty() => listen
+            .split(',')
+            .filter(|l| !l.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Listener>, String>>()?,
+        _ => match env::var("UNIX_SOCKET") {
+            Ok(path) => vec![Listener::Unix(path.into())],
+            Err(_) => {
+                let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+                let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
+                let addr: Listener = format!("{host}:{port}").parse()?;
+
+                match (addr, tls) {
+                    (Listener::Http(addr), true) => vec![Listener::Https(addr)],
+                    (addr, _) => vec![addr],
+                }
+            }
+        },
+    };
+
+    if !tls && listeners.iter().any(|l| matches!(l, Listener::Https(_))) {
+        return Err("HTTPS listeners need TLS_CERT and TLS_KEY".to_string());
+    }
+
+    #[cfg(not(unix))]
+    if listeners.iter().any(|l| matches!(l, Listener::Unix(_))) {
+        return Err("Unix sockets aren't supported on this platform".to_string());
+    }
+
+    Ok(listeners)
+}
+
+/// Redirect a request to HTTPS: to `PUBLIC_URL` when it's set, otherwise to the requested host
+///
+/// # Arguments
+/// * `https_port` - the port HTTPS is served on, added to the host when it isn't `443`
+fn redirect(req: &Request, https_port: Option<u16>) -> Response {
+    let public_url = crate::base::BaseStore::new().public_url;
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+
+    let location = match public_url.is_empty() {
+        false => format!("{}{path}", public_url.trim_end_matches('/')),
+        true => {
+            let Some(host) = req
+                .headers()
+                .get(header::HOST)
+                .and_then(|h| h.to_str().ok())
+            else {
+                return StatusCode::BAD_REQUEST.into_response();
+            };
+
+            // (strip the port, keeping IPv6 addresses whole)
+            let host = match host.rsplit_once(':') {
+                Some((name, port)) if !port.contains(']') => name,
+                _ => host,
+            };
+
+            match https_port {
+                Some(port) if port != 443 => format!("https://{host}:{port}{path}"),
+                _ => format!("https://{host}{path}"),
+            }
+        }
+    };
+
+    match HeaderValue::from_str(&location) {
+        Ok(location) => (
+            StatusCode::PERMANENT_REDIRECT,
+            [(header::LOCATION, location)],
+        )
+            .into_response(),
+        Err(_) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+/// Serve `app` on every listener until `shutdown` completes (then wait for open connections)
+pub async fn serve(
+    listeners: Vec<Listener>,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), String> {
+    let (stop, stopped) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown.await;
+        let _ = stop.send(true);
+    });
+
+    let https_port = listeners.iter().find_map(|l| match l {
+        Listener::Https(addr) => Some(addr.port()),
+        _ => None,
+    });
+
+    // (every address is bound before anything is served, so a bad one fails the start)
+    let mut bound = Vec::with_capacity(listeners.len());
+
+    for listener in listeners {
+        let tcp = match &listener {
+            Listener::Http(addr) | Listener::Https(addr) | Listener::Redirect(addr) => Some(
+                TcpListener::bind(addr)
+                    .await
+                    .map_err(|e| format!("Failed to listen on {addr}: {e}"))?,
+            ),
+            Listener::Unix(_) => None,
+        };
+
+        bound.push((listener, tcp));
+    }
+
+    let mut served = JoinSet::new();
+
+    for (listener, tcp) in bound {
+        let mut stopped = stopped.clone();
+        let shutdown = async move {
+            let _ = stopped.wait_for(|stop| *stop).await;
+        };
+
+        tracing::info!("Starting server at {listener}!");
+        let app = app.clone();
+
+        match (listener, tcp) {
+            (Listener::Http(_), Some(tcp)) => {
+                served.spawn(async move {
+                    axum::serve(tcp, app.into_make_service_with_connect_info::<SocketAddr>())
+                        .with_graceful_shutdown(shutdown)
+                        .await
+                        .map_err(|e| e.to_string())
+                });
+            }
+            (Listener::Redirect(_), Some(tcp)) => {
+                let app = Router::new()
+                    .fallback(move |req: Request| async move { redirect(&req, https_port) });
+
+                served.spawn(async move {
+                    axum::serve(tcp, app)
+                        .with_graceful_shutdown(shutdown)
+                        .await
+                        .map_err(|e| e.to_string())
+                });
+            }
+            (Listener::Https(_), Some(tcp)) => {
+                let cert = env::var("TLS_CERT").unwrap_or_default();
+                let key = env::var("TLS_KEY").unwrap_or_default();
+
+                served.spawn(crate::tls::serve(
+                    tcp,
+                    app,
+                    cert.into(),
+                    key.into(),
+                    shutdown,
+                ));
+            }
+            #[cfg(unix)]
+            (Listener::Unix(path), _) => {
+                let mode = env::var("UNIX_SOCKET_MODE")
+                    .ok()
+                    .and_then(|m| u32::from_str_radix(&m, 8).ok())
+                    .unwrap_or(0o660);
+
+                served.spawn(crate::server::serve_unix(path, mode, app, shutdown));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // (returning drops the set, which stops the other listeners)
+    while let Some(result) = served.join_next().await {
+        match result {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => return Err(e),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,648 @@
+//! Email-to-paste gateway (`MAIL_LISTEN`)
+//!
+//! A small SMTP server that turns every email it receives into a paste: the subject becomes the
+//! title and the plain text body the content. It doesn't do TLS or authentication itself, so it's
+//! meant to sit behind the instance's mail server, and `MAIL_SENDERS` should be set if it can be
+//! reached directly.
+//!
+//! The link to the new paste is returned in the reply to `DATA`, and mailed back to the sender
+//! (with the paste's edit password) through `MAIL_RELAY` if it's set.
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use pastemd::model::{PasteCreate, PasteMetadata};
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+use crate::base::BaseStore;
+use crate::database::Database;
+
+/// The largest email accepted (in bytes)
+const MAX_MAIL_SIZE: usize = 1024 * 1024;
+/// The longest line accepted (in bytes)
+const MAX_LINE_LENGTH: u64 = 64 * 1024;
+/// How long a client can stay silent before being disconnected
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A received email
+struct Mail {
+    subject: String,
+    message_id: String,
+    /// If the email was sent automatically (and shouldn't be replied to)
+    auto_submitted: bool,
+    body: String,
+}
+
+/// Receive mail at `MAIL_LISTEN` until the server stops (does nothing if it isn't set)
+pub async fn serve(database: Database) {
+    let addr = BaseStore::new().mail_listen;
+    if addr.is_empty() {
+        return;
+    }
+
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to receive mail at {addr}.");
+            return;
+        }
+    };
+
+    tracing::info!("Receiving mail at {addr}!");
+
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(handle(database.clone(), stream));
+    }
+}
+
+/// Read a line (without its line ending), or `None` if the client is gone or too slow
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Option<String> {
+    let mut line = Vec::new();
+
+    match tokio::time::timeout(
+        TIMEOUT,
+        reader.take(MAX_LINE_LENGTH).read_until(b'\n', &mut line),
+    )
+    .await
+    {
+        Ok(Ok(n)) if n > 0 => (),
+        _ => return None,
+    }
+
+    while line.last().is_some_and(|c| (*c == b'\n') | (*c == b'\r')) {
+        line.pop();
+    }
+
+    Some(String::from_utf8_lossy(&line).to_string())
+}
+
+/// Get the address out of a `MAIL FROM:<address>` (or `RCPT TO:`) argument
+fn parse_path(argument: &str) -> Option<String> {
+    let (_, path) = argument.split_once(':')?;
+    let path = path.trim();
+    let path = path.split_once('>').map(|(p, _)| p).unwrap_or(path);
+
+    Some(path.trim_start_matches('<').trim().to_lowercase())
+}
+
+/// Check if mail from `sender` is accepted (see `MAIL_SENDERS`)
+fn is_allowed_sender(base: &BaseStore, sender: &str) -> bool {
+    base.mail_senders.is_empty()
+        || base
+            .mail_senders
+            .iter()
+            .any(|s| (s == sender) || (s.starts_with('@') && sender.ends_with(s.as_str())))
+}
+
+/// Talk SMTP with one client
+async fn handle(database: Database, stream: tokio::net::TcpStream) {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+
+    macro_rules! reply {
+        ($($arg:tt)*) => {
+            if write
+                .write_all(format!("{}\r\n", format!($($arg)*)).as_bytes())
+                .await
+                .is_err()
+            {
+                return;
+            }
+        };
+    }
+
+    let base = BaseStore::new();
+    reply!("220 {} ESMTP", base.site_name);
+
+    let mut sender: Option<String> = None;
+    let mut has_recipient = false;
+
+    while let Some(line) = read_line(&mut reader).await {
+        let (verb, argument) = line.split_once(' ').unwrap_or((&line, ""));
+
+        match verb.to_uppercase().as_str() {
+            "HELO" => reply!("250 Hello"),
+            "EHLO" => reply!("250-Hello\r\n250-SIZE {MAX_MAIL_SIZE}\r\n250 8BITMIME"),
+            "MAIL" => match parse_path(argument) {
+                Some(address) if is_allowed_sender(&base, &address) => {
+                    sender = Some(address);
+                    has_recipient = false;
+                    reply!("250 OK");
+                }
+                Some(_) => reply!("550 Mail from this sender isn't accepted"),
+                None => reply!("501 Invalid sender"),
+            },
+            "RCPT" => {
+                if sender.is_none() {
+                    reply!("503 Send MAIL first");
+                } else if parse_path(argument).is_none() {
+                    reply!("501 Invalid recipient");
+                } else {
+                    has_recipient = true;
+                    reply!("250 OK");
+                }
+            }
+            "DATA" => {
+                let Some(from) = sender.take().filter(|_| has_recipient) else {
+                    reply!("503 Send MAIL and RCPT first");
+                    continue;
+                };
+
+                has_recipient = false;
+                reply!("354 End data with <CR><LF>.<CR><LF>");
+
+                let mut data = String::new();
+                let mut too_large = false;
+
+                loop {
+                    let Some(line) = read_line(&mut reader).await else {
+                        return;
+                    };
+
+                    if line == "." {
+                        break;
+                    }
+
+                    // lines starting with a dot are sent with an extra one
+                    let line = line.strip_prefix('.').unwrap_or(&line);
+
+                    if data.len() + line.len() > MAX_MAIL_SIZE {
+                        too_large = true;
+                    } else if !too_large {
+                        data.push_str(line);
+                        data.push('\n');
+                    }
+                }
+
+                if too_large {
+                    reply!("552 The email is too large");
+                    continue;
+                }
+
+                match create_paste(&database, &from, &data).await {
+                    Ok(link) => reply!("250 Stored as {link}"),
+                    Err(e) => reply!("554 {e}"),
+                }
+            }
+            "RSET" => {
+                sender = None;
+                has_recipient = false;
+                reply!("250 OK");
+            }
+            "NOOP" => reply!("250 OK"),
+            "QUIT" => {
+                reply!("221 Bye");
+                return;
+            }
+            _ => reply!("502 Command not implemented"),
+        }
+    }
+}
+
+/// Split a header block and body, and unfold the headers
+///
+/// # Returns
+/// * `(headers (lowercase names), body)`
+fn split_message(message: &str) -> (Vec<(String, String)>, &str) {
+    let (head, body) = message.split_once("\n\n").unwrap_or((message, ""));
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            // continuation of the previous header
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    (headers, body)
+}
+
+/// Get the value of a header
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Get a parameter of a header value (`boundary` in `multipart/mixed; boundary="abc"`)
+fn header_param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|p| {
+        let (n, v) = p.split_once('=')?;
+        (n.trim().to_lowercase() == name).then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Decode a quoted-printable string
+fn decode_quoted_printable(input: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for line in input.lines() {
+        let (line, soft_break) = match line.trim_end().strip_suffix('=') {
+            Some(l) => (l, true),
+            None => (line, false),
+        };
+
+        let bytes = line.as_bytes();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+            match (bytes[i], hex) {
+                (b'=', Some(b)) => {
+                    out.push(b);
+                    i += 3;
+                }
+                (c, _) => {
+                    out.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        if !soft_break {
+            out.push(b'\n');
+        }
+    }
+
+    out
+}
+
+/// Decode bytes in the given charset (only UTF-8 and Latin-1 are really supported)
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.to_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "windows-1252" => bytes.iter().map(|b| *b as char).collect(),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// Decode the encoded words (`=?utf-8?B?...?=`) in a header value
+fn decode_header(value: &str) -> String {
+    let words = Regex::new(r"=\?([^?]+)\?([BbQq])\?([^?]*)\?=").unwrap();
+    // whitespace between two encoded words isn't part of the text
+    let value = Regex::new(r"\?=\s+=\?").unwrap().replace_all(value, "?==?");
+
+    words
+        .replace_all(&value, |c: &regex::Captures| {
+            let bytes = if c[2].eq_ignore_ascii_case("b") {
+                BASE64.decode(&c[3]).unwrap_or_default()
+            } else {
+                decode_quoted_printable(&c[3].replace('_', " "))
+                    .strip_suffix(b"\n")
+                    .unwrap_or_default()
+                    .to_vec()
+            };
+
+            decode_charset(&bytes, &c[1])
+        })
+        .to_string()
+}
+
+/// Get the plain text out of a (MIME) message
+///
+/// The first `text/plain` part of multipart messages is used.
+fn plain_text(headers: &[(String, String)], body: &str) -> Option<String> {
+    let content_type = header(headers, "content-type").unwrap_or("text/plain");
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+
+    if mime.starts_with("multipart/") {
+        let boundary = format!("--{}", header_param(content_type, "boundary")?);
+
+        return body
+            .split(&boundary)
+            .skip(1)
+            .take_while(|p| !p.starts_with("--"))
+            .find_map(|part| {
+                let (headers, body) = split_message(part.trim_start_matches('\n'));
+                plain_text(&headers, body)
+            });
+    }
+
+    if mime != "text/plain" {
+        return None;
+    }
+
+    let bytes = match header(headers, "content-transfer-encoding")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "base64" => BASE64
+            .decode(body.split_whitespace().collect::<String>())
+            .ok()?,
+        "quoted-printable" => decode_quoted_printable(body),
+        _ => body.as_bytes().to_vec(),
+    };
+
+    Some(decode_charset(
+        &bytes,
+        &header_param(content_type, "charset").unwrap_or_default(),
+    ))
+}
+
+/// Parse a received email
+fn parse_mail(message: &str) -> Option<Mail> {
+    let (headers, body) = split_message(message);
+
+    Some(Mail {
+        subject: decode_header(header(&headers, "subject").unwrap_or_default()),
+        message_id: header(&headers, "message-id")
+            .unwrap_or_default()
+            .to_string(),
+        auto_submitted: header(&headers, "auto-submitted").is_some_and(|v| v != "no"),
+        body: plain_text(&headers, body)?,
+    })
+}
+
+/// Turn an email into a paste
+///
+/// # Returns
+/// * the link to the new paste
+async fn create_paste(database: &Database, from: &str, message: &str) -> Result<String, String> {
+    let base = BaseStore::new();
+
+    if base.read_only || database.get_maintenance().await {
+        return Err("Pastes can't be created right now".to_string());
+    }
+
+    let mail = parse_mail(message).ok_or("The email has no plain text body")?;
+    let content = mail.body.trim().to_string();
+
+    if content.is_empty() {
+        return Err("The email has an empty body".to_string());
+    }
+
+    let limit = crate::config::max_content_size();
+    if content.len() > limit {
+        return Err(format!(
+            "The email body is larger than the {limit} bytes allowed"
+        ));
+    }
+
+    let (password, paste) = database
+        .create_paste(PasteCreate {
+            url: String::new(),
+            content,
+            password: String::new(),
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let title: String = mail.subject.trim().chars().take(250).collect();
+    if !title.is_empty() {
+        let _ = database
+            .base
+            .edit_paste_metadata_by_url(
+                paste.url.clone(),
+                password.clone(),
+                PasteMetadata {
+                    title,
+                    ..paste.metadata.clone()
+                },
+                None,
+            )
+            .await;
+    }
+
+    let paste = database
+        .get_paste_by_url(paste.url)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let origin = base.public_url.clone();
+    crate::events::publish(crate::events::Event::Created {
+        paste: paste.clone(),
+        origin: origin.clone(),
+    });
+
+    let link = format!("{origin}/{}", paste.url);
+
+    // senders of bounces and automatic emails aren't replied to (so mail loops can't happen)
+    if !base.mail_relay.is_empty() && !from.is_empty() && !mail.auto_submitted {
+        send_reply(&base, from, &mail, &link, &password);
+    }
+
+    Ok(link)
+}
+
+// replies
+
+/// Compose and send the reply to an email (with retries)
+fn send_reply(base: &BaseStore, to: &str, mail: &Mail, link: &str, password: &str) {
+    let Ok(relay) = url::Url::parse(&base.mail_relay) else {
+        return;
+    };
+
+    let from = if base.mail_from.is_empty() {
+        format!("noreply@{}", relay.host_str().unwrap_or("localhost"))
+    } else {
+        base.mail_from.clone()
+    };
+
+    let subject = if mail.subject.is_empty() {
+        "Your paste".to_string()
+    } else {
+        format!("Re: {}", mail.subject)
+    };
+
+    let now = dorsal::utility::unix_epoch_timestamp();
+    let domain = from.split_once('@').map(|(_, d)| d).unwrap_or("localhost");
+    let message = format!(
+        "From: {from}\r\nTo: {to}\r\nSubject: =?utf-8?B?{}?=\r\nDate: {}\r\nMessage-ID: <{now}.{}@{domain}>\r\n{}Auto-Submitted: auto-replied\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\nYour paste is at {link}\r\n\r\nIts edit password is: {password}\r\n",
+        BASE64.encode(subject),
+        crate::utility::http_date(now),
+        dorsal::utility::random_id(),
+        if mail.message_id.is_empty() {
+            String::new()
+        } else {
+            format!("In-Reply-To: {}\r\n", mail.message_id)
+        }
+    );
+
+    let to = to.to_string();
+    crate::jobs::spawn_with_retries(move || {
+        let (relay, from, to, message) = (relay.clone(), from.clone(), to.clone(), message.clone());
+
+        async move {
+            tokio::task::spawn_blocking(move || send_mail(&relay, &from, &to, &message))
+                .await
+                .map_err(|_| "Sending the email stopped unexpectedly.".to_string())?
+        }
+    });
+}
+
+/// A connection to the relay (upgraded to TLS by `STARTTLS`)
+trait Connection: Read + Write {}
+impl<T: Read + Write> Connection for T {}
+
+/// Read an SMTP reply (every line of it)
+///
+/// # Returns
+/// * the reply code
+fn read_reply(stream: &mut dyn Connection) -> Result<(u16, String), String> {
+    let mut text = String::new();
+
+    loop {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+
+        while !line.ends_with(b"\r\n") {
+            if (stream
+                .read(&mut byte)
+                .map_err(|_| "Failed to read the reply.")?
+                == 0)
+                || (line.len() as u64 > MAX_LINE_LENGTH)
+            {
+                return Err("Invalid reply.".to_string());
+            }
+
+            line.push(byte[0]);
+        }
+
+        let line = String::from_utf8_lossy(&line).trim_end().to_string();
+        let code = line
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .ok_or("Invalid reply.")?;
+
+        text.push_str(line.get(4..).unwrap_or_default());
+        text.push('\n');
+
+        // the last line of a reply has a space after the code (the others have `-`)
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok((code, text));
+        }
+    }
+}
+
+/// Send a command and check its reply code
+fn command(stream: &mut dyn Connection, line: &str, expected: u16) -> Result<String, String> {
+    stream
+        .write_all(format!("{line}\r\n").as_bytes())
+        .map_err(|_| "Failed to send a command.")?;
+
+    match read_reply(stream)? {
+        (code, text) if code == expected => Ok(text),
+        (code, text) => Err(format!("The relay responded with {code} {}", text.trim())),
+    }
+}
+
+/// Send an email through the relay (blocking)
+///
+/// `smtp://` relays are upgraded to TLS if they support `STARTTLS`, `smtps://` relays use TLS
+/// from the start. Credentials in the url are only ever sent over TLS.
+fn send_mail(relay: &url::Url, from: &str, to: &str, message: &str) -> Result<(), String> {
+    let implicit_tls = match relay.scheme() {
+        "smtp" => false,
+        "smtps" => true,
+        _ => return Err("Only smtp and smtps relays are supported.".to_string()),
+    };
+
+    let host = relay
+        .host_str()
+        .ok_or("The relay has no host.")?
+        .to_string();
+    let port = relay.port().unwrap_or(if implicit_tls { 465 } else { 587 });
+
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|_| "The relay could not be resolved.")?
+        .next()
+        .ok_or("The relay could not be resolved.")?;
+
+    let tcp = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|_| "Failed to connect.")?;
+    let _ = tcp.set_read_timeout(Some(TIMEOUT));
+    let _ = tcp.set_write_timeout(Some(TIMEOUT));
+
+    let connector = native_tls::TlsConnector::new().map_err(|_| "Failed to set up TLS.")?;
+    let mut stream: Box<dyn Connection> = if implicit_tls {
+        Box::new(
+            connector
+                .connect(&host, tcp.try_clone().map_err(|_| "Failed to connect.")?)
+                .map_err(|_| "TLS handshake failed.")?,
+        )
+    } else {
+        Box::new(tcp.try_clone().map_err(|_| "Failed to connect.")?)
+    };
+
+    if read_reply(stream.as_mut())?.0 != 220 {
+        return Err("The relay didn't greet us.".to_string());
+    }
+
+    let features = command(stream.as_mut(), "EHLO localhost", 250)?;
+    let mut is_tls = implicit_tls;
+
+    if !is_tls && features.lines().any(|f| f.eq_ignore_ascii_case("starttls")) {
+        command(stream.as_mut(), "STARTTLS", 220)?;
+        stream = Box::new(
+            connector
+                .connect(&host, tcp)
+                .map_err(|_| "TLS handshake failed.")?,
+        );
+
+        is_tls = true;
+        command(stream.as_mut(), "EHLO localhost", 250)?;
+    }
+
+    if !relay.username().is_empty() {
+        if !is_tls {
+            return Err("Refusing to send credentials without TLS.".to_string());
+        }
+
+        let decode = |s: &str| {
+            percent_encoding::percent_decode_str(s)
+                .decode_utf8_lossy()
+                .to_string()
+        };
+
+        command(
+            stream.as_mut(),
+            &format!(
+                "AUTH PLAIN {}",
+                BASE64.encode(format!(
+                    "\0{}\0{}",
+                    decode(relay.username()),
+                    decode(relay.password().unwrap_or_default())
+                ))
+            ),
+            235,
+        )?;
+    }
+
+    command(stream.as_mut(), &format!("MAIL FROM:<{from}>"), 250)?;
+    command(stream.as_mut(), &format!("RCPT TO:<{to}>"), 250)?;
+    command(stream.as_mut(), "DATA", 354)?;
+
+    let mut data = String::new();
+    for line in message.split("\r\n") {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+
+    data.push('.');
+    command(stream.as_mut(), &data, 250)?;
+    let _ = command(stream.as_mut(), "QUIT", 221);
+
+    Ok(())
+}
@@ -1,45 +1,23 @@
-use axum::{routing::get, Router};
-use pastemd::{database::Database, routing::api};
+use axum::Router;
+use sealable::{AppConfig, Database};
 use std::env;
 
-mod base;
-mod markdown;
-mod pages;
-
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok(); // load .env
 
-    let port: u16 = match env::var("PORT") {
-        Ok(v) => v.parse::<u16>().unwrap(),
-        Err(_) => 8080,
-    };
+    // subcommands (`sealable paste`)
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(code) = sealable::run_command(&args).await {
+        std::process::exit(code);
+    }
 
-    // init database
-    let database = Database::new(
-        pongo::Database::env_options(),
-        pastemd::database::ServerOptions {
-            view_password: true,
-            starstraw: env::var("USE_STARSTRAW").is_ok(),
-            paste_ownership: true,
-            view_mode: if env::var("USE_STARSTRAW").is_ok() {
-                pastemd::database::ViewMode::AuthenticatedOnce
-            } else {
-                pastemd::database::ViewMode::OpenMultiple
-            },
-            table_pastes: pastemd::database::PastesTableConfig {
-                table_name: "se_pastes".to_string(),
-                prefix: "se_paste".to_string(),
-                ..Default::default()
-            },
-            table_views: pastemd::database::ViewsTableConfig {
-                table_name: "se_views".to_string(),
-                prefix: "se_views".to_string(),
-            },
-        },
-    )
-    .await;
+    let dev = args.iter().any(|a| a == "--dev");
+    sealable::init_telemetry();
+    sealable::init_sentry();
 
+    // init database
+    let database = Database::from_env().await;
     database.init().await;
 
     let pongo_database = pongo::Database::new(
@@ -61,19 +39,42 @@ async fn main() {
     starstraw_database.init().await;
 
     // ...
+    let mut config = AppConfig::new(database.clone());
+    config.dev = dev;
+
+    if let Err(e) = config.validate() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+
     let app = Router::new()
-        .route("/", get(pages::homepage))
-        .merge(pages::routes(database.clone()))
-        .merge(pages::extra_starstraw_routes(starstraw_database.clone()))
-        .nest("/api", api::routes(database.clone()))
+        .merge(sealable::routes(config))
+        .merge(sealable::extra_starstraw_routes(starstraw_database.clone()))
         .nest("/star", pongo::starstraw::routes(starstraw_database))
         .nest("/a/pongo", pongo::dashboard::routes(pongo_database.clone()))
-        .fallback(api::not_found);
+        .fallback(pastemd::routing::api::not_found)
+        .layer(axum::middleware::from_fn(sealable::frame_options))
+        // (over every route, auth and the dashboard included)
+        .layer(axum::middleware::from_fn(sealable::canonical_host));
+
+    tokio::spawn(sealable::serve_mail(database.clone()));
+
+    if dev {
+        tracing::info!("Development mode: watching layout overrides and static files.");
+    }
+
+    let listeners = match sealable::listeners_from_env() {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
-    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{port}"))
-        .await
-        .unwrap();
+    if let Err(e) = sealable::serve(listeners, app, sealable::shutdown_signal()).await {
+        tracing::error!("{e}");
+        std::process::exit(1);
+    }
 
-    println!("Starting server at http://localhost:{port}!");
-    axum::serve(listener, app).await.unwrap();
+    sealable::shutdown(&database).await;
 }
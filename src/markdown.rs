@@ -1,10 +1,86 @@
 use sauropod::markdown::parse_markdown as shared_parse_markdown;
 use regex::RegexBuilder;
+use crate::model::ContentStats;
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Instant, SystemTime};
+
+/// Rendered markdown files, keyed by path (along with the modification time they were rendered at)
+static RENDERED_FILES: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, String)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Rendered pastes, keyed by url (see [`render_paste`])
+static RENDERED_PASTES: LazyLock<Mutex<HashMap<String, RenderedPaste>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Pastes larger than this (in bytes) aren't kept in [`RENDERED_PASTES`]
+const MAX_CACHED_SIZE: usize = 1024 * 1024;
+
+/// A paste's rendered content, and what it was rendered from
+struct RenderedPaste {
+    /// The hash of the content and the options it was rendered with
+    hash: String,
+    html: String,
+    stats: ContentStats,
+    used: Instant,
+}
+
+/// Average reading speed used for [`ContentStats::reading_time`] (in words per minute)
+pub const READING_SPEED: usize = 200;
+
+/// Count the words in `input` and estimate how long it takes to read
+pub fn content_stats(input: &str) -> ContentStats {
+    let word_count = input
+        .split_whitespace()
+        .filter(|w| w.chars().any(|c| c.is_alphanumeric()))
+        .count();
+
+    ContentStats {
+        word_count,
+        reading_time: word_count.div_ceil(READING_SPEED).max(1),
+    }
+}
+
+/// Guess the text direction (`"ltr"` or `"rtl"`) of `input`
+///
+/// Content is right-to-left if it contains more Arabic/Hebrew letters than other letters.
+pub fn detect_direction(input: &str) -> &'static str {
+    let (mut rtl, mut ltr) = (0, 0);
+
+    for c in input.chars().filter(|c| c.is_alphabetic()) {
+        match c as u32 {
+            // hebrew, arabic (+ supplement, extended-a), presentation forms
+            0x0590..=0x05FF
+            | 0x0600..=0x06FF
+            | 0x0750..=0x077F
+            | 0x08A0..=0x08FF
+            | 0xFB1D..=0xFDFF
+            | 0xFE70..=0xFEFF => rtl += 1,
+            _ => ltr += 1,
+        }
+    }
+
+    if rtl > ltr {
+        "rtl"
+    } else {
+        "ltr"
+    }
+}
+
+#[tracing::instrument(skip_all)]
 pub fn parse_markdown(input: String) -> String {
-    shared_parse_markdown(
-        input,
+    let options = crate::config::render_options();
+    let start = std::time::Instant::now();
+
+    let rendered = shared_parse_markdown(
+        crate::hooks::before_render(input),
         vec![&mut |mut out: String| {
+            if !options.custom_blocks {
+                return out;
+            }
+
             // handle span block
             out = regex_replace_exp(
                 &out,
@@ -27,7 +103,107 @@ pub fn parse_markdown(input: String) -> String {
             // return
             out
         }],
-    )
+    );
+
+    crate::metrics::render(start.elapsed());
+    rendered
+}
+
+/// Render the content of a paste, reusing the previous render of the most recently viewed pastes
+/// (`RENDER_CACHE_SIZE`) until their content changes
+///
+/// # Arguments
+/// * `language` - the language of the content (see [`crate::language::as_markdown`])
+///
+/// # Returns
+/// * the rendered content and its [`ContentStats`]
+pub fn render_paste(url: &str, content: &str, language: &str) -> (String, ContentStats) {
+    let capacity = crate::config::render_cache_size();
+
+    if capacity == 0 || content.len() > MAX_CACHED_SIZE {
+        return (
+            parse_markdown(crate::language::as_markdown(content, language)),
+            content_stats(content),
+        );
+    }
+
+    let hash = crate::utility::etag(&[
+        content,
+        language,
+        &format!("{:?}", crate::config::render_options()),
+    ]);
+
+    if let Some(cached) = RENDERED_PASTES.lock().unwrap().get_mut(url) {
+        if cached.hash == hash {
+            cached.used = Instant::now();
+            crate::metrics::cache("rendered", true);
+            return (cached.html.clone(), cached.stats.clone());
+        }
+    }
+
+    crate::metrics::cache("rendered", false);
+    let html = parse_markdown(crate::language::as_markdown(content, language));
+    let stats = content_stats(content);
+
+    let mut cache = RENDERED_PASTES.lock().unwrap();
+
+    // drop the least recently viewed pastes
+    while cache.len() >= capacity && !cache.contains_key(url) {
+        let Some(oldest) = cache
+            .iter()
+            .min_by_key(|(_, r)| r.used)
+            .map(|(u, _)| u.clone())
+        else {
+            break;
+        };
+
+        cache.remove(&oldest);
+    }
+
+    cache.insert(
+        url.to_string(),
+        RenderedPaste {
+            hash,
+            html: html.clone(),
+            stats: stats.clone(),
+            used: Instant::now(),
+        },
+    );
+
+    (html, stats)
+}
+
+/// Drop the cached render of a paste (once it's edited or deleted)
+pub fn forget_paste(url: &str) {
+    RENDERED_PASTES.lock().unwrap().remove(url);
+}
+
+/// Render a markdown file from disk, reusing the previous render until the file changes
+///
+/// # Returns
+/// * `None` if the file can't be read
+pub fn render_markdown_file(path: &Path) -> Option<String> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+
+    // check in cache
+    if let Some((at, rendered)) = RENDERED_FILES.lock().unwrap().get(path) {
+        if *at == modified {
+            crate::metrics::cache("files", true);
+            return Some(rendered.clone());
+        }
+    }
+
+    crate::metrics::cache("files", false);
+
+    // render
+    let rendered = parse_markdown(std::fs::read_to_string(path).ok()?);
+
+    RENDERED_FILES
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), (modified, rendered.clone()));
+
+    Some(rendered)
 }
 
 #[allow(dead_code)]
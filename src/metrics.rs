@@ -0,0 +1,256 @@
+//! Prometheus metrics (`/metrics`, see [`crate::AppConfig::metrics`])
+//!
+//! Metrics are kept in memory and written in the Prometheus text format when scraped, so they
+//! restart from zero with the server.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::database::Database;
+
+/// The upper bounds of histogram buckets (in seconds)
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// All recorded metrics
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(|| Mutex::new(Metrics::default()));
+
+/// A histogram of durations
+#[derive(Debug, Default)]
+struct Histogram {
+    /// How many observations fit in each of [`BUCKETS`] (cumulative)
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    /// Write the histogram's samples
+    ///
+    /// # Arguments
+    /// * `labels` - labels shared by every sample (`method="GET",route="/"`), can be empty
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let (sep, set) = match labels.is_empty() {
+            true => ("", String::new()),
+            false => (",", format!("{{{labels}}}")),
+        };
+
+        for (bucket, bound) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(out, "{name}_bucket{{{labels}{sep}le=\"{bound}\"}} {bucket}");
+        }
+
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{sep}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let _ = writeln!(out, "{name}_sum{set} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{set} {}", self.count);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    /// Requests by method, route and status
+    requests: HashMap<(String, String, u16), u64>,
+    /// Request durations by method and route
+    latencies: HashMap<(String, String), Histogram>,
+    /// Markdown render durations
+    renders: Histogram,
+    /// Cache lookups by cache and if they hit
+    cache: HashMap<(&'static str, bool), u64>,
+}
+
+/// Escape a label value
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Write the `HELP` and `TYPE` lines of a metric
+fn describe(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+}
+
+/// Record a cache lookup
+///
+/// # Arguments
+/// * `cache` - the name of the cache (`metadata`, `stats`, ...)
+/// * `hit` - if the value was found
+pub fn cache(cache: &'static str, hit: bool) {
+    *METRICS
+        .lock()
+        .unwrap()
+        .cache
+        .entry((cache, hit))
+        .or_default() += 1;
+}
+
+/// Record how long rendering markdown took
+pub fn render(duration: Duration) {
+    METRICS.lock().unwrap().renders.observe(duration);
+}
+
+/// Record every request (by the route it matched)
+pub async fn layer(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = crate::middleware::route_name(&req);
+
+    let start = Instant::now();
+    let res = next.run(req).await;
+    let elapsed = start.elapsed();
+
+    let mut metrics = METRICS.lock().unwrap();
+
+    *metrics
+        .requests
+        .entry((method.clone(), route.clone(), res.status().as_u16()))
+        .or_default() += 1;
+
+    metrics
+        .latencies
+        .entry((method, route))
+        .or_default()
+        .observe(elapsed);
+
+    drop(metrics);
+    res
+}
+
+/// The metrics (`/metrics`)
+///
+/// Needs `Authorization: Bearer METRICS_TOKEN` if `METRICS_TOKEN` is set.
+pub async fn metrics_request(headers: HeaderMap, State(database): State<Database>) -> Response {
+    let token = crate::base::BaseStore::new().metrics_token;
+
+    if !token.is_empty()
+        && headers
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            != Some(token.as_str())
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    // (stats are cached, so scraping doesn't count every paste every time)
+    let stats = database.get_instance_stats().await;
+    let pool = &database.base.base.db.client;
+
+    let mut out = String::new();
+    let metrics = METRICS.lock().unwrap();
+
+    describe(
+        &mut out,
+        "sealable_http_requests_total",
+        "counter",
+        "Requests handled, by route and status.",
+    );
+    for ((method, route, status), count) in &metrics.requests {
+        let _ = writeln!(
+            out,
+            "sealable_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{status}\"}} {count}",
+            label(method),
+            label(route)
+        );
+    }
+
+    describe(
+        &mut out,
+        "sealable_http_request_duration_seconds",
+        "histogram",
+        "How long requests took, by route.",
+    );
+    for ((method, route), histogram) in &metrics.latencies {
+        histogram.write(
+            &mut out,
+            "sealable_http_request_duration_seconds",
+            &format!("method=\"{}\",route=\"{}\"", label(method), label(route)),
+        );
+    }
+
+    describe(
+        &mut out,
+        "sealable_render_duration_seconds",
+        "histogram",
+        "How long rendering markdown took.",
+    );
+    metrics
+        .renders
+        .write(&mut out, "sealable_render_duration_seconds", "");
+
+    describe(
+        &mut out,
+        "sealable_cache_lookups_total",
+        "counter",
+        "Cache lookups, by cache and result.",
+    );
+    for ((cache, hit), count) in &metrics.cache {
+        let result = if *hit { "hit" } else { "miss" };
+        let _ = writeln!(
+            out,
+            "sealable_cache_lookups_total{{cache=\"{cache}\",result=\"{result}\"}} {count}"
+        );
+    }
+
+    drop(metrics);
+
+    describe(&mut out, "sealable_pastes", "gauge", "Pastes stored.");
+    let _ = writeln!(out, "sealable_pastes {}", stats.pastes);
+
+    describe(
+        &mut out,
+        "sealable_paste_views",
+        "gauge",
+        "Views of all pastes.",
+    );
+    let _ = writeln!(out, "sealable_paste_views {}", stats.views);
+
+    describe(
+        &mut out,
+        "sealable_storage_bytes",
+        "gauge",
+        "Bytes taken up by the content of pastes, with their files and attachments.",
+    );
+    let _ = writeln!(out, "sealable_storage_bytes {}", stats.storage_bytes);
+
+    let idle = pool.num_idle();
+    describe(
+        &mut out,
+        "sealable_db_connections",
+        "gauge",
+        "Database connections, by state.",
+    );
+    let _ = writeln!(out, "sealable_db_connections{{state=\"idle\"}} {idle}");
+    let _ = writeln!(
+        out,
+        "sealable_db_connections{{state=\"active\"}} {}",
+        (pool.size() as usize).saturating_sub(idle)
+    );
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out).into_response()
+}
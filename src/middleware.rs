@@ -0,0 +1,709 @@
+//! Router-wide middleware
+use crate::database::Database;
+use crate::model::{Preferences, ThemePreference};
+use askama_axum::Template;
+use axum_extra::extract::cookie::CookieJar;
+use dorsal::DefaultReturn;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Json, Response},
+};
+
+tokio::task_local! {
+    /// The [`Preferences`] of the user sending the current request
+    static PREFERENCES: Preferences;
+    /// The locale negotiated for the current request (see [`crate::i18n::negotiate`])
+    static LOCALE: String;
+    /// The id of the current request (see [`request_id`])
+    static REQUEST_ID: String;
+    /// The CSRF token of the user sending the current request (see [`csrf`])
+    static CSRF_TOKEN: String;
+    /// The nonce inline scripts of the current response need (see [`security_headers`])
+    static NONCE: String;
+    /// The address of the client sending the current request (see [`client_ip`])
+    static CLIENT_IP: Option<IpAddr>;
+}
+
+/// Get the [`Preferences`] of the user sending the current request
+///
+/// Falls back to the default preferences outside of a request.
+pub fn current_preferences() -> Preferences {
+    PREFERENCES.try_with(|p| p.clone()).unwrap_or_default()
+}
+
+/// Get the locale of the current request
+///
+/// Falls back to [`crate::i18n::DEFAULT_LOCALE`] outside of a request.
+pub fn current_locale() -> String {
+    LOCALE
+        .try_with(|l| l.clone())
+        .unwrap_or_else(|_| crate::i18n::DEFAULT_LOCALE.to_string())
+}
+
+/// Get the id of the current request, `None` outside of a request
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Get the CSRF token of the user sending the current request (empty outside of a request)
+pub fn current_csrf_token() -> String {
+    CSRF_TOKEN.try_with(|t| t.clone()).unwrap_or_default()
+}
+
+/// Get the nonce inline `<script>`s need to run on the current page (empty outside of a request)
+pub fn current_nonce() -> String {
+    NONCE.try_with(|n| n.clone()).unwrap_or_default()
+}
+
+/// Get the address of the client sending the current request (`None` outside of a request, or
+/// when it isn't known)
+pub fn current_client_ip() -> Option<IpAddr> {
+    CLIENT_IP.try_with(|ip| *ip).ok().flatten()
+}
+
+/// Give the `<script>`s in operator HTML (`BODY_EMBED`, layout overrides) the nonce of the
+/// current page
+pub fn with_nonce(html: &str) -> String {
+    let nonce = current_nonce();
+
+    if nonce.is_empty() {
+        return html.to_string();
+    }
+
+    html.replace("<script", &format!("<script nonce=\"{nonce}\""))
+}
+
+/// Marks responses other sites are allowed to frame (`frame-ancestors *`), see
+/// [`security_headers`]
+#[derive(Debug, Clone, Copy)]
+pub struct Embeddable;
+
+#[derive(Template)]
+#[template(path = "maintenance.html")]
+struct MaintenanceTemplate {}
+
+#[derive(Template)]
+#[template(path = "read_only.html")]
+struct ReadOnlyTemplate {}
+
+/// Check if a request writes to the instance
+///
+/// Authentication and administration routes are never considered writes so they keep working
+/// (and maintenance mode can still be turned off).
+fn is_write_request(req: &Request) -> bool {
+    let path = req.uri().path();
+
+    !(matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        || path.starts_with("/api/admin/")
+        || path.starts_with("/api/auth/")
+        || (path == "/preferences")
+        || path.starts_with("/star/")
+        || path.starts_with("/a/pongo"))
+}
+
+/// Build a rejection response: JSON for `/api` routes, the given page for everything else
+fn reject(req: &Request, status: StatusCode, message: &str, page: String) -> Response {
+    rejection(req.uri().path(), status, message, page)
+}
+
+/// Build a rejection response for a request sent to `path` (see [`reject`])
+pub(crate) fn rejection(path: &str, status: StatusCode, message: &str, page: String) -> Response {
+    if path.starts_with("/api/") {
+        return (
+            status,
+            Json(DefaultReturn::<u16> {
+                success: false,
+                message: message.to_string(),
+                payload: status.as_u16(),
+            }),
+        )
+            .into_response();
+    }
+
+    (status, Html(page)).into_response()
+}
+
+/// Reject all write requests while maintenance mode is enabled
+pub async fn maintenance(State(database): State<Database>, req: Request, next: Next) -> Response {
+    if !is_write_request(&req) || !database.get_maintenance().await {
+        return next.run(req).await;
+    }
+
+    reject(
+        &req,
+        StatusCode::SERVICE_UNAVAILABLE,
+        "This instance is currently under maintenance.",
+        MaintenanceTemplate {}.render().unwrap(),
+    )
+}
+
+/// Send requests for the old url of a renamed paste to its new one (with `301 Moved
+/// Permanently`), for its pages and its API routes alike
+///
+/// Only urls no paste (or alias) is using anymore are redirected, and only once: redirects
+/// always point straight at a paste (see [`Database::move_paste`]).
+pub async fn renamed(State(database): State<Database>, req: Request, next: Next) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+
+    let path = req.uri().path();
+    let (prefix, rest) = match path.strip_prefix("/api/") {
+        Some(rest) => ("/api/", rest),
+        None => ("/", path.trim_start_matches('/')),
+    };
+
+    let (segment, rest) = rest.split_once('/').map_or((rest, ""), |(s, r)| (s, r));
+    let segment = percent_encoding::percent_decode_str(segment)
+        .decode_utf8_lossy()
+        .to_string();
+
+    if segment.is_empty() || crate::slug::is_reserved(&segment) {
+        return next.run(req).await;
+    }
+
+    let Some(target) = database.get_redirect(segment.clone()).await else {
+        return next.run(req).await;
+    };
+
+    if (target == crate::slug::canonical(&segment))
+        || database.get_paste_by_url(segment).await.is_ok()
+    {
+        return next.run(req).await;
+    }
+
+    let mut location = format!("{}{prefix}{target}", crate::config::base_path());
+    if !rest.is_empty() {
+        location.push('/');
+        location.push_str(rest);
+    }
+
+    if let Some(query) = req.uri().query() {
+        location.push('?');
+        location.push_str(query);
+    }
+
+    let mut res = StatusCode::MOVED_PERMANENTLY.into_response();
+    if let Ok(location) = HeaderValue::from_str(&location) {
+        res.headers_mut().insert(header::LOCATION, location);
+    }
+
+    res
+}
+
+/// How long clients are asked to wait before retrying turned away requests (in seconds)
+const RETRY_AFTER: u64 = 10;
+
+/// Turn away requests while `MAX_CONCURRENT_REQUESTS` are already being handled, and give up on
+/// ones taking longer than their timeout (see [`crate::config::RequestLimits`])
+///
+/// Both get a `503` with `Retry-After`, so a stuck database makes requests fail quickly instead of
+/// piling up. Only the time until the response starts counts, streamed bodies can take longer.
+///
+/// # Arguments
+/// * `permits` - one permit for each request that can be handled at once (`None` for no limit)
+pub async fn load_shed(
+    State(permits): State<Option<Arc<Semaphore>>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    let limits = crate::config::request_limits();
+
+    let busy = |message: &str| {
+        let mut res = rejection(
+            &path,
+            StatusCode::SERVICE_UNAVAILABLE,
+            message,
+            crate::pages::error_page(crate::i18n::current_text("error.busy")),
+        );
+
+        res.headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER));
+        res
+    };
+
+    let _permit = match permits {
+        Some(permits) => match permits.try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                tracing::warn!(path, "Turned away a request, too many are open.");
+                return busy("The server is too busy right now, try again later.");
+            }
+        },
+        None => None,
+    };
+
+    match tokio::time::timeout(limits.timeout_for(&path), next.run(req)).await {
+        Ok(res) => res,
+        Err(_) => {
+            tracing::warn!(path, "A request timed out.");
+            busy("The server took too long to respond, try again later.")
+        }
+    }
+}
+
+/// Reject all write requests if the instance is configured as read-only (`READ_ONLY`)
+pub async fn read_only(req: Request, next: Next) -> Response {
+    if !is_write_request(&req) || !crate::base::BaseStore::new().read_only {
+        return next.run(req).await;
+    }
+
+    reject(
+        &req,
+        StatusCode::FORBIDDEN,
+        "This instance is read-only.",
+        ReadOnlyTemplate {}.render().unwrap(),
+    )
+}
+
+/// Resolve the [`Preferences`] and locale of the user sending the request (see
+/// [`current_preferences`] and [`current_locale`])
+///
+/// Stored preferences of logged-in users take priority over the `theme` and `lang` cookies.
+pub async fn preferences(State(database): State<Database>, req: Request, next: Next) -> Response {
+    let jar = CookieJar::from_headers(req.headers());
+
+    let mut preferences = Preferences {
+        theme: match jar.get("theme").map(|c| c.value_trimmed()) {
+            Some("light") => ThemePreference::Light,
+            Some("dark") => ThemePreference::Dark,
+            _ => ThemePreference::System,
+        },
+        lang: jar
+            .get("lang")
+            .map(|c| c.value_trimmed().to_string())
+            .unwrap_or_default(),
+    };
+
+    if database.base.options.starstraw {
+        if let Some(c) = jar.get("__Secure-Token") {
+            if let Ok(ua) = database
+                .base
+                .auth
+                .get_profile_by_unhashed(c.value_trimmed().to_string())
+                .await
+            {
+                if let Some(p) = database.get_preferences_by_username(ua.username).await {
+                    preferences = p;
+                }
+            }
+        }
+    }
+
+    let locale = crate::i18n::negotiate(
+        Some(preferences.lang.as_str()).filter(|l| !l.is_empty()),
+        req.headers()
+            .get("Accept-Language")
+            .and_then(|h| h.to_str().ok()),
+    );
+
+    PREFERENCES
+        .scope(preferences, LOCALE.scope(locale, next.run(req)))
+        .await
+}
+
+/// Reject request bodies larger than their route allows (see [`crate::config::BodyLimits`])
+///
+/// Bodies announcing their size in `Content-Length` are rejected before they're read, anything
+/// else stops being read once it's too large. Either way the client gets a `413` it can show,
+/// instead of axum's plain text.
+pub async fn body_limit(req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    let limit = crate::config::body_limits().for_path(&path);
+
+    let too_large = || {
+        rejection(
+            &path,
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("The request body is larger than the {limit} bytes allowed."),
+            crate::pages::error_page(
+                crate::i18n::current_text("error.too_large").replace("{}", &limit.to_string()),
+            ),
+        )
+    };
+
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<usize>().ok());
+
+    if length.is_some_and(|length| length > limit) {
+        return too_large();
+    }
+
+    let req = req.map(|body| axum::body::Body::new(http_body_util::Limited::new(body, limit)));
+    let res = next.run(req).await;
+
+    // (the rejection of the extractor that ran into the limit)
+    let plain = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|t| t.starts_with("text/plain"));
+
+    if res.status() == StatusCode::PAYLOAD_TOO_LARGE && plain {
+        return too_large();
+    }
+
+    res
+}
+
+/// Check if a request was sent by a browser (which sends `Origin` or `Sec-Fetch-Site` with every
+/// `POST`), so it could have been sent by another site's form or script
+fn is_browser_request(req: &Request) -> bool {
+    req.headers().contains_key(header::ORIGIN) || req.headers().contains_key("Sec-Fetch-Site")
+}
+
+/// Give every user a CSRF token (the `csrf` cookie, see [`current_csrf_token`]), and reject write
+/// requests from browsers that don't send it back in `X-CSRF-Token`
+///
+/// Pages send the token with every `fetch` (see `base.html`). Requests from other clients (the
+/// CLI, `curl`, peers) can't be forged by another site, so they don't need it.
+pub async fn csrf(req: Request, next: Next) -> Response {
+    let jar = CookieJar::from_headers(req.headers());
+    let cookie = jar
+        .get("csrf")
+        .map(|c| c.value_trimmed().to_string())
+        .filter(|t| !t.is_empty());
+
+    let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+
+    if !safe && is_browser_request(&req) {
+        let header = req
+            .headers()
+            .get("X-CSRF-Token")
+            .and_then(|h| h.to_str().ok());
+
+        if cookie.is_none() || (header != cookie.as_deref()) {
+            return reject(
+                &req,
+                StatusCode::FORBIDDEN,
+                "This request is missing its CSRF token, reload the page and try again.",
+                crate::pages::error_page(crate::i18n::current_text("error.csrf")),
+            );
+        }
+    }
+
+    let new = cookie.is_none();
+    let token = cookie.unwrap_or_else(dorsal::utility::random_id);
+    let set_cookie = format!("csrf={token}; SameSite=Strict; Secure; HttpOnly; Path=/");
+
+    let mut res = CSRF_TOKEN.scope(token, next.run(req)).await;
+
+    // (shared caches would hand a cookie on a `public` response to everyone)
+    let public = res
+        .headers()
+        .get(header::CACHE_CONTROL)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|c| c.contains("public"));
+
+    if new && !public {
+        res.headers_mut()
+            .append(header::SET_COOKIE, set_cookie.parse().unwrap());
+    }
+
+    res
+}
+
+/// Give every request an id (see [`current_request_id`]), sent back in `X-Request-Id`, and keep
+/// the address of its client around (see [`current_client_ip`])
+///
+/// The `X-Request-Id` of the request is kept if a proxy already set one (and it's at most 128
+/// letters, digits, `-`, `_`, `.` or `:`), so the id matches across logs.
+pub async fn request_id(req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get("X-Request-Id")
+        .and_then(|h| h.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= 128
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+        })
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+
+    let header = HeaderValue::from_str(&id).unwrap();
+    let ip = client_ip(&req);
+    let mut res = REQUEST_ID
+        .scope(id, CLIENT_IP.scope(ip, next.run(req)))
+        .await;
+
+    res.headers_mut().insert("X-Request-Id", header);
+    res
+}
+
+/// The `Content-Security-Policy` of pages without `CONTENT_SECURITY_POLICY`
+///
+/// Scripts come from the instance itself, unpkg (highlight.js, CodeMirror, regns) and inline
+/// `<script>`s carrying the page's nonce. Pastes can show images and media from anywhere, and
+/// styles stay inline-able because pages (and rendered markdown) use `style` attributes.
+const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' 'nonce-{nonce}' https://unpkg.com; \
+    style-src 'self' 'unsafe-inline' https:; \
+    img-src * data: blob:; \
+    media-src *; \
+    font-src 'self' data: https:; \
+    connect-src 'self'; \
+    worker-src 'self' blob:; \
+    object-src 'none'; \
+    base-uri 'self'; \
+    form-action 'self'";
+
+/// Set the headers every response gets (unless it already has them): `X-Content-Type-Options`,
+/// `Referrer-Policy` and who can frame it
+///
+/// `policy` is the `Content-Security-Policy` without `frame-ancestors`, empty for none.
+fn insert_security_headers(headers: &mut axum::http::HeaderMap, policy: &str, embeddable: bool) {
+    let base = crate::base::BaseStore::new();
+
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+
+    if !base.referrer_policy.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&base.referrer_policy) {
+            headers.entry(header::REFERRER_POLICY).or_insert(value);
+        }
+    }
+
+    if headers.contains_key(header::CONTENT_SECURITY_POLICY) {
+        return;
+    }
+
+    let ancestors = match embeddable {
+        true => "*",
+        false => base.frame_ancestors.as_str(),
+    };
+
+    let policy = match policy.is_empty() {
+        true => format!("frame-ancestors {ancestors}"),
+        false => format!("{policy}; frame-ancestors {ancestors}"),
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&policy) {
+        headers.insert(header::CONTENT_SECURITY_POLICY, value);
+    }
+
+    // (for browsers without `frame-ancestors`)
+    match ancestors {
+        "'self'" => headers.insert(
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static("SAMEORIGIN"),
+        ),
+        "'none'" => headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+        _ => None,
+    };
+}
+
+/// Forbid other sites from framing pages (`FRAME_ANCESTORS`), and set `X-Content-Type-Options`
+/// and `Referrer-Policy`
+///
+/// For routes that aren't Sealable's own (starstraw, pongo), whose inline scripts don't carry a
+/// nonce. Responses that already have a `Content-Security-Policy` keep it.
+pub async fn frame_options(req: Request, next: Next) -> Response {
+    let mut res = next.run(req).await;
+
+    // (see `security_headers`)
+    if res.status() == StatusCode::NOT_MODIFIED {
+        return res;
+    }
+    let embeddable = res.extensions().get::<Embeddable>().is_some();
+
+    insert_security_headers(res.headers_mut(), "", embeddable);
+    res
+}
+
+/// Send a `Content-Security-Policy` with a fresh nonce for inline scripts (see
+/// [`current_nonce`]), along with the headers of [`frame_options`]
+///
+/// The policy is `CONTENT_SECURITY_POLICY` (where `{nonce}` is replaced), or
+/// [`DEFAULT_CONTENT_SECURITY_POLICY`]; `off` only keeps `frame-ancestors`. Responses marked
+/// [`Embeddable`] (`/:url/embed`) can be framed by any site.
+pub async fn security_headers(req: Request, next: Next) -> Response {
+    let nonce = format!("{:032x}", rand::random::<u128>());
+    let mut res = NONCE.scope(nonce.clone(), next.run(req)).await;
+
+    let configured = crate::base::BaseStore::new().content_security_policy;
+    let policy = match configured.as_str() {
+        "off" => String::new(),
+        "" => DEFAULT_CONTENT_SECURITY_POLICY.replace("{nonce}", &nonce),
+        policy => policy.replace("{nonce}", &nonce),
+    };
+
+    // a `304` keeps the policy of the page it revalidates (with that page's nonce)
+    if res.status() == StatusCode::NOT_MODIFIED {
+        return res;
+    }
+
+    let embeddable = res.extensions().get::<Embeddable>().is_some();
+    insert_security_headers(
+        res.headers_mut(),
+        policy.trim_end_matches([' ', ';']),
+        embeddable,
+    );
+
+    res
+}
+
+/// Get the route a request matched (`/:url` instead of the actual path), for metrics and traces
+pub fn route_name(req: &Request) -> String {
+    let prefix = crate::config::static_prefix();
+
+    match req.extensions().get::<MatchedPath>() {
+        Some(p) => p.as_str().to_string(),
+        // static files don't have a matched path
+        None if req.uri().path().starts_with(&format!("{prefix}/")) => format!("{prefix}/*"),
+        None => "unmatched".to_string(),
+    }
+}
+
+/// Check if a request comes through a trusted proxy (or a unix socket), whose `X-Forwarded-*`
+/// headers can be believed
+///
+/// Requests without a peer address that weren't received on a unix socket (when the router is
+/// served without `into_make_service_with_connect_info`) are never forwarded.
+fn is_forwarded(req: &Request) -> bool {
+    match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(peer)) => {
+            let peer = peer.ip().to_canonical();
+            crate::config::trusted_proxies()
+                .iter()
+                .any(|p| p.contains(peer))
+        }
+        None => req
+            .extensions()
+            .get::<crate::server::UnixSocket>()
+            .is_some(),
+    }
+}
+
+/// Redirect requests for other hosts (or ports, or plain HTTP) to `PUBLIC_URL`, and send
+/// `Strict-Transport-Security` (`HSTS_MAX_AGE`)
+///
+/// Links to the instance are always built from `PUBLIC_URL` when it's set, so this keeps visitors
+/// on the origin their cookies (and feeds, embeds and previews) belong to. `/metrics` is never
+/// redirected, scrapers usually reach the server directly.
+///
+/// It isn't part of [`crate::routes`]: it's meant to cover every route of the instance, so it's
+/// layered over the whole app (the binary's authentication and dashboard routes included).
+pub async fn canonical_host(req: Request, next: Next) -> Response {
+    let base = crate::base::BaseStore::new();
+
+    if base.canonical_redirect && !base.public_url.is_empty() && req.uri().path() != "/metrics" {
+        if let Some(location) = canonical_location(&req, &base.public_url) {
+            let mut res = StatusCode::PERMANENT_REDIRECT.into_response();
+
+            if let Ok(location) = HeaderValue::from_str(&location) {
+                res.headers_mut().insert(header::LOCATION, location);
+            }
+
+            return res;
+        }
+    }
+
+    let mut res = next.run(req).await;
+
+    if base.hsts_max_age > 0 {
+        res.headers_mut()
+            .entry(header::STRICT_TRANSPORT_SECURITY)
+            .or_insert_with(|| {
+                HeaderValue::from_str(&format!("max-age={}", base.hsts_max_age)).unwrap()
+            });
+    }
+
+    res
+}
+
+/// Get where a request should be redirected to, `None` if it's already for `public_url`
+fn canonical_location(req: &Request, public_url: &str) -> Option<String> {
+    let public = url::Url::parse(public_url).ok()?;
+    let host = req
+        .headers()
+        .get(header::HOST)?
+        .to_str()
+        .ok()?
+        .to_lowercase();
+
+    // `Host` leaves out the default port
+    let expected = match public.port() {
+        Some(port) => format!("{}:{port}", public.host_str()?),
+        None => public.host_str()?.to_string(),
+    };
+
+    let default_port = public.port_or_known_default().map(|p| format!(":{p}"));
+    let same_host =
+        host == expected || default_port.is_some_and(|port| host == format!("{expected}{port}"));
+
+    // (the scheme is only known from a proxy)
+    let same_scheme = !is_forwarded(req)
+        || req
+            .headers()
+            .get("X-Forwarded-Proto")
+            .and_then(|h| h.to_str().ok())
+            .is_none_or(|proto| proto.eq_ignore_ascii_case(public.scheme()));
+
+    if same_host && same_scheme {
+        return None;
+    }
+
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    Some(format!("{}{path}", public_url.trim_end_matches('/')))
+}
+
+/// Get the address of the client sending a request
+///
+/// `X-Forwarded-For` (or `X-Real-IP`) is only believed when the request comes from a trusted
+/// proxy (see [`crate::AppConfig::trusted_proxies`]) or a unix socket. The client is the last
+/// forwarded address that isn't a trusted proxy, since anything before it can be made up by the
+/// client.
+pub fn client_ip(req: &Request) -> Option<IpAddr> {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0.ip().to_canonical());
+
+    let proxies = crate::config::trusted_proxies();
+    let trusted = |ip: &IpAddr| proxies.iter().any(|p| p.contains(*ip));
+
+    if !is_forwarded(req) {
+        return peer;
+    }
+
+    let forwarded: Vec<IpAddr> = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .filter_map(|ip| ip.trim().parse().ok())
+        .collect();
+
+    forwarded
+        .iter()
+        .rev()
+        .find(|ip| !trusted(ip))
+        .or(forwarded.first())
+        .copied()
+        .or_else(|| {
+            req.headers()
+                .get("X-Real-IP")
+                .and_then(|h| h.to_str().ok())
+                .and_then(|h| h.trim().parse().ok())
+        })
+        .or(peer)
+}
@@ -0,0 +1,227 @@
+//! Paste mirroring between instances (`MIRROR_PEERS`)
+//!
+//! Instances listing each other in `MIRROR_PEERS` can copy pastes between them as signed
+//! [`MirrorBundle`]s, pushed to a peer's `/api/mirror` or pulled from its `/api/mirror/export`.
+//! Either way, the receiving instance checks the signature against the public key the sending
+//! instance serves at `/api/mirror/key`.
+//!
+//! Mirrored copies link back to the original paste and are only ever replaced by newer versions
+//! of it. Pastes don't keep old versions, so only the current version is mirrored.
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::Value;
+
+use crate::database::{Database, QUARANTINE_PASTE};
+use crate::model::{Availability, ExtraMetadata, MirrorBundle, MirrorPaste, SignedMirror, Visibility};
+use crate::signing;
+use pastemd::model::Paste;
+
+/// The most pastes in one bundle
+pub const MAX_MIRROR_PASTES: usize = 25;
+/// The largest bundle accepted (in bytes)
+pub const MAX_MIRROR_SIZE: usize = 16 * 1024 * 1024;
+/// How old a bundle can be when it's received (in milliseconds)
+const MAX_BUNDLE_AGE: u128 = 1000 * 60 * 60 * 12;
+
+/// Check if a paste can be mirrored (anyone with its link can see it right now, from any network)
+///
+/// Scheduled, expired and quarantined pastes, and pastes outside of their time window, are left
+/// out like they are from [`Database::can_view_paste`]. Wiki pages also need their wiki to be.
+async fn is_mirrorable(database: &Database, paste: &Paste, extra: &ExtraMetadata) -> bool {
+    if let Some(page) = database.get_page_by_url(paste.url.clone()).await {
+        if let Ok(namespace) = database.get_paste_by_url(page.namespace).await {
+            let namespace_extra = database
+                .get_extra_metadata_by_url(namespace.url.clone())
+                .await;
+
+            if !is_open(database, &namespace, &namespace_extra).await {
+                return false;
+            }
+        }
+    }
+
+    is_open(database, paste, extra).await
+}
+
+/// Check if anyone with the link of `paste` can see it right now, from any network (on its own)
+async fn is_open(database: &Database, paste: &Paste, extra: &ExtraMetadata) -> bool {
+    paste.metadata.view_password.is_empty()
+        && (extra.visibility != Visibility::Private)
+        && extra.allowed_networks.trim().is_empty()
+        && (extra.availability() == Availability::Available)
+        && !database.is_quarantined(QUARANTINE_PASTE, &paste.url).await
+        && database.can_view_paste(paste, None).await
+}
+
+/// Check if `origin` is one of the instance's `MIRROR_PEERS`
+pub fn is_peer(origin: &str) -> bool {
+    crate::base::BaseStore::new()
+        .mirror_peers
+        .iter()
+        .any(|p| p == origin.trim_end_matches('/'))
+}
+
+/// Build a signed bundle of pastes
+///
+/// Pastes that don't exist or can't be mirrored are left out.
+///
+/// # Arguments
+/// * `origin` - the public origin of this instance
+/// * `urls` - the urls of the pastes (at most [`MAX_MIRROR_PASTES`] are included)
+pub async fn export(database: &Database, origin: &str, urls: &[String]) -> Option<SignedMirror> {
+    let mut pastes = Vec::new();
+
+    for url in urls.iter().take(MAX_MIRROR_PASTES) {
+        let paste = match database.get_paste_by_url(url.clone()).await {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+
+        let extra = database.get_extra_metadata_by_url(paste.url.clone()).await;
+        if !is_mirrorable(database, &paste, &extra).await {
+            continue;
+        }
+
+        pastes.push(MirrorPaste {
+            url: paste.url,
+            content: paste.content,
+            date_published: paste.date_published,
+            date_edited: paste.date_edited,
+            metadata: paste.metadata,
+            extra,
+        });
+    }
+
+    let bundle = serde_json::to_string(&MirrorBundle {
+        origin: origin.to_string(),
+        date_created: dorsal::utility::unix_epoch_timestamp(),
+        pastes,
+    })
+    .ok()?;
+
+    let key = signing::private_key(database).await?;
+
+    Some(SignedMirror {
+        signature: BASE64.encode(signing::sign(&key, bundle.as_bytes())),
+        bundle,
+    })
+}
+
+/// Check the signature of a bundle received from a peer
+pub async fn verify(signed: &SignedMirror) -> Result<MirrorBundle, String> {
+    let bundle: MirrorBundle =
+        serde_json::from_str(&signed.bundle).map_err(|_| "Invalid bundle.")?;
+
+    if !is_peer(&bundle.origin) {
+        return Err("The bundle isn't from a peer.".to_string());
+    }
+
+    let now = dorsal::utility::unix_epoch_timestamp();
+    if bundle.date_created.abs_diff(now) > MAX_BUNDLE_AGE {
+        return Err("The bundle is too old.".to_string());
+    }
+
+    // get the peer's key
+    let response = crate::http::send(
+        "GET",
+        format!("{}/api/mirror/key", bundle.origin),
+        Vec::new(),
+        Vec::new(),
+        64 * 1024,
+    )
+    .await?;
+
+    let pem = serde_json::from_slice::<Value>(&response.body)
+        .ok()
+        .and_then(|v| v["payload"].as_str().map(|p| p.to_string()))
+        .ok_or("Failed to get the peer's key.")?;
+
+    let key = signing::parse_public_key(&pem).ok_or("Invalid peer key.")?;
+    let signature = BASE64
+        .decode(&signed.signature)
+        .map_err(|_| "Invalid signature.")?;
+
+    if !signing::verify(&key, signed.bundle.as_bytes(), &signature) {
+        return Err("Invalid signature.".to_string());
+    }
+
+    Ok(bundle)
+}
+
+/// Store the pastes of a (verified) bundle
+///
+/// Pastes are skipped if a local paste already uses their url, or if the stored copy is as new.
+///
+/// # Returns
+/// * the urls of the pastes that were stored
+pub async fn apply(database: &Database, bundle: MirrorBundle) -> Vec<String> {
+    let mut stored = Vec::new();
+
+    for mirrored in bundle.pastes.into_iter().take(MAX_MIRROR_PASTES) {
+        let valid_url = (3..=250).contains(&mirrored.url.len())
+            && mirrored
+                .url
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() | "_-.!".contains(c));
+
+        if !valid_url
+            || mirrored.content.is_empty()
+            || (mirrored.content.len() > crate::config::max_content_size())
+            || (mirrored.extra.visibility == Visibility::Private)
+            || !mirrored.metadata.view_password.is_empty()
+        {
+            continue;
+        }
+
+        let source = format!("{}/{}", bundle.origin, mirrored.url);
+        let (id, password) = match database.get_paste_by_url(mirrored.url.clone()).await {
+            Ok(existing) => {
+                let extra = database
+                    .get_extra_metadata_by_url(existing.url.clone())
+                    .await;
+
+                if (extra.source != source) || (existing.date_edited >= mirrored.date_edited) {
+                    continue;
+                }
+
+                (existing.id, existing.password)
+            }
+            // nobody knows the password of a new copy, so it can't be edited
+            Err(_) => (
+                dorsal::utility::random_id(),
+                dorsal::utility::hash(dorsal::utility::random_id()),
+            ),
+        };
+
+        let mut metadata = mirrored.metadata;
+        // usernames belong to the peer instance
+        metadata.owner.clear();
+
+        let paste = Paste {
+            id,
+            url: mirrored.url,
+            password,
+            content: mirrored.content,
+            date_published: mirrored.date_published,
+            date_edited: mirrored.date_edited,
+            metadata,
+        };
+
+        if database.store_mirrored_paste(&paste).await.is_err() {
+            continue;
+        }
+
+        let _ = database
+            .edit_extra_metadata_by_url(
+                paste.url.clone(),
+                ExtraMetadata {
+                    source,
+                    ..mirrored.extra
+                },
+            )
+            .await;
+
+        stored.push(paste.url);
+    }
+
+    stored
+}
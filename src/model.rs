@@ -0,0 +1,893 @@
+use pastemd::model::{PasteCreate, PasteEdit, PasteMetadata, PublicPaste};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Who a paste is listed to
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Listed everywhere (search, homepage, collections, ...)
+    #[default]
+    Public,
+    /// Only reachable by its direct link
+    Unlisted,
+    /// Only viewable by the paste owner (and instance administrators)
+    Private,
+}
+
+/// The text direction of a paste's rendered content
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Detected from the paste content
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+/// Paste metadata owned by sealable (stored separately from [`PasteMetadata`])
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExtraMetadata {
+    /// Paste visibility
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Text direction of the rendered content
+    #[serde(default)]
+    pub direction: Direction,
+    /// Language of the content (as a BCP 47 tag, empty if unknown)
+    #[serde(default)]
+    pub lang: String,
+    /// Language the content is written in (see [`crate::language::LANGUAGES`], empty for markdown
+    /// until it's detected)
+    #[serde(default)]
+    pub language: String,
+    /// The url the paste was imported from (empty if it wasn't imported, can't be edited)
+    #[serde(default)]
+    pub source: String,
+    /// The url of the paste this one was forked from (empty if it wasn't forked, can't be edited)
+    #[serde(default)]
+    pub forked_from: String,
+    /// When the paste is published (0 if it already is): until then only its owner can view it,
+    /// and it isn't listed anywhere (`u64`, since flattened fields can't be `u128`)
+    #[serde(default)]
+    pub publish_at: u64,
+    /// When the paste expires (0 for never): from then on nobody can view it, until it's deleted
+    /// by `sealable admin purge-expired`
+    #[serde(default)]
+    pub expires_at: u64,
+    /// If the paste takes comments
+    #[serde(default)]
+    pub comments: CommentPolicy,
+    /// When the paste can first be viewed (0 for whenever): until then, everyone but its owner
+    /// gets a page saying when it opens (unlike `publish_at`, which hides the paste entirely)
+    #[serde(default)]
+    pub available_from: u64,
+    /// When the paste stops being viewable (0 for never): from then on, everyone but its owner
+    /// gets a page saying it's over (unlike `expires_at`, the paste is kept as it is)
+    #[serde(default)]
+    pub available_until: u64,
+    /// The networks the paste can only be viewed from (comma-separated, like
+    /// `10.0.0.0/8, 192.168.1.20`), empty for anywhere
+    #[serde(default)]
+    pub allowed_networks: String,
+    /// Fields of the embedder's own (any JSON, under keys limited by `crate::schema`), sealable
+    /// only stores and shows them
+    #[serde(default, rename = "extra")]
+    pub custom: BTreeMap<String, serde_json::Value>,
+}
+
+/// Where a paste is in its time window (see [`ExtraMetadata::available_from`])
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Availability {
+    Available,
+    /// `available_from` hasn't passed
+    NotYet,
+    /// `available_until` passed
+    Over,
+}
+
+impl ExtraMetadata {
+    /// Get the networks the paste can only be viewed from (`None` if one of them is invalid)
+    pub fn networks(&self) -> Option<Vec<crate::config::IpNetwork>> {
+        self.allowed_networks
+            .split(',')
+            .filter(|n| !n.trim().is_empty())
+            .map(crate::config::IpNetwork::parse)
+            .collect()
+    }
+
+    /// Check if the paste can be viewed from `ip` (never from an unknown address, when it's only
+    /// viewable from some networks)
+    pub fn allows_network(&self, ip: Option<std::net::IpAddr>) -> bool {
+        match self.networks() {
+            Some(networks) if networks.is_empty() => true,
+            Some(networks) => ip.is_some_and(|ip| networks.iter().any(|n| n.contains(ip))),
+            None => false,
+        }
+    }
+
+    /// Check where the paste is in its time window
+    pub fn availability(&self) -> Availability {
+        let now = dorsal::utility::unix_epoch_timestamp();
+
+        if (self.available_from != 0) && (now < self.available_from as u128) {
+            Availability::NotYet
+        } else if (self.available_until != 0) && (self.available_until as u128 <= now) {
+            Availability::Over
+        } else {
+            Availability::Available
+        }
+    }
+
+    /// Check if the paste has expired
+    pub fn is_expired(&self) -> bool {
+        (self.expires_at != 0)
+            && (self.expires_at as u128 <= dorsal::utility::unix_epoch_timestamp())
+    }
+}
+
+/// If a paste takes comments (when the instance has them, see [`crate::config::CommentMode`])
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentPolicy {
+    /// Comments are shown, and anyone who can view the paste can leave one
+    #[default]
+    Open,
+    /// Comments are shown, but no new ones can be left
+    Closed,
+    /// Comments aren't shown (they're kept, in case they're opened again)
+    Off,
+}
+
+/// All of a paste's metadata, as shown in (and submitted by) the config editor
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FullMetadata {
+    #[serde(flatten)]
+    pub base: PasteMetadata,
+    #[serde(flatten)]
+    pub extra: ExtraMetadata,
+}
+
+/// Length statistics of a paste's content
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ContentStats {
+    /// Amount of words in the content
+    pub word_count: usize,
+    /// Estimated reading time (in minutes)
+    pub reading_time: usize,
+}
+
+/// A [`PublicPaste`] along with its [`ContentStats`] (returned by `/api/:url`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublicPasteInfo {
+    #[serde(flatten)]
+    pub paste: PublicPaste,
+    #[serde(flatten)]
+    pub stats: ContentStats,
+    /// The paste's custom fields (see [`ExtraMetadata::custom`])
+    #[serde(default)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// An oEmbed response describing a paste (<https://oembed.com/#section2.3>)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OEmbed {
+    pub version: String,
+    pub r#type: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub author_name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub author_url: String,
+    pub provider_name: String,
+    pub provider_url: String,
+    /// The `<iframe>` pointing at the embed view of the paste
+    pub html: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How many pastes were created on one day (see [`InstanceStats`])
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DailyPastes {
+    /// The start of the day (in milliseconds since the epoch, UTC)
+    pub day: u128,
+    pub pastes: i64,
+}
+
+/// Aggregate counts describing instance activity (`/api/v1/stats`)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct InstanceStats {
+    /// Total amount of pastes
+    pub pastes: i64,
+    /// Total amount of paste views
+    pub views: i64,
+    /// Amount of pastes created since midnight (UTC)
+    pub pastes_today: i64,
+    /// How much the content of pastes takes up (in bytes, with their files and attachments)
+    #[serde(default)]
+    pub storage_bytes: i64,
+    /// How many pastes were created on each of the last 30 days, oldest first (today included)
+    #[serde(default)]
+    pub daily_pastes: Vec<DailyPastes>,
+    /// When these stats were computed
+    pub computed_at: u128,
+}
+
+/// A paste in an account archive (see [`ArchiveManifest`])
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchiveEntry {
+    pub url: String,
+    /// The path of the paste content in the archive
+    pub file: String,
+    pub title: String,
+    pub description: String,
+    pub visibility: Visibility,
+    pub date_published: u128,
+    pub date_edited: u128,
+}
+
+/// The `manifest.json` of an account archive (`/account/archive.zip`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchiveManifest {
+    /// The username of the archived account
+    pub owner: String,
+    /// When the archive was created
+    pub date_created: u128,
+    pub pastes: Vec<ArchiveEntry>,
+}
+
+/// A paste in a [`MirrorBundle`]
+///
+/// Edit passwords aren't included, mirrored pastes can only be changed by mirroring them again.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MirrorPaste {
+    pub url: String,
+    pub content: String,
+    pub date_published: u128,
+    pub date_edited: u128,
+    pub metadata: PasteMetadata,
+    pub extra: ExtraMetadata,
+}
+
+/// Pastes sent from one instance to another (see `crate::mirror`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MirrorBundle {
+    /// The public origin of the instance the pastes are from
+    pub origin: String,
+    /// When the bundle was created
+    pub date_created: u128,
+    pub pastes: Vec<MirrorPaste>,
+}
+
+/// A [`MirrorBundle`] signed by the instance it's from (`/api/mirror`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignedMirror {
+    /// The bundle, as JSON (signed as-is, so it doesn't have to be re-encoded the same way)
+    pub bundle: String,
+    /// Base64 `rsa-sha256` signature of `bundle` by the instance signing key
+    pub signature: String,
+}
+
+/// The color theme a user prefers
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemePreference {
+    /// Follow `prefers-color-scheme`
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl ThemePreference {
+    /// The name of the theme, as stored in the `theme` cookie
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThemePreference::System => "system",
+            ThemePreference::Light => "light",
+            ThemePreference::Dark => "dark",
+        }
+    }
+}
+
+/// Per-user display preferences (stored for logged-in users, in cookies for everyone)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Preferences {
+    /// The preferred color theme
+    #[serde(default)]
+    pub theme: ThemePreference,
+    /// The preferred locale (empty to negotiate from `Accept-Language`)
+    #[serde(default)]
+    pub lang: String,
+}
+
+/// A named, ordered group of pastes owned by a single user
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Collection {
+    /// The collection name (used in `/c/:name`)
+    pub name: String,
+    /// The collection display title
+    pub title: String,
+    /// The username of the collection owner
+    pub owner: String,
+    /// Ordered list of paste urls in this collection
+    pub pastes: Vec<String>,
+    pub date_created: u128,
+}
+
+/// Editor content saved while a paste is being written (`/:url/draft`), without publishing it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Draft {
+    /// The paste being edited (empty for a new paste)
+    pub url: String,
+    pub content: String,
+    /// When the draft was last saved
+    pub date_updated: u128,
+}
+
+/// A file attached to a paste (see [`crate::attachments`])
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Attachment {
+    /// The paste's `url`
+    pub url: String,
+    /// The name the attachment is referred to by (`attachment:name`)
+    pub name: String,
+    /// Where the content is kept in the attachment storage
+    #[serde(skip_serializing)]
+    pub key: String,
+    pub content_type: String,
+    /// Size of the content (in bytes)
+    pub size: usize,
+    pub date_created: u128,
+    /// If it was flagged by a scanner (only administrators can download it, see [`crate::scan`])
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+/// Content flagged by a scanner, kept until an administrator releases it (see [`crate::scan`])
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Quarantined {
+    /// `paste` or `attachment`
+    pub kind: String,
+    /// The paste's `url`, or the attachment's storage key
+    pub id: String,
+    pub reason: String,
+    pub date_created: u128,
+}
+
+/// The body of `POST /api/:url/attachments/:name/delete`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AttachmentDelete {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+}
+
+/// The body of `PUT /:url/draft`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DraftEdit {
+    pub content: String,
+}
+
+/// How an [`Announcement`] is styled (maps to the `note-*` classes)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementLevel {
+    #[default]
+    Info,
+    Note,
+    Warn,
+    Error,
+}
+
+impl AnnouncementLevel {
+    /// The name of the level, as used in the `note-*` class
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnnouncementLevel::Info => "info",
+            AnnouncementLevel::Note => "note",
+            AnnouncementLevel::Warn => "warn",
+            AnnouncementLevel::Error => "error",
+        }
+    }
+}
+
+/// An instance-wide announcement shown at the top of every page
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Announcement {
+    /// The announcement text
+    pub text: String,
+    /// The announcement level
+    #[serde(default)]
+    pub level: AnnouncementLevel,
+    /// When the announcement stops being shown (0 for never)
+    #[serde(default)]
+    pub expires: u128,
+}
+
+impl Announcement {
+    /// Check if the announcement should still be shown
+    pub fn is_active(&self) -> bool {
+        !self.text.is_empty()
+            && ((self.expires == 0) | (self.expires > dorsal::utility::unix_epoch_timestamp()))
+    }
+}
+
+/// A paste event webhooks can subscribe to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    /// A paste was created (also through cloning or importing)
+    Create,
+    /// A paste's content or metadata was edited
+    Edit,
+    /// A paste was deleted
+    Delete,
+}
+
+/// An outgoing webhook (managed through `/api/admin/webhooks`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhook {
+    pub id: String,
+    /// The url events are posted to
+    pub url: String,
+    /// The secret payloads are signed with (`X-Sealable-Signature`)
+    pub secret: String,
+    /// The events sent to the webhook (all events if empty)
+    pub events: Vec<WebhookEvent>,
+}
+
+/// The paste a [`WebhookPayload`] is about
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookPaste {
+    pub url: String,
+    /// Absolute url of the paste
+    pub link: String,
+    pub title: String,
+    pub owner: String,
+    pub date_published: u128,
+    pub date_edited: u128,
+}
+
+/// The JSON body posted to webhooks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    /// When the event happened
+    pub date: u128,
+    pub paste: WebhookPaste,
+}
+
+// props
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionCreate {
+    /// The collection name
+    pub name: String,
+    /// The collection display title
+    #[serde(default)]
+    pub title: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionEdit {
+    /// The updated title of the collection
+    #[serde(default)]
+    pub title: String,
+    /// The updated (ordered) list of paste urls
+    #[serde(default)]
+    pub pastes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PasteImport {
+    /// The url of the remote file to import (`http` or `https`)
+    pub source: String,
+    /// The paste url
+    #[serde(default)]
+    pub url: String,
+    /// The paste edit password
+    #[serde(default)]
+    pub password: String,
+}
+
+/// A paste to create (`/api/new`), with its creation options
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NewPaste {
+    #[serde(flatten)]
+    pub base: PasteCreate,
+    /// The url of the template the paste was written from (its metadata is copied, empty for
+    /// none)
+    #[serde(default)]
+    pub template: String,
+    /// The paste view password
+    #[serde(default)]
+    pub view_password: String,
+    /// Paste visibility (private pastes need an owner)
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// How long until the paste expires (in seconds, 0 for never)
+    #[serde(default)]
+    pub expires_in: u64,
+    /// More files shown after the content (see [`PasteFile`])
+    #[serde(default)]
+    pub files: Vec<NewPasteFile>,
+}
+
+/// A file of a paste being created
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewPasteFile {
+    pub name: String,
+    pub content: String,
+}
+
+/// A named file of a paste, shown in its own section after the paste's content (gist-style)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasteFile {
+    /// The paste's `url`
+    pub url: String,
+    /// The name the file is shown (and linked) with, its extension picks its language
+    pub name: String,
+    pub content: String,
+    /// Where the file is shown among the paste's files (from 0)
+    pub position: usize,
+    pub date_edited: u128,
+}
+
+/// A page of a wiki: a paste shown at `/:namespace/:name`, with the namespace's other pages in a
+/// sidebar
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WikiPage {
+    /// The `url` of the paste the page is under
+    pub namespace: String,
+    pub name: String,
+    /// The page's own `url` (it's a paste like any other)
+    pub url: String,
+}
+
+/// The body of `POST /api/:url/pages`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WikiPageCreate {
+    /// The edit password of the namespace (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+    /// The page's name (see `crate::slug::check_page`)
+    pub name: String,
+    pub content: String,
+}
+
+/// The body of `POST /api/:url/pages/:name/delete`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WikiPageDelete {
+    /// The edit password of the namespace (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+}
+
+/// A comment left on a paste, shown below it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Comment {
+    pub id: String,
+    /// The paste's `url`
+    pub url: String,
+    /// The `id` of the comment this one answers (empty for comments on the paste itself)
+    pub reply_to: String,
+    /// The name the comment was left under (empty for anonymous comments)
+    pub author: String,
+    /// The account the comment was left with (empty when it wasn't left by a logged in user)
+    pub username: String,
+    pub content: String,
+    pub date_created: u128,
+    /// Who left the comment, so they can delete it (see `crate::api::session_owner`)
+    #[serde(skip)]
+    pub owner: String,
+}
+
+/// How many times a paste got one of the [`crate::database::REACTIONS`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReactionCount {
+    /// The name of the reaction
+    pub name: String,
+    pub emoji: String,
+    pub count: usize,
+    /// If the user asking reacted with it
+    pub reacted: bool,
+}
+
+/// The body of `POST /api/:url/stats`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasteStatsRequest {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+}
+
+/// The views of a paste on one day (see [`PasteStats`])
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DayStats {
+    /// The start of the day (in milliseconds since the epoch, UTC)
+    pub day: u128,
+    pub views: u64,
+    /// How many different visitors viewed the paste that day
+    pub visitors: u64,
+}
+
+/// How many views of a paste came from a site (see [`PasteStats`])
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReferrerCount {
+    /// The host of the site
+    pub host: String,
+    pub views: u64,
+}
+
+/// The views of a paste over the last days (see `crate::analytics`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasteStats {
+    /// Every view the paste ever got (counted like the view count on its page)
+    pub total_views: u64,
+    /// The views over `days`
+    pub views: u64,
+    /// The daily visitors over `days`, added up (visitors are only told apart within a day)
+    pub visitors: u64,
+    /// The views over `days` that didn't come from another site
+    pub direct_views: u64,
+    /// Every day, oldest first (today included)
+    pub days: Vec<DayStats>,
+    /// The sites that sent the most views, most first
+    pub referrers: Vec<ReferrerCount>,
+}
+
+/// The view counts of a paste (`/api/v1/pastes/:url/views`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasteViews {
+    pub url: String,
+    /// Every view the paste ever got (counted like the view count on its page)
+    pub views: u64,
+    /// The views of the last [`crate::analytics::RETENTION_DAYS`], counting each visitor once a
+    /// day
+    pub unique_views: u64,
+}
+
+/// The query of `/api/v1/users/:name/pastes` (and `/api/v1/me/pastes`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PastePageQuery {
+    /// The page of pastes to list (starting at 0)
+    #[serde(default)]
+    pub page: i32,
+    /// Only list the pastes with this visibility
+    #[serde(default)]
+    pub visibility: Option<Visibility>,
+}
+
+/// A paste in a [`PastePage`] (its content isn't included)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasteListing {
+    pub url: String,
+    pub title: String,
+    pub description: String,
+    pub visibility: Visibility,
+    /// If the paste needs a view password
+    pub view_password: bool,
+    pub date_published: u128,
+    pub date_edited: u128,
+}
+
+/// A page of the pastes of a user (`/api/v1/users/:name/pastes`)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PastePage {
+    /// The pastes of the page, oldest first (there can be less than a full page on every page,
+    /// the pastes the user asking can't see are left out)
+    pub pastes: Vec<PasteListing>,
+    pub page: i32,
+    /// The next page (`None` if this is the last one)
+    pub next_page: Option<i32>,
+}
+
+/// The body of `POST /api/:url/reactions`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReactionToggle {
+    /// The name of the reaction (see [`crate::database::REACTIONS`])
+    pub reaction: String,
+    /// The paste's view password (if it has one)
+    #[serde(default)]
+    pub view_password: String,
+}
+
+/// The body of `POST /api/:url/comments`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommentCreate {
+    pub content: String,
+    /// The name to show the comment with (ignored for logged in users, who get their username)
+    #[serde(default)]
+    pub author: String,
+    /// The comment being answered (only when comments are threaded)
+    #[serde(default)]
+    pub reply_to: String,
+    /// The paste's view password (if it has one)
+    #[serde(default)]
+    pub view_password: String,
+}
+
+/// The body of `POST /api/:url/comments/:id/delete`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CommentDelete {
+    /// The edit password of the paste (not needed by its owner, or by whoever left the comment)
+    #[serde(default)]
+    pub password: String,
+}
+
+/// The body of `POST /api/:url/files`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasteFileEdit {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+    /// The name of the file (replaced if the paste already has a file with this name)
+    pub name: String,
+    pub content: String,
+}
+
+/// The body of `POST /api/:url/files/:name/delete`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PasteFileDelete {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AliasEdit {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+    /// The alias to add (or remove)
+    pub alias: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PasteRename {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+    /// The url to move the paste to
+    pub new_url: String,
+}
+
+/// An edit sent to `/api/:url/edit`: a [`PasteEdit`], or new content saved with an edit link
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PasteEditBody {
+    #[serde(flatten)]
+    pub base: PasteEdit,
+    /// The token of an edit link (instead of the password, it can only change the content)
+    #[serde(default)]
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EditLinkCreate {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+    /// How long the link lasts (in seconds, see `crate::database::EDIT_LINK_LIFETIME`)
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+}
+
+/// A single-use link to edit a paste, see `Database::create_edit_token`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EditLink {
+    /// The token (only shown once)
+    pub token: String,
+    /// The path of the editor opened with the token
+    pub url: String,
+    /// When the link stops working (in milliseconds since the epoch)
+    pub expires_at: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShareLinkCreate {
+    /// The edit password of the paste (not needed by its owner)
+    #[serde(default)]
+    pub password: String,
+    /// How long the link lasts (in seconds, see `crate::signing::SHARE_LINK_LIFETIME`)
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+}
+
+/// A signed link that shows a paste without its view password, see
+/// `crate::signing::sign_share_link`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShareLink {
+    /// The path of the paste with the signature (`/:url?sig=&exp=`)
+    pub url: String,
+    /// When the link stops working (in milliseconds since the epoch)
+    pub expires_at: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PasteFork {
+    /// The url of the fork
+    #[serde(default)]
+    pub url: String,
+    /// The edit password of the fork
+    #[serde(default)]
+    pub password: String,
+    /// The view password of the paste being forked (if it has one)
+    #[serde(default)]
+    pub view_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PasteReport {
+    /// Why the paste is being reported
+    #[serde(default)]
+    pub reason: String,
+    /// The paste's view password (if it has one)
+    #[serde(default)]
+    pub view_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookCreate {
+    /// The url events are posted to (`http` or `https`)
+    pub url: String,
+    /// The secret payloads are signed with
+    pub secret: String,
+    /// The events to send (all events if empty)
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MirrorRequest {
+    /// The origin of the peer instance (must be listed in `MIRROR_PEERS`)
+    pub peer: String,
+    /// The urls of the pastes to mirror
+    pub urls: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MirrorExportQuery {
+    /// Comma-separated list of paste urls
+    #[serde(default)]
+    pub urls: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FullMetadataEdit {
+    /// The password of the paste
+    pub password: String,
+    /// The updated metadata of the paste
+    pub metadata: FullMetadata,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PreferencesEdit {
+    /// The new color theme (unchanged if not provided)
+    #[serde(default)]
+    pub theme: Option<ThemePreference>,
+    /// The new locale (unchanged if not provided)
+    #[serde(default)]
+    pub lang: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OEmbedQuery {
+    /// The url of the paste being embedded
+    pub url: String,
+    #[serde(default)]
+    pub maxwidth: Option<u32>,
+    #[serde(default)]
+    pub maxheight: Option<u32>,
+    /// The requested response format (only `json` is supported)
+    #[serde(default)]
+    pub format: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MaintenanceEdit {
+    /// If maintenance mode should be enabled
+    pub enabled: bool,
+}
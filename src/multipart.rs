@@ -0,0 +1,99 @@
+//! `multipart/form-data` request bodies (`curl -F`, file uploads)
+//!
+//! Bodies are parsed once they're fully received, so routes accepting them should keep their
+//! body limit small.
+
+/// A field of a form
+#[derive(Debug, Clone)]
+pub struct FormPart {
+    /// The field name
+    pub name: String,
+    /// The name of the uploaded file (if the field is a file)
+    pub filename: Option<String>,
+    /// The `Content-Type` of the field (empty if not provided)
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Check if a `Content-Type` is `multipart/form-data`
+pub fn is_multipart(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|m| m.trim().eq_ignore_ascii_case("multipart/form-data"))
+}
+
+/// Get a parameter of a header value (`name` in `form-data; name="f"`)
+fn param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|p| {
+        let (n, v) = p.split_once('=')?;
+        n.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Find `needle` in `haystack`, starting at `from`
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| p + from)
+}
+
+/// Parse a `multipart/form-data` body
+///
+/// # Arguments
+/// * `content_type` - the `Content-Type` of the request (which has the boundary)
+/// * `body` - the request body
+pub fn parse(content_type: &str, body: &[u8]) -> Option<Vec<FormPart>> {
+    let delimiter = format!("--{}", param(content_type, "boundary")?).into_bytes();
+    let mut parts = Vec::new();
+    let mut position = find(body, &delimiter, 0)? + delimiter.len();
+
+    loop {
+        // the last delimiter is followed by `--`
+        if body.get(position..position + 2)? == b"--" {
+            return Some(parts);
+        }
+
+        let head_start = find(body, b"\r\n", position)? + 2;
+        let head_end = find(body, b"\r\n\r\n", head_start)?;
+        let head = String::from_utf8_lossy(&body[head_start..head_end]);
+
+        let mut part = FormPart {
+            name: String::new(),
+            filename: None,
+            content_type: String::new(),
+            data: Vec::new(),
+        };
+
+        for line in head.split("\r\n") {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                part.name = param(value, "name").unwrap_or_default();
+                part.filename = param(value, "filename");
+            } else if name.trim().eq_ignore_ascii_case("content-type") {
+                part.content_type = value.trim().to_string();
+            }
+        }
+
+        let mut end_delimiter = b"\r\n".to_vec();
+        end_delimiter.extend_from_slice(&delimiter);
+
+        let data_end = find(body, &end_delimiter, head_end + 4)?;
+        part.data = body[head_end + 4..data_end].to_vec();
+        parts.push(part);
+
+        position = data_end + end_delimiter.len();
+    }
+}
+
+/// Get the first field named `name`
+pub fn field<'a>(parts: &'a [FormPart], name: &str) -> Option<&'a FormPart> {
+    parts.iter().find(|p| p.name == name)
+}
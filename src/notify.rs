@@ -0,0 +1,135 @@
+//! Chat notifications (Discord, Slack, Matrix)
+//!
+//! Messages are rendered from the `NOTIFY_*_TEMPLATE` variables, where `{title}`, `{url}`,
+//! `{link}`, `{owner}` and `{reason}` are replaced with details of the paste. Every configured
+//! channel gets every notification, and failed deliveries are retried like webhooks.
+use pastemd::model::Paste;
+use serde_json::json;
+
+use crate::base::BaseStore;
+
+/// The largest response read from a chat service (in bytes)
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+/// The longest message sent (Discord rejects messages over 2000 characters)
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// Something worth telling the instance team about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotifyEvent {
+    Create,
+    Report,
+}
+
+/// Check if Matrix notifications are configured (they need a homeserver, room and token)
+fn has_matrix(base: &BaseStore) -> bool {
+    !(base.notify_matrix_homeserver.is_empty()
+        || base.notify_matrix_room.is_empty()
+        || base.notify_matrix_token.is_empty())
+}
+
+/// Check if any chat channel is configured
+pub fn is_enabled(base: &BaseStore) -> bool {
+    !base.notify_discord_url.is_empty() || !base.notify_slack_url.is_empty() || has_matrix(base)
+}
+
+/// Render the message for an event
+fn render(template: &str, paste: &Paste, origin: &str, reason: &str) -> String {
+    let title = if paste.metadata.title.is_empty() {
+        &paste.url
+    } else {
+        &paste.metadata.title
+    };
+
+    let message = template
+        .replace("{title}", title)
+        .replace("{url}", &paste.url)
+        .replace("{link}", &format!("{origin}/{}", paste.url))
+        .replace("{owner}", &paste.metadata.owner)
+        .replace("{reason}", reason);
+
+    message.chars().take(MAX_MESSAGE_LENGTH).collect()
+}
+
+/// Deliver a request to a chat service (with retries)
+fn deliver(method: &'static str, url: String, headers: Vec<(String, String)>, body: Vec<u8>) {
+    let mut headers = headers;
+    headers.push(("Content-Type".to_string(), "application/json".to_string()));
+
+    crate::jobs::spawn_with_retries(move || {
+        let (url, headers, body) = (url.clone(), headers.clone(), body.clone());
+
+        async move {
+            match crate::http::send(method, url, headers, body, MAX_RESPONSE_SIZE).await {
+                Ok(r) if r.is_success() => Ok(()),
+                Ok(r) => Err(format!("The chat service responded with {}.", r.status)),
+                Err(e) => Err(e),
+            }
+        }
+    });
+}
+
+/// Send a notification to every configured chat channel
+///
+/// # Arguments
+/// * `event` - the event
+/// * `paste` - the paste the event is about
+/// * `origin` - the public origin of the instance
+/// * `reason` - why the paste was reported (empty for other events)
+pub fn notify(event: NotifyEvent, paste: &Paste, origin: &str, reason: &str) {
+    let base = BaseStore::new();
+
+    if !is_enabled(&base) {
+        return;
+    }
+
+    let template = match event {
+        NotifyEvent::Create => &base.notify_create_template,
+        NotifyEvent::Report => &base.notify_report_template,
+    };
+
+    if template.is_empty() {
+        return;
+    }
+
+    let message = render(template, paste, origin, reason);
+
+    if !base.notify_discord_url.is_empty() {
+        deliver(
+            "POST",
+            base.notify_discord_url.clone(),
+            Vec::new(),
+            json!({ "content": message }).to_string().into_bytes(),
+        );
+    }
+
+    if !base.notify_slack_url.is_empty() {
+        deliver(
+            "POST",
+            base.notify_slack_url.clone(),
+            Vec::new(),
+            json!({ "text": message }).to_string().into_bytes(),
+        );
+    }
+
+    if has_matrix(&base) {
+        // the transaction id makes retries of the same message idempotent
+        let room: String =
+            url::form_urlencoded::byte_serialize(base.notify_matrix_room.as_bytes()).collect();
+
+        deliver(
+            "PUT",
+            format!(
+                "{}/_matrix/client/v3/rooms/{room}/send/m.room.message/{}",
+                base.notify_matrix_homeserver,
+                dorsal::utility::random_id()
+            ),
+            vec![(
+                "Authorization".to_string(),
+                format!("Bearer {}", base.notify_matrix_token),
+            )],
+            json!({ "msgtype": "m.text", "body": message })
+                .to_string()
+                .into_bytes(),
+        );
+    }
+}
@@ -1,30 +1,97 @@
 use askama_axum::Template;
 use axum::{
+    body::Body,
     extract::{Path, State, Query},
-    response::{Html, Json, IntoResponse},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{Html, Json, IntoResponse, Redirect, Response},
     routing::{get, post, get_service},
-    Router,
+    Extension, Router,
 };
 use axum_extra::extract::cookie::CookieJar;
+use tokio_stream::wrappers::ReceiverStream;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::time::Instant;
+use std::sync::LazyLock;
 
 use tower_http::services::ServeDir;
-use pastemd::{database::Database, model::Paste};
-use crate::markdown::parse_markdown;
+use pastemd::model::{Paste, PasteError};
+use crate::access::{AccessQuery, Denied, ProtectedPaste};
+use crate::database::{Database, SearchResult};
+use crate::archive::ZipWriter;
+use crate::jobs::{JobOutput, JobStatus};
+use crate::utility::iso8601;
+use crate::model::{
+    ArchiveEntry, ArchiveManifest, Collection, Comment, CommentPolicy, ContentStats, Direction,
+    ExtraMetadata, FullMetadata, InstanceStats, PasteFile, PreferencesEdit, ReactionCount,
+    Visibility,
+};
+use crate::markdown::{detect_direction, parse_markdown, render_markdown_file, render_paste};
 use serde::{Serialize, Deserialize};
+use dorsal::DefaultReturn;
 
-pub fn routes(database: Database) -> Router {
+pub fn routes(config: &crate::config::AppConfig) -> Router {
     Router::new()
+        .route("/", get(homepage).post(crate::api::plain_upload))
+        .route(
+            "/new",
+            get(new_paste_request).post(crate::api::create_paste),
+        )
+        .route("/search", get(search_request))
+        .route("/account/archive.zip", get(archive_request))
+        .route("/c", get(collections_request))
+        .route("/c/:name", get(collection_request))
+        .route("/pages/:slug", get(info_page_request))
+        .route("/robots.txt", get(robots_request))
+        .route("/favicon.ico", get(favicon_request))
+        .route("/branding/logo", get(logo_request))
+        .route("/feed.atom", get(feed_request))
+        .route("/s/:code", get(shortlink_request))
+        .route("/export/:id", get(export_job_request))
         .route("/:url/edit/config", get(config_editor_request))
         .route("/:url/edit", get(editor_request))
+        .route("/:url/stats", get(stats_request))
+        .route(
+            "/new/draft",
+            get(crate::api::get_draft)
+                .put(crate::api::save_draft)
+                .delete(crate::api::delete_draft),
+        )
+        .route(
+            "/:url/draft",
+            get(crate::api::get_draft)
+                .put(crate::api::save_draft)
+                .delete(crate::api::delete_draft),
+        )
+        .route("/:url/print", get(print_paste_request))
+        .route("/:url/raw", get(raw_paste_request))
+        .route("/:url/raw/:name", get(raw_file_request))
+        .route("/:url/attachments/:name", get(attachment_request))
+        .route("/:url/download", get(download_paste_request))
+        .route("/:url/embed", get(embed_paste_request))
+        .route("/:url/card.png", get(card_request))
+        .route("/:url/export/pdf", get(export_pdf_request))
+        .route("/:url/export/html", get(export_html_request))
+        .route("/:url/export/png", get(export_png_request))
+        .route("/:url/feed.atom", get(paste_feed_request))
+        .route("/:url/events", get(paste_events_request))
+        .route("/:url/:page", get(wiki_page_request))
         .route("/:url", get(view_paste_request))
         .route("/api/render", post(render_markdown))
+        .route("/ws/preview", get(preview_socket))
+        .route("/ws/:url/edit", get(crate::collab::socket))
+        .route("/preferences", post(preferences_request))
         // serve static dir
-        .nest_service("/static", get_service(ServeDir::new("./static")))
+        .nest_service(
+            &config.static_prefix,
+            get_service(ServeDir::new(&config.static_dir).fallback(get(crate::assets::request)))
+                .layer(axum::middleware::from_fn(crate::assets::layer)),
+        )
         // ...
         .route("/api/auth/callback", get(starstraw::api::callback_request))
         .route("/api/auth/logout", get(starstraw::api::logout_request))
         // ...
-        .with_state(database)
+        .with_state(config.database.clone())
 }
 
 pub fn extra_starstraw_routes(database: starstraw::Database) -> Router {
@@ -33,12 +100,170 @@ pub fn extra_starstraw_routes(database: starstraw::Database) -> Router {
         .with_state(database)
 }
 
+/// A paste featured on the homepage (`PINNED`)
+struct PinnedPaste {
+    url: String,
+    title: String,
+    snippet: String,
+}
+
 #[derive(Template)]
 #[template(path = "homepage.html")]
-struct HomepageTemplate {}
+struct HomepageTemplate {
+    content: String,
+    pinned: Vec<PinnedPaste>,
+    stats: InstanceStats,
+    /// The templates offered by the instance (`PASTE_TEMPLATES`), as `(url, title)`
+    templates: Vec<(String, String)>,
+}
+
+pub async fn homepage(State(database): State<Database>) -> impl IntoResponse {
+    let base = crate::base::BaseStore::new();
+    let mut pinned = Vec::new();
+
+    for url in base.pinned {
+        // password-protected (and private) pastes are never featured
+        match database.get_paste_by_url(url).await {
+            Ok(p)
+                if p.metadata.view_password.is_empty()
+                    && database.can_view_paste(&p, None).await =>
+            {
+                pinned.push(PinnedPaste {
+                    title: if p.metadata.title.is_empty() {
+                        p.url.clone()
+                    } else {
+                        p.metadata.title
+                    },
+                    snippet: if p.metadata.description.is_empty() {
+                        p.content.chars().take(150).collect::<String>()
+                    } else {
+                        p.metadata.description
+                    },
+                    url: p.url,
+                })
+            }
+            _ => continue,
+        }
+    }
+
+    let mut templates = Vec::new();
+    for url in base.templates {
+        if let Some(p) = database.get_template_by_url(url, None).await {
+            templates.push(match p.metadata.title.is_empty() {
+                true => (p.url.clone(), p.url),
+                false => (p.url, p.metadata.title),
+            });
+        }
+    }
+
+    Html(
+        HomepageTemplate {
+            content: if base.homepage_file.is_empty() {
+                String::new()
+            } else {
+                render_markdown_file(std::path::Path::new(&base.homepage_file)).unwrap_or_default()
+            },
+            pinned,
+            stats: database.get_instance_stats().await,
+            templates,
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
+#[derive(Template)]
+#[template(path = "paste_new.html")]
+struct NewPasteTemplate {
+    /// The template the editor is filled with
+    template: Option<Paste>,
+    /// If pastes can have a view password
+    view_passwords: bool,
+    /// If pastes can be private (only for logged-in users on instances with paste ownership)
+    can_be_private: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewPasteQuery {
+    /// The url of the template to start from
+    #[serde(default)]
+    from: String,
+}
+
+/// The editor for a new paste, with all of its creation options (`/new`, `?from=:url` starts it
+/// from a template)
+///
+/// The form is published to `POST /new` (the same as `/api/new`).
+pub async fn new_paste_request(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Query(query): Query<NewPasteQuery>,
+) -> impl IntoResponse {
+    let auth_user = match jar.get("__Secure-Token") {
+        Some(c) => database
+            .base
+            .auth
+            .get_profile_by_unhashed(c.value_trimmed().to_string())
+            .await
+            .ok(),
+        None => None,
+    };
+
+    let template = match query.from.is_empty() {
+        true => None,
+        false => {
+            database
+                .get_template_by_url(query.from, auth_user.as_ref())
+                .await
+        }
+    };
+
+    Html(
+        NewPasteTemplate {
+            template,
+            view_passwords: database.base.options.view_password,
+            can_be_private: database.base.options.paste_ownership && auth_user.is_some(),
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    query: String,
+    results: Vec<SearchResult>,
+    page: i32,
+    has_next: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    page: i32,
+}
 
-pub async fn homepage() -> impl IntoResponse {
-    Html(HomepageTemplate {}.render().unwrap())
+/// Search public pastes (`/search?q=`)
+pub async fn search_request(
+    State(database): State<Database>,
+    Query(query_params): Query<SearchQuery>,
+) -> impl IntoResponse {
+    let page = query_params.page.max(0);
+    let (results, has_next) = database.search_pastes(query_params.q.clone(), page).await;
+
+    Html(
+        SearchTemplate {
+            query: query_params.q,
+            results,
+            page,
+            has_next,
+        }
+        .render()
+        .unwrap(),
+    )
 }
 
 #[derive(Template)]
@@ -49,12 +274,225 @@ struct PasteViewTemplate {
     title: String,
     views: i32,
     head_stuff: String,
+    nav: Option<CollectionNav>,
+    /// `dir` of the rendered content
+    direction: String,
+    /// `lang` of the rendered content
+    lang: String,
+    /// The name of the language the content is written in (`None` for markdown)
+    language: Option<&'static str>,
+    stats: ContentStats,
+    /// Public origin of the instance (for absolute links)
+    origin: String,
+    /// Preview text of the paste (`og:description`)
+    description: String,
+    /// Absolute url of the paste
+    canonical_url: String,
+    /// When the paste was published (ISO 8601)
+    published_time: String,
+    /// If search engines should be kept from indexing the paste
+    noindex: bool,
+    /// The url the paste was imported from
+    source: String,
+    /// The url of the paste this one was forked from
+    forked_from: String,
+    /// The paste's custom fields, as JSON for scripts on the page (empty if it has none)
+    extra_json: String,
+    /// The paste's files, shown after its content
+    files: Vec<RenderedFile>,
+    wiki: Option<WikiNav>,
+    /// `None` when the paste doesn't take comments
+    comments: Option<CommentSection>,
+    reactions: Vec<ReactionCount>,
+}
+
+/// A file of a paste, rendered for its view page
+struct RenderedFile {
+    name: String,
+    /// The name of the language the file is written in (`None` for markdown)
+    language: Option<&'static str>,
+    rendered: String,
+}
+
+/// Render the files of a paste (like its content, in the language picked from their name)
+fn render_files(files: &[PasteFile], access: &AccessQuery) -> Vec<RenderedFile> {
+    files
+        .iter()
+        .map(|f| {
+            let language = crate::language::for_file(&f.name, &f.content);
+            // (file names can't have a `/`, so this can't be the url of a paste)
+            let (rendered, _) =
+                render_paste(&format!("{}/{}", f.url, f.name), &f.content, language);
+
+            RenderedFile {
+                name: f.name.clone(),
+                language: match crate::language::is_code(language) {
+                    true => crate::language::get(language).map(|l| l.label),
+                    false => None,
+                },
+                rendered: crate::attachments::resolve(&rendered, &f.url, access),
+            }
+        })
+        .collect()
+}
+
+/// Header keeping search engines from indexing a page
+const NOINDEX: (&str, &str) = ("X-Robots-Tag", "noindex");
+
+/// Responses with an `ETag` are kept by browsers (not shared caches, they depend on cookies), but
+/// checked again before being reused
+pub(crate) const REVALIDATE: &str = "private, no-cache";
+
+/// Amount of content characters used for the preview text of pastes without a description
+const PREVIEW_LENGTH: usize = 200;
+
+/// Get the preview text of a paste (its description, or the start of its content)
+pub fn paste_description(paste: &Paste) -> String {
+    if paste.metadata.description.is_empty() {
+        paste
+            .content
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .chars()
+            .take(PREVIEW_LENGTH)
+            .collect()
+    } else {
+        paste.metadata.description.clone()
+    }
+}
+
+/// Navigation between the pastes of a collection, shown on the view page
+#[derive(Debug)]
+struct CollectionNav {
+    name: String,
+    title: String,
+    previous: Option<String>,
+    next: Option<String>,
+    position: usize,
+    total: usize,
+}
+
+/// The sidebar of a wiki, shown on the view page of its namespace and its pages
+#[derive(Debug)]
+struct WikiNav {
+    /// The `url` of the paste the pages are under
+    namespace: String,
+    title: String,
+    /// The names of the pages
+    pages: Vec<String>,
+    /// The name of the page being viewed (empty for the namespace)
+    current: String,
+}
+
+/// The comments shown below a paste
+#[derive(Debug)]
+struct CommentSection {
+    /// If new comments can be left
+    open: bool,
+    /// If comments can answer each other
+    threaded: bool,
+    /// If the user viewing the paste is logged in (and comments with their username)
+    logged_in: bool,
+    /// The comments, in the order they're shown
+    comments: Vec<ShownComment>,
+}
+
+#[derive(Debug)]
+struct ShownComment {
+    comment: Comment,
+    /// How many comments up the thread this one is answering (0 for comments on the paste)
+    depth: usize,
+    /// If the comment was left by the user viewing the paste
+    mine: bool,
+}
+
+/// Answers this deep in a thread are shown at the same depth as their parent
+const MAX_COMMENT_DEPTH: usize = 6;
+
+/// Get the comments shown below a paste (`None` when the instance or the paste doesn't take them)
+///
+/// # Arguments
+/// * `owner` - who is viewing the paste (see [`crate::api::session_owner`])
+async fn comment_section(
+    database: &Database,
+    paste: &Paste,
+    policy: &CommentPolicy,
+    owner: &str,
+    logged_in: bool,
+) -> Option<CommentSection> {
+    use crate::config::CommentMode;
+
+    let mode = crate::config::comment_mode();
+    if (mode == CommentMode::Off) || (*policy == CommentPolicy::Off) {
+        return None;
+    }
+
+    /// Add the answers to `id` (oldest first), each followed by its own answers
+    fn thread(comments: &[Comment], id: &str, depth: usize, shown: &mut Vec<(Comment, usize)>) {
+        for comment in comments.iter().filter(|c| c.reply_to == id) {
+            shown.push((comment.clone(), depth));
+            thread(comments, &comment.id, depth + 1, shown);
+        }
+    }
+
+    let comments = database.get_comments_by_url(paste.url.clone()).await;
+    let shown = match mode {
+        CommentMode::Threaded => {
+            let mut shown = Vec::with_capacity(comments.len());
+            thread(&comments, "", 0, &mut shown);
+            shown
+        }
+        _ => comments.into_iter().map(|c| (c, 0)).collect(),
+    };
+
+    Some(CommentSection {
+        open: *policy == CommentPolicy::Open,
+        threaded: mode == CommentMode::Threaded,
+        logged_in,
+        comments: shown
+            .into_iter()
+            .map(|(comment, depth)| ShownComment {
+                mine: !owner.is_empty() && (comment.owner == owner),
+                depth: depth.min(MAX_COMMENT_DEPTH),
+                comment,
+            })
+            .collect(),
+    })
+}
+
+/// Get the sidebar of the wiki a paste is part of (as its namespace, or as one of its pages)
+async fn wiki_nav(database: &Database, paste: &Paste) -> Option<WikiNav> {
+    let (namespace, current) = match database.get_page_by_url(paste.url.clone()).await {
+        Some(page) => (
+            database.get_paste_by_url(page.namespace).await.ok()?,
+            page.name,
+        ),
+        None => (paste.clone(), String::new()),
+    };
+
+    let pages = database.get_pages(namespace.url.clone()).await;
+
+    if pages.is_empty() {
+        return None;
+    }
+
+    Some(WikiNav {
+        title: match namespace.metadata.title.is_empty() {
+            true => namespace.url.clone(),
+            false => namespace.metadata.title,
+        },
+        namespace: namespace.url,
+        pages: pages.into_iter().map(|p| p.name).collect(),
+        current,
+    })
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PasteViewQuery {
+    /// The collection the paste is being viewed in
     #[serde(default)]
-    view_password: String,
+    c: String,
 }
 
 #[derive(Template)]
@@ -63,258 +501,1477 @@ struct PastePasswordTemplate {
     paste: Paste,
 }
 
+/// The page shown instead of a paste outside of its time window
+#[derive(Template)]
+#[template(path = "paste_unavailable.html")]
+struct PasteUnavailableTemplate {
+    paste: Paste,
+    /// If the window hasn't opened yet (it's over otherwise)
+    opening: bool,
+    /// When the window opens (or closed)
+    time: u64,
+}
+
 #[derive(Template)]
 #[template(path = "error.html")]
 struct ErrorViewTemplate {
     error: String,
 }
 
+/// Render an error page
+pub fn error_page(error: String) -> String {
+    ErrorViewTemplate {
+        error: error.clone(),
+    }
+    .render()
+    .unwrap_or(error)
+}
+
+/// Get the `dir` attribute of a paste's rendered content
+fn content_direction(paste: &Paste, direction: &Direction) -> &'static str {
+    match direction {
+        Direction::Auto => detect_direction(&paste.content),
+        Direction::Ltr => "ltr",
+        Direction::Rtl => "rtl",
+    }
+}
+
+/// Make the `ETag` of a response showing `paste` (see [`crate::utility::etag`])
+///
+/// # Arguments
+/// * `variant` - anything else the response depends on (locale, theme, ...)
+pub(crate) fn paste_etag(paste: &Paste, extra: &ExtraMetadata, variant: &[&str]) -> String {
+    let metadata = serde_json::to_string(&paste.metadata).unwrap_or_default();
+    let extra = serde_json::to_string(extra).unwrap_or_default();
+    let render = format!("{:?}", crate::config::render_options());
+
+    let mut parts = vec![
+        env!("CARGO_PKG_VERSION"),
+        paste.content.as_str(),
+        metadata.as_str(),
+        extra.as_str(),
+        render.as_str(),
+    ];
+
+    parts.extend_from_slice(variant);
+    crate::utility::etag(&parts)
+}
+
+/// The response to a request whose `If-None-Match` matched `etag`
+pub(crate) fn not_modified(etag: String) -> Response {
+    (
+        StatusCode::NOT_MODIFIED,
+        [
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, REVALIDATE.to_string()),
+        ],
+    )
+        .into_response()
+}
+
+/// The page shown instead of a paste the user sending the request can't view (see
+/// [`crate::access::check`])
+pub(crate) fn denied_response(denied: Denied) -> Response {
+    let page = match denied {
+        Denied::Missing(e) => ErrorViewTemplate {
+            error: crate::i18n::error_text(&e),
+        }
+        .render(),
+        Denied::Network => ErrorViewTemplate {
+            error: crate::i18n::current_text("error.network"),
+        }
+        .render(),
+        Denied::Hidden => ErrorViewTemplate {
+            error: crate::i18n::error_text(&PasteError::NotFound),
+        }
+        .render(),
+        Denied::Unavailable {
+            paste,
+            opening,
+            time,
+        } => PasteUnavailableTemplate {
+            paste,
+            opening,
+            time,
+        }
+        .render(),
+        Denied::Password(paste) => PastePasswordTemplate { paste }.render(),
+    };
+
+    ([NOINDEX], Html(page.unwrap())).into_response()
+}
+
 pub async fn view_paste_request(
     jar: CookieJar,
-    Path(url): Path<String>,
+    headers: HeaderMap,
     State(database): State<Database>,
+    protected: ProtectedPaste,
     Query(query_params): Query<PasteViewQuery>,
-) -> impl IntoResponse {
-    match database.get_paste_by_url(url).await {
-        Ok(p) => {
-            // get user from token
-            let auth_user = match jar.get("__Secure-Token") {
-                Some(c) => match database
-                    .auth
-                    .get_profile_by_unhashed(c.value_trimmed().to_string())
-                    .await
-                {
-                    Ok(ua) => Some(ua),
-                    Err(_) => None,
-                },
-                None => None,
-            };
+) -> Response {
+    let ProtectedPaste {
+        paste: p,
+        auth_user,
+        access,
+    } = protected;
 
-            // check for view password
-            if database.options.view_password == true {
-                match query_params.view_password.is_empty() {
-                    false => {
-                        if !p.metadata.view_password.is_empty()
-                            && (query_params.view_password != p.metadata.view_password)
-                        {
-                            return Html(PastePasswordTemplate { paste: p }.render().unwrap());
-                        }
-                    }
-                    true => {
-                        if !p.metadata.view_password.is_empty() {
-                            return Html(PastePasswordTemplate { paste: p }.render().unwrap());
-                        }
-                    }
-                }
-            }
+    let username = auth_user
+        .as_ref()
+        .map(|u| u.username.clone())
+        .unwrap_or_default();
 
-            // push view
-            // we could not support paste views by just.. not doing this
-            if let Err(e) = database.incr_views_by_url(p.url.clone(), auth_user).await {
-                return Html(
-                    ErrorViewTemplate {
-                        error: e.to_string(),
-                    }
-                    .render()
-                    .unwrap(),
-                );
+    // push view
+    // we could not support paste views by just.. not doing this
+    if let Err(e) = database.incr_views_by_url(p.url.clone(), auth_user).await {
+        return Html(
+            ErrorViewTemplate {
+                error: crate::i18n::error_text(&e),
             }
+            .render()
+            .unwrap(),
+        )
+        .into_response();
+    }
 
-            // ...
-            let rendered = parse_markdown(p.content.clone());
-            Html(
-                PasteViewTemplate {
-                    paste: p.clone(),
-                    rendered,
-                    title: match p.metadata.title.is_empty() {
-                        true => p.url.clone(),
-                        false => p.metadata.title,
-                    },
-                    views: database.get_views_by_url(p.url).await,
-                    head_stuff: format!(
-                        "<meta property=\"og:description\" content=\"{}\" />
-                        <meta name=\"theme-color\" content=\"{}\" />
-                        <link rel=\"icon\" href=\"{}\" />",
-                        if p.metadata.description.is_empty() {
-                            // paste preview text
-                            p.content
-                                .chars()
-                                .take(100)
-                                .collect::<String>()
-                                .replace("\"", "'")
-                        } else {
-                            p.metadata.description
-                        },
-                        if p.metadata.theme_color.is_empty() {
-                            "#6ee7b7"
+    crate::events::publish(crate::events::Event::Viewed {
+        paste: p.clone(),
+        view: crate::analytics::view(&database, &headers).await,
+    });
+
+    // get collection navigation
+    let nav = if query_params.c.is_empty() {
+        None
+    } else {
+        match database.get_collection_by_name(query_params.c).await {
+            Ok(collection) => {
+                collection
+                    .pastes
+                    .iter()
+                    .position(|u| u == &p.url)
+                    .map(|i| CollectionNav {
+                        previous: if i > 0 {
+                            Some(collection.pastes[i - 1].clone())
                         } else {
-                            &p.metadata.theme_color
+                            None
                         },
-                        if p.metadata.favicon.is_empty() {
-                            "/static/favicon.svg"
-                        } else {
-                            &p.metadata.favicon
-                        }
-                    ),
-                }
-                .render()
-                .unwrap(),
-            )
-        }
-        Err(e) => Html(
-            ErrorViewTemplate {
-                error: e.to_string(),
+                        next: collection.pastes.get(i + 1).cloned(),
+                        position: i + 1,
+                        total: collection.pastes.len(),
+                        name: collection.name,
+                        title: collection.title,
+                    })
             }
-            .render()
-            .unwrap(),
-        ),
+            Err(_) => None,
+        }
+    };
+
+    // get content direction
+    let extra = database.get_extra_metadata_by_url(p.url.clone()).await;
+    let direction = content_direction(&p, &extra.direction);
+    let files = database.get_files_by_url(p.url.clone()).await;
+    let wiki = wiki_nav(&database, &p).await;
+    let session = crate::api::session_owner(&jar, &database)
+        .await
+        .unwrap_or_default();
+    let comments = comment_section(
+        &database,
+        &p,
+        &extra.comments,
+        &session,
+        !username.is_empty(),
+    )
+    .await;
+    let reactions = database.get_reactions_by_url(p.url.clone(), &session).await;
+
+    // (the view count isn't part of it, so a refresh can show an old count)
+    let announcement = crate::database::current_announcement().map(|a| a.text);
+    let etag = paste_etag(
+        &p,
+        &extra,
+        &[
+            &crate::middleware::current_locale(),
+            crate::middleware::current_preferences().theme.as_str(),
+            // (the page carries the session's CSRF token, which changes when it's reissued)
+            &crate::middleware::current_csrf_token(),
+            &format!("{nav:?}"),
+            &format!("{wiki:?}"),
+            &format!("{comments:?}"),
+            &format!("{reactions:?}"),
+            &username,
+            &announcement.unwrap_or_default(),
+            &files
+                .iter()
+                .map(|f| format!("{}:{}", f.name, f.date_edited))
+                .collect::<Vec<String>>()
+                .join(","),
+        ],
+    );
+
+    if crate::utility::etag_matches(&headers, &etag) {
+        return not_modified(etag);
     }
-}
 
-#[derive(Template)]
-#[template(path = "paste_editor.html")]
-struct EditorTemplate {
-    paste: Paste,
-    passwordless: bool,
+    // ...
+    let (rendered, stats) = render_paste(&p.url, &p.content, &extra.language);
+    let rendered = crate::attachments::resolve(&rendered, &p.url, &access);
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    let description = paste_description(&p);
+    let noindex = (extra.visibility != Visibility::Public) | !p.metadata.view_password.is_empty();
+
+    let page = Html(
+        PasteViewTemplate {
+            paste: p.clone(),
+            rendered,
+            title: match p.metadata.title.is_empty() {
+                true => p.url.clone(),
+                false => p.metadata.title,
+            },
+            views: database.get_views_by_url(p.url.clone()).await,
+            nav,
+            direction: direction.to_string(),
+            lang: extra.lang,
+            language: match crate::language::is_code(&extra.language) {
+                true => crate::language::get(&extra.language).map(|l| l.label),
+                false => None,
+            },
+            source: extra.source,
+            forked_from: extra.forked_from,
+            // (`<` is escaped so the JSON can't close its `<script>`)
+            extra_json: match extra.custom.is_empty() {
+                true => String::new(),
+                false => serde_json::to_string(&extra.custom)
+                    .unwrap_or_default()
+                    .replace('<', "\\u003c"),
+            },
+            files: render_files(&files, &access),
+            wiki,
+            comments,
+            reactions,
+            stats,
+            description,
+            canonical_url: format!("{}/{}", origin, p.url),
+            published_time: iso8601(p.date_published),
+            noindex,
+            origin,
+            head_stuff: format!(
+                "<meta name=\"theme-color\" content=\"{}\" />
+                    <link rel=\"icon\" href=\"{}\" />",
+                if p.metadata.theme_color.is_empty() {
+                    "#6ee7b7"
+                } else {
+                    &p.metadata.theme_color
+                },
+                if p.metadata.favicon.is_empty() {
+                    crate::base::BaseStore::new().favicon_url
+                } else {
+                    p.metadata.favicon.clone()
+                }
+            ),
+        }
+        .render()
+        .unwrap(),
+    );
+
+    let cache = [
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, REVALIDATE.to_string()),
+    ];
+
+    match noindex {
+        true => ([NOINDEX], cache, page).into_response(),
+        false => (cache, page).into_response(),
+    }
 }
 
-pub async fn editor_request(
+/// A page of a wiki (`/:url/:page`), shown like any other paste (with the wiki's sidebar)
+pub async fn wiki_page_request(
     jar: CookieJar,
-    Path(url): Path<String>,
+    headers: HeaderMap,
+    uri: Uri,
+    Path((url, page)): Path<(String, String)>,
     State(database): State<Database>,
-    Query(query_params): Query<PasteViewQuery>,
-) -> impl IntoResponse {
-    // get user from token
-    let auth_user = match jar.get("__Secure-Token") {
-        Some(c) => match database
-            .auth
-            .get_profile_by_unhashed(c.value_trimmed().to_string())
-            .await
-        {
-            Ok(ua) => ua.username,
-            Err(_) => String::new(),
+    query: Query<PasteViewQuery>,
+) -> Response {
+    let page = match database.get_paste_by_url(url).await {
+        Ok(namespace) => match database.get_page(namespace.url, page.to_lowercase()).await {
+            Ok(page) => database.get_paste_by_url(page.url).await,
+            Err(e) => Err(e),
         },
-        None => String::new(),
+        Err(e) => Err(e),
     };
 
-    // ...
-    match database.get_paste_by_url(url).await {
-        Ok(p) => {
-            // check for view password
-            if database.options.view_password == true {
-                match query_params.view_password.is_empty() {
-                    false => {
-                        if !p.metadata.view_password.is_empty()
-                            && (query_params.view_password != p.metadata.view_password)
-                        {
-                            return Html(PastePasswordTemplate { paste: p }.render().unwrap());
-                        }
-                    }
-                    true => {
-                        if !p.metadata.view_password.is_empty() {
-                            return Html(PastePasswordTemplate { paste: p }.render().unwrap());
-                        }
-                    }
-                }
-            }
+    let access = Query::<AccessQuery>::try_from_uri(&uri)
+        .map(|q| q.0)
+        .unwrap_or_default();
 
-            // ...
-            let passwordless = !p.metadata.owner.is_empty() && auth_user == p.metadata.owner;
-            Html(
-                EditorTemplate {
-                    paste: p,
-                    passwordless,
-                }
-                .render()
-                .unwrap(),
-            )
-        }
+    match page {
+        Ok(page) => match ProtectedPaste::new(&database, &jar, page, &access).await {
+            Ok(protected) => {
+                view_paste_request(jar, headers, State(database), protected, query).await
+            }
+            Err(denied) => denied.into_response(),
+        },
         Err(e) => Html(
             ErrorViewTemplate {
-                error: e.to_string(),
+                error: crate::i18n::error_text(&e),
             }
             .render()
             .unwrap(),
-        ),
+        )
+        .into_response(),
     }
 }
 
-#[derive(Template)]
-#[template(path = "paste_metadata.html")]
-struct ConfigEditorTemplate {
-    paste: Paste,
-    paste_metadata: String,
-    auth_user: String,
-    passwordless: bool,
-}
+/// How often an event stream sends the view count (when it changed), and checks if it should end
+const EVENTS_TICK: Duration = Duration::from_secs(1);
 
-pub async fn config_editor_request(
-    jar: CookieJar,
-    Path(url): Path<String>,
+/// Live updates of a paste for its open view pages (`/:url/events`)
+///
+/// A stream of server-sent events: `views` (`{"views": 12}`, at most once every [`EVENTS_TICK`])
+/// when the paste is viewed, `edited` (`{"url": "..."}`, where the paste is now) when it's edited
+/// or renamed, and `deleted`, which ends the stream. Streams also end when the server shuts down.
+pub async fn paste_events_request(
     State(database): State<Database>,
-    Query(query_params): Query<PasteViewQuery>,
-) -> impl IntoResponse {
-    // get user from token
-    let auth_user = match jar.get("__Secure-Token") {
-        Some(c) => match database
-            .auth
-            .get_profile_by_unhashed(c.value_trimmed().to_string())
-            .await
-        {
-            Ok(ua) => ua.username,
-            Err(_) => String::new(),
-        },
-        None => String::new(),
+    protected: ProtectedPaste,
+) -> Response {
+    use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+    use crate::events::Event;
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut paste = protected.paste;
+
+    let Some(mut events) = crate::events::subscribe() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
     };
 
-    // ...
-    match database.get_paste_by_url(url).await {
-        Ok(p) => {
-            // check for view password
-            if database.options.view_password == true {
-                match query_params.view_password.is_empty() {
-                    false => {
-                        if !p.metadata.view_password.is_empty()
-                            && (query_params.view_password != p.metadata.view_password)
-                        {
-                            return Html(PastePasswordTemplate { paste: p }.render().unwrap());
-                        }
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<SseEvent, Infallible>>(16);
+
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(EVENTS_TICK);
+        let mut viewed = false;
+
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = ticks.tick() => {
+                    if crate::shutdown::stopping() || tx.is_closed() {
+                        return;
                     }
-                    true => {
-                        if !p.metadata.view_password.is_empty() {
-                            return Html(PastePasswordTemplate { paste: p }.render().unwrap());
+
+                    if std::mem::take(&mut viewed) {
+                        let views = database.get_views_by_url(paste.url.clone()).await;
+                        let event = SseEvent::default()
+                            .event("views")
+                            .data(serde_json::json!({ "views": views }).to_string());
+
+                        if tx.send(Ok(event)).await.is_err() {
+                            return;
                         }
                     }
+
+                    continue;
                 }
-            }
+            };
 
-            // ...
-            let passwordless = !p.metadata.owner.is_empty() && auth_user == p.metadata.owner;
-            Html(
-                ConfigEditorTemplate {
-                    paste: p.clone(),
-                    paste_metadata: match serde_json::to_string(&p.metadata) {
-                        Ok(m) => m,
-                        Err(_) => {
-                            return Html(
-                                ErrorViewTemplate {
-                                    error: pastemd::model::PasteError::Other.to_string(),
-                                }
-                                .render()
-                                .unwrap(),
-                            )
-                        }
-                    },
-                    auth_user,
-                    passwordless,
+            // (pastes keep their id when they're renamed)
+            let event = match event {
+                Ok(Event::Viewed { paste: p, .. }) if p.id == paste.id => {
+                    viewed = true;
+                    continue;
                 }
-                .render()
-                .unwrap(),
-            )
+                Ok(Event::Edited { paste: p, .. }) if p.id == paste.id => {
+                    let event = SseEvent::default()
+                        .event("edited")
+                        .data(serde_json::json!({ "url": p.url }).to_string());
+
+                    paste.url = p.url;
+                    event
+                }
+                Ok(Event::Deleted { paste: p, .. }) if p.id == paste.id => {
+                    let _ = tx
+                        .send(Ok(SseEvent::default().event("deleted").data("{}")))
+                        .await;
+                    return;
+                }
+                Err(RecvError::Closed) => return,
+                _ => continue,
+            };
+
+            if tx.send(Ok(event)).await.is_err() {
+                return;
+            }
         }
+    });
+
+    Sse::new(ReceiverStream::new(rx))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Pastes larger than this (in bytes) are streamed from the database instead of loaded at once
+const STREAM_THRESHOLD: usize = 1024 * 1024;
+/// How many characters of a streamed paste are read from the database at a time
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Parse a `Range` header against a body of `length` bytes
+///
+/// Only single ranges are supported (`bytes=0-99`, `bytes=100-` and `bytes=-100`), anything else
+/// is ignored and the whole body is sent (which clients have to accept).
+///
+/// # Returns
+/// * `None` if the whole body should be sent
+/// * `Some(Ok((start, end)))` for the (inclusive) range to send
+/// * `Some(Err(()))` if the range can't be satisfied (`416`)
+fn parse_range(header: &str, length: usize) -> Option<Result<(usize, usize), ()>> {
+    let range = header.trim().strip_prefix("bytes=")?;
+
+    if range.contains(',') {
+        return None;
+    }
+
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = match (start.is_empty(), end.is_empty()) {
+        // the last `end` bytes
+        (true, false) => {
+            let suffix: usize = end.parse().ok()?;
+
+            if suffix == 0 || length == 0 {
+                return Some(Err(()));
+            }
+
+            (length.saturating_sub(suffix), length - 1)
+        }
+        (false, _) => {
+            let start: usize = start.parse().ok()?;
+            let end: usize = match end.is_empty() {
+                true => usize::MAX,
+                false => end.parse().ok()?,
+            };
+
+            if end < start {
+                return None;
+            }
+
+            if start >= length {
+                return Some(Err(()));
+            }
+
+            (start, end.min(length - 1))
+        }
+        (true, true) => return None,
+    };
+
+    Some(Ok((start, end)))
+}
+
+/// Serve the content of a paste as text
+///
+/// Large pastes are read from the database a chunk at a time while they're sent, so concurrent
+/// downloads don't each hold a whole paste in memory. Single byte ranges (`Range`) are supported,
+/// so interrupted downloads can be resumed.
+///
+/// # Arguments
+/// * `download` - if the content is sent as a file (`Content-Disposition: attachment`)
+async fn raw_response(
+    database: Database,
+    jar: &CookieJar,
+    headers: &HeaderMap,
+    url: String,
+    access: &AccessQuery,
+    download: bool,
+) -> Response {
+    let error = |e: PasteError| {
+        Html(
+            ErrorViewTemplate {
+                error: crate::i18n::error_text(&e),
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response()
+    };
+
+    // (the size is only known for exact urls, shortlink codes are resolved by `get_paste_by_url`)
+    let size = database.get_paste_size_by_url(url.clone()).await;
+    let streamed = size.is_some_and(|(bytes, _)| bytes > STREAM_THRESHOLD);
+
+    let paste = match streamed {
+        true => database
+            .get_paste_info_by_url(url)
+            .await
+            .ok_or(PasteError::NotFound),
+        false => database.get_paste_by_url(url).await,
+    };
+
+    let p = match paste {
+        Ok(p) => match ProtectedPaste::new(&database, jar, p, access).await {
+            Ok(protected) => protected.paste,
+            Err(denied) => return denied.into_response(),
+        },
+        Err(e) => return error(e),
+    };
+
+    let language = database
+        .get_extra_metadata_by_url(p.url.clone())
+        .await
+        .language;
+
+    // (every edit changes `date_edited`)
+    let etag = crate::utility::etag(&[&p.url, &p.date_edited.to_string(), "raw", &language]);
+
+    if crate::utility::etag_matches(headers, &etag) {
+        return not_modified(etag);
+    }
+
+    let length = match (streamed, size) {
+        (true, Some((bytes, _))) => bytes,
+        _ => p.content.len(),
+    };
+
+    // (`If-Range` only sends the range if the paste wasn't edited since the client's copy)
+    let if_range = headers
+        .get(header::IF_RANGE)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.trim().trim_start_matches("W/"));
+
+    let range = match if_range {
+        Some(tag) if tag != etag => None,
+        _ => headers
+            .get(header::RANGE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| parse_range(h, length)),
+    };
+
+    let (first, last) = match range {
+        Some(Ok(range)) => range,
+        Some(Err(())) => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [
+                    (header::CONTENT_RANGE, format!("bytes */{length}")),
+                    (header::ACCEPT_RANGES, "bytes".to_string()),
+                ],
+            )
+                .into_response();
+        }
+        None => (0, length.saturating_sub(1)),
+    };
+
+    let mut res = match streamed {
+        true => {
+            let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, std::io::Error>>(2);
+            let url = p.url.clone();
+
+            tokio::spawn(async move {
+                let mut start = 0;
+                // the byte offset of the next chunk
+                let mut offset = 0;
+
+                loop {
+                    let part = database
+                        .get_paste_content_part(url.clone(), start, STREAM_CHUNK_SIZE)
+                        .await;
+
+                    let part = match part {
+                        Some(part) if part.is_empty() => return,
+                        Some(part) => part,
+                        None => {
+                            // (cuts the response short, instead of ending it as if it was complete)
+                            let _ = tx.send(Err(std::io::Error::other("read failed"))).await;
+                            return;
+                        }
+                    };
+
+                    start += STREAM_CHUNK_SIZE;
+
+                    // (chunks are read by character, so the ones before the range are still read)
+                    let part = part.into_bytes();
+                    let part_offset = offset;
+                    offset += part.len();
+
+                    if offset <= first {
+                        continue;
+                    }
+
+                    let from = first.saturating_sub(part_offset);
+                    let to = (last + 1 - part_offset).min(part.len());
+
+                    // stop once the client is gone
+                    if tx.send(Ok(part[from..to].to_vec())).await.is_err() || offset > last {
+                        return;
+                    }
+                }
+            });
+
+            Body::from_stream(ReceiverStream::new(rx)).into_response()
+        }
+        false => match range {
+            Some(_) => Body::from(p.content.into_bytes()[first..=last].to_vec()).into_response(),
+            None => Body::from(p.content).into_response(),
+        },
+    };
+
+    if range.is_some() {
+        *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+        res.headers_mut().insert(
+            header::CONTENT_RANGE,
+            format!("bytes {first}-{last}/{length}").parse().unwrap(),
+        );
+    }
+
+    let sent = match length {
+        0 => 0,
+        _ => last - first + 1,
+    };
+
+    let headers = res.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        crate::language::content_type(&language).parse().unwrap(),
+    );
+    headers.insert(header::ETAG, etag.parse().unwrap());
+    headers.insert(header::CACHE_CONTROL, REVALIDATE.parse().unwrap());
+    headers.insert(NOINDEX.0, NOINDEX.1.parse().unwrap());
+    headers.insert(header::ACCEPT_RANGES, "bytes".parse().unwrap());
+    headers.insert(header::CONTENT_LENGTH, sent.into());
+
+    if download {
+        let file_name = p.url.replace(['/', '\\', '"'], "_");
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}.md\"")
+                .parse()
+                .unwrap(),
+        );
+    }
+
+    res
+}
+
+/// The content of a paste as plain text (`/:url/raw`)
+pub async fn raw_paste_request(
+    jar: CookieJar,
+    headers: HeaderMap,
+    Path(url): Path<String>,
+    State(database): State<Database>,
+    Query(access): Query<AccessQuery>,
+) -> Response {
+    raw_response(database, &jar, &headers, url, &access, false).await
+}
+
+/// One of a paste's files as plain text (`/:url/raw/:name`)
+pub async fn raw_file_request(
+    headers: HeaderMap,
+    Path((_, name)): Path<(String, String)>,
+    State(database): State<Database>,
+    ProtectedPaste { paste: p, .. }: ProtectedPaste,
+) -> Response {
+    let file = match database.get_file(p.url, name).await {
+        Ok(f) => f,
+        Err(e) => {
+            return Html(
+                ErrorViewTemplate {
+                    error: crate::i18n::error_text(&e),
+                }
+                .render()
+                .unwrap(),
+            )
+            .into_response()
+        }
+    };
+
+    let etag = crate::utility::etag(&[&file.url, &file.name, &file.date_edited.to_string(), "raw"]);
+
+    if crate::utility::etag_matches(&headers, &etag) {
+        return not_modified(etag);
+    }
+
+    let language = crate::language::for_file(&file.name, &file.content);
+
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                crate::language::content_type(language),
+            ),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, REVALIDATE.to_string()),
+        ],
+        file.content,
+    )
+        .into_response()
+}
+
+/// A file attached to a paste (`/:url/attachments/:name`)
+///
+/// Only images, audio and video are shown in the browser, everything else is downloaded.
+pub async fn attachment_request(
+    headers: HeaderMap,
+    Path((_, name)): Path<(String, String)>,
+    State(database): State<Database>,
+    ProtectedPaste {
+        paste: p,
+        auth_user,
+        ..
+    }: ProtectedPaste,
+) -> Response {
+    let error = |e: PasteError| {
+        Html(
+            ErrorViewTemplate {
+                error: crate::i18n::error_text(&e),
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response()
+    };
+
+    let attachment = match database.get_attachment(p.url, name).await {
+        Ok(a) => a,
+        Err(e) => return error(e),
+    };
+
+    // (flagged attachments are only downloaded by administrators, to look at them)
+    if attachment.quarantined
+        && !auth_user.is_some_and(|ua| {
+            starstraw::model::SkillManager(ua.skills)
+                .has_skill(starstraw::model::SkillName::Absolute)
+        })
+    {
+        return error(PasteError::NotFound);
+    }
+
+    // (attachments are replaced under a new key)
+    let etag = crate::utility::etag(&[&attachment.key]);
+
+    if crate::utility::etag_matches(&headers, &etag) {
+        return not_modified(etag);
+    }
+
+    let Some(data) = crate::attachments::read(attachment.key).await else {
+        return error(PasteError::Other);
+    };
+
+    let disposition = match crate::attachments::is_inline(&attachment.content_type) {
+        true => "inline".to_string(),
+        false => format!("attachment; filename=\"{}\"", attachment.name),
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, attachment.content_type),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, REVALIDATE.to_string()),
+        ],
+        [NOINDEX],
+        data,
+    )
+        .into_response()
+}
+
+/// The content of a paste as a markdown file (`/:url/download`)
+pub async fn download_paste_request(
+    jar: CookieJar,
+    headers: HeaderMap,
+    Path(url): Path<String>,
+    State(database): State<Database>,
+    Query(access): Query<AccessQuery>,
+) -> Response {
+    raw_response(database, &jar, &headers, url, &access, true).await
+}
+
+#[derive(Template)]
+#[template(path = "paste_print.html")]
+struct PastePrintTemplate {
+    paste: Paste,
+    rendered: String,
+    title: String,
+    direction: String,
+    lang: String,
+}
+
+/// Render a paste without any page chrome, for printing (`/:url/print`)
+pub async fn print_paste_request(
+    State(database): State<Database>,
+    ProtectedPaste {
+        paste: p, access, ..
+    }: ProtectedPaste,
+) -> impl IntoResponse {
+    let extra = database.get_extra_metadata_by_url(p.url.clone()).await;
+    let direction = content_direction(&p, &extra.direction);
+
+    Html(
+        PastePrintTemplate {
+            rendered: crate::attachments::resolve(
+                &parse_markdown(p.content.clone()),
+                &p.url,
+                &access,
+            ),
+            title: match p.metadata.title.is_empty() {
+                true => p.url.clone(),
+                false => p.metadata.title.clone(),
+            },
+            direction: direction.to_string(),
+            lang: extra.lang,
+            paste: p,
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
+/// Render the social card image of a paste (`/:url/card.png`)
+pub async fn card_request(
+    jar: CookieJar,
+    Path(url): Path<String>,
+    State(database): State<Database>,
+) -> Response {
+    let p = match database.get_paste_by_url(url).await {
+        Ok(p) => p,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    // cards of password-protected pastes would leak their content
+    if crate::access::check(&database, &jar, &p, "").await.is_err() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let base = crate::base::BaseStore::new();
+    let png = crate::card::render_card(
+        match p.metadata.title.is_empty() {
+            true => &p.url,
+            false => &p.metadata.title,
+        },
+        &paste_description(&p),
+        &base.site_name,
+        &base.theme_accent,
+    );
+
+    // (owners get cards of their private and scheduled pastes, which shared caches can't keep)
+    let extra = database.get_extra_metadata_by_url(p.url.clone()).await;
+    let listed = (extra.visibility == Visibility::Public)
+        && extra.allowed_networks.trim().is_empty()
+        && database.can_view_paste(&p, None).await;
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (
+                header::CACHE_CONTROL,
+                match listed {
+                    true => "public, max-age=3600",
+                    false => "private, max-age=3600",
+                },
+            ),
+        ],
+        png,
+    )
+        .into_response()
+}
+
+#[derive(Template)]
+#[template(path = "paste_embed.html")]
+struct PasteEmbedTemplate {
+    paste: Paste,
+    rendered: String,
+    title: String,
+    direction: String,
+    lang: String,
+}
+
+/// Render only the body of a paste, for embedding in an iframe (`/:url/embed`)
+///
+/// This is the only route that can be framed by other sites.
+pub async fn embed_paste_request(
+    State(database): State<Database>,
+    protected: Result<ProtectedPaste, Denied>,
+) -> impl IntoResponse {
+    // (the page shown instead of the paste is embeddable too)
+    let embeddable = Extension(crate::middleware::Embeddable);
+
+    let ProtectedPaste {
+        paste: p, access, ..
+    } = match protected {
+        Ok(protected) => protected,
+        Err(denied) => return (embeddable, denied.into_response()),
+    };
+
+    let extra = database.get_extra_metadata_by_url(p.url.clone()).await;
+    let direction = content_direction(&p, &extra.direction);
+
+    (
+        embeddable,
+        Html(
+            PasteEmbedTemplate {
+                rendered: crate::attachments::resolve(
+                    &parse_markdown(p.content.clone()),
+                    &p.url,
+                    &access,
+                ),
+                title: match p.metadata.title.is_empty() {
+                    true => p.url.clone(),
+                    false => p.metadata.title.clone(),
+                },
+                direction: direction.to_string(),
+                lang: extra.lang,
+                paste: p,
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response(),
+    )
+}
+
+#[derive(Template)]
+#[template(path = "paste_export.html")]
+struct PasteExportTemplate {
+    rendered: String,
+    title: String,
+    direction: String,
+    lang: String,
+    stylesheet: String,
+    source: String,
+}
+
+/// Download a paste as a single, self-contained HTML file (`/:url/export/html`)
+///
+/// The instance stylesheet is inlined so the file looks right offline. Code blocks aren't
+/// highlighted since that needs highlight.js.
+pub async fn export_html_request(
+    headers: HeaderMap,
+    State(database): State<Database>,
+    ProtectedPaste { paste: p, .. }: ProtectedPaste,
+) -> Response {
+    let extra = database.get_extra_metadata_by_url(p.url.clone()).await;
+    let html = PasteExportTemplate {
+        rendered: parse_markdown(p.content.clone()),
+        title: match p.metadata.title.is_empty() {
+            true => p.url.clone(),
+            false => p.metadata.title.clone(),
+        },
+        direction: content_direction(&p, &extra.direction).to_string(),
+        lang: extra.lang,
+        stylesheet: crate::assets::read("style.css")
+            .map(|s| String::from_utf8_lossy(&s).to_string())
+            .unwrap_or_default(),
+        source: format!(
+            "{}/{}",
+            crate::base::BaseStore::new().origin(&headers),
+            p.url
+        ),
+    }
+    .render()
+    .unwrap();
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.html\"", p.url),
+            ),
+        ],
+        html,
+    )
+        .into_response()
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExportImageQuery {
+    /// The lines to render (`10` or `10-20`, starting at 1)
+    #[serde(default)]
+    lines: String,
+}
+
+/// Parse a line range (`10` or `10-20`)
+fn parse_line_range(lines: &str) -> Option<(usize, usize)> {
+    let (first, last) = match lines.split_once('-') {
+        Some((first, last)) => (first.trim().parse().ok()?, last.trim().parse().ok()?),
+        None => {
+            let line = lines.trim().parse().ok()?;
+            (line, line)
+        }
+    };
+
+    if (first == 0) | (last < first) {
+        return None;
+    }
+
+    Some((first, last))
+}
+
+/// Render a paste (or some of its lines) as a code image (`/:url/export/png`)
+pub async fn export_png_request(
+    ProtectedPaste { paste: p, .. }: ProtectedPaste,
+    Query(query_params): Query<ExportImageQuery>,
+) -> Response {
+    let (first, last) = match query_params.lines.is_empty() {
+        true => (1, crate::screenshot::MAX_LINES),
+        false => match parse_line_range(&query_params.lines) {
+            Some(range) => range,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Html(
+                        ErrorViewTemplate {
+                            error: crate::i18n::error_text(&PasteError::ValueError),
+                        }
+                        .render()
+                        .unwrap(),
+                    ),
+                )
+                    .into_response()
+            }
+        },
+    };
+
+    let png = crate::screenshot::render_code_image(
+        match p.metadata.title.is_empty() {
+            true => &p.url,
+            false => &p.metadata.title,
+        },
+        &p.content,
+        first,
+        last,
+        &crate::base::BaseStore::new().theme_accent,
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/png".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{}.png\"", p.url),
+            ),
+        ],
+        png,
+    )
+        .into_response()
+}
+
+/// Start exporting a paste as a PDF (`/:url/export/pdf`)
+///
+/// The PDF is built in the background, the user is redirected to the job (`/export/:id`) which
+/// downloads it once it's ready.
+pub async fn export_pdf_request(
+    headers: HeaderMap,
+    ProtectedPaste { paste: p, .. }: ProtectedPaste,
+) -> Response {
+    let title = match p.metadata.title.is_empty() {
+        true => p.url.clone(),
+        false => p.metadata.title.clone(),
+    };
+    let source = format!(
+        "{}/{}",
+        crate::base::BaseStore::new().origin(&headers),
+        p.url
+    );
+
+    match crate::jobs::spawn(move || {
+        Ok(JobOutput {
+            content_type: "application/pdf",
+            file_name: format!("{}.pdf", p.url),
+            data: crate::pdf::render_pdf(&title, &p.content, &source),
+        })
+    }) {
+        Some(id) => {
+            Redirect::to(&format!("{}/export/{id}", crate::config::base_path())).into_response()
+        }
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Html(
+                ErrorViewTemplate {
+                    error: crate::i18n::current_text("export.busy"),
+                }
+                .render()
+                .unwrap(),
+            ),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "export_pending.html")]
+struct ExportPendingTemplate {
+    id: String,
+}
+
+/// Download the result of an export job (`/export/:id`), or wait for it to finish
+pub async fn export_job_request(Path(id): Path<String>) -> Response {
+    let error = match crate::jobs::status(&id) {
+        Some(JobStatus::Pending) => {
+            return Html(ExportPendingTemplate { id }.render().unwrap()).into_response()
+        }
+        Some(JobStatus::Done(output)) => {
+            return (
+                [
+                    (header::CONTENT_TYPE, output.content_type.to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}\"", output.file_name),
+                    ),
+                ],
+                output.data,
+            )
+                .into_response()
+        }
+        Some(JobStatus::Failed(e)) => e,
+        None => crate::i18n::current_text("export.expired"),
+    };
+
+    Html(ErrorViewTemplate { error }.render().unwrap()).into_response()
+}
+
+/// An entry of an Atom feed
+struct FeedEntry {
+    id: String,
+    title: String,
+    link: String,
+    author: String,
+    summary: String,
+    /// The rendered paste content
+    content: String,
+    published: String,
+    updated: String,
+}
+
+impl FeedEntry {
+    /// Build the feed entry of the current version of a paste
+    ///
+    /// Edits change the entry id, so feed readers show every edit as a new entry.
+    fn from_paste(paste: &Paste, origin: &str) -> Self {
+        let link = format!("{origin}/{}", paste.url);
+
+        Self {
+            id: format!("{link}#{}", paste.date_edited),
+            title: match paste.metadata.title.is_empty() {
+                true => paste.url.clone(),
+                false => paste.metadata.title.clone(),
+            },
+            author: paste.metadata.owner.clone(),
+            summary: paste_description(paste),
+            content: parse_markdown(paste.content.clone()),
+            published: iso8601(paste.date_published),
+            updated: iso8601(paste.date_edited),
+            link,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "feed.xml")]
+struct FeedTemplate {
+    id: String,
+    title: String,
+    /// The page the feed is about
+    link: String,
+    self_link: String,
+    updated: String,
+    entries: Vec<FeedEntry>,
+}
+
+/// Render a feed with the Atom content type
+fn atom_response(feed: FeedTemplate) -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        feed.render().unwrap(),
+    )
+        .into_response()
+}
+
+/// Atom feed of the most recent public pastes (`/feed.atom`)
+pub async fn feed_request(headers: HeaderMap, State(database): State<Database>) -> Response {
+    let base = crate::base::BaseStore::new();
+    let origin = base.origin(&headers);
+
+    let pastes = database.get_recent_public_pastes().await;
+    let updated = pastes.iter().map(|p| p.date_edited).max().unwrap_or(0);
+
+    atom_response(FeedTemplate {
+        id: format!("{origin}/"),
+        title: base.site_name,
+        link: format!("{origin}/"),
+        self_link: format!("{origin}/feed.atom"),
+        updated: iso8601(updated),
+        entries: pastes
+            .iter()
+            .map(|p| FeedEntry::from_paste(p, &origin))
+            .collect(),
+    })
+}
+
+/// Atom feed of a paste (`/:url/feed.atom`)
+///
+/// Pastes don't keep old versions, so the feed only holds the current version of the paste. Its
+/// id changes on every edit, which is what feed readers use to notice updates.
+pub async fn paste_feed_request(
+    jar: CookieJar,
+    headers: HeaderMap,
+    Path(url): Path<String>,
+    State(database): State<Database>,
+) -> Response {
+    let p = match database.get_paste_by_url(url).await {
+        Ok(p) => p,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    // feed readers can't provide view passwords
+    if crate::access::check(&database, &jar, &p, "").await.is_err() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let origin = crate::base::BaseStore::new().origin(&headers);
+    let entry = FeedEntry::from_paste(&p, &origin);
+
+    atom_response(FeedTemplate {
+        id: entry.link.clone(),
+        title: entry.title.clone(),
+        link: entry.link.clone(),
+        self_link: format!("{}/feed.atom", entry.link),
+        updated: entry.updated.clone(),
+        entries: vec![entry],
+    })
+}
+
+/// Redirect a shortlink to its paste (`/s/:code`)
+pub async fn shortlink_request(
+    Path(code): Path<String>,
+    State(database): State<Database>,
+) -> Response {
+    match database.get_url_by_shortlink(code).await {
+        Ok(url) => Redirect::to(&format!("{}/{url}", crate::config::base_path())).into_response(),
         Err(e) => Html(
             ErrorViewTemplate {
-                error: e.to_string(),
+                error: crate::i18n::error_text(&e),
+            }
+            .render()
+            .unwrap(),
+        )
+        .into_response(),
+    }
+}
+
+/// Download all pastes owned by the current user as a zip archive (`/account/archive.zip`)
+///
+/// Pastes are fetched and compressed a page at a time while the archive is streamed, and are
+/// followed by a `manifest.json` describing them.
+pub async fn archive_request(jar: CookieJar, State(database): State<Database>) -> Response {
+    let owner = match jar.get("__Secure-Token") {
+        Some(c) => database
+            .base
+            .auth
+            .get_profile_by_unhashed(c.value_trimmed().to_string())
+            .await
+            .ok()
+            .map(|ua| ua.username),
+        None => None,
+    };
+
+    let owner = match owner {
+        Some(owner) => owner,
+        None => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Html(
+                    ErrorViewTemplate {
+                        error: crate::i18n::current_text("account.archive_logged_out"),
+                    }
+                    .render()
+                    .unwrap(),
+                ),
+            )
+                .into_response()
+        }
+    };
+
+    let file_name = format!("{owner}-archive.zip");
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, Infallible>>(4);
+
+    tokio::spawn(async move {
+        let mut zip = ZipWriter::new();
+        let mut manifest = ArchiveManifest {
+            owner: owner.clone(),
+            date_created: dorsal::utility::unix_epoch_timestamp(),
+            pastes: Vec::new(),
+        };
+
+        let mut page = 0;
+        loop {
+            let (pastes, full) = database.get_pastes_by_owner(owner.clone(), page).await;
+
+            for paste in pastes {
+                let file = format!("pastes/{}.md", paste.url.replace(['/', '\\'], "_"));
+                let chunk = zip.file(&file, paste.date_edited, paste.content.as_bytes());
+
+                // stop once the client is gone
+                if tx.send(Ok(chunk)).await.is_err() {
+                    return;
+                }
+
+                manifest.pastes.push(ArchiveEntry {
+                    visibility: database
+                        .get_extra_metadata_by_url(paste.url.clone())
+                        .await
+                        .visibility,
+                    url: paste.url,
+                    file,
+                    title: paste.metadata.title,
+                    description: paste.metadata.description,
+                    date_published: paste.date_published,
+                    date_edited: paste.date_edited,
+                });
+            }
+
+            // (a short page is the last one)
+            if !full {
+                break;
+            }
+
+            page += 1;
+        }
+
+        let chunk = zip.file(
+            "manifest.json",
+            manifest.date_created,
+            serde_json::to_string_pretty(&manifest).unwrap().as_bytes(),
+        );
+
+        if tx.send(Ok(chunk)).await.is_ok() {
+            let _ = tx.send(Ok(zip.finish())).await;
+        }
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+#[derive(Template)]
+#[template(path = "collections.html")]
+struct CollectionsTemplate {
+    collections: Vec<Collection>,
+    auth_user: String,
+}
+
+/// List the collections of the current user (`/c`)
+pub async fn collections_request(
+    jar: CookieJar,
+    State(database): State<Database>,
+) -> impl IntoResponse {
+    // get user from token
+    let auth_user = match jar.get("__Secure-Token") {
+        Some(c) => match database
+            .base
+            .auth
+            .get_profile_by_unhashed(c.value_trimmed().to_string())
+            .await
+        {
+            Ok(ua) => ua.username,
+            Err(_) => String::new(),
+        },
+        None => String::new(),
+    };
+
+    Html(
+        CollectionsTemplate {
+            collections: if auth_user.is_empty() {
+                Vec::new()
+            } else {
+                database.get_collections_by_owner(auth_user.clone()).await
+            },
+            auth_user,
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
+/// A paste listed on a collection's index page
+struct CollectionEntry {
+    url: String,
+    title: String,
+    description: String,
+}
+
+#[derive(Template)]
+#[template(path = "collection.html")]
+struct CollectionTemplate {
+    collection: Collection,
+    entries: Vec<CollectionEntry>,
+    is_owner: bool,
+}
+
+/// View the index page of a collection (`/c/:name`)
+pub async fn collection_request(
+    jar: CookieJar,
+    Path(name): Path<String>,
+    State(database): State<Database>,
+) -> impl IntoResponse {
+    // get user from token
+    let auth_user = match jar.get("__Secure-Token") {
+        Some(c) => match database
+            .base
+            .auth
+            .get_profile_by_unhashed(c.value_trimmed().to_string())
+            .await
+        {
+            Ok(ua) => ua.username,
+            Err(_) => String::new(),
+        },
+        None => String::new(),
+    };
+
+    // ...
+    match database.get_collection_by_name(name).await {
+        Ok(collection) => {
+            let is_owner = !auth_user.is_empty() && auth_user == collection.owner;
+            let mut entries = Vec::new();
+
+            for url in &collection.pastes {
+                // pastes deleted since they were added are skipped
+                if let Ok(p) = database.get_paste_by_url(url.clone()).await {
+                    // pastes the public can't view are only listed for the owner
+                    if !is_owner && !database.can_view_paste(&p, None).await {
+                        continue;
+                    }
+
+                    entries.push(CollectionEntry {
+                        title: if p.metadata.title.is_empty() {
+                            p.url.clone()
+                        } else {
+                            p.metadata.title
+                        },
+                        url: p.url,
+                        description: p.metadata.description,
+                    });
+                }
+            }
+
+            Html(
+                CollectionTemplate {
+                    is_owner,
+                    collection,
+                    entries,
+                }
+                .render()
+                .unwrap(),
+            )
+        }
+        Err(e) => Html(
+            ErrorViewTemplate {
+                error: crate::i18n::error_text(&e),
             }
             .render()
             .unwrap(),
@@ -322,12 +1979,494 @@ pub async fn config_editor_request(
     }
 }
 
+#[derive(Template)]
+#[template(path = "page.html")]
+struct InfoPageTemplate {
+    slug: String,
+    rendered: String,
+}
+
+/// `/robots.txt` served if `ROBOTS_FILE` isn't set
+const DEFAULT_ROBOTS: &str = "User-agent: *
+Disallow: /api/
+Disallow: /account/
+Disallow: /export/
+Disallow: /*/edit
+Disallow: /*/export/
+Disallow: /*/print
+Disallow: /*/embed
+";
+
+/// Serve a branding file (`LOGO`, `FAVICON`)
+fn branding_file(path: &str) -> Response {
+    match std::fs::read(path) {
+        Ok(data) => (
+            [(
+                header::CONTENT_TYPE,
+                mime_guess::from_path(path)
+                    .first_or_octet_stream()
+                    .to_string(),
+            )],
+            data,
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// The favicon (`/favicon.ico`)
+pub async fn favicon_request() -> Response {
+    let base = crate::base::BaseStore::new();
+
+    if !base.favicon.is_empty() && !crate::base::is_url(&base.favicon) {
+        return branding_file(&base.favicon);
+    }
+
+    Redirect::temporary(&base.favicon_url).into_response()
+}
+
+/// The logo, if `LOGO` is a file (`/branding/logo`)
+pub async fn logo_request() -> Response {
+    let base = crate::base::BaseStore::new();
+
+    if base.logo.is_empty() || crate::base::is_url(&base.logo) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    branding_file(&base.logo)
+}
+
+/// Serve `/robots.txt` (`ROBOTS_FILE`)
+pub async fn robots_request() -> impl IntoResponse {
+    let base = crate::base::BaseStore::new();
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        match base.robots_file.is_empty() {
+            true => DEFAULT_ROBOTS.to_string(),
+            false => std::fs::read_to_string(&base.robots_file)
+                .unwrap_or_else(|_| DEFAULT_ROBOTS.to_string()),
+        },
+    )
+}
+
+/// View an operator-provided markdown page (`/pages/:slug`)
+pub async fn info_page_request(Path(slug): Path<String>) -> impl IntoResponse {
+    let base = crate::base::BaseStore::new();
+
+    // slugs can only name a file directly inside the pages directory
+    let valid = !base.pages_dir.is_empty()
+        && !slug.is_empty()
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || (c == '-') || (c == '_'));
+
+    if valid {
+        if let Some(rendered) =
+            render_markdown_file(&std::path::Path::new(&base.pages_dir).join(format!("{slug}.md")))
+        {
+            return Html(InfoPageTemplate { slug, rendered }.render().unwrap());
+        }
+    }
+
+    Html(
+        ErrorViewTemplate {
+            error: crate::i18n::current_text("error.page_not_found"),
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
+#[derive(Template)]
+#[template(path = "paste_editor.html")]
+struct EditorTemplate {
+    paste: Paste,
+    passwordless: bool,
+    /// The token of the edit link the editor was opened with (empty for none)
+    token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EditorQuery {
+    /// The token of an edit link (see `Database::create_edit_token`)
+    #[serde(default)]
+    token: String,
+}
+
+pub async fn editor_request(
+    State(database): State<Database>,
+    ProtectedPaste {
+        paste: p,
+        auth_user,
+        ..
+    }: ProtectedPaste,
+    Query(query_params): Query<EditorQuery>,
+) -> impl IntoResponse {
+    // check edit link
+    if !query_params.token.is_empty()
+        && !database
+            .check_edit_token(p.url.clone(), query_params.token.clone())
+            .await
+    {
+        return Html(
+            ErrorViewTemplate {
+                error: crate::i18n::current_text("error.edit_link"),
+            }
+            .render()
+            .unwrap(),
+        );
+    }
+
+    // ...
+    let passwordless =
+        !p.metadata.owner.is_empty() && auth_user.is_some_and(|ua| ua.username == p.metadata.owner);
+
+    Html(
+        EditorTemplate {
+            paste: p,
+            passwordless,
+            token: query_params.token,
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
+#[derive(Template)]
+#[template(path = "paste_metadata.html")]
+struct ConfigEditorTemplate {
+    paste: Paste,
+    paste_metadata: String,
+    auth_user: String,
+    passwordless: bool,
+}
+
+pub async fn config_editor_request(
+    State(database): State<Database>,
+    ProtectedPaste {
+        paste: p,
+        auth_user,
+        ..
+    }: ProtectedPaste,
+) -> impl IntoResponse {
+    let auth_user = match auth_user {
+        Some(ua) => ua.username,
+        None => String::new(),
+    };
+
+    // ...
+    let passwordless = !p.metadata.owner.is_empty() && auth_user == p.metadata.owner;
+    Html(
+        ConfigEditorTemplate {
+            paste: p.clone(),
+            paste_metadata: match serde_json::to_string(&FullMetadata {
+                base: p.metadata.clone(),
+                extra: database.get_extra_metadata_by_url(p.url.clone()).await,
+            }) {
+                // (`<` is escaped so the JSON can't close its `<script>`)
+                Ok(m) => m.replace('<', "\\u003c"),
+                Err(_) => {
+                    return Html(
+                        ErrorViewTemplate {
+                            error: crate::i18n::error_text(&PasteError::Other),
+                        }
+                        .render()
+                        .unwrap(),
+                    )
+                }
+            },
+            auth_user,
+            passwordless,
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
+#[derive(Template)]
+#[template(path = "paste_stats.html")]
+struct PasteStatsTemplate {
+    paste: Paste,
+    passwordless: bool,
+}
+
+/// The view analytics of a paste (`/:url/stats`), loaded from `/api/:url/stats` with the edit
+/// password (right away for the paste's owner)
+pub async fn stats_request(
+    ProtectedPaste {
+        paste: p,
+        auth_user,
+        ..
+    }: ProtectedPaste,
+) -> impl IntoResponse {
+    let passwordless =
+        !p.metadata.owner.is_empty() && auth_user.is_some_and(|ua| ua.username == p.metadata.owner);
+
+    Html(
+        PasteStatsTemplate {
+            paste: p,
+            passwordless,
+        }
+        .render()
+        .unwrap(),
+    )
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct RenderMarkdown {
     pub content: String,
 }
 
+/// How many previews can be rendered at once
+///
+/// A render that times out can't be stopped, so this keeps slow ones from taking every thread.
+static RENDERS: LazyLock<tokio::sync::Semaphore> = LazyLock::new(|| {
+    tokio::sync::Semaphore::new(std::thread::available_parallelism().map_or(4, |n| n.get()))
+});
+
 /// Render markdown body
-async fn render_markdown(Json(req): Json<RenderMarkdown>) -> Result<String, ()> {
-    Ok(parse_markdown(req.content.clone()))
+///
+/// The size of the content is limited by `MAX_RENDER_SIZE` (see [`crate::config::BodyLimits`]),
+/// how often clients can render by `RATE_LIMIT_RENDER`, and how long rendering (and waiting for
+/// a turn) can take by `RENDER_TIMEOUT`.
+async fn render_markdown(Json(req): Json<RenderMarkdown>) -> Response {
+    match render_preview(req.content).await {
+        Ok(Some(html)) => html.into_response(),
+        Ok(None) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(_) => {
+            tracing::warn!("A markdown preview timed out.");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(DefaultReturn {
+                    success: false,
+                    message: "The preview took too long to render.".to_string(),
+                    payload: 503,
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Render a preview, waiting for a turn (see [`RENDERS`]) for up to `RENDER_TIMEOUT`
+///
+/// # Returns
+/// * `Ok(None)` if rendering failed
+/// * `Err` if it timed out
+async fn render_preview(content: String) -> Result<Option<String>, tokio::time::error::Elapsed> {
+    let timeout = crate::config::request_limits().render_timeout;
+
+    tokio::time::timeout(timeout, async move {
+        let permit = RENDERS.acquire().await.ok()?;
+
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            parse_markdown(content)
+        })
+        .await
+        .ok()
+    })
+    .await
+}
+
+/// How long the content of a live preview has to stay the same before it's rendered
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// The longest a live preview waits for typing to stop before it's rendered anyway
+const PREVIEW_MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// A change to the content of a live preview (`/ws/preview`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PreviewChange {
+    /// Replace `delete` characters at `at` with `insert` (counted in UTF-16 code units, like
+    /// JavaScript strings)
+    Delta {
+        at: usize,
+        #[serde(default)]
+        delete: usize,
+        #[serde(default)]
+        insert: String,
+    },
+    /// Replace the whole content
+    Content { content: String },
+}
+
+/// The editor's live preview (`/ws/preview`)
+///
+/// The editor sends its content once, then only what changes (see [`PreviewChange`]), and gets
+/// `{"html": "..."}` back once typing settles down for [`PREVIEW_DEBOUNCE`]. Changes that don't
+/// apply to the content the server has are answered with `{"resync": true}` (and the editor sends
+/// its whole content again). The content is limited like `/api/render` (`MAX_RENDER_SIZE`).
+async fn preview_socket(ws: crate::ws::WebSocketUpgrade) -> Response {
+    let max = crate::config::body_limits().render;
+
+    // (a change can carry the whole content, with room for the JSON around it)
+    ws.on_upgrade(max + 1024, move |socket| async move {
+        let (mut changes, sender) = socket.split();
+
+        // (kept as UTF-16, so offsets sent by the editor can be used as they are)
+        let mut content: Vec<u16> = Vec::new();
+        let mut deadline: Option<(Instant, Instant)> = None;
+
+        loop {
+            let change = match deadline {
+                Some((quiet, latest)) => {
+                    match tokio::time::timeout_at(quiet.min(latest), changes.recv()).await {
+                        Ok(change) => change,
+                        Err(_) => {
+                            deadline = None;
+
+                            let reply = match String::from_utf16_lossy(&content) {
+                                text if text.len() > max => serde_json::json!({
+                                    "error": format!(
+                                        "The content is larger than the {max} bytes allowed."
+                                    )
+                                }),
+                                text => match render_preview(text).await {
+                                    Ok(Some(html)) => serde_json::json!({ "html": html }),
+                                    Ok(None) => serde_json::json!({
+                                        "error": "The preview couldn't be rendered."
+                                    }),
+                                    Err(_) => serde_json::json!({
+                                        "error": "The preview took too long to render."
+                                    }),
+                                },
+                            };
+
+                            if !sender.send(&reply.to_string()).await {
+                                return;
+                            }
+
+                            continue;
+                        }
+                    }
+                }
+                None => changes.recv().await,
+            };
+
+            let Some(change) = change else {
+                return;
+            };
+
+            let applied = match serde_json::from_str::<PreviewChange>(&change) {
+                Ok(PreviewChange::Content { content: new }) => {
+                    content = new.encode_utf16().collect();
+                    true
+                }
+                Ok(PreviewChange::Delta { at, delete, insert }) => {
+                    match at.checked_add(delete).filter(|end| *end <= content.len()) {
+                        Some(end) => {
+                            content.splice(at..end, insert.encode_utf16());
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                Err(_) => {
+                    sender.close(crate::ws::close::INVALID_DATA).await;
+                    return;
+                }
+            };
+
+            if !applied {
+                deadline = None;
+
+                if !sender.send(r#"{"resync":true}"#).await {
+                    return;
+                }
+
+                continue;
+            }
+
+            let now = Instant::now();
+            deadline = Some((
+                now + PREVIEW_DEBOUNCE,
+                deadline.map_or(now + PREVIEW_MAX_DELAY, |(_, latest)| latest),
+            ));
+        }
+    })
+}
+
+/// Update the preferences of the user sending the request (`/preferences`)
+///
+/// Preferences are always stored in cookies, and saved to the user's account if they're logged in.
+async fn preferences_request(
+    jar: CookieJar,
+    State(database): State<Database>,
+    Json(req): Json<PreferencesEdit>,
+) -> impl IntoResponse {
+    let mut preferences = crate::middleware::current_preferences();
+
+    if let Some(theme) = req.theme {
+        preferences.theme = theme;
+    }
+
+    if let Some(lang) = req.lang {
+        // locale names are only ever letters, digits and dashes
+        preferences.lang = lang
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() | (*c == '-'))
+            .collect();
+    }
+
+    if database.base.options.starstraw {
+        if let Some(c) = jar.get("__Secure-Token") {
+            if let Ok(ua) = database
+                .base
+                .auth
+                .get_profile_by_unhashed(c.value_trimmed().to_string())
+                .await
+            {
+                if let Err(e) = database
+                    .edit_preferences_by_username(ua.username, preferences.clone())
+                    .await
+                {
+                    return (
+                        HeaderMap::new(),
+                        Json(DefaultReturn {
+                            success: false,
+                            message: e.to_string(),
+                            payload: (),
+                        }),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        "Set-Cookie",
+        format!(
+            "theme={}; SameSite=Lax; Secure; Path=/; HostOnly=true; Max-Age={}",
+            preferences.theme.as_str(),
+            60 * 60 * 24 * 365
+        )
+        .parse()
+        .unwrap(),
+    );
+
+    headers.append(
+        "Set-Cookie",
+        format!(
+            "lang={}; SameSite=Lax; Secure; Path=/; HostOnly=true; Max-Age={}",
+            preferences.lang,
+            60 * 60 * 24 * 365
+        )
+        .parse()
+        .unwrap(),
+    );
+
+    (
+        headers,
+        Json(DefaultReturn {
+            success: true,
+            message: String::from("Preferences updated"),
+            payload: (),
+        }),
+    )
 }
@@ -0,0 +1,476 @@
+//! PDF export (`/:url/export/pdf`)
+//!
+//! Pastes are laid out directly from their markdown using the standard PDF fonts (Helvetica and
+//! Courier), so no renderer or font files are needed. Only text is exported: headings, paragraphs,
+//! list items, quotes and code blocks keep their shape, images and tables are flattened to text.
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+/// A4 page width (in points)
+const PAGE_WIDTH: f32 = 595.0;
+/// A4 page height (in points)
+const PAGE_HEIGHT: f32 = 842.0;
+/// Space between the page edge and its content (in points)
+const MARGIN: f32 = 56.0;
+
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.0;
+const TITLE_SIZE: f32 = 24.0;
+const FOOTER_SIZE: f32 = 8.0;
+/// Line height, relative to the font size
+const LEADING: f32 = 1.4;
+/// Indentation of list items and quotes (in points)
+const INDENT: f32 = 16.0;
+
+/// Helvetica glyph widths for printable ASCII (`' '..='~'`), in 1/1000 of the font size
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// The fonts used in exports (their names in the page resources)
+#[derive(Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    fn resource(&self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+
+    /// Width of `text` at `size` (in points)
+    fn width(&self, text: &str, size: f32) -> f32 {
+        let units: f32 = text
+            .chars()
+            .map(|c| match self {
+                Font::Mono => 600.0,
+                _ => {
+                    let width = match c {
+                        ' '..='~' => HELVETICA_WIDTHS[c as usize - 32] as f32,
+                        _ => 556.0,
+                    };
+
+                    // Helvetica-Bold is slightly wider; overestimating only wraps a little early
+                    match self {
+                        Font::Bold => width * 1.08,
+                        _ => width,
+                    }
+                }
+            })
+            .sum();
+
+        units * size / 1000.0
+    }
+}
+
+/// A line of text placed on a page
+struct Line {
+    x: f32,
+    y: f32,
+    font: Font,
+    size: f32,
+    text: String,
+}
+
+/// Places lines onto pages, starting a new page when the current one is full
+struct Layout {
+    pages: Vec<Vec<Line>>,
+    /// Baseline of the next line on the current page
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Self {
+            pages: vec![Vec::new()],
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    fn line(&mut self, x: f32, font: Font, size: f32, text: String) {
+        let height = size * LEADING;
+
+        if self.y - height < MARGIN {
+            self.pages.push(Vec::new());
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+
+        self.y -= height;
+        self.pages.last_mut().unwrap().push(Line {
+            x,
+            y: self.y,
+            font,
+            size,
+            text,
+        });
+    }
+
+    /// Lay out a wrapped block of text
+    fn block(&mut self, text: &str, indent: f32, font: Font, size: f32, prefix: &str) {
+        let x = MARGIN + indent;
+        let width = PAGE_WIDTH - MARGIN - x;
+
+        for (i, line) in wrap(text, font, size, width).into_iter().enumerate() {
+            match (i, prefix.is_empty()) {
+                (0, false) => self.line(
+                    x - font.width(prefix, size),
+                    font,
+                    size,
+                    format!("{prefix}{line}"),
+                ),
+                _ => self.line(x, font, size, line),
+            }
+        }
+    }
+
+    fn gap(&mut self, size: f32) {
+        self.y -= size * 0.6;
+    }
+}
+
+/// Wrap `text` into lines no wider than `width` (long words are broken)
+fn wrap(text: &str, font: Font, size: f32, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let candidate = match line.is_empty() {
+            true => word.to_string(),
+            false => format!("{line} {word}"),
+        };
+
+        if font.width(&candidate, size) <= width {
+            line = candidate;
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+
+        // break words that don't fit on a line of their own
+        for c in word.chars() {
+            if font.width(&format!("{line}{c}"), size) > width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            line.push(c);
+        }
+    }
+
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Strip inline markdown (emphasis, code spans, links) from a line
+fn strip_inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '_' | '`' | '~' => continue,
+            '!' if chars.peek() == Some(&'[') => continue,
+            // [label](href) -> label (href)
+            ']' if chars.peek() == Some(&'(') => {
+                chars.next();
+                let href: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                out.push_str(&format!(" ({href})"));
+            }
+            '[' => continue,
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Lay out paste markdown
+fn layout_markdown(layout: &mut Layout, content: &str) {
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut in_code = false;
+
+    let flush = |layout: &mut Layout, paragraph: &mut Vec<&str>| {
+        if paragraph.is_empty() {
+            return;
+        }
+
+        layout.block(
+            &strip_inline(&paragraph.join(" ")),
+            0.0,
+            Font::Regular,
+            BODY_SIZE,
+            "",
+        );
+        layout.gap(BODY_SIZE);
+        paragraph.clear();
+    };
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            flush(layout, &mut paragraph);
+            in_code = !in_code;
+            layout.gap(CODE_SIZE);
+            continue;
+        }
+
+        if in_code {
+            // keep code as-is, only breaking lines too long for the page
+            let columns = ((PAGE_WIDTH - MARGIN * 2.0 - INDENT) / (CODE_SIZE * 0.6)) as usize;
+            let chars: Vec<char> = line.replace('\t', "    ").chars().collect();
+
+            if chars.is_empty() {
+                layout.line(MARGIN + INDENT, Font::Mono, CODE_SIZE, String::new());
+            }
+
+            for chunk in chars.chunks(columns.max(1)) {
+                layout.line(
+                    MARGIN + INDENT,
+                    Font::Mono,
+                    CODE_SIZE,
+                    chunk.iter().collect(),
+                );
+            }
+
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            flush(layout, &mut paragraph);
+            continue;
+        }
+
+        // headings
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            flush(layout, &mut paragraph);
+            let size = match level {
+                1 => 20.0,
+                2 => 16.0,
+                3 => 14.0,
+                _ => 12.0,
+            };
+
+            layout.gap(size);
+            layout.block(&strip_inline(&trimmed[level..]), 0.0, Font::Bold, size, "");
+            layout.gap(size);
+            continue;
+        }
+
+        // list items
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .or_else(|| trimmed.strip_prefix("+ "))
+        {
+            flush(layout, &mut paragraph);
+            let indent = INDENT * (1.0 + (line.len() - line.trim_start().len()) as f32 / 2.0);
+            layout.block(
+                &strip_inline(item),
+                indent,
+                Font::Regular,
+                BODY_SIZE,
+                "\u{2022} ",
+            );
+            continue;
+        }
+
+        // quotes
+        if let Some(quote) = trimmed.strip_prefix('>') {
+            flush(layout, &mut paragraph);
+            layout.block(&strip_inline(quote), INDENT, Font::Regular, BODY_SIZE, "| ");
+            continue;
+        }
+
+        // horizontal rules
+        if trimmed.len() >= 3 && trimmed.chars().all(|c| c == '-' || c == '*' || c == '_') {
+            flush(layout, &mut paragraph);
+            layout.gap(BODY_SIZE);
+            continue;
+        }
+
+        paragraph.push(trimmed);
+    }
+
+    flush(layout, &mut paragraph);
+}
+
+/// Encode text as a PDF string (WinAnsiEncoding, unsupported characters become `?`)
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                out.push(b'\\');
+                c as u8
+            }
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '\u{20ac}' => 0x80,
+            '\u{2026}' => 0x85,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            _ => b'?',
+        };
+
+        out.push(byte);
+    }
+
+    out.push(b')');
+    out
+}
+
+/// Build the (compressed) content stream of a page
+fn page_stream(lines: &[Line], footer: &str) -> Vec<u8> {
+    let mut ops = Vec::new();
+
+    let footer_line = Line {
+        x: PAGE_WIDTH - MARGIN - Font::Regular.width(footer, FOOTER_SIZE),
+        y: MARGIN / 2.0,
+        font: Font::Regular,
+        size: FOOTER_SIZE,
+        text: footer.to_string(),
+    };
+
+    for line in lines.iter().chain(std::iter::once(&footer_line)) {
+        ops.extend_from_slice(
+            format!(
+                "BT /{} {} Tf {:.2} {:.2} Td ",
+                line.font.resource(),
+                line.size,
+                line.x,
+                line.y
+            )
+            .as_bytes(),
+        );
+        ops.extend(pdf_string(&line.text));
+        ops.extend_from_slice(b" Tj ET\n");
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&ops).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Render a paste as a PDF document
+///
+/// # Arguments
+/// * `title` - the paste title (shown above the content and used as the document title)
+/// * `content` - the paste content (markdown)
+/// * `source` - where the paste can be found (shown in the footer of every page)
+pub fn render_pdf(title: &str, content: &str, source: &str) -> Vec<u8> {
+    let mut layout = Layout::new();
+    layout.block(title, 0.0, Font::Bold, TITLE_SIZE, "");
+    layout.gap(TITLE_SIZE);
+    layout_markdown(&mut layout, content);
+
+    // objects: 1 catalog, 2 page tree, 3-5 fonts, 6 info, then a page and its content per page
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    let page_count = layout.pages.len();
+    let kids: Vec<String> = (0..page_count)
+        .map(|i| format!("{} 0 R", 7 + i * 2))
+        .collect();
+
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    objects.push(
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {page_count} >>",
+            kids.join(" ")
+        )
+        .into_bytes(),
+    );
+
+    for name in ["Helvetica", "Helvetica-Bold", "Courier"] {
+        objects.push(
+            format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{name} /Encoding /WinAnsiEncoding >>"
+            )
+            .into_bytes(),
+        );
+    }
+
+    let mut info = b"<< /Title ".to_vec();
+    info.extend(pdf_string(title));
+    info.extend_from_slice(b" /Producer ");
+    info.extend(pdf_string(source));
+    info.extend_from_slice(b" >>");
+    objects.push(info);
+
+    for (i, lines) in layout.pages.iter().enumerate() {
+        let stream = page_stream(
+            lines,
+            &format!("{source} \u{2013} {} / {page_count}", i + 1),
+        );
+
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >> /Contents {} 0 R >>",
+                8 + i * 2
+            )
+            .into_bytes(),
+        );
+
+        let mut content = format!(
+            "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+            stream.len()
+        )
+        .into_bytes();
+        content.extend(stream);
+        content.extend_from_slice(b"\nendstream");
+        objects.push(content);
+    }
+
+    // write document
+    let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = out.len();
+    out.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+
+    for offset in offsets {
+        out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 6 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .as_bytes(),
+    );
+
+    out
+}
@@ -0,0 +1,127 @@
+//! Per-client rate limits for groups of routes (see [`crate::config::RateLimits`])
+//!
+//! Every client gets a bucket for each group of routes, holding up to `burst` requests and
+//! refilled with `requests` every `window`. Buckets are kept in memory, so every process of an
+//! instance counts on its own.
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::{RateLimit, RateLimits};
+
+/// How many buckets are kept before full ones are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug)]
+struct Bucket {
+    /// How many requests can be sent right now
+    tokens: f64,
+    updated: Instant,
+    /// How long the bucket takes to refill (its group's window)
+    window: Duration,
+}
+
+/// The buckets of every client
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<HashMap<(&'static str, IpAddr), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a request from a client's bucket
+    ///
+    /// # Returns
+    /// `Err` with how long until the client can send another request, if its bucket is empty
+    fn take(&self, group: &'static str, client: IpAddr, limit: RateLimit) -> Result<(), Duration> {
+        let now = Instant::now();
+        let rate = limit.requests as f64 / limit.window.as_secs_f64();
+        let burst = limit.burst as f64;
+
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            // (a bucket untouched for a whole window of its group is full again, so it's the same
+            // as none)
+            buckets.retain(|_, b| now.duration_since(b.updated) < b.window);
+        }
+
+        let bucket = buckets.entry((group, client)).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+            window: limit.window,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate));
+        }
+
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Get the key of a client: its address, or its `/64` network for IPv6 (which clients usually get
+/// whole)
+fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => IpAddr::V6((u128::from(ip) & !((1u128 << 64) - 1)).into()),
+        },
+        ip => ip,
+    }
+}
+
+/// Turn away clients sending more requests to a group of routes than its limit allows, with a
+/// `429` and `Retry-After`
+///
+/// Requests without a known client address (unix sockets without a proxy) aren't limited.
+pub async fn layer(State(limiter): State<Arc<RateLimiter>>, req: Request, next: Next) -> Response {
+    let limited = limiter
+        .limits
+        .for_request(req.method(), req.uri().path(), req.uri().query());
+
+    let (Some((group, limit)), Some(ip)) = (limited, crate::middleware::client_ip(&req)) else {
+        return next.run(req).await;
+    };
+
+    let Err(retry_after) = limiter.take(group, client_key(ip), limit) else {
+        return next.run(req).await;
+    };
+
+    tracing::debug!(group, %ip, "Rate limited a client.");
+
+    let mut res = crate::middleware::rejection(
+        req.uri().path(),
+        StatusCode::TOO_MANY_REQUESTS,
+        "Too many requests, try again later.",
+        crate::pages::error_page(crate::i18n::current_text("error.rate_limited")),
+    );
+
+    res.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(retry_after.as_secs().max(1)),
+    );
+
+    res
+}
@@ -0,0 +1,190 @@
+//! Scanning content before it's reachable (attachments, and pastes of at least `SCAN_PASTE_SIZE`)
+//!
+//! Scanners are registered in [`crate::AppConfig::scanners`], or set up from `CLAMAV_ADDRESS` and
+//! `SCANNER_URL`. Content flagged by any of them is rejected, or with `SCAN_ACTION=quarantine`,
+//! stored but only shown to administrators until it's released (`sealable admin release`).
+//! Content is also rejected when a scanner fails, so an outage doesn't let anything through.
+use pastemd::model::PasteError;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::config::ScanAction;
+
+/// How long a scanner can take to answer
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a [`Scanner`] thinks of some content
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Clean,
+    /// The content shouldn't be published (with the reason, like the name of a signature)
+    Flagged(String),
+}
+
+/// Something content is checked with before it's published
+pub trait Scanner: Send + Sync {
+    /// Scan content (run on the blocking thread pool)
+    ///
+    /// # Arguments
+    /// * `name` - the name of the file (`paste` for the content of pastes)
+    /// * `data` - the content
+    fn scan(&self, name: &str, data: &[u8]) -> Result<Verdict, String>;
+}
+
+/// A ClamAV daemon (`clamd`), reached over its unix socket or TCP
+pub struct ClamAv {
+    /// The path of the socket (anything with a `/`), or `host:port`
+    pub address: String,
+}
+
+/// Send content to `clamd` with `INSTREAM`
+///
+/// # Returns
+/// * the reply (`stream: OK`, or `stream: <signature> FOUND`)
+fn instream(mut stream: impl Read + Write, data: &[u8]) -> Result<String, String> {
+    let mut send = || -> std::io::Result<()> {
+        stream.write_all(b"zINSTREAM\0")?;
+
+        for chunk in data.chunks(64 * 1024) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes())?;
+            stream.write_all(chunk)?;
+        }
+
+        stream.write_all(&[0; 4])
+    };
+
+    send().map_err(|e| format!("Failed to send the content to clamd: {e}"))?;
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|e| format!("Failed to read the reply of clamd: {e}"))?;
+
+    Ok(reply.trim_end_matches('\0').trim().to_string())
+}
+
+impl Scanner for ClamAv {
+    fn scan(&self, _name: &str, data: &[u8]) -> Result<Verdict, String> {
+        let reply = match self.address.contains('/') {
+            #[cfg(unix)]
+            true => {
+                let stream = std::os::unix::net::UnixStream::connect(&self.address)
+                    .map_err(|e| format!("Failed to connect to clamd: {e}"))?;
+                let _ = stream.set_read_timeout(Some(TIMEOUT));
+                let _ = stream.set_write_timeout(Some(TIMEOUT));
+
+                instream(stream, data)?
+            }
+            _ => {
+                let stream = std::net::TcpStream::connect(&self.address)
+                    .map_err(|e| format!("Failed to connect to clamd: {e}"))?;
+                let _ = stream.set_read_timeout(Some(TIMEOUT));
+                let _ = stream.set_write_timeout(Some(TIMEOUT));
+
+                instream(stream, data)?
+            }
+        };
+
+        match reply.strip_suffix(" FOUND") {
+            Some(signature) => Ok(Verdict::Flagged(
+                signature.trim_start_matches("stream:").trim().to_string(),
+            )),
+            None if reply.ends_with("OK") => Ok(Verdict::Clean),
+            None => Err(format!("Unexpected reply from clamd: {reply}")),
+        }
+    }
+}
+
+/// A scanning service over HTTP
+///
+/// The content is `POST`ed as the body (with its name in `X-File-Name`), and the service answers
+/// with `{"flagged": true, "reason": "..."}` (or `{"flagged": false}`).
+pub struct HttpScanner {
+    pub url: String,
+}
+
+/// The answer of an [`HttpScanner`]
+#[derive(Deserialize)]
+struct ScanReply {
+    flagged: bool,
+    #[serde(default)]
+    reason: String,
+}
+
+impl Scanner for HttpScanner {
+    fn scan(&self, name: &str, data: &[u8]) -> Result<Verdict, String> {
+        let headers = [
+            (
+                "Content-Type".to_string(),
+                "application/octet-stream".to_string(),
+            ),
+            ("X-File-Name".to_string(), name.to_string()),
+        ];
+
+        let response =
+            crate::http::send_trusted_blocking("POST", &self.url, &headers, data, 64 * 1024)?;
+
+        if !response.is_success() {
+            return Err(format!("The scanner answered with {}.", response.status));
+        }
+
+        let reply: ScanReply = serde_json::from_slice(&response.body)
+            .map_err(|_| "The scanner's answer isn't valid.".to_string())?;
+
+        Ok(match reply.flagged {
+            true if reply.reason.is_empty() => {
+                Verdict::Flagged("Flagged by the scanner".to_string())
+            }
+            true => Verdict::Flagged(reply.reason),
+            false => Verdict::Clean,
+        })
+    }
+}
+
+/// Run every scanner on some content
+///
+/// # Returns
+/// * `None` if the content can be published
+/// * the reason it's quarantined (with [`ScanAction::Quarantine`])
+/// * [`PasteError::ValueError`] if it's rejected, [`PasteError::Other`] if a scanner failed
+pub(crate) async fn check(name: &str, data: &[u8]) -> Result<Option<String>, PasteError> {
+    let scanners = crate::config::scanners();
+
+    if scanners.is_empty() {
+        return Ok(None);
+    }
+
+    let (name, data) = (name.to_string(), data.to_vec());
+    let verdicts = tokio::task::spawn_blocking(move || {
+        scanners
+            .iter()
+            .map(|scanner| scanner.scan(&name, &data))
+            .collect::<Result<Vec<Verdict>, String>>()
+    })
+    .await
+    .map_err(|_| PasteError::Other)?;
+
+    let flagged = match verdicts {
+        Ok(verdicts) => verdicts.into_iter().find_map(|v| match v {
+            Verdict::Flagged(reason) => Some(reason),
+            Verdict::Clean => None,
+        }),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to scan content.");
+            return Err(PasteError::Other);
+        }
+    };
+
+    match (flagged, crate::config::scan_action()) {
+        (None, _) => Ok(None),
+        (Some(reason), ScanAction::Quarantine) => {
+            tracing::warn!(reason, "Quarantined flagged content.");
+            Ok(Some(reason))
+        }
+        (Some(reason), ScanAction::Reject) => {
+            tracing::warn!(reason, "Rejected flagged content.");
+            Err(PasteError::ValueError)
+        }
+    }
+}
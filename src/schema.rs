@@ -0,0 +1,263 @@
+//! The JSON Schema of paste metadata (served at `/api/schema/metadata`), and the validation of
+//! metadata submitted to `/api/:url/metadata` against it
+//!
+//! The server checks the same schema the config editor gets, so the two can't disagree. Only the
+//! keywords the schema uses are supported: `type` (`string`, `integer` or `object`), `enum`,
+//! `maxLength`, `pattern`, `minimum`, `maximum`, `maxProperties`, `propertyNames` and
+//! `additionalProperties: false`, along with `maxBytes` (our own, the longest an object can be
+//! once serialized).
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// The latest time a timestamp field can be set to (in milliseconds, the last date JavaScript can
+/// represent)
+const MAX_TIMESTAMP: u64 = 8_640_000_000_000_000;
+
+/// A length limit shared by the free-form fields
+const MAX_FIELD_LENGTH: usize = 256;
+
+/// How many custom fields (`extra`) a paste can have
+pub const MAX_EXTRA_FIELDS: usize = 32;
+
+/// How long the name of a custom field can be (in characters)
+pub const MAX_EXTRA_KEY_LENGTH: usize = 64;
+
+/// How large the custom fields of a paste can be together (in bytes, serialized as JSON)
+pub const MAX_EXTRA_SIZE: usize = 4096;
+
+/// A field that didn't pass validation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FieldError {
+    /// The name of the field (empty for the metadata as a whole)
+    pub field: String,
+    /// What's wrong with it
+    pub error: String,
+}
+
+/// A timestamp field (milliseconds since the epoch, 0 for none)
+fn timestamp(description: &str) -> Value {
+    json!({
+        "type": "integer",
+        "minimum": 0,
+        "maximum": MAX_TIMESTAMP,
+        "format": "timestamp-ms",
+        "description": description,
+    })
+}
+
+/// A free-form text field
+fn text(description: &str) -> Value {
+    json!({
+        "type": "string",
+        "maxLength": MAX_FIELD_LENGTH,
+        "description": description,
+    })
+}
+
+static SCHEMA: LazyLock<Value> = LazyLock::new(|| {
+    let languages: Vec<&str> = std::iter::once("")
+        .chain(crate::language::LANGUAGES.iter().map(|l| l.name))
+        .collect();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": "/api/schema/metadata",
+        "title": "Paste metadata",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "title": text("The title of the paste's page"),
+            "description": text("The description of the paste's page (in embeds)"),
+            "theme_color": {
+                "type": "string",
+                "maxLength": 32,
+                "description": "The theme color of the paste's page (in embeds)",
+            },
+            "favicon": {
+                "type": "string",
+                "maxLength": 2048,
+                "description": "The url of the paste's favicon",
+            },
+            "view_password": text("The password needed to view the paste (empty for none)"),
+            "owner": {
+                "type": "string",
+                "readOnly": true,
+                "description": "The username of the paste's owner (set to whoever saves the metadata)",
+            },
+            "template": {
+                "type": "string",
+                "maxLength": MAX_FIELD_LENGTH,
+                "description": "\"@\" if the paste is a template, or the url of the template it was written from",
+            },
+            "visibility": {
+                "type": "string",
+                "enum": ["public", "unlisted", "private"],
+                "description": "Where the paste is listed (private pastes need an owner)",
+            },
+            "direction": {
+                "type": "string",
+                "enum": ["auto", "ltr", "rtl"],
+                "description": "The text direction of the rendered content",
+            },
+            "lang": {
+                "type": "string",
+                "maxLength": 35,
+                "pattern": "^[A-Za-z0-9-]*$",
+                "description": "The language of the content, as a BCP 47 tag (empty if unknown)",
+            },
+            "language": {
+                "type": "string",
+                "enum": languages,
+                "description": "The language the content is written in (empty to detect it)",
+            },
+            "source": {
+                "type": "string",
+                "readOnly": true,
+                "description": "The url the paste was imported from",
+            },
+            "forked_from": {
+                "type": "string",
+                "readOnly": true,
+                "description": "The url of the paste this one was forked from",
+            },
+            "publish_at": timestamp("When the paste is published (0 if it already is)"),
+            "expires_at": timestamp("When the paste expires (0 for never)"),
+            "comments": {
+                "type": "string",
+                "enum": ["open", "closed", "off"],
+                "description": "If the paste takes comments",
+            },
+            "available_from": timestamp("When the paste can first be viewed (0 for whenever)"),
+            "available_until": timestamp("When the paste stops being viewable (0 for never)"),
+            "allowed_networks": {
+                "type": "string",
+                "maxLength": 1024,
+                "description": "The networks the paste can only be viewed from, comma-separated (empty for anywhere)",
+            },
+            "extra": {
+                "type": "object",
+                "maxProperties": MAX_EXTRA_FIELDS,
+                "propertyNames": {
+                    "type": "string",
+                    "maxLength": MAX_EXTRA_KEY_LENGTH,
+                    "pattern": "^[A-Za-z0-9_.-]+$",
+                },
+                "maxBytes": MAX_EXTRA_SIZE,
+                "description": "Custom fields, any JSON (sealable only stores and shows them)",
+            },
+        },
+    })
+});
+
+/// Get the JSON Schema of paste metadata
+pub fn metadata_schema() -> &'static Value {
+    &SCHEMA
+}
+
+/// Check `value` against a property of the schema
+fn check(name: &str, value: &Value, schema: &Value) -> Option<String> {
+    match schema["type"].as_str() {
+        Some("string") => {
+            let Some(s) = value.as_str() else {
+                return Some(String::from("Expected a string."));
+            };
+
+            if let Some(max) = schema["maxLength"].as_u64() {
+                if s.chars().count() as u64 > max {
+                    return Some(format!("Longer than {max} characters."));
+                }
+            }
+
+            if let Some(pattern) = schema["pattern"].as_str() {
+                if !Regex::new(pattern).is_ok_and(|r| r.is_match(s)) {
+                    return Some(format!("Doesn't match {pattern}."));
+                }
+            }
+        }
+        Some("integer") => {
+            let Some(n) = value.as_u64() else {
+                return Some(String::from("Expected a positive integer."));
+            };
+
+            if schema["minimum"].as_u64().is_some_and(|min| n < min) {
+                return Some(format!("Less than {}.", schema["minimum"]));
+            }
+
+            if schema["maximum"].as_u64().is_some_and(|max| n > max) {
+                return Some(format!("More than {}.", schema["maximum"]));
+            }
+        }
+        Some("object") => {
+            let Some(fields) = value.as_object() else {
+                return Some(String::from("Expected an object."));
+            };
+
+            if let Some(max) = schema["maxProperties"].as_u64() {
+                if fields.len() as u64 > max {
+                    return Some(format!("More than {max} fields."));
+                }
+            }
+
+            let names = &schema["propertyNames"];
+            if names.is_object() {
+                for key in fields.keys() {
+                    if let Some(error) = check(name, &Value::String(key.clone()), names) {
+                        return Some(format!("Field \"{key}\": {error}"));
+                    }
+                }
+            }
+
+            if let Some(max) = schema["maxBytes"].as_u64() {
+                if value.to_string().len() as u64 > max {
+                    return Some(format!("Larger than {max} bytes."));
+                }
+            }
+        }
+        _ => return Some(format!("The schema of {name} is unsupported.")),
+    }
+
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(value) {
+            return Some(format!(
+                "Expected one of {}.",
+                Value::Array(options.clone())
+            ));
+        }
+    }
+
+    None
+}
+
+/// Validate submitted metadata against the schema
+///
+/// # Returns
+/// * every field that doesn't pass (empty if the metadata is valid)
+pub fn validate_metadata(metadata: &Value) -> Vec<FieldError> {
+    let Some(fields) = metadata.as_object() else {
+        return vec![FieldError {
+            field: String::new(),
+            error: String::from("Expected an object."),
+        }];
+    };
+
+    let empty = Map::new();
+    let properties = SCHEMA["properties"].as_object().unwrap_or(&empty);
+
+    fields
+        .iter()
+        .filter_map(|(name, value)| {
+            let error = match properties.get(name) {
+                Some(schema) => check(name, value, schema)?,
+                None => String::from("Unknown field."),
+            };
+
+            Some(FieldError {
+                field: name.clone(),
+                error,
+            })
+        })
+        .collect()
+}
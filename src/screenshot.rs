@@ -0,0 +1,283 @@
+//! Code images (`/:url/export/png`)
+//!
+//! Renders (part of) a paste's source in a window on the instance accent color, with the content
+//! of fenced code blocks syntax highlighted. Highlighting is keyword based and doesn't depend on
+//! the language, besides picking which comment markers apply.
+use crate::raster::{parse_hex_color, Canvas, DEFAULT_ACCENT, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// The most lines shown in one image
+pub const MAX_LINES: usize = 60;
+/// The most characters shown per line (longer lines are cut off)
+const MAX_COLUMNS: usize = 100;
+
+/// Font pixel size
+const SCALE: usize = 2;
+/// Width of a character cell (in pixels)
+const CELL_WIDTH: usize = (GLYPH_WIDTH + 1) * SCALE;
+/// Height of a line (in pixels)
+const LINE_HEIGHT: usize = (GLYPH_HEIGHT + 4) * SCALE;
+
+/// Space between the image edge and the window (in pixels)
+const MARGIN: usize = 48;
+/// Space between the window edge and the code (in pixels)
+const PADDING: usize = 24;
+/// Height of the window title bar (in pixels)
+const TITLE_BAR: usize = 40;
+/// Narrowest the window can be (in pixels)
+const MIN_WINDOW_WIDTH: usize = 480;
+
+const WINDOW: [u8; 3] = [30, 30, 36];
+const TITLE_BAR_COLOR: [u8; 3] = [40, 40, 48];
+const LINE_NUMBER: [u8; 3] = [92, 99, 112];
+const DOTS: [[u8; 3]; 3] = [[255, 95, 86], [255, 189, 46], [39, 201, 63]];
+
+const PLAIN: [u8; 3] = [220, 223, 228];
+const KEYWORD: [u8; 3] = [198, 120, 221];
+const FUNCTION: [u8; 3] = [97, 175, 239];
+const STRING: [u8; 3] = [152, 195, 121];
+const NUMBER: [u8; 3] = [209, 154, 102];
+const COMMENT: [u8; 3] = [127, 132, 142];
+
+/// Keywords of common languages
+#[rustfmt::skip]
+const KEYWORDS: &[&str] = &[
+    "and", "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def",
+    "default", "defer", "del", "do", "elif", "else", "enum", "except", "export", "extends", "false",
+    "finally", "fn", "for", "from", "func", "function", "go", "if", "impl", "import", "in",
+    "interface", "is", "lambda", "let", "loop", "match", "mod", "move", "mut", "new", "nil", "None",
+    "not", "null", "or", "package", "pass", "pub", "raise", "return", "self", "Self", "static",
+    "struct", "super", "switch", "this", "throw", "trait", "true", "True", "False", "try", "type",
+    "typeof", "use", "var", "where", "while", "with", "yield",
+];
+
+/// Languages where `#` starts a comment
+#[rustfmt::skip]
+const HASH_COMMENTS: &[&str] = &[
+    "bash", "sh", "shell", "zsh", "py", "python", "rb", "ruby", "toml", "yaml", "yml", "r", "perl",
+    "dockerfile", "makefile", "nix", "ini", "conf",
+];
+
+/// Languages where `--` starts a comment
+const DASH_COMMENTS: &[&str] = &["sql", "lua", "haskell", "hs", "elm"];
+
+/// A highlighted line, as spans of text and their color
+type Tokens = Vec<([u8; 3], String)>;
+
+/// Highlight a line of code
+///
+/// # Arguments
+/// * `line` - the line
+/// * `lang` - the language of the code block the line is in
+/// * `in_comment` - if the line starts inside of a `/* */` comment (updated for the next line)
+fn highlight(line: &str, lang: &str, in_comment: &mut bool) -> Tokens {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out: Tokens = Vec::new();
+    let mut push = |color: [u8; 3], text: &[char]| match out.last_mut() {
+        Some((c, t)) if *c == color => t.extend(text),
+        _ => out.push((color, text.iter().collect())),
+    };
+
+    let starts_with = |i: usize, s: &str| chars[i..].iter().take(s.len()).copied().eq(s.chars());
+    let hash_comments = HASH_COMMENTS.contains(&lang);
+    let dash_comments = DASH_COMMENTS.contains(&lang);
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+
+        // block comments
+        if *in_comment || starts_with(i, "/*") {
+            let from = i + if *in_comment { 0 } else { 2 };
+            let end = (from..chars.len())
+                .find(|&j| starts_with(j, "*/"))
+                .map(|j| j + 2);
+
+            *in_comment = end.is_none();
+            let end = end.unwrap_or(chars.len());
+            push(COMMENT, &chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        // line comments
+        if (starts_with(i, "//") && !hash_comments)
+            || (c == '#' && hash_comments)
+            || (starts_with(i, "--") && dash_comments)
+        {
+            push(COMMENT, &chars[i..]);
+            break;
+        }
+
+        // strings (unterminated quotes are left as-is, e.g. rust lifetimes)
+        if matches!(c, '"' | '\'' | '`') {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j] != c {
+                j += if chars[j] == '\\' { 2 } else { 1 };
+            }
+
+            if j < chars.len() {
+                push(STRING, &chars[i..=j]);
+                i = j + 1;
+                continue;
+            }
+        }
+
+        // numbers
+        if c.is_ascii_digit() {
+            let end = (i..chars.len())
+                .find(|&j| {
+                    !(chars[j].is_ascii_alphanumeric() || chars[j] == '.' || chars[j] == '_')
+                })
+                .unwrap_or(chars.len());
+
+            push(NUMBER, &chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        // words
+        if c.is_alphabetic() || c == '_' {
+            let end = (i..chars.len())
+                .find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'))
+                .unwrap_or(chars.len());
+
+            let word: String = chars[i..end].iter().collect();
+            let color = if KEYWORDS.contains(&word.as_str()) {
+                KEYWORD
+            } else if chars.get(end) == Some(&'(') || chars.get(end) == Some(&'!') {
+                FUNCTION
+            } else {
+                PLAIN
+            };
+
+            push(color, &chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        push(PLAIN, &chars[i..=i]);
+        i += 1;
+    }
+
+    out
+}
+
+/// Render lines of a paste's source as a code image (PNG)
+///
+/// # Arguments
+/// * `title` - the paste title (shown in the title bar)
+/// * `content` - the paste content
+/// * `first` - the first line to show (starting at 1)
+/// * `last` - the last line to show (at most [`MAX_LINES`] lines are shown)
+/// * `accent` - the instance accent color (`THEME_ACCENT`)
+pub fn render_code_image(
+    title: &str,
+    content: &str,
+    first: usize,
+    last: usize,
+    accent: &str,
+) -> Vec<u8> {
+    let first = first.max(1);
+    let last = last.max(first).min(first + MAX_LINES - 1);
+
+    // highlight every line up to the last one so code blocks starting earlier are detected
+    let mut code_lang: Option<String> = None;
+    let mut in_comment = false;
+    let mut lines: Vec<(usize, Tokens)> = Vec::new();
+
+    for (i, line) in content.lines().enumerate().take(last) {
+        let line = line.replace('\t', "    ");
+        let fence = line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~");
+
+        let tokens = if fence {
+            code_lang = match code_lang {
+                Some(_) => None,
+                None => Some(line.trim_start()[3..].trim().to_lowercase()),
+            };
+
+            in_comment = false;
+            vec![(COMMENT, line)]
+        } else {
+            match code_lang {
+                Some(ref lang) => highlight(&line, lang, &mut in_comment),
+                None => vec![(PLAIN, line)],
+            }
+        };
+
+        if i + 1 >= first {
+            lines.push((i + 1, tokens));
+        }
+    }
+
+    if lines.is_empty() {
+        lines.push((first, Vec::new()));
+    }
+
+    // size the window to fit the code
+    let columns = lines
+        .iter()
+        .map(|(_, tokens)| tokens.iter().map(|(_, t)| t.chars().count()).sum::<usize>())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_COLUMNS);
+
+    let gutter = (lines.last().unwrap().0.to_string().len() + 2) * CELL_WIDTH;
+    let window_width = (PADDING * 2 + gutter + columns * CELL_WIDTH).max(MIN_WINDOW_WIDTH);
+    let window_height = TITLE_BAR + PADDING * 2 + lines.len() * LINE_HEIGHT;
+
+    let accent = parse_hex_color(accent).unwrap_or(DEFAULT_ACCENT);
+    let mut canvas = Canvas::new(
+        window_width + MARGIN * 2,
+        window_height + MARGIN * 2,
+        accent,
+    );
+
+    // window
+    canvas.fill(MARGIN, MARGIN, window_width, window_height, WINDOW);
+    canvas.fill(MARGIN, MARGIN, window_width, TITLE_BAR, TITLE_BAR_COLOR);
+
+    for (i, color) in DOTS.iter().enumerate() {
+        canvas.circle(MARGIN + 20 + i * 22, MARGIN + TITLE_BAR / 2, 6, *color);
+    }
+
+    let title_columns = (window_width - 120) / CELL_WIDTH;
+    let title: String = title.chars().take(title_columns).collect();
+    canvas.text(
+        MARGIN + (window_width - title.chars().count() * CELL_WIDTH) / 2,
+        MARGIN + (TITLE_BAR - GLYPH_HEIGHT * SCALE) / 2,
+        &title,
+        SCALE,
+        LINE_NUMBER,
+    );
+
+    // code
+    let mut y = MARGIN + TITLE_BAR + PADDING;
+    for (number, tokens) in lines {
+        let number = number.to_string();
+        canvas.text(
+            MARGIN + PADDING + gutter - (number.len() + 2) * CELL_WIDTH,
+            y,
+            &number,
+            SCALE,
+            LINE_NUMBER,
+        );
+
+        let mut column = 0;
+        for (color, text) in tokens {
+            let text: String = text.chars().take(MAX_COLUMNS - column).collect();
+            canvas.text(
+                MARGIN + PADDING + gutter + column * CELL_WIDTH,
+                y,
+                &text,
+                SCALE,
+                color,
+            );
+
+            column += text.chars().count();
+        }
+
+        y += LINE_HEIGHT;
+    }
+
+    canvas.png()
+}
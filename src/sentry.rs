@@ -0,0 +1,358 @@
+//! Error reporting to [Sentry](https://sentry.io) (`SENTRY_DSN`)
+//!
+//! Panics (like a template failing to render) and server errors are sent as Sentry events with
+//! the request they happened in. Events are queued and sent by a background thread, so reporting
+//! never holds up a request; anything past [`MAX_QUEUED_EVENTS`] is dropped.
+use std::any::Any;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use serde_json::{json, Value};
+
+/// How many events can wait to be sent before new ones are dropped
+const MAX_QUEUED_EVENTS: usize = 100;
+
+/// Request headers sent with events (cookies and credentials never are)
+const REPORTED_HEADERS: [&str; 4] = ["Accept", "Accept-Language", "Referer", "User-Agent"];
+
+/// Where events are sent, set by [`init`]
+static CLIENT: OnceLock<Client> = OnceLock::new();
+/// How many events are queued or being sent
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+tokio::task_local! {
+    /// The request being handled (see [`layer`])
+    static CONTEXT: RequestContext;
+}
+
+#[derive(Debug)]
+struct Client {
+    dsn: String,
+    /// The envelope endpoint of the project
+    url: String,
+    /// The public key of the project
+    key: String,
+    environment: String,
+    queue: SyncSender<Value>,
+}
+
+/// The request an event happened in
+#[derive(Debug, Clone)]
+struct RequestContext {
+    method: String,
+    /// The url of the request, without the query (which can hold passwords)
+    url: String,
+    route: String,
+    headers: serde_json::Map<String, Value>,
+}
+
+impl RequestContext {
+    fn new(req: &Request) -> Self {
+        let headers = req.headers();
+
+        Self {
+            method: req.method().to_string(),
+            url: format!(
+                "{}{}",
+                crate::base::BaseStore::new().origin(headers),
+                req.uri().path()
+            ),
+            route: crate::middleware::route_name(req),
+            headers: REPORTED_HEADERS
+                .iter()
+                .filter_map(|name| {
+                    let value = headers.get(*name)?.to_str().ok()?;
+                    Some((name.to_string(), Value::from(value)))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Marks responses built by [`panic_response`], so the panic isn't reported twice
+#[derive(Debug, Clone, Copy)]
+struct Panicked;
+
+/// Parse a DSN (`https://KEY@o0.ingest.sentry.io/PROJECT`)
+///
+/// # Returns
+/// * the envelope endpoint, and the public key
+fn parse_dsn(dsn: &str) -> Option<(String, String)> {
+    let url = url::Url::parse(dsn).ok()?;
+    let key = url.username().to_string();
+
+    let path = url.path().trim_end_matches('/');
+    let (prefix, project) = path.rsplit_once('/')?;
+
+    if key.is_empty() || project.is_empty() {
+        return None;
+    }
+
+    let port = url.port().map(|p| format!(":{p}")).unwrap_or_default();
+
+    Some((
+        format!(
+            "{}://{}{port}{prefix}/api/{project}/envelope/",
+            url.scheme(),
+            url.host_str()?
+        ),
+        key,
+    ))
+}
+
+/// Check if errors are reported (`SENTRY_DSN` is set)
+pub fn enabled() -> bool {
+    CLIENT.get().is_some()
+}
+
+/// Send queued events until the queue closes
+fn send(events: Receiver<Value>) {
+    while let Ok(event) = events.recv() {
+        let Some(client) = CLIENT.get() else {
+            return;
+        };
+
+        let payload = event.to_string();
+        let envelope = format!(
+            "{}\n{}\n{payload}",
+            json!({ "event_id": event["event_id"], "dsn": client.dsn }),
+            json!({ "type": "event", "length": payload.len() })
+        );
+
+        let headers = vec![
+            (
+                "Content-Type".to_string(),
+                "application/x-sentry-envelope".to_string(),
+            ),
+            (
+                "X-Sentry-Auth".to_string(),
+                format!(
+                    "Sentry sentry_version=7, sentry_client=sealable/{}, sentry_key={}",
+                    env!("CARGO_PKG_VERSION"),
+                    client.key
+                ),
+            ),
+        ];
+
+        match crate::http::send_trusted_blocking(
+            "POST",
+            &client.url,
+            &headers,
+            envelope.as_bytes(),
+            64 * 1024,
+        ) {
+            Ok(res) if res.status < 300 => (),
+            Ok(res) => tracing::warn!(status = res.status, "Sentry didn't accept an event."),
+            Err(e) => tracing::warn!(error = e, "Failed to send an event to Sentry."),
+        }
+
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Wait (blocking) until queued events are sent, or `timeout` passes
+pub fn flush(timeout: Duration) {
+    let start = Instant::now();
+
+    while PENDING.load(Ordering::SeqCst) > 0 && start.elapsed() < timeout {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Queue an event
+///
+/// # Arguments
+/// * `level` - `fatal`, `error` or `warning`
+/// * `event` - the event's own fields (`message`, `exception`, ...)
+/// * `request` - the request the event happened in
+fn capture(level: &str, event: Value, request: Option<&RequestContext>) {
+    let Some(client) = CLIENT.get() else {
+        return;
+    };
+
+    let mut event = match event {
+        Value::Object(event) => event,
+        _ => return,
+    };
+
+    event.insert(
+        "event_id".to_string(),
+        Value::from(format!("{:032x}", rand::random::<u128>())),
+    );
+    event.insert(
+        "timestamp".to_string(),
+        Value::from(dorsal::utility::unix_epoch_timestamp() as f64 / 1000.0),
+    );
+    event.insert("platform".to_string(), Value::from("native"));
+    event.insert("level".to_string(), Value::from(level));
+    event.insert("logger".to_string(), Value::from(env!("CARGO_CRATE_NAME")));
+    event.insert(
+        "release".to_string(),
+        Value::from(format!("sealable@{}", env!("CARGO_PKG_VERSION"))),
+    );
+
+    if !client.environment.is_empty() {
+        event.insert(
+            "environment".to_string(),
+            Value::from(client.environment.clone()),
+        );
+    }
+
+    let mut tags = serde_json::Map::new();
+
+    if let Some(id) = crate::middleware::current_request_id() {
+        tags.insert("request_id".to_string(), Value::from(id));
+    }
+
+    if let Some(request) = request {
+        tags.insert("route".to_string(), Value::from(request.route.clone()));
+        event.insert(
+            "request".to_string(),
+            json!({
+                "method": request.method,
+                "url": request.url,
+                "headers": request.headers,
+            }),
+        );
+    }
+
+    event.insert("tags".to_string(), Value::Object(tags));
+
+    // a full queue drops the event, Sentry being down shouldn't pile up memory
+    PENDING.fetch_add(1, Ordering::SeqCst);
+    if client.queue.try_send(Value::Object(event)).is_err() {
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Report an error (with the current request, if there is one)
+pub fn capture_message(level: &str, message: &str) {
+    if !enabled() {
+        return;
+    }
+
+    let event = json!({ "message": { "formatted": message } });
+    let _ = CONTEXT
+        .try_with(|request| capture(level, event.clone(), Some(request)))
+        .map_err(|_| capture(level, event, None));
+}
+
+/// Report panics (with the request they happened in)
+fn report_panic(info: &std::panic::PanicHookInfo) {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string());
+
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+        .unwrap_or_default();
+
+    let event = json!({
+        "exception": {
+            "values": [{
+                "type": "panic",
+                "value": message,
+                "mechanism": { "type": "panic", "handled": false },
+            }],
+        },
+        "culprit": location,
+        "extra": {
+            "location": location,
+            "backtrace": std::backtrace::Backtrace::force_capture().to_string(),
+        },
+    });
+
+    let _ = CONTEXT
+        .try_with(|request| capture("fatal", event.clone(), Some(request)))
+        .map_err(|_| capture("fatal", event, None));
+}
+
+/// Start reporting panics and server errors, if `SENTRY_DSN` is set
+///
+/// `SENTRY_ENVIRONMENT` is sent with every event (`production`, `staging`, ...). Called by the
+/// binary, like [`crate::telemetry::init`].
+pub fn init() {
+    let dsn = match std::env::var("SENTRY_DSN") {
+        Ok(dsn) if !dsn.is_empty() => dsn,
+        _ => return,
+    };
+
+    let Some((url, key)) = parse_dsn(&dsn) else {
+        tracing::error!("SENTRY_DSN isn't a valid DSN, errors won't be reported.");
+        return;
+    };
+
+    let (queue, events) = mpsc::sync_channel(MAX_QUEUED_EVENTS);
+
+    let client = Client {
+        dsn,
+        url,
+        key,
+        environment: std::env::var("SENTRY_ENVIRONMENT").unwrap_or_default(),
+        queue,
+    };
+
+    if CLIENT.set(client).is_err() {
+        return;
+    }
+
+    std::thread::spawn(move || send(events));
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        report_panic(info);
+        previous(info);
+    }));
+}
+
+/// The response sent when a handler panics (an error page instead of a dropped connection)
+pub fn panic_response(_: Box<dyn Any + Send + 'static>) -> Response {
+    let mut res = (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        // (not `error_text`, which would report the error again)
+        Html(crate::pages::error_page(crate::i18n::current_text(
+            "error.other",
+        ))),
+    )
+        .into_response();
+
+    res.extensions_mut().insert(Panicked);
+    res
+}
+
+/// Keep track of the request being handled, and report server errors
+pub async fn layer(req: Request, next: Next) -> Response {
+    let context = RequestContext::new(&req);
+    let res = CONTEXT.scope(context.clone(), next.run(req)).await;
+
+    if res.status().is_server_error() && res.extensions().get::<Panicked>().is_none() {
+        capture(
+            "error",
+            json!({
+                "message": {
+                    "formatted": format!(
+                        "{} {} responded with {}",
+                        context.method,
+                        context.route,
+                        res.status()
+                    ),
+                },
+            }),
+            Some(&context),
+        );
+    }
+
+    res
+}
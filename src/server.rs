@@ -0,0 +1,155 @@
+//! Serving the app on listeners `axum::serve` doesn't support (TLS, unix sockets)
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{extract::ConnectInfo, Router};
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
+
+/// The open connections of a listener
+#[derive(Debug)]
+pub struct Connections {
+    stop: watch::Sender<bool>,
+    open: Arc<()>,
+}
+
+/// Held by a connection while it's open
+#[derive(Debug)]
+pub struct Connection {
+    stop: watch::Receiver<bool>,
+    _open: Arc<()>,
+}
+
+impl Default for Connections {
+    fn default() -> Self {
+        Self {
+            stop: watch::channel(false).0,
+            open: Arc::new(()),
+        }
+    }
+}
+
+impl Connections {
+    /// Track a new connection
+    pub fn open(&self) -> Connection {
+        Connection {
+            stop: self.stop.subscribe(),
+            _open: self.open.clone(),
+        }
+    }
+
+    /// Ask every connection to close once its current request is done, and wait until they have
+    pub async fn close(self) {
+        let _ = self.stop.send(true);
+
+        while Arc::strong_count(&self.open) > 1 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+/// Marks the requests of a unix socket, whose only clients are proxies (their `X-Forwarded-*`
+/// headers are believed, see `crate::middleware::client_ip`)
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UnixSocket;
+
+/// Serve `app` on a connection (HTTP/1.1) until it's closed, or the listener closes
+///
+/// # Arguments
+/// * `addr` - the address of the client, if the connection has one (unix sockets don't)
+pub async fn serve_connection<I>(
+    io: I,
+    addr: Option<SocketAddr>,
+    app: Router,
+    mut connection: Connection,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = hyper::service::service_fn(move |mut req: hyper::Request<Incoming>| {
+        match addr {
+            Some(addr) => {
+                req.extensions_mut().insert(ConnectInfo(addr));
+            }
+            None => {
+                req.extensions_mut().insert(UnixSocket);
+            }
+        }
+
+        tower::ServiceExt::oneshot(app.clone(), req)
+    });
+
+    let conn = hyper::server::conn::http1::Builder::new()
+        .serve_connection(TokioIo::new(io), service)
+        .with_upgrades();
+    tokio::pin!(conn);
+
+    tokio::select! {
+        _ = &mut conn => (),
+        _ = async { connection.stop.wait_for(|stop| *stop).await.is_ok() } => {
+            conn.as_mut().graceful_shutdown();
+            let _ = conn.await;
+        }
+    }
+}
+
+/// Serve `app` on a unix socket until `shutdown` completes (then wait for open connections)
+///
+/// A socket left at `path` by a previous run is replaced, and removed again when the server
+/// stops. Clients of the socket have no address, so the client's address is always taken from
+/// `X-Forwarded-For` (see [`crate::middleware::client_ip`]).
+///
+/// # Arguments
+/// * `mode` - the permissions of the socket (`0o660` lets the proxy's group connect)
+#[cfg(unix)]
+pub async fn serve_unix(
+    path: PathBuf,
+    mode: u32,
+    app: Router,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), String> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if std::fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_socket()) {
+        let _ = std::fs::remove_file(&path);
+    }
+
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Failed to listen on {path:?}: {e}"))?;
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Failed to set the permissions of {path:?}: {e}"))?;
+
+    let connections = Connections::default();
+    tokio::pin!(shutdown);
+
+    loop {
+        let socket = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to accept a connection.");
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+
+        tokio::spawn(serve_connection(
+            socket,
+            None,
+            app.clone(),
+            connections.open(),
+        ));
+    }
+
+    drop(listener);
+    let _ = std::fs::remove_file(&path);
+    connections.close().await;
+
+    Ok(())
+}
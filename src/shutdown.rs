@@ -0,0 +1,74 @@
+//! Graceful shutdown on `SIGTERM` and `SIGINT`
+//!
+//! The first signal stops the server from accepting connections and waits for open requests
+//! (up to `SHUTDOWN_TIMEOUT` seconds), a second one exits immediately.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::database::Database;
+
+/// How long open requests get to finish by default (in seconds)
+const DEFAULT_TIMEOUT: u64 = 30;
+/// How long queued traces and error reports get to be sent
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Set once a shutdown signal is received
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle(_: libc::c_int) {
+    // (only async-signal-safe calls are allowed here)
+    if STOPPING.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(1) }
+    }
+}
+
+/// Check if the server is shutting down
+pub fn stopping() -> bool {
+    STOPPING.load(Ordering::SeqCst)
+}
+
+/// Wait for `SIGTERM` or `SIGINT`, for [`axum::serve::Serve::with_graceful_shutdown`]
+///
+/// Once the signal arrives, the process exits anyway if open requests take longer than
+/// `SHUTDOWN_TIMEOUT` seconds (30 by default).
+pub async fn signal() {
+    // SAFETY: the handler only touches an atomic and calls `_exit`
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGTERM, handle as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGINT, handle as *const () as libc::sighandler_t);
+    }
+
+    while !stopping() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let timeout = std::env::var("SHUTDOWN_TIMEOUT")
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT);
+
+    tracing::info!("Shutting down, waiting up to {timeout}s for open requests to finish.");
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(timeout)).await;
+        tracing::warn!("Requests were still open after {timeout}s, exiting anyway.");
+        std::process::exit(1);
+    });
+}
+
+/// Finish shutting down once the server stopped: write buffered view counts, send queued traces
+/// and error reports, and close the database pool
+pub async fn finish(database: &Database) {
+    crate::views::flush(database).await;
+
+    let _ = tokio::task::spawn_blocking(|| {
+        crate::telemetry::flush();
+        crate::sentry::flush(FLUSH_TIMEOUT);
+    })
+    .await;
+
+    database.base.base.db.client.close().await;
+    tracing::info!("Stopped.");
+}
@@ -0,0 +1,162 @@
+//! The instance signing key (ActivityPub, mirroring), and the key of share links
+//!
+//! Every instance has one RSA key, generated the first time it's needed and stored in
+//! `se_settings`. Signatures are RSASSA-PKCS1-v1_5 over SHA-256 (`rsa-sha256`).
+//!
+//! Share links (`/:url?sig=&exp=`) are signed with HMAC-SHA256 instead, by a secret that never
+//! leaves the instance (stored next to the RSA key).
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use pastemd::model::Paste;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::database::Database;
+
+/// Name of the setting the private key is stored in (named after its first user, ActivityPub)
+const KEY_SETTING: &str = "ap_private_key";
+
+/// Name of the setting the share link secret is stored in
+const SHARE_KEY_SETTING: &str = "share_key";
+
+/// How long share links last by default (in seconds, a day)
+pub const SHARE_LINK_LIFETIME: u64 = 24 * 60 * 60;
+
+/// How long share links can last at most (in seconds, 30 days)
+pub const MAX_SHARE_LINK_LIFETIME: u64 = 30 * 24 * 60 * 60;
+
+/// The instance private key (loaded or generated on first use)
+static PRIVATE_KEY: OnceCell<Option<RsaPrivateKey>> = OnceCell::const_new();
+
+/// The share link secret (loaded or generated on first use)
+static SHARE_KEY: OnceCell<Option<String>> = OnceCell::const_new();
+
+/// Get the instance private key
+pub async fn private_key(database: &Database) -> Option<RsaPrivateKey> {
+    PRIVATE_KEY
+        .get_or_init(|| async {
+            if let Some(pem) = database.get_setting(KEY_SETTING).await {
+                return RsaPrivateKey::from_pkcs8_pem(&pem).ok();
+            }
+
+            let key = tokio::task::spawn_blocking(|| {
+                RsaPrivateKey::new(&mut rand::thread_rng(), 2048).ok()
+            })
+            .await
+            .ok()??;
+
+            let pem = key.to_pkcs8_pem(LineEnding::LF).ok()?;
+            database
+                .set_setting(KEY_SETTING, pem.to_string())
+                .await
+                .ok()?;
+
+            Some(key)
+        })
+        .await
+        .clone()
+}
+
+/// Get the instance public key (as a SPKI PEM)
+pub async fn public_key_pem(database: &Database) -> Option<String> {
+    RsaPublicKey::from(&private_key(database).await?)
+        .to_public_key_pem(LineEnding::LF)
+        .ok()
+}
+
+/// Parse a public key PEM (SPKI or PKCS#1)
+pub fn parse_public_key(pem: &str) -> Option<RsaPublicKey> {
+    RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .ok()
+}
+
+/// Sign `data` with `key`
+pub fn sign(key: &RsaPrivateKey, data: &[u8]) -> Vec<u8> {
+    key.sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(data))
+        .unwrap_or_default()
+}
+
+/// Check if `signature` is a signature of `data` by `key`
+pub fn verify(key: &RsaPublicKey, data: &[u8], signature: &[u8]) -> bool {
+    key.verify(
+        Pkcs1v15Sign::new::<Sha256>(),
+        &Sha256::digest(data),
+        signature,
+    )
+    .is_ok()
+}
+
+/// Get the share link secret
+async fn share_key(database: &Database) -> Option<String> {
+    SHARE_KEY
+        .get_or_init(|| async {
+            if let Some(key) = database.get_setting(SHARE_KEY_SETTING).await {
+                return Some(key);
+            }
+
+            let key = dorsal::utility::random_id();
+            database
+                .set_setting(SHARE_KEY_SETTING, key.clone())
+                .await
+                .ok()?;
+
+            Some(key)
+        })
+        .await
+        .clone()
+}
+
+/// The MAC of a share link to `paste` expiring at `expires_at`
+///
+/// The paste's view password is part of what's signed, so changing it revokes every link.
+fn share_mac(key: &str, paste: &Paste, expires_at: u128) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("any key length works");
+    mac.update(
+        format!(
+            "{}\n{expires_at}\n{}",
+            paste.url, paste.metadata.view_password
+        )
+        .as_bytes(),
+    );
+    mac
+}
+
+/// Sign a share link to `paste`
+///
+/// # Arguments
+/// * `expires_at` - when the link stops working (in milliseconds since the epoch)
+///
+/// # Returns
+/// * the `sig` of the link
+pub async fn sign_share_link(
+    database: &Database,
+    paste: &Paste,
+    expires_at: u128,
+) -> Option<String> {
+    let key = share_key(database).await?;
+    Some(BASE64.encode(share_mac(&key, paste, expires_at).finalize().into_bytes()))
+}
+
+/// Check if `sig` signs a share link to `paste` that hasn't expired
+pub async fn verify_share_link(
+    database: &Database,
+    paste: &Paste,
+    sig: &str,
+    expires_at: u128,
+) -> bool {
+    if sig.is_empty() || (expires_at <= dorsal::utility::unix_epoch_timestamp()) {
+        return false;
+    }
+
+    let (Some(key), Ok(sig)) = (share_key(database).await, BASE64.decode(sig)) else {
+        return false;
+    };
+
+    share_mac(&key, paste, expires_at)
+        .verify_slice(&sig)
+        .is_ok()
+}
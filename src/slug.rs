@@ -0,0 +1,138 @@
+//! Checking the urls pastes are created (or moved) at
+//!
+//! Paste urls share the top level of the site with the app's own routes, so a paste called `api`
+//! or `search` would be shadowed by them (or shadow them, depending on the route). Those names,
+//! and the ones listed in `RESERVED_URLS`, can never be taken. The same goes for wiki pages
+//! (`/:url/:page`) and the routes under a paste.
+use pastemd::model::PasteError;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// The first segment of every route of the app
+const ROUTES: [&str; 22] = [
+    ".well-known",
+    "_dev",
+    "a",
+    "account",
+    "admin",
+    "ap",
+    "api",
+    "branding",
+    "c",
+    "export",
+    "favicon.ico",
+    "feed.atom",
+    "metrics",
+    "new",
+    "pages",
+    "preferences",
+    "robots.txt",
+    "s",
+    "search",
+    "star",
+    "static",
+    "ws",
+];
+
+/// The second segment of the routes under a paste (`/:url/raw`), which wiki pages can't be called
+const PASTE_ROUTES: [&str; 12] = [
+    "attachments",
+    "card.png",
+    "download",
+    "draft",
+    "edit",
+    "embed",
+    "events",
+    "export",
+    "feed.atom",
+    "print",
+    "raw",
+    "stats",
+];
+
+/// The longest name a wiki page can have (in characters)
+pub const MAX_PAGE_LENGTH: usize = 100;
+
+/// The shortest url a paste can have (in characters)
+pub const MIN_LENGTH: usize = 3;
+
+/// The longest url a paste can have (in characters)
+pub const MAX_LENGTH: usize = 250;
+
+/// The characters urls can be made of (the same as `pastemd`)
+static CHARSET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[\w\-.!\p{Extended_Pictographic}]+$").unwrap());
+
+/// Get the form a url is stored in (the same as `pastemd`: punycode, lowercased, without a
+/// trailing `-`)
+pub fn canonical(url: &str) -> String {
+    let mut url = idna::punycode::encode_str(url)
+        .unwrap_or_else(|| url.to_string())
+        .to_lowercase();
+
+    if url.ends_with('-') {
+        url.pop();
+    }
+
+    url
+}
+
+/// Check if a url is taken by the app (or the operator)
+pub fn is_reserved(url: &str) -> bool {
+    let prefix = crate::config::static_prefix();
+    let static_segment = prefix.trim_start_matches('/').split('/').next();
+
+    ROUTES.contains(&url)
+        || (static_segment == Some(url))
+        || crate::config::reserved_urls().iter().any(|r| r == url)
+}
+
+/// Normalize the url a paste is requested at, and check that it can be used
+///
+/// # Returns
+/// The normalized url (trimmed and lowercased, empty if none was requested)
+pub fn check(url: &str) -> Result<String, PasteError> {
+    let url = url.trim().to_lowercase();
+
+    if url.is_empty() {
+        // (a random one is picked)
+        return Ok(url);
+    }
+
+    let length = url.chars().count();
+
+    if !(MIN_LENGTH..=MAX_LENGTH).contains(&length)
+        || !CHARSET.is_match(&url)
+        // (dots at the edges make urls look like files, or like `..`)
+        || url.starts_with('.')
+        || url.ends_with('.')
+        || is_reserved(&url)
+    {
+        return Err(PasteError::ValueError);
+    }
+
+    Ok(url)
+}
+
+/// Normalize the name of a wiki page (`/:url/:page`), and check that it can be used
+///
+/// Page names are lowercase letters, digits, `-` and `_`, and can't shadow the routes under a
+/// paste.
+///
+/// # Returns
+/// The normalized name
+pub fn check_page(name: &str) -> Result<String, PasteError> {
+    let name = name.trim().to_lowercase();
+
+    if name.is_empty()
+        || (name.chars().count() > MAX_PAGE_LENGTH)
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() | matches!(c, '-' | '_'))
+        || PASTE_ROUTES.contains(&name.as_str())
+    {
+        return Err(PasteError::ValueError);
+    }
+
+    Ok(name)
+}
@@ -0,0 +1,494 @@
+//! Logs (`LOG_FORMAT`, `LOG_LEVEL`), and traces exported over OTLP (`OTEL_EXPORTER_OTLP_ENDPOINT`)
+//!
+//! Both go through [`tracing`]: events are written to stdout as text or JSON lines, one per
+//! request (see [`layer`]) and one per problem. Requests, database calls and rendering are
+//! instrumented with spans, which are only recorded when an endpoint is configured (so they cost
+//! next to nothing otherwise). Finished spans are sent in batches as OTLP/HTTP JSON, which Jaeger,
+//! Tempo and the OpenTelemetry collector accept on port 4318. Only this crate's events and spans
+//! are recorded.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use serde_json::{json, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Instrument, Level, Metadata, Subscriber};
+
+/// How often finished spans are sent
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+/// The most finished spans kept while the collector can't be reached
+const MAX_QUEUED_SPANS: usize = 10_000;
+
+/// Finished spans waiting to be exported (as OTLP JSON)
+static FINISHED: Mutex<Vec<Value>> = Mutex::new(Vec::new());
+/// Where spans are exported, set by [`init`] if an endpoint is configured
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+#[derive(Debug)]
+struct Exporter {
+    url: String,
+    headers: Vec<(String, String)>,
+    service: String,
+}
+
+thread_local! {
+    /// The spans entered on this thread (innermost last)
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Get the current time in nanoseconds
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// Create a random hex id of `len` characters (`random_id` is 64 hex characters)
+fn hex_id(len: usize) -> String {
+    dorsal::utility::random_id()[..len].to_string()
+}
+
+/// The fields of a span
+#[derive(Debug, Default)]
+struct Fields {
+    /// `otel.name`, replacing the span name
+    name: Option<String>,
+    /// `otel.kind`
+    kind: Option<String>,
+    /// `traceparent`, the W3C trace context of an incoming request
+    traceparent: Option<String>,
+    /// If the span failed (`error`, or a `5xx` `http.status_code`)
+    error: bool,
+    attributes: Vec<(String, String)>,
+}
+
+impl Fields {
+    fn add(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "otel.name" => self.name = Some(value),
+            "otel.kind" => self.kind = Some(value),
+            "traceparent" => self.traceparent = Some(value).filter(|v| !v.is_empty()),
+            name => {
+                if (name == "error") || ((name == "http.status_code") && value.starts_with('5')) {
+                    self.error = true;
+                }
+
+                self.attributes.push((name.to_string(), value));
+            }
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.add(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.add(field, format!("{value:?}"));
+    }
+}
+
+/// The message and fields of an event
+#[derive(Debug, Default)]
+struct EventFields {
+    message: String,
+    fields: serde_json::Map<String, Value>,
+}
+
+impl EventFields {
+    fn add(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(s)) => self.message = s,
+            (name, value) => {
+                self.fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.add(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.add(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.add(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.add(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.add(field, Value::from(format!("{value:?}")));
+    }
+}
+
+/// A span that hasn't finished
+#[derive(Debug)]
+struct OpenSpan {
+    name: String,
+    kind: String,
+    trace_id: String,
+    span_id: String,
+    parent_span_id: String,
+    start: u128,
+    fields: Fields,
+    /// How many handles to the span exist
+    refs: usize,
+}
+
+/// Writes events, and records spans (queuing them in [`FINISHED`] once they're closed)
+#[derive(Debug)]
+struct Tracer {
+    /// The most verbose level logged
+    level: Level,
+    /// If events are written as JSON
+    json: bool,
+    /// If spans are recorded (when there's an exporter)
+    spans: bool,
+    next_id: AtomicU64,
+    open: Mutex<HashMap<u64, OpenSpan>>,
+}
+
+impl Subscriber for Tracer {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+            && match metadata.is_span() {
+                true => self.spans,
+                false => *metadata.level() <= self.level,
+            }
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+
+        let parent = match attrs.parent() {
+            Some(p) => Some(p.into_u64()),
+            None if attrs.is_contextual() => ENTERED.with(|e| e.borrow().last().copied()),
+            None => None,
+        };
+
+        let mut open = self.open.lock().unwrap();
+
+        // a local parent, the remote parent of the request, or a new trace
+        let (trace_id, parent_span_id) = match parent.and_then(|p| open.get(&p)) {
+            Some(p) => (p.trace_id.clone(), p.span_id.clone()),
+            None => match fields
+                .traceparent
+                .as_deref()
+                .map(|t| t.split('-').collect::<Vec<&str>>())
+            {
+                Some(parts) if (parts.len() == 4) && (parts[1].len() == 32) => {
+                    (parts[1].to_string(), parts[2].to_string())
+                }
+                _ => (hex_id(32), String::new()),
+            },
+        };
+
+        open.insert(
+            id,
+            OpenSpan {
+                name: fields
+                    .name
+                    .take()
+                    .unwrap_or_else(|| attrs.metadata().name().to_string()),
+                kind: fields.kind.take().unwrap_or_default(),
+                trace_id,
+                span_id: hex_id(16),
+                parent_span_id,
+                start: now(),
+                fields,
+                refs: 1,
+            },
+        );
+
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        if let Some(s) = self.open.lock().unwrap().get_mut(&span.into_u64()) {
+            values.record(&mut s.fields);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+
+        let metadata = event.metadata();
+        let request_id = crate::middleware::current_request_id();
+        let timestamp = crate::utility::iso8601(dorsal::utility::unix_epoch_timestamp());
+
+        let line = if self.json {
+            let mut line = serde_json::Map::new();
+            line.insert("timestamp".to_string(), Value::from(timestamp));
+            line.insert("level".to_string(), Value::from(metadata.level().as_str()));
+            line.insert("target".to_string(), Value::from(metadata.target()));
+            line.insert("message".to_string(), Value::from(fields.message));
+            line.extend(fields.fields);
+
+            if let Some(id) = request_id {
+                line.insert("request_id".to_string(), Value::from(id));
+            }
+
+            Value::Object(line).to_string()
+        } else {
+            let mut line = format!("{timestamp} {:>5} {}", metadata.level(), fields.message);
+
+            for (name, value) in fields.fields {
+                match value {
+                    Value::String(s) => line.push_str(&format!(" {name}={s}")),
+                    value => line.push_str(&format!(" {name}={value}")),
+                }
+            }
+
+            if let Some(id) = request_id {
+                line.push_str(&format!(" request_id={id}"));
+            }
+
+            line
+        };
+
+        let _ = writeln!(std::io::stdout().lock(), "{line}");
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|e| e.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|e| {
+            let mut entered = e.borrow_mut();
+
+            if let Some(i) = entered.iter().rposition(|s| *s == span.into_u64()) {
+                entered.remove(i);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(s) = self.open.lock().unwrap().get_mut(&span.into_u64()) {
+            s.refs += 1;
+        }
+
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut open = self.open.lock().unwrap();
+
+        let Some(s) = open.get_mut(&span.into_u64()) else {
+            return false;
+        };
+
+        s.refs -= 1;
+        if s.refs > 0 {
+            return false;
+        }
+
+        let s = open.remove(&span.into_u64()).unwrap();
+        drop(open);
+
+        let mut finished = FINISHED.lock().unwrap();
+        if finished.len() < MAX_QUEUED_SPANS {
+            finished.push(json!({
+                "traceId": s.trace_id,
+                "spanId": s.span_id,
+                "parentSpanId": s.parent_span_id,
+                "name": s.name,
+                // SPAN_KIND_SERVER or SPAN_KIND_INTERNAL
+                "kind": if s.kind == "server" { 2 } else { 1 },
+                "startTimeUnixNano": s.start.to_string(),
+                "endTimeUnixNano": now().to_string(),
+                "attributes": s.fields.attributes.iter().map(|(key, value)| json!({
+                    "key": key,
+                    "value": { "stringValue": value },
+                })).collect::<Vec<Value>>(),
+                // STATUS_CODE_ERROR or STATUS_CODE_UNSET
+                "status": { "code": if s.fields.error { 2 } else { 0 } },
+            }));
+        }
+
+        true
+    }
+}
+
+/// Send finished spans to the collector forever
+fn export() {
+    loop {
+        std::thread::sleep(EXPORT_INTERVAL);
+        flush();
+    }
+}
+
+/// Send the finished spans now (blocking), instead of waiting for the next batch
+pub fn flush() {
+    let Some(exporter) = EXPORTER.get() else {
+        return;
+    };
+
+    let spans = std::mem::take(&mut *FINISHED.lock().unwrap());
+    if spans.is_empty() {
+        return;
+    }
+
+    let service = &exporter.service;
+    let body = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": service } }],
+            },
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    });
+
+    match crate::http::send_trusted_blocking(
+        "POST",
+        &exporter.url,
+        &exporter.headers,
+        body.to_string().as_bytes(),
+        64 * 1024,
+    ) {
+        Ok(res) if res.is_success() => (),
+        Ok(res) => tracing::warn!(
+            status = res.status,
+            "The trace collector failed the request."
+        ),
+        Err(e) => tracing::warn!(error = e, "Failed to send traces."),
+    }
+}
+
+/// Install the logger, and the span exporter if `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` or
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+///
+/// Called by the binary, apps embedding sealable can install their own `tracing` subscriber
+/// instead.
+pub fn init() {
+    let url = match std::env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT") {
+        Ok(url) if !url.is_empty() => url,
+        _ => match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            Ok(url) if !url.is_empty() => format!("{}/v1/traces", url.trim_end_matches('/')),
+            _ => String::new(),
+        },
+    };
+
+    // `key=value` pairs separated by commas (values are percent-encoded)
+    let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+    for pair in std::env::var("OTEL_EXPORTER_OTLP_HEADERS")
+        .unwrap_or_default()
+        .split(',')
+    {
+        if let Some((key, value)) = pair.split_once('=') {
+            headers.push((
+                key.trim().to_string(),
+                percent_encoding::percent_decode_str(value.trim())
+                    .decode_utf8_lossy()
+                    .to_string(),
+            ));
+        }
+    }
+
+    let tracer = Tracer {
+        level: std::env::var("LOG_LEVEL")
+            .ok()
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(Level::INFO),
+        json: std::env::var("LOG_FORMAT").is_ok_and(|f| f == "json"),
+        spans: !url.is_empty(),
+        next_id: AtomicU64::new(0),
+        open: Mutex::new(HashMap::new()),
+    };
+
+    if tracing::subscriber::set_global_default(tracer).is_err() {
+        eprintln!(
+            "A tracing subscriber is already installed, sealable won't log or export traces."
+        );
+        return;
+    }
+
+    if !url.is_empty() {
+        let _ = EXPORTER.set(Exporter {
+            url,
+            headers,
+            service: std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "sealable".to_string()),
+        });
+
+        std::thread::spawn(export);
+    }
+}
+
+/// Hide the host part of an address (the last byte of IPv4 addresses, everything after the
+/// first 48 bits of IPv6 addresses), so logs don't identify visitors
+fn anonymize(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{a}.{b}.{c}.0")
+        }
+        IpAddr::V6(ip) => {
+            let s = ip.segments();
+            format!("{:x}:{:x}:{:x}::", s[0], s[1], s[2])
+        }
+    }
+}
+
+/// Run every request in a `request` span (continuing the trace in its `traceparent` header),
+/// and log it once it's done
+pub async fn layer(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = crate::middleware::route_name(&req);
+    let path = req.uri().path().to_string();
+    let ip = crate::middleware::client_ip(&req).map(anonymize);
+    let start = std::time::Instant::now();
+
+    let span = tracing::info_span!(
+        "request",
+        otel.name = format!("{method} {route}"),
+        otel.kind = "server",
+        http.method = method,
+        http.route = route,
+        http.status_code = tracing::field::Empty,
+        http.request_id = crate::middleware::current_request_id(),
+        traceparent = req
+            .headers()
+            .get("traceparent")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default(),
+    );
+
+    let res = next.run(req).instrument(span.clone()).await;
+    let status = res.status().as_u16();
+    span.record("http.status_code", status);
+
+    let latency_ms = start.elapsed().as_millis() as u64;
+    let ip = ip.as_deref().unwrap_or("-");
+    if status >= 500 {
+        tracing::error!(target: "sealable::request", method, path, status, latency_ms, ip, "{method} {path}");
+    } else {
+        tracing::info!(target: "sealable::request", method, path, status, latency_ms, ip, "{method} {path}");
+    }
+
+    res
+}
@@ -0,0 +1,121 @@
+//! Small helpers shared between modules
+use axum::http::{header, HeaderMap};
+use sha2::{Digest, Sha256};
+
+/// Split a unix timestamp (in milliseconds) into its civil date and time (UTC)
+///
+/// # Returns
+/// * `(year, month, day, seconds since midnight)`
+pub fn civil_datetime(timestamp: u128) -> (i64, i64, i64, i64) {
+    let seconds = (timestamp / 1000) as i64;
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+    // civil date from days since the epoch (http://howardhinnant.github.io/date_algorithms.html)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, time)
+}
+
+/// Format a unix timestamp (in milliseconds) as an ISO 8601 date-time (UTC)
+pub fn iso8601(timestamp: u128) -> String {
+    let (year, month, day, time) = civil_datetime(timestamp);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        (time % 3600) / 60,
+        time % 60
+    )
+}
+
+/// Format a unix timestamp (in milliseconds) as an HTTP date (`Sun, 06 Nov 1994 08:49:37 GMT`)
+pub fn http_date(timestamp: u128) -> String {
+    let (year, month, day, time) = civil_datetime(timestamp);
+    let weekday = ((timestamp / 1000 / 86_400) + 4) % 7; // the epoch was a thursday
+
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[weekday as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        time / 3600,
+        (time % 3600) / 60,
+        time % 60
+    )
+}
+
+/// Parse an HTTP date (`Sun, 06 Nov 1994 08:49:37 GMT`) into a unix timestamp (in milliseconds)
+pub fn parse_http_date(input: &str) -> Option<u128> {
+    let mut parts = input.split_whitespace().skip(1);
+
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+
+    let mut time = parts.next()?.split(':').map(|p| p.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+
+    // days since the epoch from a civil date (the inverse of `civil_datetime`)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let timestamp = days * 86_400 + hours * 3600 + minutes * 60 + seconds;
+    u128::try_from(timestamp).ok().map(|t| t * 1000)
+}
+
+/// Make a strong `ETag` (`"..."`) from everything a response is built from
+pub fn etag(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+
+    for part in parts {
+        // (lengths keep `["ab", "c"]` and `["a", "bc"]` apart)
+        hasher.update(part.len().to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+
+    let hash: String = hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+
+    format!("\"{hash}\"")
+}
+
+/// Check if the `If-None-Match` header of a request matches `etag`
+///
+/// Tags are compared weakly (`W/"x"` matches `"x"`), since compressed responses send their
+/// `ETag` as a weak one.
+pub fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = strip(etag);
+
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .any(|tag| tag.trim() == "*" || strip(tag) == etag)
+}
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+#[rustfmt::skip]
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
@@ -0,0 +1,173 @@
+//! Buffered view counts
+//!
+//! Counting a view used to write to the cache on every request, so a popular paste turned each
+//! view into a round trip. Views are now added up in memory and written in one batch every
+//! `VIEW_FLUSH_INTERVAL` seconds (or once `VIEW_FLUSH_THRESHOLD` views are waiting), and when the
+//! server stops. Counts shown on pages include the views still waiting.
+//!
+//! Views in [`ViewMode::AuthenticatedOnce`] are still counted right away, since each one is
+//! checked against the views the user already has.
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::Duration;
+
+use pastemd::database::ViewMode;
+use tokio::sync::Notify;
+
+use crate::database::Database;
+
+/// How often waiting views are written by default (in seconds)
+const DEFAULT_INTERVAL: u64 = 10;
+/// How many views can wait before they're written early by default
+const DEFAULT_THRESHOLD: u32 = 1000;
+
+/// Views waiting to be written, by cache key
+static PENDING: LazyLock<Mutex<HashMap<String, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+/// Wakes the flushing task up early
+static FLUSH: Notify = Notify::const_new();
+/// How many views can wait (set by [`start`], views are written right away before that)
+static THRESHOLD: OnceLock<u32> = OnceLock::new();
+
+/// Get the cache key of a paste's view count (the same one `pastemd` uses)
+fn key(database: &Database, url: &str) -> String {
+    format!(
+        "{}:{}",
+        database.base.options.table_views.prefix,
+        crate::slug::canonical(url)
+    )
+}
+
+/// Count a view of a paste
+pub async fn record(database: &Database, url: String) {
+    let Some(threshold) = THRESHOLD.get() else {
+        database.base.base.cachedb.incr(key(database, &url)).await;
+        return;
+    };
+
+    let waiting = {
+        let mut pending = PENDING.lock().unwrap();
+        *pending.entry(key(database, &url)).or_default() += 1;
+        pending.values().sum::<u32>()
+    };
+
+    if waiting >= *threshold {
+        FLUSH.notify_one();
+    }
+}
+
+/// Get how many views of a paste are waiting to be written
+pub fn pending(database: &Database, url: &str) -> i32 {
+    PENDING
+        .lock()
+        .unwrap()
+        .get(&key(database, url))
+        .map_or(0, |c| *c as i32)
+}
+
+/// Move the view count of a paste to its new url (after the paste is renamed)
+///
+/// # Arguments
+/// * `url` - the paste's old `url`
+/// * `new_url` - the paste's new `url`
+pub async fn rename(database: &Database, url: &str, new_url: &str) {
+    let (old_key, new_key) = (key(database, url), key(database, new_url));
+
+    {
+        let mut pending = PENDING.lock().unwrap();
+        if let Some(count) = pending.remove(&old_key) {
+            *pending.entry(new_key.clone()).or_default() += count;
+        }
+    }
+
+    let cache = &database.base.base.cachedb;
+    if let Some(count) = cache.get(old_key.clone()).await {
+        cache.set(new_key, count).await;
+        cache.remove(old_key).await;
+    }
+
+    if database.base.options.view_mode == ViewMode::AuthenticatedOnce {
+        let query: String = if (database.base.base.db._type == "sqlite")
+            | (database.base.base.db._type == "mysql")
+        {
+            "UPDATE \":t\" SET \"url\" = ? WHERE \"url\" = ?"
+        } else {
+            "UPDATE \":t\" SET \"url\" = $1 WHERE \"url\" = $2"
+        }
+        .replace(":t", &database.base.options.table_views.table_name);
+
+        let _ = dorsal::query(&query)
+            .bind::<String>(crate::slug::canonical(new_url))
+            .bind::<String>(crate::slug::canonical(url))
+            .execute(&database.base.base.db.client)
+            .await;
+    }
+}
+
+/// Check if views are being buffered (see [`start`])
+pub fn buffering() -> bool {
+    THRESHOLD.get().is_some()
+}
+
+/// Write every waiting view (and the analytics buckets waiting with them)
+///
+/// Views that fail to be written are kept for the next flush.
+pub async fn flush(database: &Database) {
+    crate::analytics::flush(database).await;
+
+    let batch = std::mem::take(&mut *PENDING.lock().unwrap());
+
+    if batch.is_empty() {
+        return;
+    }
+
+    let mut pipe = redis::pipe();
+    for (key, count) in &batch {
+        pipe.incr(key, *count).ignore();
+    }
+
+    let written = database
+        .base
+        .base
+        .cachedb
+        .client
+        .get_connection()
+        .and_then(|mut con| pipe.query::<()>(&mut con));
+
+    if let Err(e) = written {
+        tracing::warn!(error = %e, "Failed to write view counts, retrying later.");
+
+        let mut pending = PENDING.lock().unwrap();
+        for (key, count) in batch {
+            *pending.entry(key).or_default() += count;
+        }
+    }
+}
+
+/// Start writing waiting views in the background (only the first call does anything)
+///
+/// Nothing is buffered when `VIEW_FLUSH_INTERVAL` is `0`.
+pub fn start(database: Database) {
+    if database.base.options.view_mode == ViewMode::AuthenticatedOnce {
+        return;
+    }
+
+    let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+    let interval = var("VIEW_FLUSH_INTERVAL").unwrap_or(DEFAULT_INTERVAL);
+    let threshold = var("VIEW_FLUSH_THRESHOLD").map_or(DEFAULT_THRESHOLD, |t| t.max(1) as u32);
+
+    if interval == 0 || THRESHOLD.set(threshold).is_err() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(interval)) => (),
+                _ = FLUSH.notified() => (),
+            }
+
+            flush(&database).await;
+        }
+    });
+}
@@ -0,0 +1,92 @@
+//! Outgoing webhooks (`/api/admin/webhooks`)
+//!
+//! Paste events are posted as JSON ([`WebhookPayload`]) to every webhook subscribed to them. The
+//! body is signed with the webhook's secret (`X-Sealable-Signature: sha256=<hex HMAC-SHA256>`), and
+//! deliveries that fail are retried with backoff (see [`crate::jobs::spawn_with_retries`]).
+use hmac::{Hmac, Mac};
+use pastemd::model::Paste;
+use sha2::Sha256;
+
+use crate::database::Database;
+use crate::model::{WebhookEvent, WebhookPaste, WebhookPayload};
+
+/// The largest response read from a webhook (in bytes)
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+/// Sign a payload with a webhook secret
+///
+/// # Returns
+/// * the `X-Sealable-Signature` header value
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length works");
+    mac.update(body);
+
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+
+    format!("sha256={hex}")
+}
+
+/// Send an event to the webhooks subscribed to it
+///
+/// # Arguments
+/// * `event` - the event
+/// * `paste` - the paste the event is about (as it was before being deleted, for `delete`)
+/// * `origin` - the public origin of the instance
+pub async fn fire(database: &Database, event: WebhookEvent, paste: &Paste, origin: &str) {
+    let webhooks = database.get_webhooks().await;
+
+    let payload = WebhookPayload {
+        event,
+        date: dorsal::utility::unix_epoch_timestamp(),
+        paste: WebhookPaste {
+            url: paste.url.clone(),
+            link: format!("{origin}/{}", paste.url),
+            title: paste.metadata.title.clone(),
+            owner: paste.metadata.owner.clone(),
+            date_published: paste.date_published,
+            date_edited: paste.date_edited,
+        },
+    };
+
+    let body = match serde_json::to_vec(&payload) {
+        Ok(b) => b,
+        Err(_) => return,
+    };
+
+    let event_name = serde_json::to_value(event)
+        .ok()
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .unwrap_or_default();
+
+    for webhook in webhooks
+        .into_iter()
+        .filter(|w| w.events.is_empty() || w.events.contains(&event))
+    {
+        let headers = vec![
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("X-Sealable-Event".to_string(), event_name.clone()),
+            (
+                "X-Sealable-Signature".to_string(),
+                signature(&webhook.secret, &body),
+            ),
+        ];
+
+        let body = body.clone();
+        crate::jobs::spawn_with_retries(move || {
+            let (url, headers, body) = (webhook.url.clone(), headers.clone(), body.clone());
+
+            async move {
+                match crate::http::send("POST", url, headers, body, MAX_RESPONSE_SIZE).await {
+                    Ok(r) if r.is_success() => Ok(()),
+                    Ok(r) => Err(format!("The webhook responded with {}.", r.status)),
+                    Err(e) => Err(e),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,364 @@
+//! WebSockets (RFC 6455), for the editor's live preview and editing together
+//!
+//! Only what browsers need is supported: text messages (possibly fragmented), pings and closing.
+//! Handlers take a [`WebSocketUpgrade`] and [`WebSocketUpgrade::on_upgrade`] it, then read
+//! messages from a channel (so waiting for one can be raced against anything else) and answer
+//! through a [`Sender`].
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper_util::rt::TokioIo;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::{mpsc, Mutex};
+
+/// Appended to the client's key to prove the server speaks WebSocket (see RFC 6455, 1.3)
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How many received messages can wait for the handler before the socket stops being read
+const BACKLOG: usize = 16;
+
+/// Status codes of close frames
+pub mod close {
+    pub const NORMAL: u16 = 1000;
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const UNSUPPORTED: u16 = 1003;
+    pub const INVALID_DATA: u16 = 1007;
+    pub const POLICY_VIOLATION: u16 = 1008;
+    pub const TOO_LARGE: u16 = 1009;
+}
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// A request to open a WebSocket (rejected with `400` if it isn't one)
+///
+/// Requests sent from another site (by their `Origin`) are rejected with `403`, since the
+/// browser sends them with the user's cookies.
+pub struct WebSocketUpgrade {
+    key: String,
+    on_upgrade: OnUpgrade,
+}
+
+/// Check if a comma-separated header has `token` (case-insensitively)
+fn has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case(token))
+}
+
+/// Check that a request comes from the same site as the instance (or isn't from a browser)
+fn same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|h| h.to_str().ok()) else {
+        return true;
+    };
+
+    let origin_host = url::Url::parse(origin).ok().and_then(|u| {
+        u.host_str().map(|host| match u.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        })
+    });
+
+    let public_url = crate::base::BaseStore::new().public_url;
+    let public_host = url::Url::parse(&public_url).ok().and_then(|u| {
+        u.host_str().map(|host| match u.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        })
+    });
+
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+
+    origin_host.is_some_and(|o| (Some(o.as_str()) == host) || (Some(&o) == public_host.as_ref()))
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for WebSocketUpgrade {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let headers = &parts.headers;
+
+        if (parts.method != Method::GET)
+            || !has_token(headers, header::CONNECTION, "upgrade")
+            || !has_token(headers, header::UPGRADE, "websocket")
+            || (headers
+                .get(header::SEC_WEBSOCKET_VERSION)
+                .and_then(|h| h.to_str().ok())
+                != Some("13"))
+        {
+            return Err((StatusCode::BAD_REQUEST, "Expected a WebSocket.").into_response());
+        }
+
+        if !same_origin(headers) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Cross-site WebSockets aren't allowed.",
+            )
+                .into_response());
+        }
+
+        let key = headers
+            .get(header::SEC_WEBSOCKET_KEY)
+            .and_then(|h| h.to_str().ok())
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty());
+
+        match (key, parts.extensions.remove::<OnUpgrade>()) {
+            (Some(key), Some(on_upgrade)) => Ok(Self { key, on_upgrade }),
+            _ => Err((StatusCode::BAD_REQUEST, "Expected a WebSocket.").into_response()),
+        }
+    }
+}
+
+impl WebSocketUpgrade {
+    /// Accept the WebSocket, and run `handler` on it once the connection is upgraded
+    ///
+    /// # Arguments
+    /// * `max_message` - the largest message the client can send (in bytes), larger ones close
+    ///   the socket
+    pub fn on_upgrade<F, Fut>(self, max_message: usize, handler: F) -> Response
+    where
+        F: FnOnce(WebSocket) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let accept = BASE64.encode(Sha1::digest(format!("{}{GUID}", self.key).as_bytes()));
+        let on_upgrade = self.on_upgrade;
+
+        tokio::spawn(async move {
+            match on_upgrade.await {
+                Ok(upgraded) => {
+                    handler(WebSocket {
+                        io: TokioIo::new(upgraded),
+                        max_message,
+                    })
+                    .await
+                }
+                Err(e) => tracing::debug!(error = %e, "Failed to upgrade to a WebSocket."),
+            }
+        });
+
+        Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap()
+    }
+}
+
+/// An open WebSocket
+pub struct WebSocket {
+    io: TokioIo<Upgraded>,
+    max_message: usize,
+}
+
+impl WebSocket {
+    /// Start reading the socket
+    ///
+    /// # Returns
+    /// * the text messages sent by the client (the channel closes with the socket)
+    /// * the [`Sender`] to answer with
+    pub fn split(self) -> (mpsc::Receiver<String>, Sender) {
+        let (reader, writer) = tokio::io::split(self.io);
+        let sender = Sender {
+            writer: Arc::new(Mutex::new(Some(writer))),
+        };
+
+        let (tx, rx) = mpsc::channel(BACKLOG);
+        tokio::spawn(read_messages(reader, self.max_message, sender.clone(), tx));
+
+        (rx, sender)
+    }
+}
+
+/// A frame read from the socket
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Read a frame sent by the client
+///
+/// # Returns
+/// * `Err(Some(code))` if the socket should be closed with `code`
+/// * `Err(None)` if the connection is gone
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max: usize,
+) -> Result<Frame, Option<u16>> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await.map_err(|_| None)?;
+
+    let (fin, opcode) = (head[0] & 0x80 != 0, head[0] & 0x0F);
+
+    // (clients have to mask everything they send, and no extension is negotiated)
+    if (head[0] & 0x70 != 0) || (head[1] & 0x80 == 0) {
+        return Err(Some(close::PROTOCOL_ERROR));
+    }
+
+    let length = match head[1] & 0x7F {
+        126 => reader.read_u16().await.map_err(|_| None)? as u64,
+        127 => reader.read_u64().await.map_err(|_| None)?,
+        length => length as u64,
+    };
+
+    if length > max as u64 {
+        return Err(Some(close::TOO_LARGE));
+    }
+
+    let mut mask = [0u8; 4];
+    reader.read_exact(&mut mask).await.map_err(|_| None)?;
+
+    let mut payload = vec![0u8; length as usize];
+    reader.read_exact(&mut payload).await.map_err(|_| None)?;
+
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+/// Read the socket until it's closed, passing text messages on to `messages`
+async fn read_messages(
+    mut reader: ReadHalf<TokioIo<Upgraded>>,
+    max: usize,
+    sender: Sender,
+    messages: mpsc::Sender<String>,
+) {
+    let mut message: Option<Vec<u8>> = None;
+
+    let code = loop {
+        let frame = match read_frame(&mut reader, max).await {
+            Ok(frame) => frame,
+            Err(Some(code)) => break code,
+            Err(None) => {
+                sender.gone().await;
+                return;
+            }
+        };
+
+        match frame.opcode {
+            OP_PING => {
+                let _ = sender.frame(OP_PONG, &frame.payload).await;
+                continue;
+            }
+            OP_PONG => continue,
+            OP_CLOSE => break close::NORMAL,
+            OP_BINARY => break close::UNSUPPORTED,
+            OP_TEXT if message.is_none() => message = Some(frame.payload),
+            OP_CONTINUATION if message.is_some() => {
+                let data = message.as_mut().unwrap();
+
+                if data.len() + frame.payload.len() > max {
+                    break close::TOO_LARGE;
+                }
+
+                data.extend(frame.payload);
+            }
+            _ => break close::PROTOCOL_ERROR,
+        }
+
+        if !frame.fin {
+            continue;
+        }
+
+        match String::from_utf8(message.take().unwrap_or_default()) {
+            Ok(text) => {
+                if messages.send(text).await.is_err() {
+                    // (the handler is done with the socket)
+                    break close::NORMAL;
+                }
+            }
+            Err(_) => break close::INVALID_DATA,
+        }
+    };
+
+    sender.close(code).await;
+}
+
+/// The writing half of a [`WebSocket`] (cheap to clone)
+#[derive(Clone)]
+pub struct Sender {
+    /// `None` once the socket is closed
+    writer: Arc<Mutex<Option<WriteHalf<TokioIo<Upgraded>>>>>,
+}
+
+impl Sender {
+    /// Write a frame (unmasked, servers don't mask)
+    async fn frame(&self, opcode: u8, payload: &[u8]) -> Result<(), ()> {
+        let mut writer = self.writer.lock().await;
+        let Some(io) = writer.as_mut() else {
+            return Err(());
+        };
+
+        let mut frame = Vec::with_capacity(payload.len() + 10);
+        frame.push(0x80 | opcode);
+
+        match payload.len() {
+            length if length < 126 => frame.push(length as u8),
+            length if length <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend((length as u16).to_be_bytes());
+            }
+            length => {
+                frame.push(127);
+                frame.extend((length as u64).to_be_bytes());
+            }
+        }
+
+        frame.extend_from_slice(payload);
+
+        if io.write_all(&frame).await.is_err() || io.flush().await.is_err() {
+            *writer = None;
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    /// Send a text message
+    ///
+    /// # Returns
+    /// `false` if the socket is closed
+    pub async fn send(&self, text: &str) -> bool {
+        self.frame(OP_TEXT, text.as_bytes()).await.is_ok()
+    }
+
+    /// Close the socket with a status code (nothing can be sent after this)
+    pub async fn close(&self, code: u16) {
+        let _ = self.frame(OP_CLOSE, &code.to_be_bytes()).await;
+
+        if let Some(mut io) = self.writer.lock().await.take() {
+            let _ = io.shutdown().await;
+        }
+    }
+
+    /// Forget the connection after it's gone (without the close frame)
+    async fn gone(&self) {
+        self.writer.lock().await.take();
+    }
+}
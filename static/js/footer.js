@@ -63,6 +63,11 @@
 
     self.update_theme_icon(); // initial update
 
+    document.getElementById("theme_button")?.addEventListener("click", (e) => {
+        e.preventDefault();
+        trigger("sealable:toggle_theme");
+    });
+
     self.define("toggle_theme", function () {
         if (
             window.PASTE_USES_CUSTOM_THEME &&
@@ -84,6 +89,17 @@
         }
 
         self.update_theme_icon();
+
+        // persist
+        fetch(`${globalThis._app_base.base_path}/preferences`, {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/json",
+            },
+            body: JSON.stringify({
+                theme: window.localStorage.getItem("theme"),
+            }),
+        });
     });
 
     // wants redirect
@@ -91,7 +107,7 @@
         document.querySelectorAll('[data-wants-redirect="true"]'),
     )) {
         element.href = `${element.href}?callback=${encodeURIComponent(
-            `${window.location.origin}/api/auth/callback`,
+            `${window.location.origin}${globalThis._app_base.base_path}/api/auth/callback`,
         )}`;
     }
 
@@ -99,9 +115,15 @@
     const auth = reg_ns("auth");
 
     for (const element of Array.from(
-        document.querySelectorAll('a[href="/api/auth/logout"]'),
+        document.querySelectorAll(
+            `a[href="${globalThis._app_base.base_path}/api/auth/logout"]`,
+        ),
     )) {
-        element.href = "javascript:trigger('auth:logout')";
+        element.href = "#";
+        element.addEventListener("click", (e) => {
+            e.preventDefault();
+            trigger("auth:logout");
+        });
     }
 
     auth.define("logout", function (imports) {
@@ -113,6 +135,6 @@
             return;
         }
 
-        window.location.href = "/api/auth/logout";
+        window.location.href = `${globalThis._app_base.base_path}/api/auth/logout`;
     });
 })();
@@ -2,7 +2,7 @@
 
 // load worker util
 let worker_util_url;
-const worker_util = fetch("/static/js/worker.js")
+const worker_util = fetch(`${globalThis._app_base.static}/js/worker.js`)
     .then((r) => r.text())
     .then((t) => {
         worker_util_url = URL.createObjectURL(
@@ -24,7 +24,7 @@ reg_ns("bundled_env").define("enter_env", async ({ $ }, code) => {
     if (worker_util_url === undefined) {
         // at this point it doesn't really matter because this only happens
         // on paste view anyways, so we don't really need a blob to cache
-        worker_util_url = `${window.location.origin}/static/js/worker.js`;
+        worker_util_url = `${window.location.origin}${globalThis._app_base.static}/js/worker.js`;
     }
     
     // create blob
@@ -0,0 +1,273 @@
+(() => {
+    const self = reg_ns("collab");
+
+    // operations, in the format of ot.js (see `src/collab.rs`)
+    const is_retain = (c) => typeof c === "number" && c > 0;
+    const is_delete = (c) => typeof c === "number" && c < 0;
+    const is_insert = (c) => typeof c === "string";
+
+    function builder() {
+        const ops = [];
+        const last = (n = 1) => ops[ops.length - n];
+
+        return {
+            ops,
+            retain(n) {
+                if (n === 0) return;
+                if (is_retain(last())) ops[ops.length - 1] += n;
+                else ops.push(n);
+            },
+            insert(text) {
+                if (text === "") return;
+
+                // (inserts always go before deletes)
+                if (is_insert(last())) ops[ops.length - 1] += text;
+                else if (is_delete(last()) && is_insert(last(2)))
+                    ops[ops.length - 2] += text;
+                else if (is_delete(last())) ops.splice(ops.length - 1, 0, text);
+                else ops.push(text);
+            },
+            delete(n) {
+                if (n === 0) return;
+                if (is_delete(last())) ops[ops.length - 1] -= n;
+                else ops.push(-n);
+            },
+        };
+    }
+
+    // transform two operations made against the same content, `a`'s text goes first
+    function transform(a, b) {
+        const [a2, b2] = [builder(), builder()];
+        let [i, j] = [0, 0];
+        let [x, y] = [a[i++], b[j++]];
+
+        while (x !== undefined || y !== undefined) {
+            if (is_insert(x)) {
+                a2.insert(x);
+                b2.retain(x.length);
+                x = a[i++];
+                continue;
+            }
+
+            if (is_insert(y)) {
+                a2.retain(y.length);
+                b2.insert(y);
+                y = b[j++];
+                continue;
+            }
+
+            if (x === undefined || y === undefined) {
+                throw new Error("The operations don't fit the same content.");
+            }
+
+            const n = Math.min(Math.abs(x), Math.abs(y));
+
+            if (is_retain(x) && is_retain(y)) {
+                a2.retain(n);
+                b2.retain(n);
+            } else if (is_delete(x) && is_retain(y)) {
+                a2.delete(n);
+            } else if (is_retain(x) && is_delete(y)) {
+                b2.delete(n);
+            }
+
+            x = Math.abs(x) > n ? x - Math.sign(x) * n : a[i++];
+            y = Math.abs(y) > n ? y - Math.sign(y) * n : b[j++];
+        }
+
+        return [a2.ops, b2.ops];
+    }
+
+    function apply(cm, op) {
+        cm.operation(() => {
+            let index = 0;
+
+            for (const c of op) {
+                if (is_retain(c)) {
+                    index += c;
+                } else if (is_insert(c)) {
+                    cm.replaceRange(c, cm.posFromIndex(index), null, "collab");
+                    index += c.length;
+                } else {
+                    cm.replaceRange(
+                        "",
+                        cm.posFromIndex(index),
+                        cm.posFromIndex(index - c),
+                        "collab",
+                    );
+                }
+            }
+        });
+    }
+
+    // join the session of a paste
+    self.define(
+        "join",
+        function (_, url, password, name, status, editing_with) {
+            const cm = globalThis.editor;
+
+            const socket = new WebSocket(
+                `${location.protocol === "https:" ? "wss" : "ws"}://${location.host}${globalThis._app_base.base_path}/ws/${url}/edit`,
+            );
+
+            let id = null;
+            let revision = 0;
+            let outstanding = null; // sent, waiting for its `ack`
+            let buffer = []; // made while waiting
+            let cursors = {}; // bookmarks of the others' cursors
+            let cursor_timeout = null;
+            let rejoining = false;
+
+            const send = (message) => socket.send(JSON.stringify(message));
+
+            const show_cursor = (editor, cursor) => {
+                if (cursors[editor]) {
+                    cursors[editor].clear();
+                }
+
+                const widget = document.createElement("span");
+                widget.className = "collab_cursor";
+                widget.style.borderLeft = "2px solid #6ee7b7";
+                widget.style.marginLeft = "-1px";
+
+                cursors[editor] = cm.setBookmark(cm.posFromIndex(cursor), {
+                    widget,
+                    insertLeft: true,
+                });
+            };
+
+            const on_change = (_, change) => {
+                if (change.origin === "collab" || id === null) {
+                    return;
+                }
+
+                const from = cm.indexFromPos(change.from);
+                const to = cm.indexFromPos(change.to);
+                const length = cm.getValue().length;
+
+                const op = builder();
+                op.retain(from);
+                op.delete(to - from);
+                op.insert(change.text.join("\n"));
+                op.retain(length - to);
+
+                if (outstanding === null) {
+                    outstanding = op.ops;
+                    send({ type: "op", revision, op: outstanding });
+                } else {
+                    buffer.push(op.ops);
+                }
+            };
+
+            const on_cursor = () => {
+                if (cursor_timeout) {
+                    clearTimeout(cursor_timeout);
+                }
+
+                cursor_timeout = setTimeout(() => {
+                    if (socket.readyState === WebSocket.OPEN && id !== null) {
+                        send({
+                            type: "cursor",
+                            cursor: cm.indexFromPos(cm.getCursor()),
+                        });
+                    }
+                }, 100);
+            };
+
+            socket.addEventListener("open", () => {
+                send({ type: "join", password, name });
+            });
+
+            socket.addEventListener("message", (event) => {
+                const message = JSON.parse(event.data);
+
+                switch (message.type) {
+                    case "joined":
+                        id = message.id;
+                        revision = message.revision;
+
+                        if (cm.getValue() !== message.content) {
+                            cm.operation(() => {
+                                cm.replaceRange(
+                                    message.content,
+                                    cm.posFromIndex(0),
+                                    cm.posFromIndex(cm.getValue().length),
+                                    "collab",
+                                );
+                            });
+                        }
+
+                        cm.on("beforeChange", on_change);
+                        cm.on("cursorActivity", on_cursor);
+                        break;
+                    case "ack":
+                        revision = message.revision;
+                        outstanding = buffer.shift() || null;
+
+                        if (outstanding !== null) {
+                            send({ type: "op", revision, op: outstanding });
+                        }
+                        break;
+                    case "op": {
+                        let op = message.op;
+
+                        if (outstanding !== null) {
+                            [outstanding, op] = transform(outstanding, op);
+
+                            for (let i = 0; i < buffer.length; i++) {
+                                [buffer[i], op] = transform(buffer[i], op);
+                            }
+                        }
+
+                        revision = message.revision;
+                        apply(cm, op);
+                        break;
+                    }
+                    case "cursor":
+                        show_cursor(message.editor, message.cursor);
+                        break;
+                    case "editors": {
+                        for (const editor of Object.keys(cursors)) {
+                            cursors[editor].clear();
+                        }
+
+                        cursors = {};
+                        const others = message.editors.filter((e) => e.id !== id);
+
+                        for (const editor of others) {
+                            show_cursor(editor.id, editor.cursor);
+                        }
+
+                        status.innerText =
+                            others.length > 0
+                                ? `${editing_with} ${others.map((e) => e.name).join(", ")}`
+                                : "";
+                        break;
+                    }
+                    case "error":
+                        status.innerText = message.message;
+
+                        if (message.rejoin) {
+                            rejoining = true;
+                            socket.close();
+                        }
+                        break;
+                }
+            });
+
+            socket.addEventListener("close", () => {
+                cm.off("beforeChange", on_change);
+                cm.off("cursorActivity", on_cursor);
+
+                for (const editor of Object.keys(cursors)) {
+                    cursors[editor].clear();
+                }
+
+                if (rejoining) {
+                    self.join(url, password, name, status, editing_with);
+                }
+            });
+        },
+        ["string", "string", "string", "object", "string"],
+    );
+})();
@@ -48,6 +48,66 @@
             const preview_tab = document.getElementById("preview_tab");
 
             if (text_button && preview_button) {
+                // live preview: changes are sent as they're typed, and the server sends the
+                // rendered content back once typing settles down
+                let socket = null;
+                let rendered = false;
+
+                const connect = () => {
+                    socket = new WebSocket(
+                        `${location.protocol === "https:" ? "wss" : "ws"}://${location.host}${globalThis._app_base.base_path}/ws/preview`,
+                    );
+
+                    socket.addEventListener("open", () => {
+                        socket.send(
+                            JSON.stringify({
+                                content: globalThis.editor.getValue(),
+                            }),
+                        );
+                    });
+
+                    socket.addEventListener("message", (event) => {
+                        const message = JSON.parse(event.data);
+
+                        if (message.resync) {
+                            socket.send(
+                                JSON.stringify({
+                                    content: globalThis.editor.getValue(),
+                                }),
+                            );
+                        } else if (message.html !== undefined) {
+                            preview_tab.innerHTML = message.html;
+                            markdown.fix_markdown("preview_tab");
+                            rendered = true;
+                        }
+                    });
+
+                    // (previews are rendered with `/api/render` until it's back)
+                    socket.addEventListener("close", () => {
+                        socket = null;
+                        rendered = false;
+                        setTimeout(connect, 5000);
+                    });
+                };
+
+                if (globalThis.WebSocket && globalThis.editor) {
+                    connect();
+
+                    globalThis.editor.on("change", (cm, change) => {
+                        if (!socket || socket.readyState !== WebSocket.OPEN) {
+                            return;
+                        }
+
+                        socket.send(
+                            JSON.stringify({
+                                at: cm.indexFromPos(change.from),
+                                delete: change.removed.join("\n").length,
+                                insert: change.text.join("\n"),
+                            }),
+                        );
+                    });
+                }
+
                 text_button.addEventListener("click", () => {
                     preview_button.classList.add("secondary");
                     text_button.classList.remove("secondary");
@@ -63,10 +123,14 @@
                     text_tab.style.display = "none";
                     preview_tab.style.display = "block";
 
+                    if (rendered) {
+                        return;
+                    }
+
                     // render
                     preview_tab.innerHTML = "";
                     preview_tab.innerHTML = await (
-                        await fetch("/api/render", {
+                        await fetch(`${globalThis._app_base.base_path}/api/render`, {
                             method: "POST",
                             headers: {
                                 "Content-Type": "application/json",
@@ -83,6 +147,58 @@
         });
     });
 
+    // drafts
+    self.define(
+        "autosave",
+        async function (_, path, restore_prompt) {
+            let saved = globalThis.editor.getValue();
+
+            // offer the draft left by a closed tab
+            const res = await fetch(path);
+
+            if (res.ok) {
+                const draft = (await res.json()).payload;
+
+                if (
+                    draft.content !== saved &&
+                    draft.content.trim() !== "" &&
+                    confirm(restore_prompt)
+                ) {
+                    globalThis.editor.setValue(draft.content);
+                    saved = draft.content;
+                }
+            }
+
+            let stored_timeout = null;
+            globalThis.editor.on("change", () => {
+                if (stored_timeout) {
+                    clearTimeout(stored_timeout);
+                }
+
+                stored_timeout = setTimeout(async () => {
+                    const content = globalThis.editor.getValue();
+
+                    if (content === saved) {
+                        return;
+                    }
+
+                    const res = await fetch(path, {
+                        method: "PUT",
+                        headers: {
+                            "Content-Type": "application/json",
+                        },
+                        body: JSON.stringify({ content }),
+                    });
+
+                    if (res.ok) {
+                        saved = content;
+                    }
+                }, 3000);
+            });
+        },
+        ["string", "string"],
+    );
+
     // url check
     self.define("check_url", function (_, bind_to) {
         let stored_timeout = null;
@@ -103,7 +219,9 @@
 
             stored_timeout = setTimeout(async () => {
                 // fetch url
-                const exists = await (await fetch(`/api/${value}`)).ok;
+                const exists = await (
+                    await fetch(`${globalThis._app_base.base_path}/api/${value}`)
+                ).ok;
 
                 if (!exists) {
                     // paste does not exist
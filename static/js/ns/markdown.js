@@ -75,40 +75,53 @@
         ["string"],
     );
 
-    markdown.define("use_template", function ({ $ }, paste_url) {
+    // ask for the url and password of a new paste, then create it through `endpoint`
+    markdown.define("new_paste_dialog", function ({ $ }, endpoint, body) {
         $.dialog = document.getElementById("template_dialog");
         $.dialog.showModal();
 
-        document
-            .getElementById("template_form")
-            .addEventListener("submit", async (e) => {
-                e.preventDefault();
-
-                const res = await (
-                    await fetch("/api/clone", {
-                        method: "POST",
-                        headers: {
-                            "Content-Type": "application/json",
-                        },
-                        body: JSON.stringify({
-                            url: e.target.url.value,
-                            password: e.target.password.value,
-                            source: paste_url,
-                        }),
-                    })
-                ).json();
-
-                if (res.success === false) {
-                    trigger("sealable:gen_secret", [
-                        "note-error",
-                        "Error",
-                        res.message,
-                    ]);
-
-                    $.dialog.close();
-                } else {
-                    window.location.href = `/${res.payload[1].url}?SECRET=${res.payload[0]}`;
-                }
-            });
+        document.getElementById("close_template_dialog").onclick = (e) => {
+            e.preventDefault();
+            $.dialog.close();
+        };
+
+        document.getElementById("template_form").onsubmit = async (e) => {
+            e.preventDefault();
+
+            const res = await (
+                await fetch(endpoint, {
+                    method: "POST",
+                    headers: {
+                        "Content-Type": "application/json",
+                    },
+                    body: JSON.stringify({
+                        url: e.target.url.value,
+                        password: e.target.password.value,
+                        ...body,
+                    }),
+                })
+            ).json();
+
+            if (res.success === false) {
+                trigger("sealable:gen_secret", [
+                    "note-error",
+                    "Error",
+                    res.message,
+                ]);
+
+                $.dialog.close();
+            } else {
+                window.location.href = `${globalThis._app_base.base_path}/${res.payload[1].url}?SECRET=${res.payload[0]}`;
+            }
+        };
+    });
+
+    markdown.define("fork", function ({ $ }, paste_url) {
+        $.new_paste_dialog(`${globalThis._app_base.base_path}/api/${paste_url}/fork`, {
+            view_password:
+                new URLSearchParams(window.location.search).get(
+                    "view_password",
+                ) || "",
+        });
     });
 })();
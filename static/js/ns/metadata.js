@@ -2,16 +2,96 @@
 (() => {
     const metadata = reg_ns("metadata");
 
+    // (the schema is served by `/api/schema/metadata`, see `src/schema.rs`)
+    const property = ($, name) => (($.schema || {}).properties || {})[name] || {};
+
+    // check metadata against the schema, like the server will
+    function validate($, metadata) {
+        const errors = [];
+
+        for (const [name, value] of Object.entries(metadata)) {
+            const field = property($, name);
+
+            if (!field.type) {
+                errors.push(`${name}: Unknown field.`);
+            } else if (field.type === "string" && typeof value !== "string") {
+                errors.push(`${name}: Expected a string.`);
+            } else if (field.type === "integer" && !Number.isInteger(value)) {
+                errors.push(`${name}: Expected a positive integer.`);
+            } else if (
+                field.type === "object" &&
+                (typeof value !== "object" || value === null || Array.isArray(value))
+            ) {
+                errors.push(`${name}: Expected an object.`);
+            } else if (
+                field.maxProperties &&
+                Object.keys(value).length > field.maxProperties
+            ) {
+                errors.push(`${name}: More than ${field.maxProperties} fields.`);
+            } else if (field.maxLength && [...value].length > field.maxLength) {
+                errors.push(`${name}: Longer than ${field.maxLength} characters.`);
+            } else if (field.pattern && !new RegExp(field.pattern).test(value)) {
+                errors.push(`${name}: Doesn't match ${field.pattern}.`);
+            } else if (field.minimum !== undefined && value < field.minimum) {
+                errors.push(`${name}: Less than ${field.minimum}.`);
+            } else if (field.maximum !== undefined && value > field.maximum) {
+                errors.push(`${name}: More than ${field.maximum}.`);
+            } else if (field.enum && !field.enum.includes(value)) {
+                errors.push(`${name}: Expected one of ${JSON.stringify(field.enum)}.`);
+            }
+        }
+
+        return errors;
+    }
+
     metadata.define(
         "metadata_editor",
-        function ({ $ }, bind_to, paste_url, metadata) {
+        function ({ $ }, bind_to, paste_url, metadata, schema) {
             $.metadata = metadata;
+            $.schema = schema || $.schema;
 
             globalThis.update_metadata_value = (name, value) => {
                 $.metadata[name] = value;
                 console.log(metadata);
             };
 
+            // (inline handlers are blocked by the Content-Security-Policy)
+            if (!bind_to.dataset.listening) {
+                bind_to.dataset.listening = "true";
+
+                bind_to.addEventListener("click", (e) => {
+                    const button = e.target.closest("[data-action]");
+
+                    if (button) {
+                        globalThis[button.getAttribute("data-action")]();
+                    }
+                });
+
+                bind_to.addEventListener("change", (e) => {
+                    const field = e.target.getAttribute("data-field");
+
+                    if (field && e.target.dataset.json) {
+                        // (left as text while it doesn't parse, so it fails validation)
+                        try {
+                            globalThis.update_metadata_value(
+                                field,
+                                JSON.parse(e.target.value || "{}"),
+                            );
+                        } catch {
+                            globalThis.update_metadata_value(field, e.target.value);
+                        }
+                    } else if (field && e.target.type === "datetime-local") {
+                        // (times are stored as milliseconds, 0 for none)
+                        globalThis.update_metadata_value(
+                            field,
+                            e.target.value ? new Date(e.target.value).getTime() : 0,
+                        );
+                    } else if (field) {
+                        globalThis.update_metadata_value(field, e.target.value);
+                    }
+                });
+            }
+
             // ...
             if (Object.entries($.metadata).length == 0) {
                 bind_to.innerHTML = `<div class="card secondary round">
@@ -45,7 +125,7 @@
 
                         bind_to.innerHTML += `<div class="card secondary round flex justify-between items-center gap-2" style="flex-wrap: wrap;" id="field:${field[0]}">
                             <label for="field_input:${field[0]}">${field[0]}</label>
-                            <button class=\"theme:primary round\" onclick=\"globalThis.mark_as_template()\" type=\"button\">Mark as Template</button>
+                            <button class=\"theme:primary round\" data-action=\"mark_as_template\" type=\"button\">Mark as Template</button>
                         </div>`;
                     } else if (paste_is_template) {
                         globalThis.mark_as_not_template = () => {
@@ -59,27 +139,93 @@
 
                         bind_to.innerHTML += `<div class="card secondary round flex justify-between items-center gap-2" style="flex-wrap: wrap;" id="field:${field[0]}">
                             <label for="field_input:${field[0]}">${field[0]}</label>
-                            <button class=\"theme:primary round\" onclick=\"globalThis.mark_as_not_template()\" type=\"button\">Unmark as Template</button>
+                            <button class=\"theme:primary round\" data-action=\"mark_as_not_template\" type=\"button\">Unmark as Template</button>
                         </div>`;
                     } else if (paste_source) {
                         bind_to.innerHTML += `<div class="card secondary round flex justify-between items-center gap-2" style="flex-wrap: wrap;" id="field:${field[0]}">
                             <label for="field_input:${field[0]}">${field[0]}</label>
-                            <a class=\"button !text-sky-800 dark:!text-sky-300 round\" href=\"/${paste_source}\" title=\"${paste_source}\">View Source</button>
+                            <a class=\"button !text-sky-800 dark:!text-sky-300 round\" href=\"${globalThis._app_base.base_path}/${paste_source}\" title=\"${paste_source}\">View Source</button>
                         </div>`;
                     }
 
                     continue;
                 }
 
+                const schema = property($, field[0]);
+
+                if (schema.format === "timestamp-ms") {
+                    // (`datetime-local` inputs show local time, without a timezone)
+                    const local = field[1]
+                        ? new Date(
+                              field[1] - new Date().getTimezoneOffset() * 60000,
+                          )
+                              .toISOString()
+                              .slice(0, 16)
+                        : "";
+
+                    bind_to.innerHTML += `<div class="card secondary round flex justify-between items-center gap-2" style="flex-wrap: wrap;" id="field:${field[0]}">
+                        <label for="field_input:${field[0]}">${field[0]}</label>
+                        <input
+                          id="field_input:${field[0]}"
+                          type="datetime-local"
+                          value="${local}"
+                          data-field="${field[0]}"
+                          style="width: max-content"
+                        />
+                    </div>`;
+
+                    continue;
+                }
+
+                if (schema.type === "object") {
+                    const json = JSON.stringify(field[1], null, 4)
+                        .replaceAll("&", "&amp;")
+                        .replaceAll("<", "&lt;");
+
+                    bind_to.innerHTML += `<div class="card secondary round flex flex-col gap-2" id="field:${field[0]}">
+                        <label for="field_input:${field[0]}">${field[0]}</label>
+                        <textarea
+                          id="field_input:${field[0]}"
+                          data-field="${field[0]}"
+                          data-json="true"
+                          rows="4"
+                          class="font-mono"
+                        >${json}</textarea>
+                    </div>`;
+
+                    continue;
+                }
+
+                if (schema.enum) {
+                    const options = schema.enum
+                        .map(
+                            (v) =>
+                                `<option value="${v}" ${field[1] === v ? "selected" : ""}>${v || "auto"}</option>`,
+                        )
+                        .join("");
+
+                    bind_to.innerHTML += `<div class="card secondary round flex justify-between items-center gap-2" style="flex-wrap: wrap;" id="field:${field[0]}">
+                        <label for="field_input:${field[0]}">${field[0]}</label>
+                        <select
+                          id="field_input:${field[0]}"
+                          data-field="${field[0]}"
+                          style="width: max-content"
+                        >${options}</select>
+                    </div>`;
+
+                    continue;
+                }
+
                 bind_to.innerHTML += `<div class="card secondary round flex justify-between items-center gap-2" style="flex-wrap: wrap;" id="field:${field[0]}">
                     <label for="field_input:${field[0]}">${field[0]}</label>
                     <input 
                       id="field_input:${field[0]}" 
                       type="text" 
                       value="${field[1].replace('"', '\\"')}"
-                      onchange="globalThis.update_metadata_value('${field[0]}', event.target.value)"
+                      data-field="${field[0]}"
                       style="width: max-content"
-                      ${field[0] === "owner" ? "disabled" : ""}
+                      ${schema.maxLength ? `maxlength="${schema.maxLength}"` : ""}
+                      ${schema.readOnly ? "disabled" : ""}
                     />
                 </div>`;
             }
@@ -92,8 +238,20 @@
             .addEventListener("submit", async (e) => {
                 e.preventDefault();
 
+                const errors = validate($, $.metadata);
+
+                if (errors.length > 0) {
+                    trigger("sealable:gen_secret", [
+                        "note-error",
+                        "Error",
+                        errors.join("<br />"),
+                    ]);
+
+                    return;
+                }
+
                 const res = await (
-                    await fetch(`/api/${paste_url}/metadata`, {
+                    await fetch(`${globalThis._app_base.base_path}/api/${paste_url}/metadata`, {
                         method: "POST",
                         headers: {
                             "Content-Type": "application/json",
@@ -112,4 +270,63 @@
                 }
             });
     });
+
+    metadata.define("rename_hook", function (_, paste_url) {
+        document
+            .getElementById("rename_button")
+            .addEventListener("click", async () => {
+                const form = document.getElementById("submit_form");
+
+                const res = await (
+                    await fetch(`${globalThis._app_base.base_path}/api/${paste_url}/rename`, {
+                        method: "POST",
+                        headers: {
+                            "Content-Type": "application/json",
+                        },
+                        body: JSON.stringify({
+                            password: form.password.value,
+                            new_url: document.getElementById("new_url").value,
+                        }),
+                    })
+                ).json();
+
+                if (res.success === false) {
+                    window.location.href = `?SECRET=${res.message}&SECRET_TYPE=note-error&SECRET_TITLE=Error`;
+                } else {
+                    window.location.href = `${globalThis._app_base.base_path}/${res.payload}/edit/config?SECRET=${res.message}`;
+                }
+            });
+    });
+
+    metadata.define("share_hook", function (_, paste_url) {
+        document
+            .getElementById("share_button")
+            .addEventListener("click", async () => {
+                const form = document.getElementById("submit_form");
+
+                const res = await (
+                    await fetch(`${globalThis._app_base.base_path}/api/${paste_url}/share_links`, {
+                        method: "POST",
+                        headers: {
+                            "Content-Type": "application/json",
+                        },
+                        body: JSON.stringify({
+                            password: form.password.value,
+                            expires_in: parseInt(
+                                document.getElementById("share_expires_in").value,
+                            ),
+                        }),
+                    })
+                ).json();
+
+                if (res.success === false) {
+                    window.location.href = `?SECRET=${res.message}&SECRET_TYPE=note-error&SECRET_TITLE=Error`;
+                } else {
+                    const link = document.getElementById("share_link");
+                    link.value = `${location.origin}${res.payload.url}`;
+                    link.style.display = "block";
+                    link.select();
+                }
+            });
+    });
 })();
@@ -114,6 +114,15 @@
         $.ctx.style.animation = "fadein 0.05s ease-in-out 1 running";
         $.ctx.style.width = "15rem";
 
+        // (inline handlers are blocked by the Content-Security-Policy)
+        $.ctx.addEventListener("click", (e) => {
+            const button = e.target.closest("[data-trigger]");
+
+            if (button) {
+                trigger(button.getAttribute("data-trigger"));
+            }
+        });
+
         document.body.addEventListener("contextmenu", (e) => {
             if (e.target && e.target.nodeName === "INPUT") {
                 return;
@@ -166,7 +175,7 @@
         if (selection !== "") {
             $.ctx.innerHTML += `<button 
                 class="w-full round green option small"
-                onclick="trigger('rune:copy_selection')"
+                data-trigger="rune:copy_selection"
             >
                 <svg
                     xmlns="http://www.w3.org/2000/svg"
@@ -198,7 +207,7 @@
             ) {
                 $.ctx.innerHTML += `<button 
                     class="w-full round option small"
-                    onclick="trigger('rune:click_target')"
+                    data-trigger="rune:click_target"
                 >
                     <svg
                         xmlns="http://www.w3.org/2000/svg"
@@ -228,7 +237,7 @@
             if (event.target.getAttribute("data-line")) {
                 $.ctx.innerHTML += `<button 
                     class="w-full red round option small"
-                    onclick="trigger('rune:remove_selected_line')"
+                    data-trigger="rune:remove_selected_line"
                 >
                     <svg
                         xmlns="http://www.w3.org/2000/svg"
@@ -5,8 +5,8 @@ module.exports = {
     theme: {
         extend: {
             colors: {
-                brand: "rgb(16 185 129)",
-                "brand-low": "rgb(16 185 129)",
+                brand: "var(--color-brand, rgb(16 185 129))",
+                "brand-low": "var(--color-brand-low, rgb(16 185 129))",
             },
             animation: {
                 "fade-in": "fadein 0.25s ease-in-out 1 running",
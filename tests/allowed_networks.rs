@@ -0,0 +1,24 @@
+//! Pastes with `allowed_networks` can't be read from anywhere else, whichever route asks
+mod common;
+
+use common::{assert_hidden, create_paste, edit_metadata, get, CLIENT, SECRET};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn allowed_networks_protect_every_route() {
+    let app = common::app("allowed-networks").await;
+    let code = create_paste(&app, "office", "").await;
+    edit_metadata(
+        &app,
+        "office",
+        serde_json::json!({ "allowed_networks": "10.0.0.0/8" }),
+    )
+    .await;
+
+    assert_hidden(&app, CLIENT, "office", &code).await;
+
+    // (the paste is still there for its network)
+    for uri in ["/office/raw", "/api/office"] {
+        let res = get(&app, "10.1.2.3", uri).await;
+        assert!(res.body.contains(SECRET), "{uri} from the allowed network");
+    }
+}
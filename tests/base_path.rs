@@ -0,0 +1,63 @@
+//! The app mounted under a path links and redirects to its routes under that path
+mod common;
+
+use axum::http::Method;
+use common::{get, send, CLIENT};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn base_path_prefixes_links_and_redirects() {
+    let mut config = common::config("base-path").await;
+    config.base_path = "/docs".to_string();
+
+    let app = axum::Router::new().nest("/docs", sealable::routes(config));
+
+    let res = send(
+        &app,
+        CLIENT,
+        Method::POST,
+        "/docs/api/new",
+        Some(serde_json::json!({ "url": "mounted", "content": "# Mounted\n\n[notes](attachment:notes.txt)", "password": "x" })),
+    )
+    .await;
+    assert!(res.status.is_success(), "creating: {}", res.body);
+
+    let res = get(&app, CLIENT, "/docs/mounted").await;
+    assert!(
+        res.body.contains("href=\"/docs/mounted/edit"),
+        "{}",
+        res.body
+    );
+    assert!(res.body.contains("base_path: \"/docs\""));
+    assert!(res
+        .body
+        .contains("href=\"/docs/mounted/attachments/notes.txt"));
+    assert!(!res.body.contains("href=\"/mounted"));
+
+    // (the stylesheet is linked under the path too, and served there)
+    assert!(res.body.contains("href=\"/docs/static/style"));
+    let res = get(&app, CLIENT, "/docs/static/js/footer.js").await;
+    assert!(res.status.is_success(), "{} {}", res.status, res.body);
+
+    let res = send(
+        &app,
+        CLIENT,
+        Method::POST,
+        "/docs/api/mounted/shortlink",
+        None,
+    )
+    .await;
+    let link: serde_json::Value = serde_json::from_str(&res.body).unwrap();
+    let link = link["message"].as_str().unwrap().to_string();
+    assert!(link.starts_with("/docs/s/"), "{link}");
+
+    let res = send(&app, CLIENT, Method::GET, &link, None).await;
+    assert_eq!(res.location.as_deref(), Some("/docs/mounted"));
+
+    // (absolute links too)
+    let res = get(&app, CLIENT, "/docs/feed.atom").await;
+    assert!(
+        res.body.contains("://localhost/docs/mounted"),
+        "{}",
+        res.body
+    );
+}
@@ -0,0 +1,546 @@
+//! Helpers shared by the integration tests: the app on a fresh database, and requests to it
+//!
+//! Each test file is its own process, so each gets its own working directory (where the sqlite
+//! database, `main.db`, lives). The cache is redis on its default port: a small stand-in for it is
+//! started unless one is already listening there.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use tower::ServiceExt;
+
+/// The address requests come from, unless a test gives another one
+pub const CLIENT: &str = "203.0.113.7";
+
+/// Every route that shows a paste (or part of it) to a client
+///
+/// `{url}` is replaced with the paste's url, and `{code}` with its short link. The paste has the
+/// file, attachment and wiki page [`create_paste`] adds.
+pub const ALTERNATE_ROUTES: &[&str] = &[
+    "/{url}",
+    "/{url}/raw",
+    "/{url}/raw/notes.md",
+    "/{url}/attachments/notes.txt",
+    "/{url}/download",
+    "/{url}/print",
+    "/{url}/embed",
+    "/{url}/card.png",
+    "/{url}/export/html",
+    "/{url}/export/pdf",
+    "/{url}/export/png",
+    "/{url}/feed.atom",
+    "/{url}/events",
+    "/{url}/edit",
+    "/{url}/edit/config",
+    "/{url}/stats",
+    "/{url}/chapter",
+    "/{url}.chapter",
+    "/{url}.chapter/raw",
+    "/s/{code}",
+    "/api/{url}",
+    "/api/{url}/files",
+    "/api/{url}/attachments",
+    "/api/{url}/pages",
+    "/api/{url}/comments",
+    "/api/{url}.chapter",
+    "/api/oembed?url=/{url}",
+    "/api/mirror/export?urls={url},{url}.chapter",
+    "/ap/pastes/{url}",
+    "/feed.atom",
+    "/search?q=marmalade",
+];
+
+/// Content only the protected pastes have (and that no page would show otherwise)
+pub const SECRET: &str = "marmalade-4471";
+
+/// Content types of the exports and cards (whose content can't be searched for [`SECRET`])
+const BINARY_TYPES: [&str; 2] = ["application/pdf", "image/png"];
+
+/// How long a response can take to finish (event streams of pastes that can be viewed don't)
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A response to a test request
+pub struct Response {
+    pub status: StatusCode,
+    pub content_type: String,
+    /// Where the response redirects to
+    pub location: Option<String>,
+    pub body: String,
+}
+
+/// Start the app on a fresh database (in its own directory, named after the test)
+pub async fn app(name: &str) -> Router {
+    sealable::routes(config(name).await)
+}
+
+/// Get the config of the app on a fresh database (see [`app`])
+pub async fn config(name: &str) -> sealable::AppConfig {
+    let dir = std::env::temp_dir().join(format!("sealable-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::File::create(dir.join("main.db")).unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+
+    for limit in ["VIEW", "WRITE", "UNLOCK", "RENDER", "COMMENT"] {
+        std::env::set_var(format!("RATE_LIMIT_{limit}"), "off");
+    }
+
+    // (`/api/mirror/export` is only served with peers)
+    std::env::set_var("MIRROR_PEERS", "https://peer.example.com");
+
+    start_cache();
+
+    let database = sealable::Database::from_env().await;
+    database.init().await;
+
+    let mut config = sealable::AppConfig::new(database);
+    config.activitypub = true;
+    // (the tests run in their own directory)
+    config.static_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("static");
+    config
+}
+
+/// Send a request from `client` (with `headers`)
+async fn request(
+    app: &Router,
+    client: &str,
+    method: Method,
+    uri: &str,
+    headers: &[(header::HeaderName, String)],
+    body: Vec<u8>,
+) -> Response {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::HOST, "localhost");
+
+    for (name, value) in headers {
+        req = req.header(name, value);
+    }
+
+    let mut req = req.body(Body::from(body)).unwrap();
+    req.extensions_mut().insert(ConnectInfo(SocketAddr::new(
+        client.parse::<IpAddr>().unwrap(),
+        40000,
+    )));
+
+    let res = app.clone().oneshot(req).await.unwrap();
+    let status = res.status();
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let location = res
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|h| h.to_str().ok())
+        .map(String::from);
+
+    let body = tokio::time::timeout(
+        RESPONSE_TIMEOUT,
+        axum::body::to_bytes(res.into_body(), usize::MAX),
+    )
+    .await
+    .unwrap_or_else(|_| panic!("{uri} kept streaming ({status})"))
+    .unwrap();
+
+    Response {
+        status,
+        content_type,
+        location,
+        body: String::from_utf8_lossy(&body).to_string(),
+    }
+}
+
+/// Send a JSON request from `client`
+pub async fn send(
+    app: &Router,
+    client: &str,
+    method: Method,
+    uri: &str,
+    json: Option<serde_json::Value>,
+) -> Response {
+    send_as(app, client, None, method, uri, json).await
+}
+
+/// Send a JSON request from `client`, signed in with `token` (see [`sign_up`])
+pub async fn send_as(
+    app: &Router,
+    client: &str,
+    token: Option<&str>,
+    method: Method,
+    uri: &str,
+    json: Option<serde_json::Value>,
+) -> Response {
+    let mut headers = Vec::new();
+    if let Some(token) = token {
+        headers.push((header::COOKIE, format!("__Secure-Token={token}")));
+    }
+
+    let body = match json {
+        Some(json) => {
+            headers.push((header::CONTENT_TYPE, "application/json".to_string()));
+            json.to_string().into_bytes()
+        }
+        None => Vec::new(),
+    };
+
+    request(app, client, method, uri, &headers, body).await
+}
+
+/// Create an account (the app has to be started with `USE_STARSTRAW` set)
+///
+/// # Returns
+/// The account's token
+pub async fn sign_up(username: &str) -> String {
+    let database = sealable::Database::from_env().await;
+    database.base.auth.init().await;
+    database
+        .base
+        .auth
+        .create_profile(username.to_string())
+        .await
+        .unwrap_or_else(|_| panic!("signing up {username}"))
+}
+
+/// `GET uri` from `client`, following redirects within the app (and waiting for exports to be done)
+pub async fn get(app: &Router, client: &str, uri: &str) -> Response {
+    get_as(app, client, None, uri).await
+}
+
+/// [`get`], signed in with `token`
+pub async fn get_as(app: &Router, client: &str, token: Option<&str>, uri: &str) -> Response {
+    let mut uri = uri.to_string();
+
+    for _ in 0..100 {
+        let res = send_as(app, client, token, Method::GET, &uri, None).await;
+
+        match res.location {
+            Some(ref location) if location.starts_with('/') => uri = location.clone(),
+            // (exports are rendered in the background, their page reloads until they're done)
+            None if uri.starts_with("/export/") && res.body.contains("http-equiv=\"refresh\"") => {
+                tokio::time::sleep(Duration::from_millis(100)).await
+            }
+            _ => return res,
+        }
+    }
+
+    panic!("{uri} never finished");
+}
+
+/// Create a public paste at `url` (with [`SECRET`] as its content, and `x` as its password)
+///
+/// It's given a file (`notes.md`), an attachment (`notes.txt`) and a wiki page (`chapter`) with
+/// [`SECRET`] in them too.
+///
+/// # Returns
+/// The code of the paste's short link
+pub async fn create_paste(app: &Router, url: &str, view_password: &str) -> String {
+    let content = format!("# Notes\n\nThe {SECRET} recipe ([notes](attachment:notes.txt)).");
+    let res = send(
+        app,
+        CLIENT,
+        Method::POST,
+        "/api/new",
+        Some(serde_json::json!({
+            "url": url,
+            "content": content,
+            "password": "x",
+            "view_password": view_password,
+        })),
+    )
+    .await;
+    assert_eq!(res.status, StatusCode::OK, "creating {url}: {}", res.body);
+
+    for route in ["files", "pages"] {
+        let res = send(
+            app,
+            CLIENT,
+            Method::POST,
+            &format!("/api/{url}/{route}"),
+            Some(serde_json::json!({
+                "password": "x",
+                "name": if route == "files" { "notes.md" } else { "chapter" },
+                "content": content,
+            })),
+        )
+        .await;
+        assert_eq!(
+            res.status,
+            StatusCode::OK,
+            "adding {route} to {url}: {}",
+            res.body
+        );
+    }
+
+    let boundary = "sealable-test";
+    let form = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nx\r\n\
+        --{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\n\
+        Content-Type: text/plain\r\n\r\n{content}\r\n--{boundary}--\r\n"
+    );
+    let res = request(
+        app,
+        CLIENT,
+        Method::POST,
+        &format!("/api/{url}/attachments"),
+        &[(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={boundary}"),
+        )],
+        form.into_bytes(),
+    )
+    .await;
+    assert_eq!(
+        res.status,
+        StatusCode::OK,
+        "attaching to {url}: {}",
+        res.body
+    );
+
+    let res = send(
+        app,
+        CLIENT,
+        Method::POST,
+        &format!("/api/{url}/shortlink?view_password={view_password}"),
+        None,
+    )
+    .await;
+    assert_eq!(res.status, StatusCode::OK, "linking {url}: {}", res.body);
+
+    serde_json::from_str::<serde_json::Value>(&res.body).unwrap()["payload"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Edit the metadata of the paste at `url` (created by [`create_paste`])
+pub async fn edit_metadata(app: &Router, url: &str, metadata: serde_json::Value) {
+    edit_metadata_as(app, None, url, metadata).await
+}
+
+/// [`edit_metadata`], signed in with `token` (which makes the account the paste's owner)
+pub async fn edit_metadata_as(
+    app: &Router,
+    token: Option<&str>,
+    url: &str,
+    metadata: serde_json::Value,
+) {
+    let res = send_as(
+        app,
+        CLIENT,
+        token,
+        Method::POST,
+        &format!("/api/{url}/metadata"),
+        Some(serde_json::json!({ "password": "x", "metadata": metadata })),
+    )
+    .await;
+
+    assert_eq!(res.status, StatusCode::OK, "editing {url}: {}", res.body);
+}
+
+/// Check that none of the [`ALTERNATE_ROUTES`] give the paste at `url` (with the short link
+/// `code`) away to `client`, and that it can't be cloned
+pub async fn assert_hidden(app: &Router, client: &str, url: &str, code: &str) {
+    for route in ALTERNATE_ROUTES {
+        let uri = route.replace("{url}", url).replace("{code}", code);
+        let res = get(app, client, &uri).await;
+
+        assert!(
+            !res.body.contains(SECRET) && !BINARY_TYPES.contains(&res.content_type.as_str()),
+            "{uri} gave the content away ({}, {})",
+            res.status,
+            res.content_type
+        );
+
+        // (listings of every paste don't mention it at all)
+        if !route.contains("{url}") {
+            assert!(
+                !res.body.contains(&format!("/{url}")),
+                "{uri} listed the paste"
+            );
+        }
+    }
+
+    let res = send(
+        app,
+        client,
+        Method::POST,
+        "/api/clone",
+        Some(serde_json::json!({ "source": url, "url": format!("{url}-copy"), "password": "x" })),
+    )
+    .await;
+
+    assert_ne!(
+        res.status,
+        StatusCode::OK,
+        "/api/clone cloned the paste: {}",
+        res.body
+    );
+}
+
+/// Check that the public paste at `url` (with the short link `code`) can be seen by `client` on
+/// every one of the [`ALTERNATE_ROUTES`] that shows its content
+///
+/// (the editors, stats, event stream, oEmbed and lists of attachments, pages and comments don't,
+/// and the listings of every paste are left out)
+pub async fn assert_shown(app: &Router, client: &str, url: &str, code: &str) {
+    for route in ALTERNATE_ROUTES {
+        if !route.contains('{')
+            || route.starts_with("/api/oembed")
+            || [
+                "/edit",
+                "/edit/config",
+                "/stats",
+                "/events",
+                "/attachments",
+                "/pages",
+                "/comments",
+            ]
+            .iter()
+            .any(|r| route.ends_with(r))
+        {
+            continue;
+        }
+
+        let uri = route.replace("{url}", url).replace("{code}", code);
+        let res = get(app, client, &uri).await;
+
+        assert!(
+            res.body.contains(SECRET) || BINARY_TYPES.contains(&res.content_type.as_str()),
+            "{uri} didn't show the paste ({}, {})",
+            res.status,
+            res.content_type
+        );
+    }
+}
+
+/// Start a stand-in for redis on its default port, unless something is listening there already
+///
+/// It only knows the commands the app uses (and answers `OK` to any other).
+fn start_cache() {
+    let listener = match TcpListener::bind("127.0.0.1:6379") {
+        Ok(listener) => listener,
+        // (a redis server is already running)
+        Err(_) => return,
+    };
+
+    let store = Arc::new(Mutex::new(HashMap::<String, String>::new()));
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let store = store.clone();
+            std::thread::spawn(move || serve_cache(stream, &store));
+        }
+    });
+}
+
+/// Answer the commands of one cache connection
+fn serve_cache(stream: TcpStream, store: &Mutex<HashMap<String, String>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+
+    while let Some(args) = read_command(&mut reader) {
+        let mut store = store.lock().unwrap();
+        let reply = match (args[0].to_uppercase().as_str(), &args[1..]) {
+            ("GET", [key, ..]) => bulk(store.get(key)),
+            ("SET", [key, value, ..]) => {
+                store.insert(key.clone(), value.clone());
+                "+OK\r\n".to_string()
+            }
+            ("DEL", keys) => {
+                let removed = keys.iter().filter(|k| store.remove(*k).is_some()).count();
+                format!(":{removed}\r\n")
+            }
+            (command @ ("INCR" | "INCRBY" | "DECR" | "DECRBY"), [key, by @ ..]) => {
+                let by = by.first().and_then(|b| b.parse::<i64>().ok()).unwrap_or(1);
+                let by = if command.starts_with("DECR") { -by } else { by };
+                let value = store
+                    .get(key)
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .unwrap_or(0)
+                    + by;
+                store.insert(key.clone(), value.to_string());
+                format!(":{value}\r\n")
+            }
+            ("KEYS", [pattern, ..]) => {
+                let keys: Vec<&String> = store.keys().filter(|k| glob(pattern, k)).collect();
+                let mut reply = format!("*{}\r\n", keys.len());
+                for key in keys {
+                    reply.push_str(&bulk(Some(key)));
+                }
+                reply
+            }
+            ("MGET", keys) => {
+                let mut reply = format!("*{}\r\n", keys.len());
+                for key in keys {
+                    reply.push_str(&bulk(store.get(key)));
+                }
+                reply
+            }
+            ("EXPIRE", _) => ":1\r\n".to_string(),
+            _ => "+OK\r\n".to_string(),
+        };
+
+        if writer.write_all(reply.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Read a command (an array of bulk strings)
+fn read_command(reader: &mut impl BufRead) -> Option<Vec<String>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok().filter(|n| *n > 0)?;
+    let count: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let len: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).ok()?;
+        arg.truncate(len);
+        args.push(String::from_utf8(arg).ok()?);
+    }
+
+    (!args.is_empty()).then_some(args)
+}
+
+/// Encode a bulk string reply (`nil` for `None`)
+fn bulk(value: Option<&String>) -> String {
+    match value {
+        Some(value) => format!("${}\r\n{value}\r\n", value.len()),
+        None => "$-1\r\n".to_string(),
+    }
+}
+
+/// Match `key` against a `KEYS` pattern (only `*` and `?` are special)
+fn glob(pattern: &str, key: &str) -> bool {
+    match (pattern.chars().next(), key.chars().next()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            glob(&pattern[1..], key)
+                || key
+                    .chars()
+                    .next()
+                    .is_some_and(|c| glob(pattern, &key[c.len_utf8()..]))
+        }
+        (Some('?'), Some(c)) => glob(&pattern[1..], &key[c.len_utf8()..]),
+        (Some(p), Some(c)) if p == c => glob(&pattern[p.len_utf8()..], &key[c.len_utf8()..]),
+        _ => false,
+    }
+}
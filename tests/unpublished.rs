@@ -0,0 +1,64 @@
+//! Private pastes, and pastes that aren't published or open yet, can't be read by anyone else,
+//! whichever route asks
+mod common;
+
+use common::{
+    assert_hidden, create_paste, edit_metadata, edit_metadata_as, get_as, sign_up, CLIENT, SECRET,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unpublished_pastes_are_hidden_from_every_route() {
+    std::env::set_var("USE_STARSTRAW", "true");
+    let app = common::app("unpublished").await;
+    let token = sign_up("owner").await;
+
+    // (a day from now)
+    let tomorrow = (std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+        + 86_400_000) as u64;
+
+    let private = create_paste(&app, "private", "").await;
+    edit_metadata_as(
+        &app,
+        Some(&token),
+        "private",
+        serde_json::json!({ "visibility": "private" }),
+    )
+    .await;
+
+    let scheduled = create_paste(&app, "scheduled", "").await;
+    edit_metadata(
+        &app,
+        "scheduled",
+        serde_json::json!({ "publish_at": tomorrow }),
+    )
+    .await;
+
+    let opening = create_paste(&app, "opening", "").await;
+    edit_metadata(
+        &app,
+        "opening",
+        serde_json::json!({ "available_from": tomorrow }),
+    )
+    .await;
+
+    assert_hidden(&app, CLIENT, "private", &private).await;
+    assert_hidden(&app, CLIENT, "scheduled", &scheduled).await;
+    assert_hidden(&app, CLIENT, "opening", &opening).await;
+
+    // (the owner of the private paste still sees it)
+    for uri in ["/private/raw", "/api/private", "/private/chapter"] {
+        let res = get_as(&app, CLIENT, Some(&token), uri).await;
+        assert!(res.body.contains(SECRET), "{uri} for the owner");
+    }
+
+    // (and finds it in their listing, which a name matching it as a LIKE pattern doesn't)
+    let res = get_as(&app, CLIENT, Some(&token), "/api/v1/me/pastes").await;
+    assert!(res.body.contains("\"private\""), "{}", res.body);
+
+    let other = sign_up("own_r").await;
+    let res = get_as(&app, CLIENT, Some(&other), "/api/v1/me/pastes").await;
+    assert!(!res.body.contains("\"private\""), "{}", res.body);
+}
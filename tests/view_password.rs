@@ -0,0 +1,67 @@
+//! Pastes with a view password can't be read without it, whichever route asks
+mod common;
+
+use axum::http::Method;
+use common::{assert_hidden, assert_shown, create_paste, get, send, CLIENT, SECRET};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn view_password_protects_every_route() {
+    let app = common::app("view-password").await;
+    let code = create_paste(&app, "locked", "hunter2").await;
+    assert_hidden(&app, CLIENT, "locked", &code).await;
+
+    // a wrong password unlocks nothing
+    let res = get(&app, CLIENT, "/locked/raw?view_password=hunter3").await;
+    assert!(!res.body.contains(SECRET));
+
+    // ...and the right one does
+    for uri in ["/locked/raw", "/api/locked"] {
+        let res = get(&app, CLIENT, &format!("{uri}?view_password=hunter2")).await;
+        assert!(res.body.contains(SECRET), "{uri} with the view password");
+    }
+
+    // a share link opens the paste, and the attachments it links to
+    let res = send(
+        &app,
+        CLIENT,
+        Method::POST,
+        "/api/locked/share_links",
+        Some(serde_json::json!({ "password": "x" })),
+    )
+    .await;
+    let link = serde_json::from_str::<serde_json::Value>(&res.body).unwrap()["payload"]["url"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let query = link.split_once('?').unwrap().1;
+
+    for route in ["", "/print", "/embed"] {
+        let res = get(&app, CLIENT, &format!("/locked{route}?{query}")).await;
+        assert!(
+            res.body.contains(SECRET),
+            "/locked{route} with a share link"
+        );
+
+        let attachment = res
+            .body
+            .split("href=\"")
+            .find(|h| h.starts_with("/locked/attachments/notes.txt"))
+            .and_then(|h| h.split('"').next())
+            .unwrap_or_else(|| panic!("/locked{route} links the attachment"))
+            .replace("&amp;", "&");
+        assert!(!attachment.contains("hunter2"));
+
+        let res = get(&app, CLIENT, &attachment).await;
+        assert!(
+            res.body.contains(SECRET),
+            "{attachment} from /locked{route}"
+        );
+    }
+
+    // (the same routes do show and list pastes without one)
+    let code = create_paste(&app, "unlocked", "").await;
+    assert_shown(&app, CLIENT, "unlocked", &code).await;
+
+    let res = get(&app, CLIENT, "/feed.atom").await;
+    assert!(res.body.contains("/unlocked"));
+}